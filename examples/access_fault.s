@@ -0,0 +1,2 @@
+li t0, 2000
+lw a0, 0(t0)
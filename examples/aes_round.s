@@ -0,0 +1,28 @@
+lw s0, 0(a0)
+lw s1, 1(a0)
+lw s2, 2(a0)
+lw s3, 3(a0)
+lw t0, 0(a1)
+aes32esmi t0, t0, s0, 0
+aes32esmi t0, t0, s1, 1
+aes32esmi t0, t0, s2, 2
+aes32esmi t0, t0, s3, 3
+sw t0, 0(a2)
+lw t0, 1(a1)
+aes32esmi t0, t0, s1, 0
+aes32esmi t0, t0, s2, 1
+aes32esmi t0, t0, s3, 2
+aes32esmi t0, t0, s0, 3
+sw t0, 1(a2)
+lw t0, 2(a1)
+aes32esmi t0, t0, s2, 0
+aes32esmi t0, t0, s3, 1
+aes32esmi t0, t0, s0, 2
+aes32esmi t0, t0, s1, 3
+sw t0, 2(a2)
+lw t0, 3(a1)
+aes32esmi t0, t0, s3, 0
+aes32esmi t0, t0, s0, 1
+aes32esmi t0, t0, s1, 2
+aes32esmi t0, t0, s2, 3
+sw t0, 3(a2)
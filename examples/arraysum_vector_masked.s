@@ -0,0 +1,11 @@
+vsetvli t0, x0, e32, m1, ta, ma
+vmv.s.x v8, x0
+vsetvli t0, a1, e32, m1, ta, ma
+vle32.v v1, (a0)
+vmsgt.vi v0, v1, 0
+vredsum.vs v8, v1, v8, v0.t
+add a0, a0, t0
+sub a1, a1, t0
+bnez a1, -24
+vmv.x.s a0, v8
+ret
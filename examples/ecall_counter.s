@@ -0,0 +1,8 @@
+li t0, 5
+csrw mhpmevent3, t0
+li t0, 24
+csrw mtvec, t0
+ecall
+nop
+csrr a0, hpmcounter3
+ret
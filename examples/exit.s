@@ -0,0 +1,6 @@
+li a0, 1
+ebreak
+li a0, 3
+li a7, 93
+ecall
+li a0, 4
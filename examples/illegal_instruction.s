@@ -0,0 +1,4 @@
+li a0, 1
+.word 0xffffffff
+li a0, 2
+ret
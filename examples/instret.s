@@ -0,0 +1,6 @@
+rdinstret t0
+addi a0, a0, 1
+addi a0, a0, 1
+addi a0, a0, 1
+rdinstret t1
+sub a0, t1, t0
@@ -0,0 +1,20 @@
+li t0, 49
+csrw mtvec, t0
+li t0, 8
+csrw mie, t0
+csrsi mstatus, 8
+li t1, 3
+wait:
+blt s0, t1, 0
+mv a0, s0
+ret
+nop
+nop
+nop
+vectors:
+mret
+nop
+nop
+software:
+addi s0, s0, 1
+mret
@@ -2,10 +2,10 @@ addi sp, sp, -16
 sw ra, 0(sp)
 sw s1, 8(sp)
 mv s1, a0
-call 72
+call 76
 srai t0, a0, 1
 li t1, 0
-bge t1, t0, 40
+bge t1, t0, 44
 add t2, s1, t1
 sub t3, a0, t1
 addi t3, t3, -1
@@ -15,7 +15,7 @@ lb t5, 0(t3)
 sb t4, 0(t3)
 sb t5, 0(t2)
 addi t1, t1, 1
-j -44
+j -40
 lw s1, 8(sp)
 lw ra, 0(sp)
 addi sp, sp, 16
@@ -23,8 +23,8 @@ ret
 li t0, 0
 add t1, t0, a0
 lb t1, 0(t1)
-beqz t1, 8
+beqz t1, 12
 addi t0, t0, 1
-j -20
+j -16
 mv a0, t0
 ret
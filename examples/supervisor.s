@@ -0,0 +1,55 @@
+li t1, 1024
+addi t0, t1, 1025
+sw t0, 0(t1)
+li t0, 11
+sw t0, 1024(t1)
+li t0, 27
+sw t0, 1025(t1)
+lui t0, 1
+addi t0, t0, -1001
+sw t0, 1026(t1)
+lui t0, 2
+addi t0, t0, 256
+csrw medeleg, t0
+li t0, 152
+csrw stvec, t0
+li t0, 192
+csrw mtvec, t0
+lui t0, 524288
+addi t0, t0, 1
+csrw satp, t0
+lui t0, 1
+addi t0, t0, -2048
+csrw mstatus, t0
+li t0, 104
+csrw mepc, t0
+mret
+supervisor:
+lui t0, 1
+addi t0, t0, 120
+csrw sepc, t0
+sret
+user:
+li t0, 1024
+add t0, t0, t0
+li t1, 42
+sw t1, 0(t0)
+lw a0, 0(t0)
+ecall
+lw t1, 1024(t0)
+nop
+supervisor_trap:
+csrr t2, scause
+add a0, a0, t2
+li t3, 13
+beq t2, t3, 20
+csrr t2, sepc
+addi t2, t2, 4
+csrw sepc, t2
+sret
+sfence.vma
+ecall
+machine_trap:
+csrr t2, mcause
+add a0, a0, t2
+ret
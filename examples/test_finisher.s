@@ -0,0 +1,4 @@
+li a0, 7
+li t0, 1000
+sw a0, 0(t0)
+li a0, 0
@@ -0,0 +1,23 @@
+li t0, 64
+csrw mtvec, t0
+lui s1, 2049
+lui s2, 2051
+addi s2, s2, -2
+lw t0, 0(s2)
+addi t0, t0, 20
+sw zero, 1(s1)
+sw t0, 0(s1)
+li t0, 128
+csrw mie, t0
+csrsi mstatus, 8
+li t1, 3
+wait:
+blt s0, t1, 0
+mv a0, s0
+ret
+timer:
+addi s0, s0, 1
+lw t0, 0(s1)
+addi t0, t0, 20
+sw t0, 0(s1)
+mret
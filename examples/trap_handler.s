@@ -0,0 +1,15 @@
+li t0, 32
+csrw mtvec, t0
+ecall
+ebreak
+lw t1, 2000(x0)
+.word 0xffffffff
+mv a0, s0
+ret
+handler:
+csrr t1, mcause
+add s0, s0, t1
+csrr t1, mepc
+addi t1, t1, 4
+csrw mepc, t1
+mret
@@ -1,6 +1,23 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::str::FromStr;
+use crate::csr;
 use crate::instruction;
+use crate::instruction::Instruction;
+use crate::isa::Isa;
 use crate::immediates::{IImmediate, Immediate, JImmediate, SImmediate, UImmediate};
 use crate::immediates::BImmediate;
+use crate::trace::{TraceEvent, Tracer};
+use crate::vector;
+
+#[derive(Debug)]
+struct AesOperation {
+    instruction: String,
+    source1: String,
+    source2: String,
+    destination: String,
+    byte_select: String
+}
 
 #[derive(Debug)]
 struct BOperation {
@@ -10,6 +27,14 @@ struct BOperation {
     offset: String
 }
 
+#[derive(Debug)]
+struct CsrOperation {
+    instruction: String,
+    destination: String,
+    csr: String,
+    source: String
+}
+
 #[derive(Debug)]
 struct IOperation {
     instruction: String,
@@ -32,6 +57,13 @@ struct ROperation {
     destination: String
 }
 
+#[derive(Debug)]
+struct UnaryOperation {
+    instruction: String,
+    source: String,
+    destination: String
+}
+
 #[derive(Debug)]
 struct SOperation {
     instruction: String,
@@ -47,16 +79,65 @@ struct UOperation {
     immediate: String
 }
 
+#[derive(Debug)]
+struct VConfigOperation {
+    instruction: String,
+    destination: String,
+    source: String,
+    vtype: String
+}
+
+#[derive(Debug)]
+struct VMemoryOperation {
+    instruction: String,
+    register: String,
+    base: String,
+    stride: String,
+    masked: bool
+}
+
+#[derive(Debug)]
+struct VOperation {
+    instruction: String,
+    destination: String,
+    source2: String,
+    source1: String,
+    masked: bool
+}
+
 trait Operation {
-    fn compile(self) -> u32;
+    fn compile(self) -> Result<u32, String>;
+}
+
+impl Operation for AesOperation {
+    fn compile(self) -> Result<u32, String> {
+        let rs1: u32 = parse_number(&self.source1)?;
+        let rs2: u32 = parse_number(&self.source2)?;
+        let rd: u32 = parse_number(&self.destination)?;
+        let bs = parse_unsigned(&self.byte_select, 2)?;
+        let op: u32 = match self.instruction.as_str() {
+            "aes32esi" => instruction::AES32ESI,
+            "aes32esmi" => instruction::AES32ESMI,
+            "aes32dsi" => instruction::AES32DSI,
+            "aes32dsmi" => instruction::AES32DSMI,
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
+        };
+
+        Ok(instruction::OP
+            | (rd << 7)
+            | ((op & 0b111) << 12)
+            | (rs1 << 15)
+            | (rs2 << 20)
+            | ((op >> 3) << 25)
+            | (bs << 30))
+    }
 }
 
 impl Operation for BOperation {
-    fn compile(self) -> u32 {
-        println!("[compiling] {:?}", self);
-        let rs1: u32 = self.source1.parse().unwrap();
-        let rs2: u32 = self.source2.parse().unwrap();
-        let imm: i32 = self.offset.parse().unwrap();
+    fn compile(self) -> Result<u32, String> {
+        let rs1: u32 = parse_number(&self.source1)?;
+        let rs2: u32 = parse_number(&self.source2)?;
+        let imm = parse_offset(&self.offset, 13)?;
         let op: u32 = match self.instruction.as_str() {
             "beq" => instruction::BEQ,
             "bne" => instruction::BNE,
@@ -64,23 +145,45 @@ impl Operation for BOperation {
             "bltu" => instruction::BLTU,
             "bge" => instruction::BGE,
             "bgeu" => instruction::BGEU,
-            _ => panic!("oops!")
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
+        };
+
+        Ok(instruction::BRANCH
+            | BImmediate::from(imm as u32).to_instruction_bitmask()
+            | (op << 12)
+            | (rs1 << 15)
+            | (rs2 << 20))
+    }
+}
+
+impl Operation for CsrOperation {
+    fn compile(self) -> Result<u32, String> {
+        let rd: u32 = parse_number(&self.destination)?;
+        let csr: u32 = parse_csr(&self.csr)?;
+        // rs1 for the register forms, a 5-bit unsigned immediate for the others
+        let rs1: u32 = parse_unsigned(&self.source, 5)?;
+        let op: u32 = match self.instruction.as_str() {
+            "csrrw" => instruction::CSRRW,
+            "csrrs" => instruction::CSRRS,
+            "csrrc" => instruction::CSRRC,
+            "csrrwi" => instruction::CSRRWI,
+            "csrrsi" => instruction::CSRRSI,
+            "csrrci" => instruction::CSRRCI,
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
         };
 
-        instruction::BRANCH
-            + BImmediate::from(imm as u32).to_instruction_bitmask()
-            + (op << 12)
-            + (rs1 << 15)
-            + (rs2 << 20)
+        Ok(instruction::SYSTEM
+            | (rd << 7)
+            | (op << 12)
+            | (rs1 << 15)
+            | (csr << 20))
     }
 }
 
 impl Operation for IOperation {
-    fn compile(self) -> u32 {
-        println!("[compiling] {:?}", self);
-        let rs1: u32 = self.source.parse().unwrap();
-        let imm: i32 = self.immediate.parse().unwrap();
-        let rd: u32 = self.destination.parse().unwrap();
+    fn compile(self) -> Result<u32, String> {
+        let rs1: u32 = parse_number(&self.source)?;
+        let rd: u32 = parse_number(&self.destination)?;
         let op: u32 = match self.instruction.as_str() {
             "addi" => instruction::ADDI,
             "slti" => instruction::SLTI,
@@ -91,62 +194,78 @@ impl Operation for IOperation {
             "slli" => instruction::SLLI,
             "srli" => instruction::SRLI,
             "srai" => instruction::SRAI,
+            "rori" => instruction::RORI,
+            "bclri" => instruction::BCLRI,
+            "bexti" => instruction::BEXTI,
+            "binvi" => instruction::BINVI,
+            "bseti" => instruction::BSETI,
+            "addiw" => instruction::ADDI,
+            "slliw" => instruction::SLLI,
+            "srliw" => instruction::SRLI,
+            "sraiw" => instruction::SRAI,
             "jalr" => instruction::JALR,
             "lw" => instruction::LW,
             "lh" => instruction::LH,
             "lhu" => instruction::LHU,
             "lb" => instruction::LB,
             "lbu" => instruction::LBU,
-            _ => panic!("oops!")
+            "lwu" => instruction::LWU,
+            "ld" => instruction::LD,
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
+        };
+        // Shift amounts are 6 bits wide, or 5 for the word shifts, and share the immediate with funct7
+        let imm = match self.instruction.as_str() {
+            "slliw" | "srliw" | "sraiw" => parse_unsigned(&self.immediate, 5)? as i32,
+            "slli" | "srli" | "srai" | "rori" | "bclri" | "bexti" | "binvi" | "bseti" => parse_unsigned(&self.immediate, 6)? as i32,
+            _ => parse_signed(&self.immediate, 12)?
         };
 
         let imm = IImmediate::from(imm as u32);
 
-        match self.instruction.as_str() {
+        Ok(match self.instruction.as_str() {
             "jalr" => {
                 instruction::JALR
-                    + (rd << 7)
-                    + (rs1 << 15)
-                    + imm.to_instruction_bitmask()
+                    | (rd << 7)
+                    | (rs1 << 15)
+                    | imm.to_instruction_bitmask()
             },
-            "lw"|"lh"|"lhu"|"lb"|"lbu" => {
+            "lw"|"lh"|"lhu"|"lb"|"lbu"|"lwu"|"ld" => {
                 instruction::LOAD
-                    + (rd << 7)
-                    + (op << 12)
-                    + (rs1 << 15)
-                    + imm.to_instruction_bitmask()
+                    | (rd << 7)
+                    | (op << 12)
+                    | (rs1 << 15)
+                    | imm.to_instruction_bitmask()
             },
-            _ => {
-                instruction::OP_IMM
-                    + (rd << 7)
-                    + ((op & 0b111) << 12)
-                    + (rs1 << 15)
-                    + imm.to_instruction_bitmask()
-                    + ((op >> 3) << 25)
+            token => {
+                let opcode = if W_OPS.contains(&token) { instruction::OP_IMM_32 } else { instruction::OP_IMM };
+                opcode
+                    | (rd << 7)
+                    | ((op & 0b111) << 12)
+                    | (rs1 << 15)
+                    | imm.to_instruction_bitmask()
+                    | ((op >> 3) << 25)
             }
-        }
+        })
     }
 }
 
 impl Operation for JOperation {
-    fn compile(self) -> u32 {
-        println!("[compiling] {:?}", self);
-        let rd: u32 = self.destination.parse().unwrap();
-        let imm: i32 = self.immediate.parse().unwrap();
+    fn compile(self) -> Result<u32, String> {
+        let rd: u32 = parse_number(&self.destination)?;
+        let imm = parse_offset(&self.immediate, 21)?;
         let op = instruction::JAL;
 
-        op
-            + (rd << 7)
-            + JImmediate::from(imm as u32).to_instruction_bitmask()
+        Ok(op
+            | (rd << 7)
+            | JImmediate::from(imm as u32).to_instruction_bitmask())
     }
 }
 
 impl Operation for ROperation {
-    fn compile(self) -> u32 {
-        println!("[compiling] {:?}", self);
-        let rs1: u32 = self.source1.parse().unwrap();
-        let rs2: u32 = self.source2.parse().unwrap();
-        let rd: u32 = self.destination.parse().unwrap();
+    fn compile(self) -> Result<u32, String> {
+        let rs1: u32 = parse_number(&self.source1)?;
+        let rs2: u32 = parse_number(&self.source2)?;
+        let rd: u32 = parse_number(&self.destination)?;
         let op: u32 = match self.instruction.as_str() {
             "add" => instruction::ADD,
             "slt" => instruction::SLT,
@@ -158,51 +277,193 @@ impl Operation for ROperation {
             "srl" => instruction::SRL,
             "sub" => instruction::SUB,
             "sra" => instruction::SRA,
-            _ => panic!("oops!")
+            "sh1add" => instruction::SH1ADD,
+            "sh2add" => instruction::SH2ADD,
+            "sh3add" => instruction::SH3ADD,
+            "andn" => instruction::ANDN,
+            "orn" => instruction::ORN,
+            "xnor" => instruction::XNOR,
+            "min" => instruction::MIN,
+            "minu" => instruction::MINU,
+            "max" => instruction::MAX,
+            "maxu" => instruction::MAXU,
+            "rol" => instruction::ROL,
+            "ror" => instruction::ROR,
+            "clmul" => instruction::CLMUL,
+            "clmulh" => instruction::CLMULH,
+            "clmulr" => instruction::CLMULR,
+            "bclr" => instruction::BCLR,
+            "bext" => instruction::BEXT,
+            "binv" => instruction::BINV,
+            "bset" => instruction::BSET,
+            "pack" => instruction::PACK,
+            "packh" => instruction::PACKH,
+            "xperm4" => instruction::XPERM4,
+            "xperm8" => instruction::XPERM8,
+            "sha512sum0r" => instruction::SHA512SUM0R,
+            "sha512sum1r" => instruction::SHA512SUM1R,
+            "sha512sig0l" => instruction::SHA512SIG0L,
+            "sha512sig0h" => instruction::SHA512SIG0H,
+            "sha512sig1l" => instruction::SHA512SIG1L,
+            "sha512sig1h" => instruction::SHA512SIG1H,
+            "addw" => instruction::ADD,
+            "subw" => instruction::SUB,
+            "sllw" => instruction::SLL,
+            "srlw" => instruction::SRL,
+            "sraw" => instruction::SRA,
+            "packw" => instruction::PACKW,
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
         };
 
-        instruction::OP
-            + (rd << 7)
-            + ((op & 0b111) << 12)
-            + (rs1 << 15)
-            + (rs2 << 20)
-            + ((op >> 3) << 25)
+        let opcode = if W_OPS.contains(&self.instruction.as_str()) { instruction::OP_32 } else { instruction::OP };
+        Ok(opcode
+            | (rd << 7)
+            | ((op & 0b111) << 12)
+            | (rs1 << 15)
+            | (rs2 << 20)
+            | ((op >> 3) << 25))
+    }
+}
+
+impl Operation for UnaryOperation {
+    fn compile(self) -> Result<u32, String> {
+        let rs1: u32 = parse_number(&self.source)?;
+        let rd: u32 = parse_number(&self.destination)?;
+        let (op, funct3): (u32, u32) = match self.instruction.as_str() {
+            "clz" => (instruction::CLZ, instruction::SLLI),
+            "ctz" => (instruction::CTZ, instruction::SLLI),
+            "cpop" => (instruction::CPOP, instruction::SLLI),
+            "sext.b" => (instruction::SEXT_B, instruction::SLLI),
+            "sext.h" => (instruction::SEXT_H, instruction::SLLI),
+            "orc.b" => (instruction::ORC_B, instruction::SRLI),
+            "rev8" => (instruction::REV8, instruction::SRLI),
+            "brev8" => (instruction::BREV8, instruction::SRLI),
+            "zip" => (instruction::ZIP, instruction::SLLI),
+            "unzip" => (instruction::UNZIP, instruction::SRLI),
+            "sha256sum0" => (instruction::SHA256SUM0, instruction::SLLI),
+            "sha256sum1" => (instruction::SHA256SUM1, instruction::SLLI),
+            "sha256sig0" => (instruction::SHA256SIG0, instruction::SLLI),
+            "sha256sig1" => (instruction::SHA256SIG1, instruction::SLLI),
+            // zext.h is the register-register `pack rd, rs1, x0`, which `compile_checked` turns into packw on RV64
+            "zext.h" => {
+                return Ok(instruction::OP
+                    | (rd << 7)
+                    | ((instruction::ZEXT_H & 0b111) << 12)
+                    | (rs1 << 15)
+                    | ((instruction::ZEXT_H >> 3) << 25))
+            },
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
+        };
+
+        Ok(instruction::OP_IMM
+            | (rd << 7)
+            | (funct3 << 12)
+            | (rs1 << 15)
+            | (op << 20))
     }
 }
 
 impl Operation for SOperation {
-    fn compile(self) -> u32 {
-        println!("[compiling] {:?}", self);
-        let rs1: u32 = self.base.parse().unwrap();
-        let rs2: u32 = self.source.parse().unwrap();
-        let imm: i32 = self.offset.parse().unwrap();
+    fn compile(self) -> Result<u32, String> {
+        let rs1: u32 = parse_number(&self.base)?;
+        let rs2: u32 = parse_number(&self.source)?;
+        let imm = parse_signed(&self.offset, 12)?;
         let width: u32 = match self.instruction.as_str() {
             "sw" => instruction::SW,
             "sh" => instruction::SH,
             "sb" => instruction::SB,
-            _ => panic!("oops!")
+            "sd" => instruction::SD,
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
         };
 
-        instruction::STORE
-            + SImmediate::from(imm as u32).to_instruction_bitmask()
-            + (width << 12)
-            + (rs1 << 15)
-            + (rs2 << 20)
+        Ok(instruction::STORE
+            | SImmediate::from(imm as u32).to_instruction_bitmask()
+            | (width << 12)
+            | (rs1 << 15)
+            | (rs2 << 20))
     }
 }
 
 impl Operation for UOperation {
-    fn compile(self) -> u32 {
-        println!("[compiling] {:?}", self);
-        let rd: u32 = self.destination.parse().unwrap();
-        let imm: u32 = self.immediate.parse().unwrap();
+    fn compile(self) -> Result<u32, String> {
+        let rd: u32 = parse_number(&self.destination)?;
+        let imm = parse_unsigned(&self.immediate, 20)?;
         let op: u32 = match self.instruction.as_str() {
             "lui" => instruction::LUI,
             "auipc" => instruction::AUIPC,
-            _ => panic!("oops!")
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
+        };
+
+        Ok(op | (rd << 7) | UImmediate::from(imm << 12).to_instruction_bitmask())
+    }
+}
+
+impl Operation for VConfigOperation {
+    fn compile(self) -> Result<u32, String> {
+        let rd: u32 = parse_number(&self.destination)?;
+        // rs1, or a 5-bit unsigned application vector length for vsetivli
+        let rs1 = parse_unsigned(&self.source, 5)?;
+        let funct: u32 = match self.instruction.as_str() {
+            "vsetvli" => instruction::VSETVLI,
+            "vsetivli" => instruction::VSETIVLI,
+            "vsetvl" => instruction::VSETVL,
+            _ => return Err(format!("unknown instruction '{}'", self.instruction))
         };
 
-        op + (rd << 7) + UImmediate::from(imm << 12).to_instruction_bitmask()
+        // vsetvl encodes rs2 where the others encode the vtype immediate
+        let upper = match funct {
+            instruction::VSETVLI => parse_unsigned(&self.vtype, 11)?,
+            instruction::VSETIVLI => (funct << 10) | parse_unsigned(&self.vtype, 10)?,
+            _ => (funct << 10) | parse_unsigned(&self.vtype, 5)?
+        };
+        Ok(instruction::OP_V
+            | (rd << 7)
+            | (instruction::OPCFG << 12)
+            | (rs1 << 15)
+            | (upper << 20))
+    }
+}
+
+impl Operation for VMemoryOperation {
+    fn compile(self) -> Result<u32, String> {
+        let vd: u32 = parse_number(&self.register)?;
+        let rs1: u32 = parse_number(&self.base)?;
+        let rs2: u32 = parse_number(&self.stride)?;
+        let (store, strided, width) = parse_vector_memory(&self.instruction)
+            .ok_or_else(|| format!("unknown instruction '{}'", self.instruction))?;
+        let opcode = if store { instruction::STORE_FP } else { instruction::LOAD_FP };
+        let mop = if strided { instruction::VSTRIDED } else { instruction::VUNIT_STRIDE };
+
+        Ok(opcode
+            | (vd << 7)
+            | (width << 12)
+            | (rs1 << 15)
+            | (rs2 << 20)
+            | (if self.masked { 0 } else { 1 << 25 })
+            | (mop << 26))
+    }
+}
+
+impl Operation for VOperation {
+    fn compile(self) -> Result<u32, String> {
+        let vd: u32 = parse_number(&self.destination)?;
+        let vs2: u32 = parse_number(&self.source2)?;
+        let (funct3, funct6) = vector_encoding(&self.instruction)
+            .ok_or_else(|| format!("unknown instruction '{}'", self.instruction))?;
+        // The .vi forms take a 5-bit immediate in place of vs1, unsigned for the shifts
+        let vs1 = match (funct3, funct6) {
+            (instruction::OPIVI, instruction::VSLL | instruction::VSRL | instruction::VSRA) => parse_unsigned(&self.source1, 5)?,
+            (instruction::OPIVI, _) => parse_signed(&self.source1, 5)? as u32 & 0b11111,
+            _ => parse_number(&self.source1)?
+        };
+
+        Ok(instruction::OP_V
+            | (vd << 7)
+            | (funct3 << 12)
+            | (vs1 << 15)
+            | (vs2 << 20)
+            | (if self.masked { 0 } else { 1 << 25 })
+            | (funct6 << 26))
     }
 }
 
@@ -215,6 +476,18 @@ const B_OPS: &[&str] = &[
     "bgeu"
 ];
 
+const CSR_OPS: &[&str] = &[
+    "csrrw",
+    "csrrs",
+    "csrrc"
+];
+
+const CSR_OPS_IMM: &[&str] = &[
+    "csrrwi",
+    "csrrsi",
+    "csrrci"
+];
+
 const I_OPS: &[&str] = &[
     "addi",
     "slti",
@@ -225,6 +498,15 @@ const I_OPS: &[&str] = &[
     "slli",
     "srli",
     "srai",
+    "rori",
+    "bclri",
+    "bexti",
+    "binvi",
+    "bseti",
+    "addiw",
+    "slliw",
+    "srliw",
+    "sraiw",
     "jalr"
 ];
 
@@ -233,7 +515,9 @@ const I_OPS_LOAD: &[&str] = &[
     "lh",
     "lhu",
     "lb",
-    "lbu"
+    "lbu",
+    "lwu",
+    "ld"
 ];
 
 const R_OPS: &[&str] = &[
@@ -247,12 +531,87 @@ const R_OPS: &[&str] = &[
     "srl",
     "sub",
     "sra",
+    "sh1add",
+    "sh2add",
+    "sh3add",
+    "andn",
+    "orn",
+    "xnor",
+    "min",
+    "minu",
+    "max",
+    "maxu",
+    "rol",
+    "ror",
+    "clmul",
+    "clmulh",
+    "clmulr",
+    "bclr",
+    "bext",
+    "binv",
+    "bset",
+    "pack",
+    "packh",
+    "xperm4",
+    "xperm8",
+    "sha512sum0r",
+    "sha512sum1r",
+    "sha512sig0l",
+    "sha512sig0h",
+    "sha512sig1l",
+    "sha512sig1h",
+    "addw",
+    "subw",
+    "sllw",
+    "srlw",
+    "sraw",
+    "packw"
+];
+
+/// RV64 instructions operating on the lower 32 bits, encoded with OP_IMM_32 or OP_32
+const W_OPS: &[&str] = &[
+    "addiw",
+    "slliw",
+    "srliw",
+    "sraiw",
+    "addw",
+    "subw",
+    "sllw",
+    "srlw",
+    "sraw",
+    "packw"
+];
+
+const AES_OPS: &[&str] = &[
+    "aes32esi",
+    "aes32esmi",
+    "aes32dsi",
+    "aes32dsmi"
+];
+
+const UNARY_OPS: &[&str] = &[
+    "clz",
+    "ctz",
+    "cpop",
+    "sext.b",
+    "sext.h",
+    "zext.h",
+    "orc.b",
+    "rev8",
+    "brev8",
+    "zip",
+    "unzip",
+    "sha256sum0",
+    "sha256sum1",
+    "sha256sig0",
+    "sha256sig1"
 ];
 
 const S_OPS: &[&str] = &[
     "sw",
     "sh",
-    "sb"
+    "sb",
+    "sd"
 ];
 
 const U_OPS: &[&str] = &[
@@ -260,70 +619,274 @@ const U_OPS: &[&str] = &[
     "auipc"
 ];
 
-fn compile_line(instruction: &str) -> u32 {
+/// Moves and merges, which are encoded like the other OP-V instructions but have their own operands
+const V_MOVE_OPS: &[(&str, u32, u32)] = &[
+    ("vmv.v.v", instruction::OPIVV, instruction::VMERGE),
+    ("vmv.v.x", instruction::OPIVX, instruction::VMERGE),
+    ("vmv.v.i", instruction::OPIVI, instruction::VMERGE),
+    ("vmerge.vvm", instruction::OPIVV, instruction::VMERGE),
+    ("vmerge.vxm", instruction::OPIVX, instruction::VMERGE),
+    ("vmerge.vim", instruction::OPIVI, instruction::VMERGE),
+    ("vmv.x.s", instruction::OPMVV, instruction::VWXUNARY0),
+    ("vmv.s.x", instruction::OPMVX, instruction::VRXUNARY0),
+    ("vmv1r.v", instruction::OPIVI, instruction::VMVNRR),
+    ("vmv2r.v", instruction::OPIVI, instruction::VMVNRR),
+    ("vmv4r.v", instruction::OPIVI, instruction::VMVNRR),
+    ("vmv8r.v", instruction::OPIVI, instruction::VMVNRR),
+];
+
+const V_CONFIG_OPS: &[&str] = &[
+    "vsetvli",
+    "vsetivli",
+    "vsetvl"
+];
+
+/// The funct3 and funct6 of an OP-V instruction
+fn vector_encoding(token: &str) -> Option<(u32, u32)> {
+    V_MOVE_OPS.iter()
+        .find(|(name, _, _)| *name == token)
+        .map(|(_, funct3, funct6)| (*funct3, *funct6))
+        .or_else(|| vector::encoding_of(token))
+}
+
+/// Splits a vector load or store like `vlse32.v` into whether it stores, whether it is strided and its width
+fn parse_vector_memory(token: &str) -> Option<(bool, bool, u32)> {
+    let (store, rest) = match token.strip_prefix("vl") {
+        Some(rest) => (false, rest),
+        None => (true, token.strip_prefix("vs")?)
+    };
+    let (strided, rest) = match rest.strip_prefix("se") {
+        Some(rest) => (true, rest),
+        None => (false, rest.strip_prefix('e')?)
+    };
+    let width = match rest.strip_suffix(".v")? {
+        "8" => instruction::VE8,
+        "16" => instruction::VE16,
+        "32" => instruction::VE32,
+        "64" => instruction::VE64,
+        _ => return None
+    };
+    Some((store, strided, width))
+}
+
+fn parse_vector_register(token: &str) -> Result<&str, String> {
+    let name = token.trim_end_matches(',');
+    name.strip_prefix('v')
+        .filter(|index| index.parse::<u32>().is_ok_and(|index| index < 32))
+        .ok_or_else(|| format!("'{}' is not a vector register", name))
+}
+
+/// The operand in place of vs1: an integer register for the .vx forms, an immediate for the .vi forms
+fn parse_vector_operand(token: &str, funct3: u32) -> Result<&str, String> {
+    match funct3 {
+        instruction::OPIVX | instruction::OPMVX => parse_register(token),
+        instruction::OPIVI => Ok(token.trim_end_matches(',')),
+        _ => parse_vector_register(token)
+    }
+}
+
+fn compile_line(instruction: &str) -> Result<u32, String> {
     let tokens = instruction.split_whitespace().collect::<Vec<_>>();
-    match tokens[0] {
+    // How many tokens the instruction reads, those past it are operands it does not take
+    let used = Cell::new(0);
+    let operand = |index: usize| {
+        used.set(used.get().max(index + 1));
+        tokens.get(index).copied().ok_or_else(|| String::from("missing operand"))
+    };
+    // A masked vector instruction ends in v0.t, read once the operands before it have been
+    let masked = || {
+        let masked = tokens.last() == Some(&"v0.t") && used.get() == tokens.len() - 1;
+        if masked {
+            used.set(tokens.len());
+        }
+        masked
+    };
+    let binary = match operand(0)? {
+        "ecall" => Ok(instruction::SYSTEM | ((instruction::ECALL as u32) << 20)),
+        "ebreak" => Ok(instruction::SYSTEM | ((instruction::EBREAK as u32) << 20)),
+        "sret" => Ok(instruction::SYSTEM | ((instruction::SRET as u32) << 20)),
+        "mret" => Ok(instruction::SYSTEM | ((instruction::MRET as u32) << 20)),
+        "wfi" => Ok(instruction::SYSTEM | ((instruction::WFI as u32) << 20)),
+        "sfence.vma" => {
+            // Both operands are optional and default to x0, which fences every address and address space
+            let optional = |index: usize| {
+                used.set(used.get().max(index + 1));
+                tokens.get(index).map_or(Ok(0), |token| parse_number::<u32>(parse_register(token)?))
+            };
+            Ok(instruction::SYSTEM | (optional(1)? << 15) | (((instruction::SFENCE_VMA as u32) | optional(2)?) << 20))
+        },
+        token if V_CONFIG_OPS.contains(&token) => {
+            let source = match token {
+                "vsetivli" => operand(2)?.trim_end_matches(','),
+                _ => parse_register(operand(2)?)?
+            };
+            let vtype = match token {
+                "vsetvl" => parse_register(operand(3)?)?.to_owned(),
+                _ => {
+                    operand(3)?;
+                    used.set(tokens.len());
+                    let settings: Vec<&str> = tokens[3..].iter().map(|t| t.trim_end_matches(',')).collect();
+                    vector::vtype_of(&settings)
+                        .map(|vtype| vtype.to_string())
+                        .unwrap_or_else(|| settings[0].to_owned())
+                }
+            };
+            VConfigOperation {
+                instruction: token.to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                source: source.to_owned(),
+                vtype
+            }.compile()
+        },
+        token if parse_vector_memory(token).is_some() => {
+            let (_, strided, _) = parse_vector_memory(token).unwrap();
+            let (_, base) = parse_base_and_offset(operand(2)?.trim_end_matches(','))?;
+            VMemoryOperation {
+                instruction: token.to_owned(),
+                register: parse_vector_register(operand(1)?)?.to_owned(),
+                base: parse_register(base)?.to_owned(),
+                stride: if strided { parse_register(operand(3)?)?.to_owned() } else { String::from("0") },
+                masked: masked()
+            }.compile()
+        },
+        token if V_MOVE_OPS.iter().any(|(name, _, _)| *name == token) => {
+            let (funct3, funct6) = vector_encoding(token).unwrap();
+            let (destination, source2, source1) = match (funct3, funct6) {
+                (instruction::OPMVV, instruction::VWXUNARY0) =>
+                    (parse_register(operand(1)?)?, parse_vector_register(operand(2)?)?, "0"),
+                (instruction::OPMVX, instruction::VRXUNARY0) =>
+                    (parse_vector_register(operand(1)?)?, "0", parse_register(operand(2)?)?),
+                // The immediate of vmv<nr>r.v is the number of registers less one
+                (_, instruction::VMVNRR) => {
+                    let count = match &token[3..4] { "1" => "0", "2" => "1", "4" => "3", _ => "7" };
+                    (parse_vector_register(operand(1)?)?, parse_vector_register(operand(2)?)?, count)
+                },
+                _ if token.starts_with("vmv") =>
+                    (parse_vector_register(operand(1)?)?, "0", parse_vector_operand(operand(2)?, funct3)?),
+                // vmerge always selects with v0, which is spelled out as a fourth operand
+                _ => {
+                    if parse_vector_register(operand(4)?)? != "0" {
+                        return Err(format!("vmerge selects with v0, not '{}'", operand(4)?));
+                    }
+                    (parse_vector_register(operand(1)?)?, parse_vector_register(operand(2)?)?, parse_vector_operand(operand(3)?, funct3)?)
+                }
+            };
+            VOperation {
+                instruction: token.to_owned(),
+                destination: destination.to_owned(),
+                source2: source2.to_owned(),
+                source1: source1.to_owned(),
+                masked: token.starts_with("vmerge")
+            }.compile()
+        },
+        token if vector::encoding_of(token).is_some() => {
+            let (funct3, _) = vector::encoding_of(token).unwrap();
+            VOperation {
+                instruction: token.to_owned(),
+                destination: parse_vector_register(operand(1)?)?.to_owned(),
+                source2: parse_vector_register(operand(2)?)?.to_owned(),
+                source1: parse_vector_operand(operand(3)?, funct3)?.to_owned(),
+                masked: masked()
+            }.compile()
+        },
         token if R_OPS.contains(&token) => {
             ROperation {
                 instruction: token.to_owned(),
-                destination: parse_register(tokens[1]).to_owned(),
-                source1: parse_register(tokens[2]).to_owned(),
-                source2: parse_register(tokens[3]).to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                source1: parse_register(operand(2)?)?.to_owned(),
+                source2: parse_register(operand(3)?)?.to_owned(),
             }.compile()
         },
         token if I_OPS.contains(&token) => {
             IOperation {
                 instruction: token.to_owned(),
-                destination: parse_register(tokens[1]).to_owned(),
-                source: parse_register(tokens[2]).to_owned(),
-                immediate: tokens[3].to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                source: parse_register(operand(2)?)?.to_owned(),
+                immediate: operand(3)?.to_owned(),
+            }.compile()
+        },
+        token if AES_OPS.contains(&token) => {
+            AesOperation {
+                instruction: token.to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                source1: parse_register(operand(2)?)?.to_owned(),
+                source2: parse_register(operand(3)?)?.to_owned(),
+                byte_select: operand(4)?.to_owned()
+            }.compile()
+        },
+        token if UNARY_OPS.contains(&token) => {
+            UnaryOperation {
+                instruction: token.to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                source: parse_register(operand(2)?)?.to_owned(),
             }.compile()
         },
         token if I_OPS_LOAD.contains(&token) => {
-            let (offset, base) = parse_base_and_offset(tokens[2]);
+            let (offset, base) = parse_base_and_offset(operand(2)?)?;
             IOperation {
                 instruction: token.to_owned(),
-                destination: parse_register(tokens[1]).to_owned(),
-                source: parse_register(base).to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                source: parse_register(base)?.to_owned(),
                 immediate: offset.to_owned(),
             }.compile()
         },
         token if U_OPS.contains(&token) => {
             UOperation {
                 instruction: token.to_owned(),
-                destination: parse_register(tokens[1]).to_owned(),
-                immediate: tokens[2].to_owned()
+                destination: parse_register(operand(1)?)?.to_owned(),
+                immediate: operand(2)?.to_owned()
             }.compile()
         },
         "jal" => {
             JOperation {
-                destination: parse_register(tokens[1]).to_owned(),
-                immediate: tokens[2].to_owned()
+                destination: parse_register(operand(1)?)?.to_owned(),
+                immediate: operand(2)?.to_owned()
             }.compile()
         },
         token if B_OPS.contains(&token) => {
             BOperation {
                 instruction: token.to_owned(),
-                source1: parse_register(tokens[1]).to_owned(),
-                source2: parse_register(tokens[2]).to_owned(),
-                offset: tokens[3].to_owned()
+                source1: parse_register(operand(1)?)?.to_owned(),
+                source2: parse_register(operand(2)?)?.to_owned(),
+                offset: operand(3)?.to_owned()
+            }.compile()
+        },
+        token if CSR_OPS.contains(&token) => {
+            CsrOperation {
+                instruction: token.to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                csr: operand(2)?.trim_end_matches(',').to_owned(),
+                source: parse_register(operand(3)?)?.to_owned()
+            }.compile()
+        },
+        token if CSR_OPS_IMM.contains(&token) => {
+            CsrOperation {
+                instruction: token.to_owned(),
+                destination: parse_register(operand(1)?)?.to_owned(),
+                csr: operand(2)?.trim_end_matches(',').to_owned(),
+                source: operand(3)?.to_owned()
             }.compile()
         },
         token if S_OPS.contains(&token) => {
-            let (offset, base) = parse_base_and_offset(tokens[2]);
+            let (offset, base) = parse_base_and_offset(operand(2)?)?;
             SOperation {
                 instruction: token.to_owned(),
-                source: parse_register(tokens[1]).to_owned(),
-                base: parse_register(base).to_owned(),
+                source: parse_register(operand(1)?)?.to_owned(),
+                base: parse_register(base)?.to_owned(),
                 offset: offset.to_owned()
             }.compile()
         },
-        _ => panic!("oops! token not found: {}", tokens[0])
+        token => Err(format!("unknown instruction '{}'", token))
+    }?;
+    match tokens.get(used.get()) {
+        Some(extra) => Err(format!("unexpected operand '{}'", extra.trim_end_matches(','))),
+        None => Ok(binary)
     }
 }
 
-fn parse_register(token: &str) -> &str {
-    match token.trim_end_matches(',') {
+fn parse_register(token: &str) -> Result<&str, String> {
+    let name = token.trim_end_matches(',');
+    let index = match name {
         t if t.starts_with('x') => t.trim_start_matches('x'),
         "zero" => "0",
         "ra" => "1",
@@ -357,103 +920,316 @@ fn parse_register(token: &str) -> &str {
         "t4" => "29",
         "t5" => "30",
         "t6" => "31",
-        _ => panic!("oops!")
+        _ => ""
+    };
+    match index.parse::<u32>() {
+        Ok(number) if number < 32 => Ok(index),
+        _ => Err(format!("'{}' is not a register", name))
     }
 }
 
-fn parse_base_and_offset(token: &str) -> (&str, &str) {
+/// CSRs can be given either by name (`mstatus`) or by address (`0x300` or `768`)
+fn parse_csr(token: &str) -> Result<u32, String> {
+    if let Some(address) = csr::address_of(token) {
+        return Ok(address);
+    }
+
+    let address = match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok()
+    };
+    address.filter(|&address| address < 1 << 12).ok_or_else(|| format!("'{}' is not a CSR", token))
+}
+
+fn parse_base_and_offset(token: &str) -> Result<(&str, &str), String> {
     token.strip_suffix(')')
         .and_then(|c| c.split_once('('))
-        .unwrap()
+        .ok_or_else(|| format!("expected <offset>(<register>), not '{}'", token))
+}
+
+/// An immediate or a register number. Labels are not resolved, so they are rejected here too
+fn parse_number<T: FromStr>(token: &str) -> Result<T, String> {
+    token.parse().map_err(|_| format!("'{}' is not a number", token))
+}
+
+/// An immediate for a field `bits` wide that holds a two's complement value
+fn parse_signed(token: &str, bits: u32) -> Result<i32, String> {
+    let value: i64 = parse_number(token)?;
+    let limit = 1i64 << (bits - 1);
+    if (-limit..limit).contains(&value) {
+        Ok(value as i32)
+    } else {
+        Err(format!("'{}' is out of range, expected {} to {}", token, -limit, limit - 1))
+    }
+}
+
+/// A branch or jump offset, which leaves out its lowest bit as instructions are at least 2-byte aligned
+fn parse_offset(token: &str, bits: u32) -> Result<i32, String> {
+    match parse_signed(token, bits)? {
+        offset if offset % 2 != 0 => Err(format!("'{}' is not a multiple of 2", token)),
+        offset => Ok(offset)
+    }
+}
+
+/// An immediate for a field `bits` wide that holds an unsigned value
+fn parse_unsigned(token: &str, bits: u32) -> Result<u32, String> {
+    let value: i64 = parse_number(token)?;
+    if (0..1 << bits).contains(&value) {
+        Ok(value as u32)
+    } else {
+        Err(format!("'{}' is out of range, expected 0 to {}", token, (1i64 << bits) - 1))
+    }
 }
 
-fn pseudo_to_base_instructions(instruction: &str) -> Option<Vec<String>> {
+fn pseudo_to_base_instructions(instruction: &str) -> Result<Option<Vec<String>>, String> {
     let tokens = instruction.split_whitespace()
         .map(|t| t.trim_end_matches(','))
         .collect::<Vec<_>>();
-    match tokens[0] {
+    // How many tokens the pseudo instruction reads, those past it are operands it does not take
+    let used = Cell::new(0);
+    let operand = |index: usize| {
+        used.set(used.get().max(index + 1));
+        tokens.get(index).copied().ok_or_else(|| format!("missing operand in '{}'", instruction))
+    };
+    // The `, v0.t` suffix of a masked vector pseudo instruction, if it has one after its operands
+    let vector_mask = || {
+        if tokens.last() == Some(&"v0.t") && used.get() == tokens.len() - 1 {
+            used.set(tokens.len());
+            ", v0.t"
+        } else {
+            ""
+        }
+    };
+    let expanded = match operand(0)? {
         "nop" => Some(vec![
             String::from("addi x0, x0, 0")
         ]),
         "li" => Some(vec![
-            format!("addi {rd}, x0, {imm}", rd=tokens[1], imm=tokens[2])
+            format!("addi {rd}, x0, {imm}", rd=operand(1)?, imm=operand(2)?)
         ]),
         "mv" => Some(vec![
-            format!("addi {rd}, {rs}, 0", rd=tokens[1], rs=tokens[2])
+            format!("addi {rd}, {rs}, 0", rd=operand(1)?, rs=operand(2)?)
         ]),
         "not" => Some(vec![
-            format!("xori {rd}, {rs}, -1", rd=tokens[1], rs=tokens[2])
+            format!("xori {rd}, {rs}, -1", rd=operand(1)?, rs=operand(2)?)
         ]),
         "neg" => Some(vec![
-            format!("sub {rd}, x0, {rs}", rd=tokens[1], rs=tokens[2])
+            format!("sub {rd}, x0, {rs}", rd=operand(1)?, rs=operand(2)?)
+        ]),
+        "negw" => Some(vec![
+            format!("subw {rd}, x0, {rs}", rd=operand(1)?, rs=operand(2)?)
+        ]),
+        "sext.w" => Some(vec![
+            format!("addiw {rd}, {rs}, 0", rd=operand(1)?, rs=operand(2)?)
         ]),
         "seqz" => Some(vec![
-            format!("sltiu {rd}, {rs}, 1", rd=tokens[1], rs=tokens[2])
+            format!("sltiu {rd}, {rs}, 1", rd=operand(1)?, rs=operand(2)?)
         ]),
         "snez" => Some(vec![
-            format!("sltu {rd}, x0, {rs}", rd=tokens[1], rs=tokens[2])
+            format!("sltu {rd}, x0, {rs}", rd=operand(1)?, rs=operand(2)?)
         ]),
         "sltz" => Some(vec![
-            format!("slt {rd}, {rs}, x0", rd=tokens[1], rs=tokens[2])
+            format!("slt {rd}, {rs}, x0", rd=operand(1)?, rs=operand(2)?)
         ]),
         "sqtz" => Some(vec![
-            format!("slt {rd}, x0, {rs}", rd=tokens[1], rs=tokens[2])
+            format!("slt {rd}, x0, {rs}", rd=operand(1)?, rs=operand(2)?)
         ]),
         "beqz" => Some(vec![
-            format!("beq {rs}, x0, {offset}", rs=tokens[1], offset=tokens[2])
+            format!("beq {rs}, x0, {offset}", rs=operand(1)?, offset=operand(2)?)
         ]),
         "bnez" => Some(vec![
-            format!("bne {rs}, x0, {offset}", rs=tokens[1], offset=tokens[2])
+            format!("bne {rs}, x0, {offset}", rs=operand(1)?, offset=operand(2)?)
         ]),
         "bgt" => Some(vec![
-            format!("blt {rt}, {rs}, {offset}", rt=tokens[2], rs=tokens[1], offset=tokens[3])
+            format!("blt {rt}, {rs}, {offset}", rt=operand(2)?, rs=operand(1)?, offset=operand(3)?)
         ]),
         "ble" => Some(vec![
-            format!("bge {rt}, {rs}, {offset}", rt=tokens[2], rs=tokens[1], offset=tokens[3])
+            format!("bge {rt}, {rs}, {offset}", rt=operand(2)?, rs=operand(1)?, offset=operand(3)?)
         ]),
         "j" => Some(vec![
-            format!("jal x0, {offset}", offset=tokens[1])
+            format!("jal x0, {offset}", offset=operand(1)?)
         ]),
         "ret" => Some(vec![
             String::from("jalr x0, x1, 0")
         ]),
+        "rdcycle" => Some(vec![
+            format!("csrrs {rd}, cycle, x0", rd=operand(1)?)
+        ]),
+        "rdcycleh" => Some(vec![
+            format!("csrrs {rd}, cycleh, x0", rd=operand(1)?)
+        ]),
+        "rdtime" => Some(vec![
+            format!("csrrs {rd}, time, x0", rd=operand(1)?)
+        ]),
+        "rdtimeh" => Some(vec![
+            format!("csrrs {rd}, timeh, x0", rd=operand(1)?)
+        ]),
+        "rdinstret" => Some(vec![
+            format!("csrrs {rd}, instret, x0", rd=operand(1)?)
+        ]),
+        "rdinstreth" => Some(vec![
+            format!("csrrs {rd}, instreth, x0", rd=operand(1)?)
+        ]),
+        "csrr" => Some(vec![
+            format!("csrrs {rd}, {csr}, x0", rd=operand(1)?, csr=operand(2)?)
+        ]),
+        "csrw" => Some(vec![
+            format!("csrrw x0, {csr}, {rs}", csr=operand(1)?, rs=operand(2)?)
+        ]),
+        "csrs" => Some(vec![
+            format!("csrrs x0, {csr}, {rs}", csr=operand(1)?, rs=operand(2)?)
+        ]),
+        "csrc" => Some(vec![
+            format!("csrrc x0, {csr}, {rs}", csr=operand(1)?, rs=operand(2)?)
+        ]),
+        "csrwi" => Some(vec![
+            format!("csrrwi x0, {csr}, {imm}", csr=operand(1)?, imm=operand(2)?)
+        ]),
+        "csrsi" => Some(vec![
+            format!("csrrsi x0, {csr}, {imm}", csr=operand(1)?, imm=operand(2)?)
+        ]),
+        "csrci" => Some(vec![
+            format!("csrrci x0, {csr}, {imm}", csr=operand(1)?, imm=operand(2)?)
+        ]),
+        "vneg.v" => Some(vec![
+            format!("vrsub.vx {vd}, {vs}, x0{mask}", vd=operand(1)?, vs=operand(2)?, mask=vector_mask())
+        ]),
+        "vnot.v" => Some(vec![
+            format!("vxor.vi {vd}, {vs}, -1{mask}", vd=operand(1)?, vs=operand(2)?, mask=vector_mask())
+        ]),
+        "vmnot.m" => Some(vec![
+            format!("vmnand.mm {vd}, {vs}, {vs}", vd=operand(1)?, vs=operand(2)?)
+        ]),
         "call" => {
-            let msb = tokens[1].parse::<u32>().unwrap() >> 12;
-            let lsb = tokens[1].parse::<u32>().unwrap() & 0b111111111111;
+            // jalr sign-extends the low 12 bits, so auipc makes up for it when bit 11 is set
+            let offset: i32 = parse_number(operand(1)?)?;
+            let lsb = offset << 20 >> 20;
+            let msb = (offset.wrapping_sub(lsb) >> 12) & 0xFFFFF;
             Some(vec![
                 format!("auipc x6, {offset}", offset=msb),
                 format!("jalr x1, x6, {offset}", offset=(lsb))
             ])
         },
         _ => None
+    };
+    match tokens.get(used.get()) {
+        Some(extra) if expanded.is_some() => Err(format!("unexpected operand '{}' in '{}'", extra, instruction)),
+        _ => Ok(expanded)
     }
 }
 
-pub fn compile(instructions: Vec<String>) -> Vec<u32> {
-    return instructions
-        .iter()
-        .flat_map(|instruction| {
-            pseudo_to_base_instructions(instruction)
-                .unwrap_or(vec![instruction.to_string()])
-        })
-        .map(|instruction: String| {
-            println!("[compiling] Instruction: '{}'", instruction);
-            let binary = compile_line(&instruction);
-            println!("[compiling] Output: '{:0>32b}'", binary);
-            binary
-        })
-        .collect();
+/// Everything after a `#` is a comment
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default().trim()
+}
+
+/// Splits a `name:` label off the start of a line, the rest of which may still hold an instruction
+fn strip_label(line: &str) -> (Option<&str>, &str) {
+    match line.split_once(':') {
+        Some((label, rest)) if is_label(label) => (Some(label), rest.trim()),
+        _ => (None, line)
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Where the instructions of a program come from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// The address of every label, that of the instruction following it
+    pub labels: HashMap<String, usize>,
+    /// The index of the source line of every instruction, a pseudo instruction's line for each one it expands to
+    pub lines: Vec<usize>
+}
+
+pub fn source_map(instructions: &[String]) -> Result<SourceMap, String> {
+    let mut map = SourceMap::default();
+    for (index, line) in instructions.iter().enumerate() {
+        let (label, instruction) = strip_label(strip_comment(line));
+        if let Some(label) = label {
+            if map.labels.insert(label.to_string(), 4 * map.lines.len()).is_some() {
+                return Err(format!("line {}: label '{}' is defined more than once", index + 1, label));
+            }
+        }
+        if !instruction.is_empty() {
+            let count = pseudo_to_base_instructions(instruction)
+                .map_err(|error| format!("line {}: {}", index + 1, error))?
+                .map_or(1, |instructions| instructions.len());
+            map.lines.extend(std::iter::repeat_n(index, count));
+        }
+    }
+    Ok(map)
+}
+
+/// A `.word` directive emits its value as is, whether it is a valid instruction or not
+fn parse_word(instruction: &str) -> Option<u32> {
+    let value = instruction.trim().strip_prefix(".word")?.trim();
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok()
+    }
+}
+
+/// Compiles for a hart implementing `isa`, rejecting any instruction that it does not support.
+/// Errors start with the number of the offending source line, counting from 1
+pub fn compile(instructions: Vec<String>, isa: &Isa, tracer: &mut dyn Tracer) -> Result<Vec<u32>, String> {
+    let mut binaries = Vec::new();
+    for (index, line) in instructions.iter().enumerate() {
+        let at_line = |error: String| format!("line {}: {}", index + 1, error);
+        let instruction = strip_label(strip_comment(line)).1;
+        if instruction.is_empty() {
+            continue;
+        }
+        let expanded = pseudo_to_base_instructions(instruction)
+            .map_err(at_line)?
+            .unwrap_or(vec![instruction.to_string()]);
+        for instruction in expanded {
+            let binary = match parse_word(&instruction) {
+                Some(word) => word,
+                None => compile_checked(&instruction, isa).map_err(at_line)?
+            };
+            if tracer.enabled() {
+                tracer.trace(&TraceEvent::Compile { line: &instruction, word: binary });
+            }
+            binaries.push(binary);
+        }
+    }
+    Ok(binaries)
+}
+
+fn compile_checked(instruction: &str, isa: &Isa) -> Result<u32, String> {
+    let binary = compile_line(instruction).map_err(|error| format!("{} in '{}'", error, instruction))?;
+    // On RV64 pack zero-extends a word rather than a halfword, so zext.h is packw instead
+    let binary = match instruction.split_whitespace().next() {
+        Some("zext.h") if isa.xlen() == 64 => binary - instruction::OP + instruction::OP_32,
+        _ => binary
+    };
+    let decoded = Instruction::from(binary)
+        .ok_or_else(|| format!("'{}' is not a valid instruction", instruction))?;
+    if let Some(index) = decoded.registers().into_iter().find(|&index| index >= isa.registers()) {
+        return Err(format!("register x{} does not exist in {}: '{}'", index, isa, instruction));
+    }
+    if !decoded.is_supported_by(isa) {
+        return Err(format!("'{}' is not part of {}", instruction, isa));
+    }
+    Ok(binary)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::assembly_compiler::{compile, compile_line};
+    use crate::assembly_compiler::{compile, compile_line, source_map};
+    use crate::trace::NoopTracer;
+    use crate::isa::Isa;
 
     #[test]
     fn test_compile_add() {
         let instruction = "add x5, x0, x1";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0000000_00001_00000_000_00101_0110011)
     }
@@ -462,7 +1238,7 @@ mod tests {
     fn test_compile_slt() {
         let instruction = "slt x5, x0, x1";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0000000_00001_00000_010_00101_0110011)
     }
@@ -471,7 +1247,7 @@ mod tests {
     fn test_compile_sra() {
         let instruction = "sra x5, x0, x1";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0100000_00001_00000_101_00101_0110011)
     }
@@ -480,7 +1256,7 @@ mod tests {
     fn test_compile_addi() {
         let instruction = "addi x5, x4, 20";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b000000010100_00100_000_00101_0010011)
     }
@@ -489,7 +1265,7 @@ mod tests {
     fn test_compile_slti() {
         let instruction = "slti x5, x4, 20";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b000000010100_00100_010_00101_0010011)
     }
@@ -498,16 +1274,111 @@ mod tests {
     fn test_compile_srai() {
         let instruction = "srai x5, x0, 20";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0100000_10100_00000_101_00101_0010011)
     }
 
+    #[test]
+    fn test_compile_sh2add() {
+        let instruction = "sh2add x5, x4, x1";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0010000_00001_00100_100_00101_0110011)
+    }
+
+    #[test]
+    fn test_compile_rori() {
+        let instruction = "rori x5, x4, 7";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0110000_00111_00100_101_00101_0010011)
+    }
+
+    #[test]
+    fn test_compile_bseti() {
+        let instruction = "bseti x5, x4, 31";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0010100_11111_00100_001_00101_0010011)
+    }
+
+    #[test]
+    fn test_compile_cpop() {
+        let instruction = "cpop a0, a1";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0110000_00010_01011_001_01010_0010011)
+    }
+
+    #[test]
+    fn test_compile_rev8() {
+        let instruction = "rev8 a0, a1";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0110100_11000_01011_101_01010_0010011)
+    }
+
+    #[test]
+    fn test_compile_zext_h() {
+        let instruction = "zext.h a0, a1";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0000100_00000_01011_100_01010_0110011)
+    }
+
+    #[test]
+    fn test_compile_zext_h_rv64() {
+        let instructions = vec![String::from("zext.h a0, a1"), String::from("packw a0, a1, a2")];
+
+        let ops = compile(instructions, &Isa::all(64), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b0000100_00000_01011_100_01010_0111011, 0b0000100_01100_01011_100_01010_0111011])
+    }
+
+    #[test]
+    fn test_compile_packh() {
+        let instruction = "packh a0, a1, a2";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0000100_01100_01011_111_01010_0110011)
+    }
+
+    #[test]
+    fn test_compile_aes32esmi() {
+        let instruction = "aes32esmi a0, a0, a1, 2";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b10_10011_01011_01010_000_01010_0110011)
+    }
+
+    #[test]
+    fn test_compile_aes_byte_select_out_of_range() {
+        assert_eq!(Err(String::from("'7' is out of range, expected 0 to 3")), compile_line("aes32esi a0, a0, a1, 7"));
+    }
+
+    #[test]
+    fn test_compile_sha256sig0() {
+        let instruction = "sha256sig0 a0, a1";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0001000_00010_01011_001_01010_0010011)
+    }
+
     #[test]
     fn test_compile_lui() {
         let instruction = "lui x5, 1234";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
         println!("{:0>32b}", op);
         assert_eq!(op, 0b00000000010011010010_00101_0110111)
     }
@@ -516,7 +1387,7 @@ mod tests {
     fn test_compile_jal() {
         let instruction = "jal x5, 1234";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         println!("{:0>32b}", op);
         assert_eq!(op, 0b01001101001000000000001011101111)
@@ -526,7 +1397,7 @@ mod tests {
     fn test_compile_jalr() {
         let instruction = "jalr x5, x3, 1234";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b010011010010_00011_000_00101_1100111)
     }
@@ -535,7 +1406,7 @@ mod tests {
     fn test_compile_beq() {
         let instruction = "beq x5, x3, 1234";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0_100110_00011_00101_000_1001_0_1100011)
     }
@@ -544,7 +1415,7 @@ mod tests {
     fn test_compile_bltu() {
         let instruction = "bltu x5, x3, 1234";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0_100110_00011_00101_110_1001_0_1100011)
     }
@@ -553,7 +1424,7 @@ mod tests {
     fn test_compile_lw() {
         let instruction = "lw t2, 0(t3)";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b000000000000_11100_010_00111_0000011)
     }
@@ -562,7 +1433,7 @@ mod tests {
     fn test_compile_lbu() {
         let instruction = "lbu t2, 0(t3)";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b000000000000_11100_100_00111_0000011)
     }
@@ -571,7 +1442,7 @@ mod tests {
     fn test_compile_sw() {
         let instruction = "sw t2, 0(t3)";
 
-        let op = compile_line(instruction);
+        let op = compile_line(instruction).unwrap();
 
         assert_eq!(op, 0b0000000_00111_11100_010_00000_0100011)
     }
@@ -580,7 +1451,7 @@ mod tests {
     fn test_compile_beqz() {
         let instruction = "beqz t2, 6".to_string();
 
-        let ops = compile(vec![instruction]);
+        let ops = compile(vec![instruction], &Isa::all(32), &mut NoopTracer).unwrap();
 
         assert_eq!(ops, vec![0b0_000000_00000_00111_000_0011_0_1100011])
     }
@@ -589,12 +1460,397 @@ mod tests {
     fn test_compile_call() {
         let instruction = "call 123456789".to_string();
 
-        let ops = compile(vec![instruction]);
+        let ops = compile(vec![instruction], &Isa::all(32), &mut NoopTracer).unwrap();
 
         assert_eq!(ops, vec![
-            0b00000111010110111100_00110_0010111,
+            0b00000111010110111101_00110_0010111,
             0b110100010101_00110_000_00001_1100111
         ])
     }
 
+    #[test]
+    fn test_compile_call_backwards() {
+        let instruction = "call -8".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![
+            0b00000000000000000000_00110_0010111,
+            0b111111111000_00110_000_00001_1100111
+        ])
+    }
+
+    #[test]
+    fn test_compile_csrrw() {
+        let instruction = "csrrw x0, mtvec, t0";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b001100000101_00101_001_00000_1110011)
+    }
+
+    #[test]
+    fn test_compile_csrrs_numeric() {
+        let instruction = "csrrs a0, 0xF14, x0";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b111100010100_00000_010_01010_1110011)
+    }
+
+    #[test]
+    fn test_compile_csrrci() {
+        let instruction = "csrrci a0, mstatus, 8";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b001100000000_01000_111_01010_1110011)
+    }
+
+    #[test]
+    fn test_compile_csr_immediate_out_of_range() {
+        let instruction = "csrrwi x1, mstatus, 100".to_string();
+
+        assert_eq!(Err(String::from("line 1: '100' is out of range, expected 0 to 31 in 'csrrwi x1, mstatus, 100'")), compile(vec![instruction], &Isa::all(32), &mut NoopTracer));
+        assert!(compile_line("csrrwi x0, 0xfff, 4000000").is_err());
+        assert!(compile_line("csrrsi x0, mstatus, -1").is_err());
+    }
+
+    #[test]
+    fn test_compile_rdinstret() {
+        let instruction = "rdinstret a0".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b110000000010_00000_010_01010_1110011])
+    }
+
+    #[test]
+    fn test_compile_csrr() {
+        let instruction = "csrr a0, mscratch".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b001101000000_00000_010_01010_1110011])
+    }
+
+    #[test]
+    fn test_compile_slli_rv64() {
+        let instruction = "slli x5, x5, 33";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b000000_100001_00101_001_00101_0010011)
+    }
+
+    #[test]
+    fn test_compile_sraiw() {
+        let instruction = "sraiw a0, a1, 3";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0100000_00011_01011_101_01010_0011011)
+    }
+
+    #[test]
+    fn test_compile_subw() {
+        let instruction = "subw a0, a1, a2";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0100000_01100_01011_000_01010_0111011)
+    }
+
+    #[test]
+    fn test_compile_ld() {
+        let instruction = "ld a0, 8(sp)";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b000000001000_00010_011_01010_0000011)
+    }
+
+    #[test]
+    fn test_compile_sd() {
+        let instruction = "sd a0, 8(sp)";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0000000_01010_00010_011_01000_0100011)
+    }
+
+    #[test]
+    fn test_compile_sext_w() {
+        let instruction = "sext.w a0, a1".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all(64), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b000000000000_01011_000_01010_0011011])
+    }
+
+    #[test]
+    fn test_compile_rv32e() {
+        let instruction = "add a5, a0, a1".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all_embedded(), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b0000000_01011_01010_000_01111_0110011])
+    }
+
+    #[test]
+    fn test_compile_rv32e_upper_register() {
+        let instruction = "add a0, a0, a6".to_string();
+
+        let result = compile(vec![instruction], &Isa::all_embedded(), &mut NoopTracer);
+
+        assert!(result.unwrap_err().starts_with("line 1: register x16 does not exist in rv32e"));
+    }
+
+    #[test]
+    fn test_compile_rv32e_csr_immediate() {
+        let instruction = "csrrwi zero, mscratch, 31".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all_embedded(), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b001101000000_11111_101_00000_1110011])
+    }
+
+    #[test]
+    fn test_compile_isa() {
+        let instructions = vec!["sh1add a0, a0, a1".to_string(), "csrr a0, mscratch".to_string()];
+
+        let ops = compile(instructions, &Isa::parse("rv32i_zicsr_zba").unwrap(), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops.len(), 2)
+    }
+
+    #[test]
+    fn test_compile_isa_disabled_extension() {
+        let instruction = "clz a0, a1".to_string();
+
+        assert_eq!(Err(String::from("line 1: 'clz a0, a1' is not part of rv32i_zicsr_zba")), compile(vec![instruction], &Isa::parse("rv32i_zicsr_zba").unwrap(), &mut NoopTracer));
+    }
+
+    #[test]
+    fn test_compile_isa_rv64_only() {
+        let instruction = "ld a0, 0(a1)".to_string();
+
+        assert_eq!(Err(String::from("line 1: 'ld a0, 0(a1)' is not part of rv32i")), compile(vec![instruction], &Isa::parse("rv32i").unwrap(), &mut NoopTracer));
+    }
+
+
+    #[test]
+    fn test_compile_vsetvli() {
+        let instruction = "vsetvli t0, a1, e32, m1, ta, ma";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b0_00011010000_01011_111_00101_1010111)
+    }
+
+    #[test]
+    fn test_compile_vsetivli() {
+        let instruction = "vsetivli zero, 4, e16, m2";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b11_0000001001_00100_111_00000_1010111)
+    }
+
+    #[test]
+    fn test_compile_vsetvl() {
+        let instruction = "vsetvl a0, a1, a2";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b1000000_01100_01011_111_01010_1010111)
+    }
+
+    #[test]
+    fn test_compile_vle32() {
+        let instruction = "vle32.v v1, (a0)";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b000_0_00_1_00000_01010_110_00001_0000111)
+    }
+
+    #[test]
+    fn test_compile_vsse32_masked() {
+        let instruction = "vsse32.v v1, (a0), a1, v0.t";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b000_0_10_0_01011_01010_110_00001_0100111)
+    }
+
+    #[test]
+    fn test_compile_vadd_vi_masked() {
+        let instruction = "vadd.vi v1, v2, -1, v0.t";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b000000_0_00010_11111_011_00001_1010111)
+    }
+
+    #[test]
+    fn test_compile_vector_immediate_out_of_range() {
+        assert_eq!(Err(String::from("'100' is out of range, expected -16 to 15")), compile_line("vadd.vi v1, v2, 100"));
+        assert_eq!(Err(String::from("'-1' is out of range, expected 0 to 31")), compile_line("vsll.vi v1, v2, -1"));
+        assert_eq!(Err(String::from("'32' is out of range, expected 0 to 31")), compile_line("vsetivli zero, 32, e16, m2"));
+        assert_eq!(compile_line("vsra.vi v1, v2, 31").unwrap(), 0b101001_1_00010_11111_011_00001_1010111);
+    }
+
+    #[test]
+    fn test_compile_vredsum() {
+        let instruction = "vredsum.vs v8, v1, v8";
+
+        let op = compile_line(instruction).unwrap();
+
+        assert_eq!(op, 0b000000_1_00001_01000_010_01000_1010111)
+    }
+
+    #[test]
+    fn test_compile_vmv() {
+        assert_eq!(compile_line("vmv.x.s a0, v8").unwrap(), 0b010000_1_01000_00000_010_01010_1010111);
+        assert_eq!(compile_line("vmv.s.x v8, x0").unwrap(), 0b010000_1_00000_00000_110_01000_1010111);
+        assert_eq!(compile_line("vmv.v.i v4, 5").unwrap(), 0b010111_1_00000_00101_011_00100_1010111);
+        assert_eq!(compile_line("vmv2r.v v2, v4").unwrap(), 0b100111_1_00100_00001_011_00010_1010111);
+        assert_eq!(compile_line("vmerge.vxm v1, v2, a0, v0").unwrap(), 0b010111_0_00010_01010_100_00001_1010111);
+    }
+
+    #[test]
+    fn test_compile_vnot() {
+        let instruction = "vnot.v v1, v2, v0.t".to_string();
+
+        let ops = compile(vec![instruction], &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b001011_0_00010_11111_011_00001_1010111])
+    }
+
+    #[test]
+    fn test_compile_isa_without_vector() {
+        let instruction = "vle32.v v1, (a0)".to_string();
+
+        assert_eq!(Err(String::from("line 1: 'vle32.v v1, (a0)' is not part of rv32i")), compile(vec![instruction], &Isa::parse("rv32i").unwrap(), &mut NoopTracer));
+    }
+
+    #[test]
+    fn test_compile_word() {
+        let instructions = vec![String::from(".word 0xffffffff"), String::from(".word 19")];
+
+        let ops = compile(instructions, &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0xFFFFFFFF, 19])
+    }
+
+    #[test]
+    fn test_compile_system() {
+        let instructions = vec![
+            String::from("ecall"), String::from("ebreak"), String::from("sret"), String::from("mret"),
+            String::from("wfi"), String::from("sfence.vma"), String::from("sfence.vma a0, a1")
+        ];
+
+        let ops = compile(instructions, &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0x00000073, 0x00100073, 0x10200073, 0x30200073, 0x10500073, 0x12000073, 0x12b50073])
+    }
+
+    #[test]
+    fn test_compile_comments() {
+        let instructions = vec![
+            String::from("# a comment on its own line"),
+            String::from(""),
+            String::from("addi x1, x0, 1 # and one after an instruction"),
+        ];
+
+        let ops = compile(instructions, &Isa::all(32), &mut NoopTracer).unwrap();
+
+        assert_eq!(ops, vec![0b000000000001_00000_000_00001_0010011])
+    }
+
+    #[test]
+    fn test_compile_labels() {
+        let instructions = vec![
+            String::from("start:"),
+            String::from("addi x1, x0, 1"),
+            String::from("loop: addi x1, x1, 1 # labels may share a line"),
+            String::from("end: # or a line of their own"),
+        ];
+
+        let ops = compile(instructions.clone(), &Isa::all(32), &mut NoopTracer).unwrap();
+        let map = source_map(&instructions).unwrap();
+
+        assert_eq!(ops, vec![0b000000000001_00000_000_00001_0010011, 0b000000000001_00001_000_00001_0010011]);
+        assert_eq!((map.labels["start"], map.labels["loop"], map.labels["end"]), (0, 4, 8));
+        assert_eq!(map.lines, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_duplicate_label() {
+        let instructions = vec![String::from("loop:"), String::from("loop: nop")];
+
+        assert_eq!(source_map(&instructions), Err(String::from("line 2: label 'loop' is defined more than once")));
+    }
+
+    #[test]
+    fn test_compile_invalid_shift() {
+        let instruction = "slli a0, a1, 64".to_string();
+
+        assert_eq!(Err(String::from("line 1: '64' is out of range, expected 0 to 63 in 'slli a0, a1, 64'")), compile(vec![instruction], &Isa::all(64), &mut NoopTracer));
+        assert_eq!(Err(String::from("line 1: 'slli a0, a1, 40' is not part of rv32i")), compile(vec![String::from("slli a0, a1, 40")], &Isa::parse("rv32i").unwrap(), &mut NoopTracer));
+    }
+
+    #[test]
+    fn test_compile_immediate_out_of_range() {
+        for instruction in [
+            "srai x1, x2, -1", "rori x1, x2, -1", "bseti x1, x2, -1", "slliw x1, x2, 32",
+            "addi x1, x0, 5000", "addi x1, x0, -2049", "lw x1, 2048(x2)", "sb x1, 4000(x2)",
+            "beq x1, x2, 4096", "jal x1, -1048578", "lui x1, 1048576", "auipc x1, -1"
+        ] {
+            assert!(compile_line(instruction).unwrap_err().contains("is out of range"), "{}", instruction);
+        }
+        assert_eq!(compile_line("addi x1, x0, -2048").unwrap(), 0b100000000000_00000_000_00001_0010011);
+        assert_eq!(compile_line("sb x1, 2047(x2)").unwrap(), 0b0111111_00001_00010_000_11111_0100011);
+    }
+
+    #[test]
+    fn test_compile_odd_offset() {
+        assert_eq!(Err(String::from("'3' is not a multiple of 2")), compile_line("beq x1, x2, 3"));
+        assert_eq!(Err(String::from("'-5' is not a multiple of 2")), compile_line("jal x1, -5"));
+    }
+
+    #[test]
+    fn test_compile_unknown_instruction() {
+        let instructions = vec![String::from("nop"), String::from("frob a0, a1")];
+
+        assert_eq!(Err(String::from("line 2: unknown instruction 'frob' in 'frob a0, a1'")), compile(instructions, &Isa::all(32), &mut NoopTracer));
+    }
+
+    #[test]
+    fn test_compile_invalid_register() {
+        let instruction = "add a0, a1, y2".to_string();
+
+        assert_eq!(Err(String::from("line 1: 'y2' is not a register in 'add a0, a1, y2'")), compile(vec![instruction], &Isa::all(32), &mut NoopTracer));
+        assert_eq!(Err(String::from("'x32' is not a register")), compile_line("add a0, x32, a1"));
+    }
+
+    #[test]
+    fn test_compile_extra_operand() {
+        assert_eq!(Err(String::from("unexpected operand 'x4'")), compile_line("add x1, x2, x3, x4"));
+        assert_eq!(Err(String::from("unexpected operand 'x1'")), compile_line("ecall x1"));
+        assert_eq!(Err(String::from("unexpected operand 'a2'")), compile_line("sfence.vma a0, a1, a2"));
+        assert_eq!(Err(String::from("unexpected operand 'x9'")), compile_line("vadd.vv v1, v2, v3, x9, v0.t"));
+        assert_eq!(Err(String::from("vmerge selects with v0, not 'v4'")), compile_line("vmerge.vvm v1, v2, v3, v4"));
+        assert_eq!(Err(String::from("line 1: unexpected operand 'a2' in 'mv a0, a1, a2'")), compile(vec![String::from("mv a0, a1, a2")], &Isa::all(32), &mut NoopTracer));
+        assert_eq!(Err(String::from("line 1: unexpected operand 'v0.t' in 'vmnot.m v1, v2, v0.t'")), compile(vec![String::from("vmnot.m v1, v2, v0.t")], &Isa::all(32), &mut NoopTracer));
+    }
+
+    #[test]
+    fn test_compile_non_numeric_immediate() {
+        let instructions = vec![String::from("loop:"), String::from("beq a0, a1, loop")];
+
+        assert_eq!(Err(String::from("line 2: 'loop' is not a number in 'beq a0, a1, loop'")), compile(instructions, &Isa::all(32), &mut NoopTracer));
+        assert_eq!(Err(String::from("line 1: missing operand in 'li a0'")), compile(vec![String::from("li a0")], &Isa::all(32), &mut NoopTracer));
+    }
 }
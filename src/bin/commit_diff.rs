@@ -0,0 +1,39 @@
+//! Compares two commit logs, like the ones of `spike --log-commits` and `CommitLogTracer`,
+//! and reports the first commit they disagree on.
+//!
+//! Usage: `commit_diff <expected> <found> [context]`, exiting with 0 when the logs match,
+//! 1 when they diverge and 2 when they cannot be read.
+
+use std::{env, fs, process};
+use risc_v_emulator::trace::compare_commit_logs;
+
+/// Commits shown before the divergence unless told otherwise
+const CONTEXT: usize = 5;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!("usage: {} <expected> <found> [context]", args[0]);
+        process::exit(2);
+    }
+    let context = match args.get(3).map(|context| context.parse()) {
+        None => CONTEXT,
+        Some(Ok(context)) => context,
+        Some(Err(_)) => {
+            eprintln!("context must be a number of commits, not '{}'", args[3]);
+            process::exit(2);
+        }
+    };
+
+    let read = |path: &str| fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("cannot read {}: {}", path, error);
+        process::exit(2);
+    });
+    match compare_commit_logs(&read(&args[1]), &read(&args[2]), context) {
+        None => println!("commit logs match"),
+        Some(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! A debug adapter for editors like VS Code, speaking the Debug Adapter Protocol on standard input and output.
+//!
+//! Configure it as the adapter's executable and launch with `{"program": "path/to/program.s"}`,
+//! see `risc_v_emulator::dap` for the other launch arguments.
+
+use std::io;
+use std::process;
+use risc_v_emulator::dap::DebugAdapter;
+
+fn main() {
+    if let Err(error) = DebugAdapter::new().serve(io::stdin().lock(), io::stdout()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,494 @@
+//! A gdb like debugger for RV32 assembly programs.
+//!
+//! Usage: `rvdb [--isa <isa>] [--reg <register>=<value>]... [--mem <address>=<value>,...]... [--history <n>] [--cells <n>] [--tlb <entries>[,<ways>[,lru|fifo|random]]] [--time manual|instructions|wallclock[:<hz>]] [--gdb <address>|-] <program.s>`
+//!
+//! The program is assembled and stopped before its first instruction, with the registers and
+//! memory cells given on the command line set. `run` starts it over with the same setup.
+//! An empty line repeats the last command, `help` lists the others. The last instructions executed,
+//! 10000 unless `--history` says otherwise, can be undone with `reverse-step` and `reverse-continue`.
+//! Memory has 1024 cells unless `--cells` asks for more, e.g. for page tables. `--tlb` puts a TLB in front of
+//! Sv32 translation, fully associative with LRU replacement unless the ways and policy are given.
+//! The CLINT's `mtime` ticks once per instruction unless `--time` makes it wait for writes or follow the
+//! host's clock, at 10 MHz unless another frequency is given.
+//!
+//! With `--gdb` there is no prompt, gdb debugs the program instead through the remote serial protocol,
+//! on a TCP address like `localhost:1234` or on standard input and output for `-`:
+//! `target remote localhost:1234` or `target remote | rvdb --gdb - program.s`.
+
+use std::{env, process};
+use std::net::TcpListener;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use risc_v_emulator::clint::TimeSource;
+use risc_v_emulator::debug::{is_call, Breakpoint, Location, WatchAccess, Watchpoint};
+use risc_v_emulator::disassembler::{disassemble, register_index, register_name};
+use risc_v_emulator::gdb::GdbStub;
+use risc_v_emulator::processor::{HaltConditions, HaltReason, Processor};
+use risc_v_emulator::snapshot::Snapshot;
+use risc_v_emulator::tlb::{Replacement, TlbConfig};
+
+const HELP: &str = "\
+step [n]                   execute n instructions, 1 by default
+next [n]                   like step, but runs calls until they return
+continue                   run until a breakpoint, a watchpoint or the end of the program
+reverse-step [n]           undo n instructions, 1 by default
+reverse-continue           undo instructions back to a breakpoint or watchpoint
+run                        start the program over and continue
+break <label|address>      stop before the instruction at the address
+watch <register|address>   stop after a write to the register or memory cell, or to cells <start>..<end>
+rwatch <address>           stop after a read of the memory cells
+awatch <address>           stop after a read or write of the memory cells
+info registers             print the integer registers
+info breakpoints           print the breakpoints and watchpoints
+info tlb                   print the TLB statistics
+x/<n>w <address>           print n memory cells
+disassemble                print the program, marking the next instruction
+set reg <register> <value> change a register
+save <file>                write the state of the machine to a snapshot file
+restore <file>             return the machine to the state of a snapshot file
+quit                       leave the debugger";
+
+/// Instructions that can be undone unless told otherwise
+const HISTORY: usize = 10000;
+
+/// The frequency of `mtime` following the host's clock unless told otherwise, that of QEMU's virt machine
+const WALL_CLOCK_HZ: u64 = 10_000_000;
+
+/// Cells printed per line by `x`
+const CELLS_PER_LINE: usize = 4;
+
+/// How the program is loaded, again for every `run`
+struct Setup {
+    isa: Option<String>,
+    registers: Vec<(usize, u32)>,
+    memory: Vec<(usize, Vec<u32>)>,
+    history: usize,
+    /// The size of memory, if not the default
+    cells: Option<usize>,
+    tlb: Option<TlbConfig>,
+    time: TimeSource,
+    /// Where to serve gdb, `-` for standard input and output
+    gdb: Option<String>,
+    path: String
+}
+
+struct Debugger {
+    setup: Setup,
+    processor: Processor,
+    /// The cells holding the program's instructions
+    program: (usize, usize),
+    /// Every breakpoint and watchpoint, in the order their ids were given out
+    points: Vec<Point>
+}
+
+enum Point {
+    Break(usize),
+    Watch(Watchpoint)
+}
+
+fn main() {
+    let setup = parse_args(env::args().skip(1).collect()).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!("usage: rvdb [--isa <isa>] [--reg <register>=<value>]... [--mem <address>=<value>,...]... [--history <n>] [--cells <n>] [--tlb <entries>[,<ways>[,lru|fifo|random]]] [--time manual|instructions|wallclock[:<hz>]] [--gdb <address>|-] <program.s>");
+        process::exit(2);
+    });
+    if let Some(address) = &setup.gdb {
+        if let Err(error) = serve_gdb(&setup, address) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    let mut debugger = Debugger::new(setup).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(rvdb) ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let command = match line.trim() {
+            "" => last.clone(),
+            command => command.to_string()
+        };
+        if command == "quit" || command == "q" {
+            break;
+        }
+        if let Err(error) = debugger.execute(&command) {
+            println!("{}", error);
+        }
+        last = command;
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Setup, String> {
+    let mut setup = Setup { isa: None, registers: Vec::new(), memory: Vec::new(), history: HISTORY, cells: None, tlb: None, time: TimeSource::Instructions, gdb: None, path: String::new() };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--isa" => setup.isa = Some(value("--isa")?),
+            "--gdb" => setup.gdb = Some(value("--gdb")?),
+            "--history" => {
+                let history = value("--history")?;
+                setup.history = history.parse().map_err(|_| format!("'{}' is not a number of instructions", history))?;
+            },
+            "--cells" => {
+                let cells = value("--cells")?;
+                setup.cells = Some(cells.parse().map_err(|_| format!("'{}' is not a number of cells", cells))?);
+            },
+            "--tlb" => setup.tlb = Some(parse_tlb(&value("--tlb")?)?),
+            "--time" => setup.time = parse_time(&value("--time")?)?,
+            "--reg" => {
+                let assignment = value("--reg")?;
+                let (register, number) = assignment.split_once('=').ok_or(format!("expected <register>=<value>, not '{}'", assignment))?;
+                setup.registers.push((parse_register(register)?, parse_number(number)?));
+            },
+            "--mem" => {
+                let assignment = value("--mem")?;
+                let (address, values) = assignment.split_once('=').ok_or(format!("expected <address>=<value>,..., not '{}'", assignment))?;
+                let values = values.split(',').map(parse_number).collect::<Result<_, _>>()?;
+                setup.memory.push((parse_number(address)? as usize, values));
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {}", flag)),
+            path if setup.path.is_empty() => setup.path = path.to_string(),
+            _ => return Err(String::from("only one program can be debugged"))
+        }
+    }
+    if setup.path.is_empty() {
+        return Err(String::from("no program to debug"));
+    }
+    Ok(setup)
+}
+
+impl Debugger {
+    fn new(setup: Setup) -> Result<Debugger, String> {
+        let (processor, program) = load(&setup)?;
+        Ok(Debugger { setup, processor, program, points: Vec::new() })
+    }
+
+    fn execute(&mut self, command: &str) -> Result<(), String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        match (name, args.as_slice()) {
+            ("step" | "s" | "stepi" | "si", args) => {
+                for _ in 0..count(args)? {
+                    let outcome = self.processor.step().map_err(|error| error.to_string())?;
+                    if let Some(reason) = outcome.halt {
+                        return self.stopped(reason);
+                    }
+                }
+                self.print_next()
+            },
+            ("next" | "n" | "nexti" | "ni", args) => {
+                for _ in 0..count(args)? {
+                    let pc = self.processor.pc();
+                    let call = self.instruction(pc).is_some_and(is_call);
+                    let outcome = self.processor.run_until(|outcome| !call || outcome.next_pc == pc + 4)
+                        .map_err(|error| error.to_string())?;
+                    if let Some(reason) = outcome.halt {
+                        return self.stopped(reason);
+                    }
+                }
+                self.print_next()
+            },
+            ("continue" | "c", []) => self.resume(),
+            ("reverse-step" | "rs" | "reverse-stepi" | "rsi", args) => {
+                for _ in 0..count(args)? {
+                    if self.processor.step_back().is_none() {
+                        return self.stopped(HaltReason::StartOfHistory);
+                    }
+                }
+                self.print_next()
+            },
+            ("reverse-continue" | "rc", []) => {
+                let reason = self.processor.reverse_continue();
+                self.stopped(reason)
+            },
+            ("run" | "r", []) => {
+                let (processor, program) = load(&self.setup)?;
+                self.processor = processor;
+                self.program = program;
+                for point in std::mem::take(&mut self.points) {
+                    self.add(point);
+                }
+                self.resume()
+            },
+            ("break" | "b", [location]) => {
+                let address = match self.processor.label(location) {
+                    Some(address) => address,
+                    None => parse_number(location)? as usize
+                };
+                let id = self.add(Point::Break(address));
+                println!("Breakpoint {} at {}", id, self.describe(address));
+                Ok(())
+            },
+            ("watch", [target]) => {
+                let watchpoint = match parse_register(target) {
+                    Ok(index) => Watchpoint::Register(index),
+                    Err(_) => Watchpoint::Memory { range: parse_range(target)?, access: WatchAccess::Write }
+                };
+                let id = self.add(Point::Watch(watchpoint));
+                println!("Watchpoint {}: {}", id, target);
+                Ok(())
+            },
+            ("rwatch" | "awatch", [target]) => {
+                let access = if name == "rwatch" { WatchAccess::Read } else { WatchAccess::Access };
+                let id = self.add(Point::Watch(Watchpoint::Memory { range: parse_range(target)?, access }));
+                println!("Watchpoint {}: {}", id, target);
+                Ok(())
+            },
+            ("info" | "i", ["registers" | "reg" | "r"]) => {
+                for index in 0..self.processor.register_count() {
+                    let value = self.processor.get_registry_value(index).map_err(|error| error.to_string())?;
+                    println!("{:<8} {:#010x} {}", register_name(index), value, value as i32);
+                }
+                println!("{:<8} {:#010x}", "pc", self.processor.pc());
+                Ok(())
+            },
+            ("info" | "i", ["breakpoints" | "break" | "b"]) => {
+                for (id, breakpoint) in self.processor.breakpoints() {
+                    println!("{:<3} breakpoint {}, hit {} times", id, self.describe(breakpoint.address), breakpoint.hits());
+                }
+                for (id, watchpoint) in self.processor.watchpoints() {
+                    println!("{:<3} watchpoint {:?}", id, watchpoint);
+                }
+                Ok(())
+            },
+            ("info" | "i", ["tlb"]) => {
+                let stats = self.processor.tlb_stats().ok_or("there is no TLB, start rvdb with --tlb")?;
+                println!("hits      {}", stats.hits);
+                println!("misses    {}", stats.misses);
+                println!("hit rate  {:.1}%", stats.hit_rate() * 100.0);
+                println!("evictions {}", stats.evictions);
+                println!("fences    {}", stats.fences);
+                Ok(())
+            },
+            ("disassemble" | "disas", []) => {
+                for address in (self.program.0..self.program.1).map(|cell| cell * 4) {
+                    let marker = if address == self.processor.pc() { "=>" } else { "  " };
+                    if let Some(label) = self.processor.label_at(address) {
+                        println!("{}:", label);
+                    }
+                    println!("{} {:#010x}: {}", marker, address, disassemble(self.instruction(address).unwrap_or_default()));
+                }
+                Ok(())
+            },
+            ("set", ["reg" | "register", register, value]) => {
+                set_register(&mut self.processor, parse_register(register)?, parse_number(value)?)
+            },
+            ("set", ["reg" | "register", assignment]) if assignment.contains('=') => {
+                let (register, value) = assignment.split_once('=').unwrap_or_default();
+                set_register(&mut self.processor, parse_register(register)?, parse_number(value)?)
+            },
+            ("save", [path]) => {
+                self.processor.snapshot().save(path).map_err(|error| error.to_string())?;
+                println!("Saved the machine state to {}", path);
+                Ok(())
+            },
+            ("restore", [path]) => {
+                let snapshot = Snapshot::load(path).map_err(|error| error.to_string())?;
+                self.processor.restore(&snapshot);
+                self.program = snapshot.program();
+                self.print_next()
+            },
+            ("help" | "h", []) => {
+                println!("{}", HELP);
+                Ok(())
+            },
+            (examine, [address]) if examine.starts_with("x/") && examine.ends_with('w') => {
+                let cells: usize = examine[2..examine.len() - 1].parse().or_else(|_| match &examine[2..examine.len() - 1] {
+                    "" => Ok(1),
+                    count => Err(format!("'{}' is not a number of cells", count))
+                })?;
+                self.examine(parse_number(address)? as usize, cells)
+            },
+            ("x", [address]) => self.examine(parse_number(address)? as usize, 1),
+            _ => Err(format!("unknown command '{}', try help", command))
+        }
+    }
+
+    fn resume(&mut self) -> Result<(), String> {
+        let outcome = self.processor.run_until(|_| false).map_err(|error| error.to_string())?;
+        self.stopped(outcome.halt.unwrap_or(HaltReason::EndOfProgram))
+    }
+
+    fn add(&mut self, point: Point) -> usize {
+        let id = match &point {
+            Point::Break(address) => self.processor.add_breakpoint(Breakpoint::new(*address)),
+            Point::Watch(watchpoint) => self.processor.add_watchpoint(watchpoint.clone())
+        };
+        self.points.push(point);
+        id
+    }
+
+    fn stopped(&self, reason: HaltReason) -> Result<(), String> {
+        match reason {
+            HaltReason::Breakpoint(id) => println!("Breakpoint {}, {}", id, self.describe(self.processor.pc())),
+            HaltReason::StartOfHistory => println!("No more reverse-execution history."),
+            HaltReason::Watchpoint(hit) => {
+                let location = match hit.location {
+                    Location::Register(index) => register_name(index).to_string(),
+                    Location::Memory(address) => format!("[{:#x}]", address)
+                };
+                println!("Watchpoint {}: {}", hit.id, location);
+                if hit.old == hit.new {
+                    println!("Value = {:#x}", hit.new);
+                } else {
+                    println!("Old value = {:#x}\nNew value = {:#x}", hit.old, hit.new);
+                }
+            },
+            reason => {
+                println!("Program stopped: {:?}, exit code {}", reason, reason.exit_code());
+                return Ok(());
+            }
+        }
+        self.print_next()
+    }
+
+    fn print_next(&self) -> Result<(), String> {
+        let pc = self.processor.pc();
+        match self.instruction(pc) {
+            Some(word) => println!("=> {}: {}", self.describe(pc), disassemble(word)),
+            None => println!("=> {} is outside of the program", self.describe(pc))
+        }
+        Ok(())
+    }
+
+    fn examine(&self, address: usize, cells: usize) -> Result<(), String> {
+        let values = self.processor.get_copy_of_memory(address..address + cells).map_err(|error| error.to_string())?;
+        for (line, values) in values.chunks(CELLS_PER_LINE).enumerate() {
+            let values: Vec<String> = values.iter().map(|value| format!("{:#010x}", value)).collect();
+            println!("{:#010x}: {}", address + line * CELLS_PER_LINE, values.join(" "));
+        }
+        Ok(())
+    }
+
+    /// The instruction word at `address`, if it is part of the program
+    fn instruction(&self, address: usize) -> Option<u32> {
+        if !(self.program.0..self.program.1).contains(&(address / 4)) {
+            return None;
+        }
+        self.processor.get_copy_of_memory(address / 4..address / 4 + 1).ok().map(|cells| cells[0])
+    }
+
+    fn describe(&self, address: usize) -> String {
+        match self.processor.label_at(address) {
+            Some(label) => format!("{:#010x} <{}>", address, label),
+            None => format!("{:#010x}", address)
+        }
+    }
+}
+
+fn serve_gdb(setup: &Setup, address: &str) -> Result<(), String> {
+    let (processor, _) = load(setup)?;
+    let mut stub = GdbStub::new(processor);
+    if address == "-" {
+        return stub.serve(io::stdin(), io::stdout()).map_err(|error| error.to_string());
+    }
+    let listener = TcpListener::bind(address).map_err(|error| format!("cannot listen on {}: {}", address, error))?;
+    eprintln!("Waiting for gdb on {}", address);
+    let (stream, _) = listener.accept().map_err(|error| error.to_string())?;
+    let input = stream.try_clone().map_err(|error| error.to_string())?;
+    stub.serve(input, stream).map_err(|error| error.to_string())
+}
+
+fn load(setup: &Setup) -> Result<(Processor, (usize, usize)), String> {
+    let mut processor = match &setup.isa {
+        Some(isa) => Processor::from_isa(isa).map_err(|error| error.to_string())?,
+        None => Processor::new()
+    };
+    processor.set_halt_conditions(HaltConditions { ecall_exit: true, ebreak: true, ..Default::default() });
+    processor.set_history_limit(setup.history);
+    if let Some(cells) = setup.cells {
+        processor.set_memory_size(cells);
+    }
+    processor.set_tlb(setup.tlb).map_err(|error| error.to_string())?;
+    processor.set_time_source(setup.time);
+    let program = processor.load_instructions(&setup.path).map_err(|error| error.to_string())?;
+    for &(index, value) in &setup.registers {
+        set_register(&mut processor, index, value)?;
+    }
+    for (address, values) in &setup.memory {
+        processor.store_in_memory(*address, values).map_err(|error| error.to_string())?;
+    }
+    Ok((processor, program))
+}
+
+fn set_register(processor: &mut Processor, index: usize, value: u32) -> Result<(), String> {
+    processor.set_register_value(index, value).map_err(|error| error.to_string())
+}
+
+fn count(args: &[&str]) -> Result<usize, String> {
+    match args {
+        [] => Ok(1),
+        [count] => count.parse().map_err(|_| format!("'{}' is not a count", count)),
+        _ => Err(String::from("expected at most one count"))
+    }
+}
+
+/// Registers by ABI name, as `x0` to `x31` or `fp`, with an optional `$` like in gdb
+fn parse_register(name: &str) -> Result<usize, String> {
+    let name = name.strip_prefix('$').unwrap_or(name);
+    register_index(name).ok_or(format!("'{}' is not a register", name))
+}
+
+/// A TLB of `<entries>[,<ways>[,<policy>]]`, fully associative with LRU replacement by default
+fn parse_tlb(text: &str) -> Result<TlbConfig, String> {
+    let mut fields = text.split(',');
+    let mut count = |name: &str| fields.next()
+        .map(|field| field.parse::<usize>().map_err(|_| format!("'{}' is not a number of {}", field, name)))
+        .transpose();
+    let entries = count("entries")?.ok_or("--tlb needs a number of entries")?;
+    let ways = count("ways")?.unwrap_or(entries);
+    let replacement = match fields.next() {
+        None | Some("lru") => Replacement::Lru,
+        Some("fifo") => Replacement::Fifo,
+        Some("random") => Replacement::Random,
+        Some(policy) => return Err(format!("unknown replacement policy '{}', expected lru, fifo or random", policy))
+    };
+    Ok(TlbConfig { entries, ways, replacement, ..TlbConfig::default() })
+}
+
+/// `manual`, `instructions` or `wallclock`, optionally followed by `:<hz>`
+fn parse_time(text: &str) -> Result<TimeSource, String> {
+    match text.split_once(':') {
+        None if text == "manual" => Ok(TimeSource::Manual),
+        None if text == "instructions" => Ok(TimeSource::Instructions),
+        None if text == "wallclock" => Ok(TimeSource::WallClock(WALL_CLOCK_HZ)),
+        Some(("wallclock", hz)) => match hz.parse() {
+            Ok(hz) if hz > 0 => Ok(TimeSource::WallClock(hz)),
+            _ => Err(format!("'{}' is not a frequency in Hz", hz))
+        },
+        _ => Err(format!("unknown time source '{}', expected manual, instructions or wallclock[:<hz>]", text))
+    }
+}
+
+/// Decimal numbers, which may be negative, and hexadecimal ones starting with `0x`
+fn parse_number(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse::<u32>().ok().or_else(|| text.parse::<i32>().ok().map(|value| value as u32))
+    };
+    value.ok_or(format!("'{}' is not a number", text))
+}
+
+/// A single memory cell or the cells `start..end`, with an optional `*` like in gdb
+fn parse_range(text: &str) -> Result<Range<usize>, String> {
+    let text = text.strip_prefix('*').unwrap_or(text);
+    match text.split_once("..") {
+        Some((start, end)) => Ok(parse_number(start)? as usize..parse_number(end)? as usize),
+        None => {
+            let address = parse_number(text)? as usize;
+            Ok(address..address + 1)
+        }
+    }
+}
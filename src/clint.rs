@@ -0,0 +1,256 @@
+//! The core-local interruptor: the machine timer and the software interrupt of a hart.
+//!
+//! Its registers are mapped at the conventional byte addresses of the SiFive CLINT, and since loads and stores
+//! address whole cells they are reached at those addresses divided by four. The 64-bit `mtime` and `mtimecmp`
+//! take two cells each on RV32, the upper half second, while on RV64 the first cell holds the whole value.
+
+use std::time::Instant;
+use crate::memory::Memory;
+use crate::trap::{Exception, Interrupt};
+use crate::xlen::Xlen;
+
+/// The byte address of `msip`, whose lowest bit raises the machine software interrupt
+pub const MSIP: usize = 0x0200_0000;
+/// The byte address of `mtimecmp`, the machine timer interrupt is pending while `mtime >= mtimecmp`
+pub const MTIMECMP: usize = 0x0200_4000;
+/// The byte address of `mtime`, which the `time` CSR shows too
+pub const MTIME: usize = 0x0200_bff8;
+
+/// What makes `mtime` advance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSource {
+    /// Only writes to `mtime` and `Processor::advance_time` change it, so runs are repeatable tick for tick
+    Manual,
+    /// One tick for every instruction retired, like the `time` CSR of a hart without a timer device
+    Instructions,
+    /// The host's clock, ticking at the frequency in Hz
+    WallClock(u64)
+}
+
+/// The timer and software interrupt registers of a single hart
+#[derive(Debug, Clone)]
+pub struct Clint {
+    source: TimeSource,
+    /// The value of `mtime`, as of `epoch` for the wall clock
+    mtime: u64,
+    epoch: Instant,
+    mtimecmp: u64,
+    msip: bool,
+    /// The levels of the timer and software interrupt lines when `edges` last looked at them
+    lines: [bool; 2]
+}
+
+impl Clint {
+    /// A CLINT whose `mtimecmp` starts at its maximum, so that no timer interrupt is pending until the program sets it
+    pub fn new(source: TimeSource) -> Clint {
+        Clint { source, mtime: 0, epoch: Instant::now(), mtimecmp: u64::MAX, msip: false, lines: [false; 2] }
+    }
+
+    pub fn source(&self) -> TimeSource {
+        self.source
+    }
+
+    /// Switches to another time source, continuing from the current `mtime`
+    pub fn set_source(&mut self, source: TimeSource) {
+        self.set_mtime(self.mtime());
+        self.source = source;
+    }
+
+    pub fn mtime(&self) -> u64 {
+        match self.source {
+            TimeSource::WallClock(frequency) => {
+                let ticks = self.epoch.elapsed().as_nanos() * frequency as u128 / 1_000_000_000;
+                self.mtime.wrapping_add(ticks as u64)
+            },
+            _ => self.mtime
+        }
+    }
+
+    pub fn set_mtime(&mut self, mtime: u64) {
+        self.mtime = mtime;
+        self.epoch = Instant::now();
+    }
+
+    pub fn mtimecmp(&self) -> u64 {
+        self.mtimecmp
+    }
+
+    pub fn msip(&self) -> bool {
+        self.msip
+    }
+
+    /// Moves `mtime` forward by `ticks`, whatever the time source
+    pub fn advance(&mut self, ticks: u64) {
+        self.mtime = self.mtime.wrapping_add(ticks);
+    }
+
+    /// Counts an instruction retired, which is a tick for `TimeSource::Instructions`
+    pub fn retire(&mut self) {
+        if self.source == TimeSource::Instructions {
+            self.advance(1);
+        }
+    }
+
+    /// The interrupts whose line changed level since the last call, with the new level. Only changes are
+    /// reported so that the host can still raise and clear the same interrupts in `mip` itself
+    pub fn edges(&mut self) -> impl Iterator<Item = (Interrupt, bool)> {
+        let lines = [self.mtime() >= self.mtimecmp, self.msip];
+        let previous = std::mem::replace(&mut self.lines, lines);
+        [Interrupt::MachineTimer, Interrupt::MachineSoftware].into_iter()
+            .zip(lines.into_iter().zip(previous))
+            .filter(|(_, (level, was))| level != was)
+            .map(|(interrupt, (level, _))| (interrupt, level))
+    }
+
+    /// `mtime`, `mtimecmp` and `msip`, as a snapshot saves them
+    pub(crate) fn state(&self) -> (u64, u64, bool) {
+        (self.mtime(), self.mtimecmp, self.msip)
+    }
+
+    /// Puts back the registers `state` returned, taking the interrupt lines to be at the levels they imply
+    pub(crate) fn restore(&mut self, (mtime, mtimecmp, msip): (u64, u64, bool)) {
+        self.set_mtime(mtime);
+        self.mtimecmp = mtimecmp;
+        self.msip = msip;
+        self.lines = [mtime >= mtimecmp, msip];
+    }
+
+    /// The register and its half at a cell, the upper half being `true`
+    fn register(cell: usize) -> Option<(usize, bool)> {
+        [MSIP, MTIMECMP, MTIME].into_iter().find_map(|register| match cell.checked_sub(register / 4) {
+            Some(0) => Some((register, false)),
+            Some(1) if register != MSIP => Some((register, true)),
+            _ => None
+        })
+    }
+
+    fn load<X: Xlen>(&self, cell: usize) -> Option<X> {
+        let (register, upper) = Clint::register(cell)?;
+        let value = match register {
+            MSIP => self.msip as u64,
+            MTIMECMP => self.mtimecmp,
+            _ => self.mtime()
+        };
+        Some(X::from_u64(if upper { value >> 32 } else { value }))
+    }
+
+    /// Writes a register, returning `false` if the cell is not one of them
+    fn store<X: Xlen>(&mut self, cell: usize, value: X) -> bool {
+        let Some((register, upper)) = Clint::register(cell) else { return false };
+        let old = match register {
+            MSIP => {
+                self.msip = value.as_u64() & 1 != 0;
+                return true;
+            },
+            MTIMECMP => self.mtimecmp,
+            _ => self.mtime()
+        };
+        let value = value.as_u64();
+        let new = match (upper, X::BITS) {
+            (true, _) => (old & 0xffffffff) | (value << 32),
+            (false, 32) => (old & !0xffffffff) | (value & 0xffffffff),
+            (false, _) => value
+        };
+        match register {
+            MTIMECMP => self.mtimecmp = new,
+            _ => self.set_mtime(new)
+        }
+        true
+    }
+}
+
+impl Default for Clint {
+    fn default() -> Self {
+        Clint::new(TimeSource::Instructions)
+    }
+}
+
+/// A hart's memory with the CLINT's registers mapped over it
+pub(crate) struct Bus<'a, X: Xlen> {
+    memory: &'a mut Vec<X>,
+    clint: &'a mut Clint
+}
+
+impl<'a, X: Xlen> Bus<'a, X> {
+    pub(crate) fn new(memory: &'a mut Vec<X>, clint: &'a mut Clint) -> Bus<'a, X> {
+        Bus { memory, clint }
+    }
+}
+
+impl<X: Xlen> Memory<X> for Bus<'_, X> {
+    fn load(&mut self, address: usize) -> Result<X, Exception> {
+        match self.clint.load(address) {
+            Some(value) => Ok(value),
+            None => self.memory.load(address)
+        }
+    }
+
+    fn store(&mut self, address: usize, value: X) -> Result<(), Exception> {
+        match self.clint.store(address, value) {
+            true => Ok(()),
+            false => self.memory.store(address, value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clint::{Bus, Clint, TimeSource, MSIP, MTIME, MTIMECMP};
+    use crate::memory::Memory;
+    use crate::trap::{Exception, Interrupt};
+
+    #[test]
+    fn test_registers_rv32() {
+        let mut clint = Clint::new(TimeSource::Manual);
+        let mut memory = vec![0u32; 4];
+        let mut bus = Bus::new(&mut memory, &mut clint);
+
+        assert_eq!(bus.store(MTIMECMP / 4, 7), Ok(()));
+        assert_eq!((bus.load(MTIMECMP / 4), bus.load(MTIMECMP / 4 + 1)), (Ok(7), Ok(u32::MAX)));
+        assert_eq!(bus.store(MTIME / 4 + 1, 1), Ok(()));
+        assert_eq!(bus.store(MSIP / 4, 3), Ok(()));
+        assert_eq!(bus.load(MSIP / 4 + 1), Err(Exception::LoadAccessFault(MSIP / 4 + 1)));
+        assert_eq!(bus.store(2, 5), Ok(()));
+        assert_eq!((clint.mtime(), clint.mtimecmp(), clint.msip()), (1 << 32, u32::MAX as u64 * (1 << 32) + 7, true));
+        assert_eq!(memory[2], 5);
+    }
+
+    #[test]
+    fn test_registers_rv64() {
+        let mut clint = Clint::new(TimeSource::Manual);
+        let mut memory = vec![0u64; 4];
+        let mut bus = Bus::new(&mut memory, &mut clint);
+
+        assert_eq!(bus.store(MTIMECMP / 4, 1 << 40 | 7), Ok(()));
+        assert_eq!((bus.load(MTIMECMP / 4), bus.load(MTIMECMP / 4 + 1)), (Ok(1 << 40 | 7), Ok(1 << 8)));
+    }
+
+    #[test]
+    fn test_edges() {
+        let mut clint = Clint::new(TimeSource::Instructions);
+        let mut memory = vec![0u32; 4];
+        Bus::new(&mut memory, &mut clint).store(MTIMECMP / 4 + 1, 0).unwrap();
+        Bus::new(&mut memory, &mut clint).store(MTIMECMP / 4, 2).unwrap();
+
+        assert_eq!(clint.edges().collect::<Vec<_>>(), vec![]);
+        clint.retire();
+        clint.retire();
+        clint.msip = true;
+        assert_eq!(clint.edges().collect::<Vec<_>>(), vec![(Interrupt::MachineTimer, true), (Interrupt::MachineSoftware, true)]);
+        assert_eq!(clint.edges().collect::<Vec<_>>(), vec![]);
+        clint.mtimecmp = 3;
+        assert_eq!(clint.edges().collect::<Vec<_>>(), vec![(Interrupt::MachineTimer, false)]);
+    }
+
+    #[test]
+    fn test_time_sources() {
+        let mut clint = Clint::new(TimeSource::Manual);
+        clint.retire();
+        clint.advance(5);
+        assert_eq!(clint.mtime(), 5);
+
+        clint.set_source(TimeSource::WallClock(1_000_000_000));
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert!(clint.mtime() >= 5 + 1_000_000);
+    }
+}
@@ -0,0 +1,260 @@
+//! Helpers for the scalar cryptography extensions (Zbkb, Zbkx, Zknd, Zkne and Zknh)
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16
+];
+
+/// The inverse of the AES S-box, derived from `SBOX`
+fn inverse_sbox(value: u8) -> u8 {
+    SBOX.iter()
+        .position(|&s| s == value)
+        .unwrap() as u8
+}
+
+/// Multiplication in GF(2^8) modulo the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a, b, 0u8);
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Selects byte `bs` of `rs2`, runs it through `transform` and XORs the result,
+/// rotated back into position `bs`, into `rs1`
+fn aes32(rs1: u32, rs2: u32, bs: u32, transform: impl Fn(u8) -> u32) -> u32 {
+    let shamt = bs * 8;
+    let byte = (rs2 >> shamt) as u8;
+    rs1 ^ transform(byte).rotate_left(shamt)
+}
+
+pub fn aes32esi(rs1: u32, rs2: u32, bs: u32) -> u32 {
+    aes32(rs1, rs2, bs, |byte| SBOX[byte as usize] as u32)
+}
+
+pub fn aes32esmi(rs1: u32, rs2: u32, bs: u32) -> u32 {
+    aes32(rs1, rs2, bs, |byte| {
+        let s = SBOX[byte as usize];
+        u32::from_le_bytes([gf_mul(s, 2), s, s, gf_mul(s, 3)])
+    })
+}
+
+pub fn aes32dsi(rs1: u32, rs2: u32, bs: u32) -> u32 {
+    aes32(rs1, rs2, bs, |byte| inverse_sbox(byte) as u32)
+}
+
+pub fn aes32dsmi(rs1: u32, rs2: u32, bs: u32) -> u32 {
+    aes32(rs1, rs2, bs, |byte| {
+        let s = inverse_sbox(byte);
+        u32::from_le_bytes([gf_mul(s, 0xe), gf_mul(s, 0x9), gf_mul(s, 0xd), gf_mul(s, 0xb)])
+    })
+}
+
+pub fn sha256sig0(x: u32) -> u32 {
+    x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3)
+}
+
+pub fn sha256sig1(x: u32) -> u32 {
+    x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10)
+}
+
+pub fn sha256sum0(x: u32) -> u32 {
+    x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+}
+
+pub fn sha256sum1(x: u32) -> u32 {
+    x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+}
+
+/// The RV32 SHA-512 instructions each compute one half of a 64-bit function,
+/// with the two halves of the operand split across `rs1` and `rs2`
+pub fn sha512sum0r(rs1: u32, rs2: u32) -> u32 {
+    (rs1 << 25) ^ (rs1 << 30) ^ (rs1 >> 28) ^ (rs2 >> 7) ^ (rs2 >> 2) ^ (rs2 << 4)
+}
+
+pub fn sha512sum1r(rs1: u32, rs2: u32) -> u32 {
+    (rs1 << 23) ^ (rs1 >> 14) ^ (rs1 >> 18) ^ (rs2 >> 9) ^ (rs2 << 18) ^ (rs2 << 14)
+}
+
+pub fn sha512sig0l(rs1: u32, rs2: u32) -> u32 {
+    (rs1 >> 1) ^ (rs1 >> 7) ^ (rs1 >> 8) ^ (rs2 << 31) ^ (rs2 << 25) ^ (rs2 << 24)
+}
+
+pub fn sha512sig0h(rs1: u32, rs2: u32) -> u32 {
+    (rs1 >> 1) ^ (rs1 >> 7) ^ (rs1 >> 8) ^ (rs2 << 31) ^ (rs2 << 24)
+}
+
+pub fn sha512sig1l(rs1: u32, rs2: u32) -> u32 {
+    (rs1 << 3) ^ (rs1 >> 6) ^ (rs1 >> 19) ^ (rs2 >> 29) ^ (rs2 << 26) ^ (rs2 << 13)
+}
+
+pub fn sha512sig1h(rs1: u32, rs2: u32) -> u32 {
+    (rs1 << 3) ^ (rs1 >> 6) ^ (rs1 >> 19) ^ (rs2 >> 29) ^ (rs2 << 13)
+}
+
+/// Reverses the order of the bits within every byte
+pub fn brev8(x: u64) -> u64 {
+    u64::from_le_bytes(x.to_le_bytes().map(|byte| byte.reverse_bits()))
+}
+
+/// Interleaves the lower half of `x` into the even bits and the upper half into the odd bits
+pub fn zip(x: u32) -> u32 {
+    (0..16).fold(0, |result, i| {
+        result | (((x >> i) & 1) << (2 * i)) | (((x >> (i + 16)) & 1) << (2 * i + 1))
+    })
+}
+
+/// The inverse of `zip`
+pub fn unzip(x: u32) -> u32 {
+    (0..16).fold(0, |result, i| {
+        result | (((x >> (2 * i)) & 1) << i) | (((x >> (2 * i + 1)) & 1) << (i + 16))
+    })
+}
+
+/// Replaces every `width`-bit element of `indices` with the element of `table` it indexes,
+/// or zero if the index is out of range for an `xlen`-bit register
+fn xperm(table: u64, indices: u64, width: u32, xlen: u32) -> u64 {
+    let mask = (1 << width) - 1;
+    (0..xlen).step_by(width as usize).fold(0, |result, position| {
+        let index = ((indices >> position) & mask) as u32;
+        let element = if index * width < xlen { (table >> (index * width)) & mask } else { 0 };
+        result | (element << position)
+    })
+}
+
+pub fn xperm4(rs1: u64, rs2: u64, xlen: u32) -> u64 {
+    xperm(rs1, rs2, 4, xlen)
+}
+
+pub fn xperm8(rs1: u64, rs2: u64, xlen: u32) -> u64 {
+    xperm(rs1, rs2, 8, xlen)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::*;
+
+    /// The state at the start of round 1 of the FIPS-197 Appendix B cipher example, as columns
+    const ROUND_1_STATE: [u32; 4] = [0xbee33d19, 0x2be2f4a0, 0x2a8dc69a, 0x0848f8e9];
+    const ROUND_1_KEY: [u32; 4] = [0x17fefaa0, 0xb12c5488, 0x3939a323, 0x05766c2a];
+    const ROUND_2_STATE: [u32; 4] = [0xf27f9ca4, 0x2b359f68, 0x43ea5b6b, 0x49506a02];
+
+    #[test]
+    fn test_sbox() {
+        assert_eq!(aes32esi(0, 0x53, 0), 0xed);
+        assert_eq!(aes32dsi(0, 0xed, 0), 0x53);
+    }
+
+    #[test]
+    fn test_gf_mul() {
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+    }
+
+    #[test]
+    fn test_aes32esmi_round() {
+        let state: Vec<u32> = (0..4)
+            .map(|column| (0..4).fold(ROUND_1_KEY[column], |t, bs| {
+                aes32esmi(t, ROUND_1_STATE[(column + bs as usize) % 4], bs)
+            }))
+            .collect();
+
+        assert_eq!(state, ROUND_2_STATE);
+    }
+
+    #[test]
+    fn test_aes32dsmi_inverts_aes32esmi() {
+        for byte in 0..=255u32 {
+            // MixColumns of the substituted byte, which the inverse instructions must undo
+            let mixed = aes32esmi(0, byte, 0);
+            let substituted = (0..4).fold(0, |t, bs| aes32esi(t, mixed, bs));
+            let unmixed = (0..4).fold(0, |t, bs| aes32dsmi(t, substituted, bs));
+
+            assert_eq!(aes32dsi(0, unmixed, 0), byte);
+        }
+    }
+
+    #[test]
+    fn test_sha256sig1() {
+        // W[17] of the FIPS 180-4 "abc" message schedule is sig1(W[15]) as everything else is zero
+        assert_eq!(sha256sig1(0x18), 0x000f0000);
+    }
+
+    #[test]
+    fn test_sha256sum() {
+        let x = 0x6a09e667u32;
+
+        assert_eq!(sha256sum0(x), x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22));
+        assert_eq!(sha256sum1(x), x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25));
+        assert_eq!(sha256sig0(x), x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3));
+    }
+
+    #[test]
+    fn test_sha512_halves() {
+        let x = 0x6a09e667f3bcc908u64;
+        let (lo, hi) = (x as u32, (x >> 32) as u32);
+
+        let sum0 = x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39);
+        let sum1 = x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41);
+        let sig0 = x.rotate_right(1) ^ x.rotate_right(8) ^ (x >> 7);
+        let sig1 = x.rotate_right(19) ^ x.rotate_right(61) ^ (x >> 6);
+
+        assert_eq!((sha512sum0r(lo, hi), sha512sum0r(hi, lo)), (sum0 as u32, (sum0 >> 32) as u32));
+        assert_eq!((sha512sum1r(lo, hi), sha512sum1r(hi, lo)), (sum1 as u32, (sum1 >> 32) as u32));
+        assert_eq!((sha512sig0l(lo, hi), sha512sig0h(hi, lo)), (sig0 as u32, (sig0 >> 32) as u32));
+        assert_eq!((sha512sig1l(lo, hi), sha512sig1h(hi, lo)), (sig1 as u32, (sig1 >> 32) as u32));
+    }
+
+    #[test]
+    fn test_brev8() {
+        assert_eq!(brev8(0x01020380), 0x8040c001);
+    }
+
+    #[test]
+    fn test_zip_unzip() {
+        assert_eq!(zip(0xffff0000), 0xaaaaaaaa);
+        assert_eq!(unzip(0xaaaaaaaa), 0xffff0000);
+        assert_eq!(unzip(zip(0x12345678)), 0x12345678);
+    }
+
+    #[test]
+    fn test_xperm8() {
+        assert_eq!(xperm8(0x44332211, 0x00010203, 32), 0x11223344);
+        assert_eq!(xperm8(0x44332211, 0x04ff0100, 32), 0x00002211);
+    }
+
+    #[test]
+    fn test_xperm4() {
+        assert_eq!(xperm4(0x76543210, 0x01234567, 32), 0x01234567);
+        assert_eq!(xperm4(0x76543210, 0x000000f8, 32), 0x00000000);
+    }
+
+    #[test]
+    fn test_xperm8_rv64() {
+        assert_eq!(xperm8(0x88776655_44332211, 0x00010203_04050607, 64), 0x11223344_55667788);
+        assert_eq!(xperm8(0x44332211, 0x04050607, 32), 0x00000000);
+    }
+}
@@ -0,0 +1,1008 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::isa::{Extension, Isa};
+use crate::trap::{Exception, Interrupt, Trap};
+
+/// Unprivileged counters/timers
+pub const CYCLE: u32    = 0xC00;
+pub const TIME: u32     = 0xC01;
+pub const INSTRET: u32  = 0xC02;
+pub const CYCLEH: u32   = 0xC80;
+pub const TIMEH: u32    = 0xC81;
+pub const INSTRETH: u32 = 0xC82;
+pub const HPMCOUNTER3: u32  = 0xC03;
+pub const HPMCOUNTER3H: u32 = 0xC83;
+
+/// Unprivileged vector CSRs
+pub const VL: u32    = 0xC20;
+pub const VTYPE: u32 = 0xC21;
+pub const VLENB: u32 = 0xC22;
+
+/// Supervisor trap setup
+pub const SSTATUS: u32 = 0x100;
+pub const SIE: u32     = 0x104;
+pub const STVEC: u32   = 0x105;
+pub const SCOUNTEREN: u32 = 0x106;
+
+/// Supervisor trap handling
+pub const SSCRATCH: u32 = 0x140;
+pub const SEPC: u32     = 0x141;
+pub const SCAUSE: u32   = 0x142;
+pub const STVAL: u32    = 0x143;
+pub const SIP: u32      = 0x144;
+
+/// Supervisor protection and translation
+pub const SATP: u32 = 0x180;
+
+/// Machine information registers
+pub const MVENDORID: u32 = 0xF11;
+pub const MARCHID: u32   = 0xF12;
+pub const MIMPID: u32    = 0xF13;
+pub const MHARTID: u32   = 0xF14;
+
+/// Machine trap setup
+pub const MSTATUS: u32 = 0x300;
+pub const MISA: u32    = 0x301;
+pub const MEDELEG: u32 = 0x302;
+pub const MIDELEG: u32 = 0x303;
+pub const MIE: u32     = 0x304;
+pub const MTVEC: u32   = 0x305;
+pub const MCOUNTEREN: u32 = 0x306;
+
+/// Machine trap handling
+pub const MSCRATCH: u32 = 0x340;
+pub const MEPC: u32     = 0x341;
+pub const MCAUSE: u32   = 0x342;
+pub const MTVAL: u32    = 0x343;
+pub const MIP: u32      = 0x344;
+
+/// Machine counters/timers
+pub const MCYCLE: u32    = 0xB00;
+pub const MINSTRET: u32  = 0xB02;
+pub const MCYCLEH: u32   = 0xB80;
+pub const MINSTRETH: u32 = 0xB82;
+pub const MHPMCOUNTER3: u32  = 0xB03;
+pub const MHPMCOUNTER3H: u32 = 0xB83;
+
+/// Machine counter setup
+pub const MCOUNTINHIBIT: u32 = 0x320;
+pub const MHPMEVENT3: u32    = 0x323;
+
+/// The number of programmable hpm counters, mhpmcounter3 through mhpmcounter31
+const HPM_COUNTERS: u32 = 29;
+
+/// mstatus fields, those up to SPP and SUM and MXR are visible in sstatus too
+pub const MSTATUS_SIE: u32  = 1 << 1;
+pub const MSTATUS_MIE: u32  = 1 << 3;
+pub const MSTATUS_SPIE: u32 = 1 << 5;
+pub const MSTATUS_MPIE: u32 = 1 << 7;
+pub const MSTATUS_SPP: u32  = 1 << 8;
+pub const MSTATUS_MPP: u32  = 0b11 << 11;
+/// Lets supervisor mode load and store on user pages
+pub const MSTATUS_SUM: u32  = 1 << 18;
+/// Makes executable pages readable
+pub const MSTATUS_MXR: u32  = 1 << 19;
+const SSTATUS_MASK: u32 = MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_SUM | MSTATUS_MXR;
+
+/// The interrupts that can be delegated to supervisor mode, and raised by writing mip
+const SUPERVISOR_INTERRUPTS: u64 = 0x222;
+/// Every exception can be delegated, except for an ecall from machine mode
+const DELEGABLE_EXCEPTIONS: u64 = 0xb3ff;
+
+/// The mtvec mode that sends interrupts to the base address plus four times their cause
+pub const MTVEC_VECTORED: u64 = 1;
+
+/// Events that the mhpmcounters can count, selected by writing the code to the matching mhpmevent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpmEvent {
+    Load = 1,
+    Store = 2,
+    TakenBranch = 3,
+    Jump = 4,
+    Ecall = 5
+}
+
+/// The privilege levels a hart can run in, encoded as in the privileged spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Privilege {
+    User = 0b00,
+    Supervisor = 0b01,
+    Machine = 0b11
+}
+
+/// Describes a single CSR: its assembler name and which bits software may write.
+///
+/// Bits outside of `write_mask` are WARL fields that keep their current value
+/// when written, so a read-only CSR simply has a mask of zero.
+struct CsrDescriptor {
+    address: u32,
+    name: &'static str,
+    write_mask: u64
+}
+
+const CSRS: &[CsrDescriptor] = &[
+    CsrDescriptor { address: CYCLE, name: "cycle", write_mask: 0 },
+    CsrDescriptor { address: TIME, name: "time", write_mask: 0 },
+    CsrDescriptor { address: INSTRET, name: "instret", write_mask: 0 },
+    CsrDescriptor { address: CYCLEH, name: "cycleh", write_mask: 0 },
+    CsrDescriptor { address: TIMEH, name: "timeh", write_mask: 0 },
+    CsrDescriptor { address: INSTRETH, name: "instreth", write_mask: 0 },
+    CsrDescriptor { address: VL, name: "vl", write_mask: 0 },
+    CsrDescriptor { address: VTYPE, name: "vtype", write_mask: 0 },
+    CsrDescriptor { address: VLENB, name: "vlenb", write_mask: 0 },
+    CsrDescriptor { address: SSTATUS, name: "sstatus", write_mask: SSTATUS_MASK as u64 },
+    CsrDescriptor { address: SIE, name: "sie", write_mask: SUPERVISOR_INTERRUPTS },
+    CsrDescriptor { address: STVEC, name: "stvec", write_mask: u64::MAX },
+    CsrDescriptor { address: SCOUNTEREN, name: "scounteren", write_mask: u32::MAX as u64 },
+    CsrDescriptor { address: SSCRATCH, name: "sscratch", write_mask: u64::MAX },
+    CsrDescriptor { address: SEPC, name: "sepc", write_mask: !0b11 },
+    CsrDescriptor { address: SCAUSE, name: "scause", write_mask: u64::MAX },
+    CsrDescriptor { address: STVAL, name: "stval", write_mask: u64::MAX },
+    CsrDescriptor { address: SIP, name: "sip", write_mask: 1 << Interrupt::SupervisorSoftware as u32 },
+    // Only Bare and Sv32 exist, so the mode of an RV64 satp stays Bare
+    CsrDescriptor { address: SATP, name: "satp", write_mask: !(0xf << 60) },
+    CsrDescriptor { address: MVENDORID, name: "mvendorid", write_mask: 0 },
+    CsrDescriptor { address: MARCHID, name: "marchid", write_mask: 0 },
+    CsrDescriptor { address: MIMPID, name: "mimpid", write_mask: 0 },
+    CsrDescriptor { address: MHARTID, name: "mhartid", write_mask: 0 },
+    CsrDescriptor { address: MSTATUS, name: "mstatus", write_mask: (SSTATUS_MASK | MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP) as u64 },
+    CsrDescriptor { address: MISA, name: "misa", write_mask: 0 },
+    CsrDescriptor { address: MEDELEG, name: "medeleg", write_mask: DELEGABLE_EXCEPTIONS },
+    CsrDescriptor { address: MIDELEG, name: "mideleg", write_mask: SUPERVISOR_INTERRUPTS },
+    CsrDescriptor { address: MIE, name: "mie", write_mask: 0xaaa },
+    CsrDescriptor { address: MTVEC, name: "mtvec", write_mask: u64::MAX },
+    CsrDescriptor { address: MCOUNTEREN, name: "mcounteren", write_mask: u32::MAX as u64 },
+    CsrDescriptor { address: MSCRATCH, name: "mscratch", write_mask: u64::MAX },
+    CsrDescriptor { address: MEPC, name: "mepc", write_mask: !0b11 },
+    CsrDescriptor { address: MCAUSE, name: "mcause", write_mask: u64::MAX },
+    CsrDescriptor { address: MTVAL, name: "mtval", write_mask: u64::MAX },
+    CsrDescriptor { address: MIP, name: "mip", write_mask: SUPERVISOR_INTERRUPTS },
+    CsrDescriptor { address: MCYCLE, name: "mcycle", write_mask: u64::MAX },
+    CsrDescriptor { address: MINSTRET, name: "minstret", write_mask: u64::MAX },
+    CsrDescriptor { address: MCYCLEH, name: "mcycleh", write_mask: u64::MAX },
+    CsrDescriptor { address: MINSTRETH, name: "minstreth", write_mask: u64::MAX },
+    CsrDescriptor { address: MCOUNTINHIBIT, name: "mcountinhibit", write_mask: !0b10 },
+];
+
+/// The hpm CSRs are numbered 3 to 31, so they are described once per range
+/// with their name being `prefix`, the counter number and `suffix`.
+struct CsrRange {
+    first: u32,
+    prefix: &'static str,
+    suffix: &'static str,
+    write_mask: u64
+}
+
+const CSR_RANGES: &[CsrRange] = &[
+    CsrRange { first: HPMCOUNTER3, prefix: "hpmcounter", suffix: "", write_mask: 0 },
+    CsrRange { first: HPMCOUNTER3H, prefix: "hpmcounter", suffix: "h", write_mask: 0 },
+    CsrRange { first: MHPMCOUNTER3, prefix: "mhpmcounter", suffix: "", write_mask: u64::MAX },
+    CsrRange { first: MHPMCOUNTER3H, prefix: "mhpmcounter", suffix: "h", write_mask: u64::MAX },
+    CsrRange { first: MHPMEVENT3, prefix: "mhpmevent", suffix: "", write_mask: u64::MAX },
+];
+
+fn range_of(address: u32) -> Option<&'static CsrRange> {
+    CSR_RANGES.iter()
+        .find(|range| (range.first..range.first + HPM_COUNTERS).contains(&address))
+}
+
+/// Returns the write mask of the CSR at `address`, or `None` if there is no such CSR.
+fn write_mask(address: u32) -> Option<u64> {
+    CSRS.iter()
+        .find(|csr| csr.address == address)
+        .map(|csr| csr.write_mask)
+        .or_else(|| range_of(address).map(|range| range.write_mask))
+}
+
+/// Looks up the address of a CSR by its assembler name, e.g. `mstatus`.
+pub fn address_of(name: &str) -> Option<u32> {
+    CSRS.iter()
+        .find(|csr| csr.name == name)
+        .map(|csr| csr.address)
+        .or_else(|| CSR_RANGES.iter().find_map(|range| {
+            let number: u32 = name.strip_prefix(range.prefix)?
+                .strip_suffix(range.suffix)?
+                .parse()
+                .ok()?;
+            (3..3 + HPM_COUNTERS).contains(&number)
+                .then(|| range.first + number - 3)
+        }))
+}
+
+/// Looks up the assembler name of the CSR at `address`.
+pub fn name_of(address: u32) -> Option<String> {
+    CSRS.iter()
+        .find(|csr| csr.address == address)
+        .map(|csr| csr.name.to_owned())
+        .or_else(|| range_of(address)
+            .map(|range| format!("{}{}{}", range.prefix, address - range.first + 3, range.suffix)))
+}
+
+/// The unprivileged counters are read-only shadows of the machine counters, except for `time`.
+fn shadowed(address: u32) -> u32 {
+    let is_counter = (CYCLE..CYCLE + 32).contains(&address) || (CYCLEH..CYCLEH + 32).contains(&address);
+    if is_counter && address != TIME && address != TIMEH {
+        address - 0x100
+    } else {
+        address
+    }
+}
+
+/// The lowest privilege level allowed to access a CSR is encoded in bits 9:8 of its address.
+fn required_privilege(address: u32) -> Privilege {
+    match (address >> 8) & 0b11 {
+        0b00 => Privilege::User,
+        0b01 => Privilege::Supervisor,
+        _ => Privilege::Machine
+    }
+}
+
+/// The upper halves of the 64-bit counters, which only exist on RV32.
+fn is_high_half(address: u32) -> bool {
+    (MCYCLEH..MCYCLEH + 32).contains(&address) || (CYCLEH..CYCLEH + 32).contains(&address)
+}
+
+/// The 64-bit counters, whose upper halves are accessed separately on RV32.
+fn is_counter(address: u32) -> bool {
+    (MCYCLE..MCYCLE + 32).contains(&address) || (CYCLE..CYCLE + 32).contains(&address)
+}
+
+/// The extension that provides a CSR, if it is not part of every hart with Zicsr.
+fn extension_of(address: u32) -> Option<Extension> {
+    let address = address & !0x80;
+    match address {
+        CYCLE..=INSTRET => Some(Extension::Zicntr),
+        VL..=VLENB => Some(Extension::V),
+        _ if (HPMCOUNTER3..HPMCOUNTER3 + HPM_COUNTERS).contains(&address) => Some(Extension::Zihpm),
+        // Delegation only has somewhere to go and counters only somewhere to be enabled for with the lower levels
+        MEDELEG | MIDELEG => Some(Extension::S),
+        MCOUNTEREN => Some(Extension::U),
+        _ if required_privilege(address) == Privilege::Supervisor => Some(Extension::S),
+        _ => None
+    }
+}
+
+/// CSRs with bits 11:10 set to `0b11` are read-only.
+fn is_read_only(address: u32) -> bool {
+    (address >> 10) & 0b11 == 0b11
+}
+
+/// The CSRs of a hart. Values are stored 64 bits wide and truncated to XLEN when read.
+#[derive(Debug)]
+pub struct CsrFile {
+    values: HashMap<u32, u64>,
+    privilege: Privilege,
+    xlen: u32,
+    extensions: Vec<Extension>
+}
+
+impl CsrFile {
+    pub fn new(isa: &Isa) -> CsrFile {
+        let mut values: HashMap<u32, u64> = HashMap::new();
+        values.insert(MISA, isa.misa());
+        // Harts start in machine mode, and so does an mret before any trap
+        values.insert(MSTATUS, MSTATUS_MPP as u64);
+        if isa.has(Extension::V) {
+            values.insert(VLENB, isa.vlen() as u64 / 8);
+            // Harts start with vill set until the first vset{i}vl{i}
+            values.insert(VTYPE, 1 << (isa.xlen() - 1));
+        }
+
+        CsrFile {
+            values,
+            privilege: Privilege::Machine,
+            xlen: isa.xlen(),
+            extensions: [Extension::S, Extension::U, Extension::V, Extension::Zicntr, Extension::Zihpm]
+                .into_iter()
+                .filter(|extension| isa.has(*extension))
+                .collect()
+        }
+    }
+
+    pub fn privilege(&self) -> Privilege {
+        self.privilege
+    }
+
+    pub fn xlen(&self) -> u32 {
+        self.xlen
+    }
+
+    /// The value of every CSR that was written, by address. The 64-bit counters are stored whole at the address of
+    /// their lower half
+    pub(crate) fn values(&self) -> BTreeMap<u32, u64> {
+        self.values.iter().map(|(&address, &value)| (address, value)).collect()
+    }
+
+    /// Replaces the privilege level and every CSR value with ones `values` returned
+    pub(crate) fn restore(&mut self, privilege: Privilege, values: &BTreeMap<u32, u64>) {
+        self.privilege = privilege;
+        self.values = values.iter().map(|(&address, &value)| (address, value)).collect();
+    }
+
+    /// Whether the program installed a trap handler by writing `mtvec`, whatever address it wrote, zero included.
+    /// Until then exceptions, `ecall` and `ebreak` included, are reported to the host instead. Being one of the
+    /// written CSRs, it is undone and restored along with them
+    pub fn has_trap_handler(&self) -> bool {
+        self.values.contains_key(&MTVEC)
+    }
+
+    /// Enters the trap handler and returns its address. Traps from supervisor or user mode that `medeleg` or
+    /// `mideleg` delegate go to the supervisor handler in `stvec`, recorded in `sepc`, `scause` and `stval`, with
+    /// SIE moving to SPIE and the privilege level to SPP. All others go to the machine handler in `mtvec`,
+    /// recorded in `mepc`, `mcause` and `mtval`, with MIE moving to MPIE and the privilege level to MPP
+    pub fn trap(&mut self, trap: Trap, pc: u64, tval: u64) -> u64 {
+        let delegation = match trap {
+            Trap::Exception(exception) => self.get(MEDELEG) >> exception.cause(),
+            Trap::Interrupt(interrupt) => self.get(MIDELEG) >> interrupt.cause()
+        };
+        let mstatus = self.get(MSTATUS);
+        let tvec = if self.privilege <= Privilege::Supervisor && delegation & 1 != 0 {
+            self.put(SEPC, pc);
+            self.put(SCAUSE, trap.cause(self.xlen));
+            self.put(STVAL, tval);
+            let spie = if mstatus & MSTATUS_SIE as u64 != 0 { MSTATUS_SPIE } else { 0 };
+            let spp = if self.privilege == Privilege::Supervisor { MSTATUS_SPP } else { 0 };
+            self.put(MSTATUS, mstatus & !(MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP) as u64 | (spie | spp) as u64);
+            self.privilege = Privilege::Supervisor;
+            self.get(STVEC)
+        } else {
+            self.put(MEPC, pc);
+            self.put(MCAUSE, trap.cause(self.xlen));
+            self.put(MTVAL, tval);
+            let mpie = if mstatus & MSTATUS_MIE as u64 != 0 { MSTATUS_MPIE } else { 0 };
+            let mpp = (self.privilege as u32) << MSTATUS_MPP.trailing_zeros();
+            self.put(MSTATUS, mstatus & !(MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP) as u64 | (mpie | mpp) as u64);
+            self.privilege = Privilege::Machine;
+            self.get(MTVEC)
+        };
+
+        match trap {
+            Trap::Interrupt(interrupt) if tvec & 0b11 == MTVEC_VECTORED => (tvec & !0b11) + 4 * interrupt.cause() as u64,
+            _ => tvec & !0b11
+        }
+    }
+
+    /// Returns from the machine trap handler to the address in `mepc`, which it returns. The privilege level
+    /// becomes MPP, which is left at the least privileged level the hart implements, and MPIE moves back to MIE
+    /// and is set. Only machine mode may `mret`
+    pub fn mret(&mut self) -> Result<u64, Exception> {
+        if self.privilege < Privilege::Machine {
+            return Err(Exception::IllegalInstruction);
+        }
+        let mstatus = self.get(MSTATUS);
+        let mie = if mstatus & MSTATUS_MPIE as u64 != 0 { MSTATUS_MIE } else { 0 };
+        self.privilege = match (mstatus >> MSTATUS_MPP.trailing_zeros()) & 0b11 {
+            0b00 => Privilege::User,
+            0b01 => Privilege::Supervisor,
+            _ => Privilege::Machine
+        };
+        let lowest = if self.implements(Privilege::User) { Privilege::User } else { Privilege::Machine };
+        let mpp = (lowest as u32) << MSTATUS_MPP.trailing_zeros();
+        self.put(MSTATUS, mstatus & !(MSTATUS_MIE | MSTATUS_MPP) as u64 | (mie | MSTATUS_MPIE | mpp) as u64);
+        Ok(self.get(MEPC))
+    }
+
+    /// Returns from the supervisor trap handler to the address in `sepc`, which it returns. The privilege level
+    /// becomes SPP, which is left at user mode, and SPIE moves back to SIE and is set. User mode may not `sret`,
+    /// and neither may a hart without supervisor mode
+    pub fn sret(&mut self) -> Result<u64, Exception> {
+        if self.privilege < Privilege::Supervisor || !self.implements(Privilege::Supervisor) {
+            return Err(Exception::IllegalInstruction);
+        }
+        let mstatus = self.get(MSTATUS);
+        let sie = if mstatus & MSTATUS_SPIE as u64 != 0 { MSTATUS_SIE } else { 0 };
+        self.privilege = if mstatus & MSTATUS_SPP as u64 != 0 { Privilege::Supervisor } else { Privilege::User };
+        self.put(MSTATUS, mstatus & !(MSTATUS_SIE | MSTATUS_SPP) as u64 | (sie | MSTATUS_SPIE) as u64);
+        Ok(self.get(SEPC))
+    }
+
+    /// The interrupt to take before the next instruction, the one with the highest priority of those pending
+    /// and enabled in `mie`. Interrupts for machine mode, those not delegated in `mideleg`, are taken in any
+    /// lower privilege level and in machine mode if `mstatus.MIE` is set. Delegated ones are taken in user mode
+    /// and in supervisor mode if `mstatus.SIE` is set, and come after any for machine mode
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        let mstatus = self.get(MSTATUS);
+        let pending = self.get(MIP) & self.get(MIE);
+        let delegated = self.get(MIDELEG);
+        let machine = match self.privilege {
+            Privilege::Machine => mstatus & MSTATUS_MIE as u64 != 0,
+            _ => true
+        };
+        let supervisor = match self.privilege {
+            Privilege::Machine => false,
+            Privilege::Supervisor => mstatus & MSTATUS_SIE as u64 != 0,
+            Privilege::User => true
+        };
+        let enabled = if machine { pending & !delegated } else { 0 } | if supervisor { pending & delegated } else { 0 };
+        let highest = |interrupts: u64| Interrupt::PRIORITY.into_iter().find(|interrupt| interrupts & interrupt.bit() != 0);
+        highest(enabled & !delegated).or_else(|| highest(enabled))
+    }
+
+    /// Raises or clears an interrupt in `mip`, as the device behind it would
+    pub fn set_pending(&mut self, interrupt: Interrupt, pending: bool) {
+        let mip = self.get(MIP) & !interrupt.bit();
+        self.put(MIP, if pending { mip | interrupt.bit() } else { mip });
+    }
+
+    /// Records an exception in the machine trap CSRs: the pc of the faulting instruction,
+    /// the cause and the exception-specific value, e.g. the faulting instruction word.
+    pub fn record_exception(&mut self, exception: Exception, pc: u64, tval: u64) {
+        self.put(MEPC, pc);
+        self.put(MCAUSE, exception.cause() as u64);
+        self.put(MTVAL, tval);
+    }
+
+    /// Reads a CSR as an instruction would, checking that it exists and is accessible.
+    pub fn read(&self, address: u32) -> Result<u64, Exception> {
+        self.check_access(address)?;
+        Ok(self.get(address) & self.xlen_mask())
+    }
+
+    /// Writes a CSR as an instruction would. Read-only fields keep their value.
+    pub fn write(&mut self, address: u32, value: u64) -> Result<(), Exception> {
+        self.check_access(address)?;
+        if is_read_only(address) {
+            return Err(Exception::IllegalInstruction);
+        }
+
+        let write_mask = write_mask(address).unwrap_or(0) & self.xlen_mask();
+        let mut value = (self.get(address) & !write_mask) | (value & write_mask);
+        if (address == MTVEC || address == STVEC) && value & 0b11 > 1 {
+            // Only direct and vectored modes exist, anything else is left unchanged
+            value = (value & !0b11) | (self.get(address) & 0b11);
+        }
+        if address == MSTATUS {
+            // MPP only holds the levels the hart implements, which never include a hypervisor mode
+            let implemented = match (value & MSTATUS_MPP as u64) >> MSTATUS_MPP.trailing_zeros() {
+                0b00 => self.implements(Privilege::User),
+                0b01 => self.implements(Privilege::Supervisor),
+                0b10 => false,
+                _ => true
+            };
+            if !implemented {
+                value = (value & !MSTATUS_MPP as u64) | (self.get(MSTATUS) & MSTATUS_MPP as u64);
+            }
+        }
+        if range_of(address).is_some_and(|range| range.first == MHPMEVENT3) && value > HpmEvent::Ecall as u64 {
+            // Unsupported events count nothing
+            value = 0;
+        }
+        self.put(address, value);
+        Ok(())
+    }
+
+    /// Reads a CSR without any access checks, as the hart itself would.
+    pub fn get(&self, address: u32) -> u64 {
+        if let Some((target, mask)) = self.view(address) {
+            return self.raw(target) & mask;
+        }
+        let address = shadowed(address);
+        if is_high_half(address) {
+            return self.raw(address - 0x80) >> 32;
+        }
+        self.raw(address)
+    }
+
+    /// Writes a CSR without any access checks, bypassing its write mask.
+    ///
+    /// On RV32 the halves of a 64-bit counter are written independently.
+    pub fn put(&mut self, address: u32, value: u64) {
+        let address = shadowed(address);
+        if let Some((target, mask)) = self.view(address) {
+            let value = (self.raw(target) & !mask) | (value & mask);
+            self.values.insert(target, value);
+        } else if is_high_half(address) {
+            let low = address - 0x80;
+            let value = (self.raw(low) & 0xFFFFFFFF) | (value << 32);
+            self.values.insert(low, value);
+        } else if is_counter(address) && self.xlen == 32 {
+            let value = (self.raw(address) & !0xFFFFFFFF) | (value & 0xFFFFFFFF);
+            self.values.insert(address, value);
+        } else {
+            self.values.insert(address, value);
+        }
+    }
+
+    /// Advances the counters after an instruction retired, `event` being what it did if anything.
+    pub fn retire(&mut self, event: Option<HpmEvent>) {
+        let inhibit = self.get(MCOUNTINHIBIT);
+        if inhibit & 0b001 == 0 {
+            self.increment(MCYCLE);
+        }
+        if inhibit & 0b100 == 0 {
+            self.increment(MINSTRET);
+        }
+
+        if let Some(event) = event {
+            self.count(event);
+        }
+    }
+
+    /// Advances the hpm counters that `mhpmevent3` to `mhpmevent31` select `event` for and `mcountinhibit` lets count
+    pub fn count(&mut self, event: HpmEvent) {
+        let inhibit = self.get(MCOUNTINHIBIT);
+        for counter in 0..HPM_COUNTERS {
+            if self.get(MHPMEVENT3 + counter) == event as u64 && inhibit & (1 << (counter + 3)) == 0 {
+                self.increment(MHPMCOUNTER3 + counter);
+            }
+        }
+    }
+
+    /// The supervisor CSRs that are restricted views of machine CSRs: `sstatus` shows the supervisor fields
+    /// of `mstatus`, and `sie` and `sip` show the interrupts `mideleg` delegates
+    fn view(&self, address: u32) -> Option<(u32, u64)> {
+        match address {
+            SSTATUS => Some((MSTATUS, SSTATUS_MASK as u64)),
+            SIE => Some((MIE, self.raw(MIDELEG))),
+            SIP => Some((MIP, self.raw(MIDELEG))),
+            _ => None
+        }
+    }
+
+    /// Shows the `mtime` of the CLINT in `time` and, on RV32, `timeh`
+    pub fn set_time(&mut self, mtime: u64) {
+        self.values.insert(TIME, mtime);
+    }
+
+    fn raw(&self, address: u32) -> u64 {
+        self.values.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Increments the full 64 bits of a counter
+    fn increment(&mut self, address: u32) {
+        let value = self.raw(address).wrapping_add(1);
+        self.values.insert(address, value);
+    }
+
+    fn xlen_mask(&self) -> u64 {
+        u64::MAX >> (64 - self.xlen)
+    }
+
+    /// Whether the hart has the privilege level, which it always does for machine mode
+    fn implements(&self, privilege: Privilege) -> bool {
+        match privilege {
+            Privilege::Machine => true,
+            Privilege::Supervisor => self.extensions.contains(&Extension::S),
+            Privilege::User => self.extensions.contains(&Extension::U)
+        }
+    }
+
+    fn check_access(&self, address: u32) -> Result<(), Exception> {
+        if write_mask(address).is_none() || self.privilege < required_privilege(address) {
+            return Err(Exception::IllegalInstruction);
+        }
+        if is_high_half(address) && self.xlen != 32 {
+            return Err(Exception::IllegalInstruction);
+        }
+        if extension_of(address).is_some_and(|extension| !self.extensions.contains(&extension)) {
+            return Err(Exception::IllegalInstruction);
+        }
+        if (CYCLE..CYCLE + 32).contains(&(address & !0x80)) {
+            // mcounteren lets supervisor and user mode read a counter, then scounteren lets user mode read it
+            let enable = 1 << (address & 0b11111);
+            let enabled = match self.privilege {
+                Privilege::Machine => true,
+                Privilege::Supervisor => self.get(MCOUNTEREN) & enable != 0,
+                Privilege::User => self.get(MCOUNTEREN) & self.get(SCOUNTEREN) & enable != 0
+            };
+            if !enabled {
+                return Err(Exception::IllegalInstruction);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for CsrFile {
+    fn default() -> Self {
+        Self::new(&Isa::all(32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csr::*;
+    use crate::trap::{Exception, Interrupt, Trap};
+
+    #[test]
+    fn test_address_of() {
+        assert_eq!(address_of("mstatus"), Some(MSTATUS));
+        assert_eq!(address_of("mcycle"), Some(MCYCLE));
+        assert_eq!(address_of("nope"), None);
+    }
+
+    #[test]
+    fn test_name_of() {
+        assert_eq!(name_of(MTVEC), Some(String::from("mtvec")));
+        assert_eq!(name_of(0x7FF), None);
+    }
+
+    #[test]
+    fn test_address_of_hpm() {
+        assert_eq!(address_of("mhpmcounter3"), Some(MHPMCOUNTER3));
+        assert_eq!(address_of("hpmcounter31h"), Some(HPMCOUNTER3H + 28));
+        assert_eq!(address_of("mhpmevent32"), None);
+    }
+
+    #[test]
+    fn test_name_of_hpm() {
+        assert_eq!(name_of(MHPMEVENT3 + 1), Some(String::from("mhpmevent4")));
+        assert_eq!(name_of(HPMCOUNTER3H + 28), Some(String::from("hpmcounter31h")));
+    }
+
+    #[test]
+    fn test_retire_counts_cycles_and_instructions() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.retire(None);
+        csr.retire(None);
+
+        assert_eq!(csr.read(CYCLE), Ok(2));
+        assert_eq!(csr.read(INSTRET), Ok(2));
+        // time belongs to the CLINT rather than the hart
+        assert_eq!(csr.read(TIME), Ok(0));
+        csr.set_time(u32::MAX as u64 + 2);
+        assert_eq!((csr.read(TIME), csr.read(TIMEH)), (Ok(1), Ok(1)));
+    }
+
+    #[test]
+    fn test_retire_carries_into_high_half() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MINSTRET, u32::MAX as u64).unwrap();
+
+        csr.retire(None);
+
+        assert_eq!(csr.read(INSTRET), Ok(0));
+        assert_eq!(csr.read(INSTRETH), Ok(1));
+    }
+
+    #[test]
+    fn test_retire_counts_selected_event() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MHPMEVENT3, HpmEvent::Load as u64).unwrap();
+        csr.write(MHPMEVENT3 + 1, HpmEvent::Store as u64).unwrap();
+
+        csr.retire(Some(HpmEvent::Load));
+        csr.retire(Some(HpmEvent::Load));
+        csr.retire(None);
+
+        assert_eq!(csr.read(HPMCOUNTER3), Ok(2));
+        assert_eq!(csr.read(HPMCOUNTER3 + 1), Ok(0));
+    }
+
+    #[test]
+    fn test_mcountinhibit() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MCOUNTINHIBIT, 0b101).unwrap();
+
+        csr.retire(None);
+
+        assert_eq!(csr.read(MCYCLE), Ok(0));
+        assert_eq!(csr.read(MINSTRET), Ok(0));
+    }
+
+    #[test]
+    fn test_write_warl_mhpmevent() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MHPMEVENT3, 42).unwrap();
+
+        assert_eq!(csr.get(MHPMEVENT3), 0);
+    }
+
+    #[test]
+    fn test_write_user_counter() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        assert_eq!(csr.write(CYCLE, 1), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_write_read() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MSCRATCH, 0xdeadbeef).unwrap();
+
+        assert_eq!(csr.read(MSCRATCH), Ok(0xdeadbeef));
+    }
+
+    #[test]
+    fn test_write_read_only() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        assert_eq!(csr.write(MHARTID, 1), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_access_nonexistent() {
+        let csr = CsrFile::new(&Isa::all(32));
+
+        assert_eq!(csr.read(0x7FF), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_counter_enables() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MCOUNTEREN, 0b101).unwrap();
+        csr.write(SCOUNTEREN, 0b001).unwrap();
+        csr.write(MSTATUS, (Privilege::Supervisor as u64) << 11).unwrap();
+
+        csr.mret().unwrap();
+        assert_eq!(csr.privilege(), Privilege::Supervisor);
+        assert_eq!(csr.read(INSTRET), Ok(0));
+        assert_eq!(csr.read(INSTRETH), Ok(0));
+        assert_eq!(csr.read(TIME), Err(Exception::IllegalInstruction));
+
+        csr.sret().unwrap();
+        assert_eq!(csr.privilege(), Privilege::User);
+        assert_eq!(csr.read(CYCLE), Ok(0));
+        assert_eq!(csr.read(INSTRET), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.read(HPMCOUNTER3), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_trap_handler_at_zero() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        let reset = csr.values();
+        assert!(!csr.has_trap_handler());
+
+        csr.write(MTVEC, 0).unwrap();
+        assert!(csr.has_trap_handler());
+
+        csr.restore(Privilege::Machine, &reset);
+        assert!(!csr.has_trap_handler());
+    }
+
+    #[test]
+    fn test_write_warl_mstatus() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MSTATUS, 0xFFFFFFFF).unwrap();
+        assert_eq!(csr.get(MSTATUS), (SSTATUS_MASK | MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP) as u64);
+
+        csr.write(MSTATUS, 0b10 << 11).unwrap();
+        assert_eq!(csr.get(MSTATUS), MSTATUS_MPP as u64);
+    }
+
+    #[test]
+    fn test_write_warl_mepc() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MEPC, 0x103).unwrap();
+
+        assert_eq!(csr.get(MEPC), 0x100);
+    }
+
+    #[test]
+    fn test_write_warl_mtvec_mode() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MTVEC, 0x101).unwrap();
+        csr.write(MTVEC, 0x202).unwrap();
+
+        assert_eq!(csr.get(MTVEC), 0x201);
+    }
+
+    #[test]
+    fn test_write_warl_misa() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MISA, 0).unwrap();
+
+        assert_eq!(csr.get(MISA), (1 << 30) | (1 << 21) | (1 << 20) | (1 << 18) | (1 << 8) | (1 << 1));
+    }
+
+    #[test]
+    fn test_misa_rv64() {
+        let csr = CsrFile::new(&Isa::parse("rv64isu").unwrap());
+
+        assert_eq!(csr.read(MISA), Ok((2 << 62) | (1 << 20) | (1 << 18) | (1 << 8)));
+    }
+
+    #[test]
+    fn test_counter_rv64() {
+        let mut csr = CsrFile::new(&Isa::all(64));
+        csr.write(MCYCLE, u32::MAX as u64).unwrap();
+
+        csr.retire(None);
+
+        assert_eq!(csr.read(CYCLE), Ok(1 << 32));
+        assert_eq!(csr.read(CYCLEH), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_write_rv32_truncates() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.write(MSCRATCH, 0x1_0000_0001).unwrap();
+
+        assert_eq!(csr.read(MSCRATCH), Ok(1));
+    }
+
+    #[test]
+    fn test_misa_rv32e() {
+        let csr = CsrFile::new(&Isa::parse("rv32eu").unwrap());
+
+        assert_eq!(csr.read(MISA), Ok((1 << 30) | (1 << 20) | (1 << 4)));
+    }
+
+    #[test]
+    fn test_machine_mode_only() {
+        let mut csr = CsrFile::new(&Isa::parse("rv32i_zicsr").unwrap());
+
+        csr.write(MSTATUS, 0).unwrap();
+        assert_eq!(csr.get(MSTATUS) & MSTATUS_MPP as u64, MSTATUS_MPP as u64);
+        csr.write(MSTATUS, (Privilege::Supervisor as u64) << 11).unwrap();
+        assert_eq!(csr.get(MSTATUS) & MSTATUS_MPP as u64, MSTATUS_MPP as u64);
+
+        csr.mret().unwrap();
+        assert_eq!(csr.privilege(), Privilege::Machine);
+        assert_eq!(csr.get(MSTATUS) & MSTATUS_MPP as u64, MSTATUS_MPP as u64);
+        assert_eq!(csr.sret(), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.write(MEDELEG, 1 << 8), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.read(SSTATUS), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.read(MCOUNTEREN), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_machine_and_user_mode() {
+        let mut csr = CsrFile::new(&Isa::parse("rv32iu_zicsr").unwrap());
+
+        csr.write(MSTATUS, (Privilege::Supervisor as u64) << 11).unwrap();
+        assert_eq!(csr.get(MSTATUS) & MSTATUS_MPP as u64, MSTATUS_MPP as u64);
+        csr.write(MSTATUS, 0).unwrap();
+        csr.write(MCOUNTEREN, 1).unwrap();
+
+        csr.mret().unwrap();
+        assert_eq!(csr.privilege(), Privilege::User);
+        assert_eq!(csr.get(MSTATUS) & MSTATUS_MPP as u64, 0);
+    }
+
+    #[test]
+    fn test_counters_without_zicntr() {
+        let csr = CsrFile::new(&Isa::parse("rv32i_zicsr").unwrap());
+
+        assert_eq!(csr.read(CYCLE), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.read(HPMCOUNTER3H), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.read(MCYCLE), Ok(0));
+    }
+
+    #[test]
+    fn test_counters_with_zicntr() {
+        let csr = CsrFile::new(&Isa::parse("rv32i_zicntr").unwrap());
+
+        assert_eq!(csr.read(INSTRETH), Ok(0));
+        assert_eq!(csr.read(HPMCOUNTER3), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_record_exception() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+
+        csr.record_exception(Exception::IllegalInstruction, 0x40, 0xFFFFFFFF);
+
+        assert_eq!(csr.read(MEPC), Ok(0x40));
+        assert_eq!(csr.read(MCAUSE), Ok(2));
+        assert_eq!(csr.read(MTVAL), Ok(0xFFFFFFFF));
+    }
+
+    #[test]
+    fn test_trap_and_mret() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MTVEC, 0x100).unwrap();
+        csr.write(MSTATUS, MSTATUS_MIE as u64).unwrap();
+
+        assert_eq!(csr.trap(Trap::Exception(Exception::StoreAccessFault(2000)), 0x40, 2000), 0x100);
+        assert_eq!((csr.get(MEPC), csr.get(MCAUSE), csr.get(MTVAL)), (0x40, 7, 2000));
+        assert_eq!(csr.get(MSTATUS), (MSTATUS_MPIE | MSTATUS_MPP) as u64);
+
+        csr.put(MEPC, 0x44);
+        assert_eq!(csr.mret(), Ok(0x44));
+        assert_eq!(csr.get(MSTATUS), (MSTATUS_MIE | MSTATUS_MPIE) as u64);
+        assert_eq!(csr.privilege(), Privilege::Machine);
+        assert_eq!(csr.mret(), Ok(0x44));
+        assert_eq!(csr.privilege(), Privilege::User);
+        assert_eq!(csr.mret(), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_delegation_and_sret() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MTVEC, 0x100).unwrap();
+        csr.write(STVEC, 0x200).unwrap();
+        csr.write(MEDELEG, 1 << 8 | 1 << 13).unwrap();
+        csr.write(MSTATUS, MSTATUS_SIE as u64).unwrap();
+        csr.mret().unwrap();
+
+        assert_eq!(csr.trap(Trap::Exception(Exception::LoadPageFault(0x1000)), 0x40, 0x1000), 0x200);
+        assert_eq!((csr.get(SEPC), csr.get(SCAUSE), csr.get(STVAL), csr.get(MEPC)), (0x40, 13, 0x1000, 0));
+        assert_eq!(csr.get(SSTATUS), MSTATUS_SPIE as u64);
+        assert_eq!(csr.privilege(), Privilege::Supervisor);
+        // Exceptions in supervisor mode are delegated too, but ecalls from supervisor mode are not
+        assert_eq!(csr.trap(Trap::Exception(Exception::EnvironmentCall(Privilege::Supervisor)), 0x200, 0), 0x100);
+        assert_eq!((csr.get(MCAUSE), csr.privilege()), (9, Privilege::Machine));
+        assert_eq!(csr.get(MSTATUS) & MSTATUS_MPP as u64, 0b01 << 11);
+
+        csr.mret().unwrap();
+        csr.write(SEPC, 0x44).unwrap();
+        assert_eq!(csr.sret(), Ok(0x44));
+        assert_eq!(csr.get(SSTATUS), (MSTATUS_SIE | MSTATUS_SPIE) as u64);
+        assert_eq!(csr.privilege(), Privilege::User);
+        assert_eq!(csr.sret(), Err(Exception::IllegalInstruction));
+        assert_eq!(csr.read(SSTATUS), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_supervisor_views() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MIE, 0xaaa).unwrap();
+        csr.write(MIP, 0x222).unwrap();
+
+        assert_eq!((csr.read(SIE), csr.read(SIP)), (Ok(0), Ok(0)));
+        csr.write(MIDELEG, 0xfff).unwrap();
+        assert_eq!((csr.read(SIE), csr.read(SIP)), (Ok(0x222), Ok(0x222)));
+        csr.write(SIP, 0).unwrap();
+        assert_eq!(csr.get(MIP), 0x220);
+
+        csr.write(SSTATUS, u64::MAX).unwrap();
+        assert_eq!(csr.get(MSTATUS), (SSTATUS_MASK | MSTATUS_MPP) as u64);
+        assert_eq!(csr.read(SSTATUS), Ok(SSTATUS_MASK as u64));
+    }
+
+    #[test]
+    fn test_vectored_interrupts() {
+        let mut csr = CsrFile::new(&Isa::all(64));
+        csr.write(MTVEC, 0x100 | MTVEC_VECTORED).unwrap();
+
+        assert_eq!(csr.trap(Trap::Interrupt(Interrupt::MachineTimer), 0x40, 0), 0x11c);
+        assert_eq!(csr.get(MCAUSE), (1 << 63) | 7);
+        assert_eq!(csr.trap(Trap::Exception(Exception::Breakpoint), 0x40, 0x40), 0x100);
+    }
+
+    #[test]
+    fn test_pending_interrupt() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.set_pending(Interrupt::MachineTimer, true);
+        csr.set_pending(Interrupt::MachineSoftware, true);
+        csr.write(MIE, 0x888).unwrap();
+
+        assert_eq!(csr.write(MIP, 0), Ok(()));
+        assert_eq!(csr.get(MIP), 0x88);
+        assert_eq!(csr.pending_interrupt(), None);
+        csr.write(MSTATUS, MSTATUS_MIE as u64).unwrap();
+        assert_eq!(csr.pending_interrupt(), Some(Interrupt::MachineSoftware));
+        csr.set_pending(Interrupt::MachineSoftware, false);
+        assert_eq!(csr.pending_interrupt(), Some(Interrupt::MachineTimer));
+    }
+
+    #[test]
+    fn test_pending_interrupt_by_privilege() {
+        let mut csr = CsrFile::new(&Isa::all(32));
+        csr.write(MIE, 0xaaa).unwrap();
+        csr.write(MIDELEG, 0x222).unwrap();
+        csr.set_pending(Interrupt::SupervisorTimer, true);
+
+        // Delegated interrupts are never taken in machine mode
+        csr.write(MSTATUS, (MSTATUS_MIE | MSTATUS_SIE) as u64 | 0b01 << 11).unwrap();
+        assert_eq!(csr.pending_interrupt(), None);
+        csr.mret().unwrap();
+        assert_eq!(csr.pending_interrupt(), Some(Interrupt::SupervisorTimer));
+        csr.write(SSTATUS, 0).unwrap();
+        assert_eq!(csr.pending_interrupt(), None);
+
+        // Machine interrupts are taken in supervisor mode whatever MIE says, and ahead of delegated ones
+        csr.set_pending(Interrupt::MachineTimer, true);
+        assert_eq!(csr.pending_interrupt(), Some(Interrupt::MachineTimer));
+    }
+
+    #[test]
+    fn test_vector_csrs() {
+        let mut csr = CsrFile::new(&Isa::parse("rv32iv_zicsr_zvl256b").unwrap());
+
+        assert_eq!(csr.read(VLENB), Ok(32));
+        assert_eq!(csr.read(VTYPE), Ok(1 << 31));
+        assert_eq!(csr.write(VL, 4), Err(Exception::IllegalInstruction));
+
+        let csr = CsrFile::new(&Isa::parse("rv32i_zicsr").unwrap());
+        assert_eq!(csr.read(VL), Err(Exception::IllegalInstruction));
+    }
+}
@@ -0,0 +1,638 @@
+//! A debug adapter, speaking the Debug Adapter Protocol that editors like VS Code use, for one RV32 program.
+//!
+//! The `launch` request takes the `program` to debug and optionally `stopOnEntry`, an `isa` string,
+//! `registers` to set like `{"a0": 512}` and `memory` cells to fill like `{"512": [1, 2, 3]}`.
+//! Breakpoints are set on source lines, steps go from line to line and the registers are shown as
+//! variables grouped by their ABI role. Memory is read as bytes through `Processor::read_bytes`,
+//! registers pointing to it hold cell numbers so their memory reference is four times their value,
+//! except for `pc` and `ra` which hold instruction addresses.
+//!
+//! The last `HISTORY` instructions can be undone with `stepBack` and `reverseContinue`.
+//! Programs run synchronously, `pause` cannot interrupt a program that does not stop by itself.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::debug::{is_call, Breakpoint};
+use crate::disassembler::{register_index, register_name};
+use crate::json::Json;
+use crate::processor::{ExecError, HaltConditions, HaltReason, Processor, StepOutcome};
+
+/// The only thread, the hart
+const THREAD: usize = 1;
+
+/// Instructions that can be stepped back over
+const HISTORY: usize = 10000;
+
+/// The largest message the adapter accepts
+const MAX_CONTENT_LENGTH: usize = 1 << 20;
+
+/// The most bytes a single readMemory request returns, editors ask for the rest in further requests
+const MAX_READ: usize = 1 << 16;
+
+/// Variable groups, each with the registers it holds
+const SCOPES: [(&str, &[usize]); 4] = [
+    ("Arguments", &[10, 11, 12, 13, 14, 15, 16, 17]),
+    ("Temporaries", &[5, 6, 7, 28, 29, 30, 31]),
+    ("Saved", &[8, 9, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27]),
+    ("Special", &[0, 1, 2, 3, 4])
+];
+
+pub struct DebugAdapter {
+    processor: Option<Processor>,
+    program: String,
+    stop_on_entry: bool,
+    configured: bool,
+    /// The breakpoints of every source by path, with their id, line and hit count to stop at
+    breakpoints: BTreeMap<String, Vec<SourceBreakpoint>>,
+    /// The processor's breakpoint ids with the ids they have in the protocol and their source
+    active: Vec<(usize, usize, String)>,
+    next_id: usize,
+    seq: i64,
+    done: bool
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SourceBreakpoint {
+    id: usize,
+    line: usize,
+    hits: usize
+}
+
+/// How execution stopped, which becomes the events that tell the editor
+enum Stop {
+    Step,
+    Entry,
+    Breakpoint(Vec<usize>),
+    Exception(String),
+    Exited(i32)
+}
+
+impl DebugAdapter {
+    pub fn new() -> DebugAdapter {
+        DebugAdapter {
+            processor: None,
+            program: String::new(),
+            stop_on_entry: false,
+            configured: false,
+            breakpoints: BTreeMap::new(),
+            active: Vec::new(),
+            next_id: 1,
+            seq: 1,
+            done: false
+        }
+    }
+
+    /// Answers the requests read from `input` until the editor disconnects or closes the stream
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        while !self.done {
+            let mut length = None;
+            loop {
+                let mut header = String::new();
+                if input.read_line(&mut header)? == 0 {
+                    return Ok(());
+                }
+                match header.trim_end().split_once(':') {
+                    Some(("Content-Length", value)) => length = value.trim().parse().ok(),
+                    Some(_) => {},
+                    None => break
+                }
+            }
+            let length = length.unwrap_or(0);
+            if length > MAX_CONTENT_LENGTH {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("a Content-Length of {} is too large", length)));
+            }
+            let mut content = vec![0; length];
+            input.read_exact(&mut content)?;
+
+            let messages = match Json::parse(&String::from_utf8_lossy(&content)) {
+                Ok(request) => self.handle(&request),
+                Err(error) => vec![self.event("output", Json::object([
+                    ("category", Json::from("stderr")),
+                    ("output", Json::from(format!("invalid message: {}\n", error)))
+                ]))]
+            };
+            for mut message in messages {
+                // Messages are numbered in the order they are sent, the response before the events it caused
+                if let Json::Object(members) = &mut message {
+                    members[0].1 = Json::from(self.seq);
+                    self.seq += 1;
+                }
+                let content = message.to_string();
+                write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The response to a request followed by the events it caused
+    fn handle(&mut self, request: &Json) -> Vec<Json> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or_default().to_string();
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Object(Vec::new()));
+        let mut events = Vec::new();
+        let result = match command.as_str() {
+            "initialize" => {
+                events.push(self.event("initialized", Json::Null));
+                Ok(Json::object([
+                    ("supportsConfigurationDoneRequest", Json::from(true)),
+                    ("supportsHitConditionalBreakpoints", Json::from(true)),
+                    ("supportsReadMemoryRequest", Json::from(true)),
+                    ("supportsStepBack", Json::from(true)),
+                    ("supportsTerminateRequest", Json::from(true))
+                ]))
+            },
+            "launch" => self.launch(&arguments).map(|verified| {
+                for breakpoint in verified {
+                    events.push(self.event("breakpoint", Json::object([("reason", Json::from("changed")), ("breakpoint", breakpoint)])));
+                }
+                if self.configured {
+                    events.extend(self.start());
+                }
+                Json::Null
+            }),
+            "setBreakpoints" => Ok(self.set_breakpoints(&arguments)),
+            "configurationDone" => {
+                self.configured = true;
+                if self.processor.is_some() {
+                    events.extend(self.start());
+                }
+                Ok(Json::Null)
+            },
+            "threads" => Ok(Json::object([("threads", Json::from(vec![Json::object([
+                ("id", Json::from(THREAD)),
+                ("name", Json::from("hart 0"))
+            ])]))])),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(Json::object([("scopes", Json::from(SCOPES.iter().enumerate().map(|(index, (name, _))| Json::object([
+                ("name", Json::from(*name)),
+                ("variablesReference", Json::from(index + 1)),
+                ("expensive", Json::from(false))
+            ])).collect::<Vec<_>>()))])),
+            "variables" => self.variables(&arguments),
+            "readMemory" => self.read_memory(&arguments),
+            "continue" | "next" | "stepIn" | "stepOut" | "stepBack" | "reverseContinue" if self.processor.is_some() => {
+                let stop = match command.as_str() {
+                    "continue" => self.resume(),
+                    "next" => self.step_line(true),
+                    "stepIn" => self.step_line(false),
+                    "stepBack" => self.step_back(),
+                    "reverseContinue" => self.reverse_continue(),
+                    _ => self.step_out()
+                };
+                events.extend(self.stopped(stop));
+                Ok(match command.as_str() {
+                    "continue" => Json::object([("allThreadsContinued", Json::from(true))]),
+                    _ => Json::Null
+                })
+            },
+            "continue" | "next" | "stepIn" | "stepOut" | "stepBack" | "reverseContinue" => Err(String::from("no program is running")),
+            "pause" => Ok(Json::Null),
+            "disconnect" | "terminate" => {
+                self.done = command == "disconnect";
+                if command == "terminate" {
+                    events.push(self.event("terminated", Json::Null));
+                }
+                Ok(Json::Null)
+            },
+            _ => Err(format!("{} is not supported", command))
+        };
+
+        let mut response = vec![
+            ("seq", Json::from(0usize)),
+            ("type", Json::from("response")),
+            ("request_seq", request.get("seq").cloned().unwrap_or(Json::Null)),
+            ("command", Json::from(command)),
+            ("success", Json::from(result.is_ok()))
+        ];
+        match result {
+            Ok(Json::Null) => {},
+            Ok(body) => response.push(("body", body)),
+            Err(message) => response.push(("message", Json::from(message)))
+        }
+        let mut messages = vec![members(response)];
+        messages.extend(events);
+        messages
+    }
+
+    /// Loads the program, returning the breakpoints that were set before
+    fn launch(&mut self, arguments: &Json) -> Result<Vec<Json>, String> {
+        let program = arguments.get("program").and_then(Json::as_str).ok_or("launch needs the program to debug")?;
+        let mut processor = match arguments.get("isa").and_then(Json::as_str) {
+            Some(isa) => Processor::from_isa(isa).map_err(|error| error.to_string())?,
+            None => Processor::new()
+        };
+        processor.set_halt_conditions(HaltConditions { ecall_exit: true, ebreak: true, ..Default::default() });
+        processor.set_history_limit(HISTORY);
+        processor.load_instructions(program).map_err(|error| error.to_string())?;
+        if let Some(Json::Object(registers)) = arguments.get("registers") {
+            for (name, value) in registers {
+                let index = register_index(name).ok_or(format!("'{}' is not a register", name))?;
+                let value = value.as_i64().ok_or(format!("'{}' is not a value for {}", value, name))?;
+                processor.set_register_value(index, value as u32).map_err(|error| error.to_string())?;
+            }
+        }
+        if let Some(Json::Object(memory)) = arguments.get("memory") {
+            for (address, values) in memory {
+                let address: usize = address.parse().map_err(|_| format!("'{}' is not a memory cell", address))?;
+                let values: Vec<u32> = values.as_array().unwrap_or_default().iter()
+                    .map(|value| value.as_i64().map(|value| value as u32).ok_or(format!("'{}' is not a value", value)))
+                    .collect::<Result<_, _>>()?;
+                processor.store_in_memory(address, &values).map_err(|error| error.to_string())?;
+            }
+        }
+
+        self.program = program.to_string();
+        self.stop_on_entry = arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+        self.processor = Some(processor);
+        let paths: Vec<String> = self.breakpoints.keys().cloned().collect();
+        Ok(paths.iter().flat_map(|path| self.apply_breakpoints(path)).collect())
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let path = arguments.get("source").and_then(|source| source.get("path")).and_then(Json::as_str).unwrap_or_default().to_string();
+        let requested = arguments.get("breakpoints").and_then(Json::as_array).unwrap_or_default();
+        let breakpoints = requested.iter()
+            .map(|breakpoint| {
+                let id = self.next_id;
+                self.next_id += 1;
+                SourceBreakpoint {
+                    id,
+                    line: breakpoint.get("line").and_then(Json::as_i64).unwrap_or(0) as usize,
+                    hits: breakpoint.get("hitCondition").and_then(Json::as_str).and_then(|hits| hits.trim().parse().ok()).unwrap_or(1)
+                }
+            })
+            .collect();
+        self.breakpoints.insert(path.clone(), breakpoints);
+        Json::object([("breakpoints", Json::from(self.apply_breakpoints(&path)))])
+    }
+
+    /// Replaces the processor's breakpoints in a source with those last set, describing each of them
+    fn apply_breakpoints(&mut self, path: &str) -> Vec<Json> {
+        let breakpoints = self.breakpoints.get(path).cloned().unwrap_or_default();
+        let same_file = self.is_program(path);
+        let Some(processor) = &mut self.processor else {
+            return breakpoints.iter().map(|breakpoint| Json::object([
+                ("id", Json::from(breakpoint.id)),
+                ("verified", Json::from(false)),
+                ("line", Json::from(breakpoint.line)),
+                ("message", Json::from("the program is not loaded yet"))
+            ])).collect();
+        };
+
+        for (processor_id, _, _) in self.active.iter().filter(|(_, _, source)| source == path) {
+            processor.remove_breakpoint(*processor_id);
+        }
+        self.active.retain(|(_, _, source)| source != path);
+        breakpoints.iter().map(|breakpoint| {
+            let address = processor.line_address(breakpoint.line).filter(|_| same_file);
+            match address {
+                Some(address) => {
+                    let processor_id = processor.add_breakpoint(Breakpoint::new(address).ignore(breakpoint.hits.saturating_sub(1)));
+                    self.active.push((processor_id, breakpoint.id, path.to_string()));
+                    Json::object([
+                        ("id", Json::from(breakpoint.id)),
+                        ("verified", Json::from(true)),
+                        ("line", Json::from(processor.source_line(address).unwrap_or(breakpoint.line)))
+                    ])
+                },
+                None => Json::object([
+                    ("id", Json::from(breakpoint.id)),
+                    ("verified", Json::from(false)),
+                    ("line", Json::from(breakpoint.line)),
+                    ("message", Json::from(if same_file { "there is no instruction on or after this line" } else { "not the program being debugged" }))
+                ])
+            }
+        }).collect()
+    }
+
+    fn is_program(&self, path: &str) -> bool {
+        match (fs::canonicalize(path), fs::canonicalize(&self.program)) {
+            (Ok(path), Ok(program)) => path == program,
+            _ => path == self.program
+        }
+    }
+
+    fn start(&mut self) -> Vec<Json> {
+        let stop = if self.stop_on_entry { Stop::Entry } else { self.resume() };
+        self.stopped(stop)
+    }
+
+    fn resume(&mut self) -> Stop {
+        let result = self.processor.as_mut().map(|processor| processor.run_until(|_| false));
+        self.stop(result)
+    }
+
+    /// Steps until the pc is on another line or back at the start, running calls to their end when `over_calls` is set
+    fn step_line(&mut self, over_calls: bool) -> Stop {
+        let Some(start) = self.processor.as_ref().map(Processor::pc) else { return Stop::Step };
+        loop {
+            let Some(processor) = &mut self.processor else { return Stop::Step };
+            let pc = processor.pc();
+            let line = processor.source_line(pc);
+            let call = over_calls && processor.read_bytes(pc, 4).is_ok_and(|bytes| is_call(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])));
+            // A breakpoint on the instruction being stepped from does not stop the step
+            let mut outcome = match processor.step() {
+                Ok(outcome) if !outcome.retired && matches!(outcome.halt, Some(HaltReason::Breakpoint(_))) => processor.step(),
+                outcome => outcome
+            };
+            if call && matches!(outcome, Ok(StepOutcome { halt: None, .. })) {
+                outcome = processor.run_until(|outcome| outcome.next_pc == pc + 4);
+            }
+            if !matches!(outcome, Ok(StepOutcome { halt: None, .. })) {
+                return self.stop(Some(outcome));
+            }
+            let now = processor.pc();
+            if now == start || processor.source_line(now) != line {
+                return Stop::Step;
+            }
+        }
+    }
+
+    /// Undoes instructions back to the previous line, or to the same line when going back loops around to it
+    fn step_back(&mut self) -> Stop {
+        let Some(processor) = &mut self.processor else { return Stop::Step };
+        let start = processor.pc();
+        let line = processor.source_line(start);
+        while let Some(pc) = processor.step_back() {
+            if pc == start || processor.source_line(pc) != line {
+                return Stop::Step;
+            }
+        }
+        Stop::Step
+    }
+
+    /// Undoes instructions back to a breakpoint, a watchpoint or the start of the history
+    fn reverse_continue(&mut self) -> Stop {
+        let Some(processor) = &mut self.processor else { return Stop::Step };
+        match processor.reverse_continue() {
+            HaltReason::Breakpoint(id) => Stop::Breakpoint(self.hit_ids(id)),
+            _ => Stop::Step
+        }
+    }
+
+    /// Runs until the function returns to the address in `ra`
+    fn step_out(&mut self) -> Stop {
+        let result = self.processor.as_mut().map(|processor| {
+            let ra = processor.get_registry_value(1).unwrap_or_default() as usize;
+            processor.run_until(|outcome| outcome.next_pc == ra)
+        });
+        match result {
+            Some(Ok(StepOutcome { halt: None, .. })) => Stop::Step,
+            result => self.stop(result)
+        }
+    }
+
+    fn stop(&self, result: Option<Result<StepOutcome, ExecError>>) -> Stop {
+        match result {
+            None => Stop::Exited(0),
+            Some(Err(error)) => Stop::Exception(error.to_string()),
+            Some(Ok(outcome)) => match outcome.halt {
+                None => Stop::Step,
+                Some(HaltReason::Breakpoint(id)) => Stop::Breakpoint(self.hit_ids(id)),
+                Some(HaltReason::Ebreak) => Stop::Breakpoint(Vec::new()),
+                Some(HaltReason::StartOfHistory) => Stop::Step,
+                Some(reason) => Stop::Exited(reason.exit_code())
+            }
+        }
+    }
+
+    /// The ids the client gave the breakpoints behind a processor breakpoint
+    fn hit_ids(&self, id: usize) -> Vec<usize> {
+        self.active.iter()
+            .filter(|(processor_id, _, _)| *processor_id == id)
+            .map(|(_, id, _)| *id)
+            .collect()
+    }
+
+    fn stopped(&self, stop: Stop) -> Vec<Json> {
+        let (reason, details) = match stop {
+            Stop::Exited(code) => return vec![
+                self.event("exited", Json::object([("exitCode", Json::from(code as i64))])),
+                self.event("terminated", Json::Null)
+            ],
+            Stop::Step => ("step", Vec::new()),
+            Stop::Entry => ("entry", Vec::new()),
+            Stop::Breakpoint(ids) => ("breakpoint", vec![("hitBreakpointIds", Json::from(ids.into_iter().map(Json::from).collect::<Vec<_>>()))]),
+            Stop::Exception(text) => ("exception", vec![("text", Json::from(text))])
+        };
+        let mut body = vec![
+            ("reason".to_string(), Json::from(reason)),
+            ("threadId".to_string(), Json::from(THREAD)),
+            ("allThreadsStopped".to_string(), Json::from(true))
+        ];
+        body.extend(details.into_iter().map(|(name, value)| (name.to_string(), value)));
+        vec![self.event("stopped", Json::Object(body))]
+    }
+
+    fn stack_trace(&self) -> Result<Json, String> {
+        let processor = self.processor.as_ref().ok_or("no program is running")?;
+        let pc = processor.pc();
+        let function = processor.labels()
+            .filter(|&(_, address)| address <= pc)
+            .max_by_key(|&(name, address)| (address, std::cmp::Reverse(name)))
+            .map_or("main", |(name, _)| name);
+        let name = Path::new(&self.program).file_name().map_or(self.program.clone(), |name| name.to_string_lossy().into_owned());
+        Ok(Json::object([
+            ("stackFrames", Json::from(vec![Json::object([
+                ("id", Json::from(0usize)),
+                ("name", Json::from(function)),
+                ("source", Json::object([("name", Json::from(name)), ("path", Json::from(self.program.as_str()))])),
+                ("line", Json::from(processor.source_line(pc).unwrap_or(0))),
+                ("column", Json::from(1usize)),
+                ("instructionPointerReference", Json::from(format!("{:#x}", pc)))
+            ])])),
+            ("totalFrames", Json::from(1usize))
+        ]))
+    }
+
+    fn variables(&self, arguments: &Json) -> Result<Json, String> {
+        let processor = self.processor.as_ref().ok_or("no program is running")?;
+        let reference = arguments.get("variablesReference").and_then(Json::as_i64).unwrap_or(0) as usize;
+        let (_, registers) = SCOPES.get(reference.wrapping_sub(1)).ok_or("there are no such variables")?;
+        let mut variables: Vec<(String, u32, usize)> = registers.iter()
+            .filter_map(|&index| processor.get_registry_value(index).ok().map(|value| (index, value)))
+            .map(|(index, value)| {
+                let pointer = if index == 1 { value as usize } else { value as usize * 4 };
+                (register_name(index).to_string(), value, pointer)
+            })
+            .collect();
+        if reference == SCOPES.len() {
+            variables.push((String::from("pc"), processor.pc() as u32, processor.pc()));
+        }
+        Ok(Json::object([("variables", Json::from(variables.into_iter().map(|(name, value, pointer)| Json::object([
+            ("name", Json::from(name.as_str())),
+            ("value", Json::from(format!("{:#010x} ({})", value, value as i32))),
+            ("evaluateName", Json::from(name)),
+            ("memoryReference", Json::from(format!("{:#x}", pointer))),
+            ("variablesReference", Json::from(0usize))
+        ])).collect::<Vec<_>>()))]))
+    }
+
+    fn read_memory(&self, arguments: &Json) -> Result<Json, String> {
+        let processor = self.processor.as_ref().ok_or("no program is running")?;
+        let reference = arguments.get("memoryReference").and_then(Json::as_str).unwrap_or_default();
+        let address = match reference.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => reference.parse()
+        }.map_err(|_| format!("'{}' is not an address", reference))?;
+        let address = address.checked_add_signed(arguments.get("offset").and_then(Json::as_i64).unwrap_or(0) as isize)
+            .ok_or("the address is out of range")?;
+        let count = arguments.get("count").and_then(Json::as_i64).unwrap_or(0);
+        let count = usize::try_from(count).map_err(|_| format!("'{}' is not a byte count", count))?.min(MAX_READ);
+
+        // Only the part of the range inside of memory is read
+        let data = processor.read_bytes(address, count).or_else(|error| {
+            let readable = (error.size * 4).saturating_sub(address).min(count);
+            processor.read_bytes(address, readable)
+        }).unwrap_or_default();
+        Ok(Json::object([
+            ("address", Json::from(format!("{:#x}", address))),
+            ("data", Json::from(base64(&data))),
+            ("unreadableBytes", Json::from(count - data.len()))
+        ]))
+    }
+
+    fn event(&self, event: &str, body: Json) -> Json {
+        let mut message = vec![("seq", Json::from(0usize)), ("type", Json::from("event")), ("event", Json::from(event))];
+        if body != Json::Null {
+            message.push(("body", body));
+        }
+        members(message)
+    }
+}
+
+impl Default for DebugAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn members(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, &byte)| bits | (byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            encoded.push(match index <= chunk.len() {
+                true => ALPHABET[(bits >> (18 - 6 * index) & 0b111111) as usize] as char,
+                false => '='
+            });
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dap::{base64, DebugAdapter, MAX_READ};
+    use crate::json::Json;
+
+    /// The messages a debug adapter sends in reply to the requests, by the command or event they are for
+    fn session(requests: &[&str]) -> Vec<Json> {
+        let input: String = requests.iter()
+            .enumerate()
+            .map(|(seq, request)| {
+                let request = request.replacen('{', &format!(r#"{{"seq":{},"type":"request","#, seq + 1), 1);
+                format!("Content-Length: {}\r\n\r\n{}", request.len(), request)
+            })
+            .collect();
+        let mut output = Vec::new();
+        DebugAdapter::new().serve(input.as_bytes(), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|message| Json::parse(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect()
+    }
+
+    fn describe(message: &Json) -> String {
+        let kind = message.get("command").or(message.get("event")).and_then(Json::as_str).unwrap_or_default();
+        match message.get("body") {
+            Some(body) => format!("{} {}", kind, body),
+            None => kind.to_string()
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let messages = session(&[
+            r#"{"command":"initialize","arguments":{"adapterID":"rvdap"}}"#,
+            r#"{"command":"setBreakpoints","arguments":{"source":{"path":"examples/arraysum.s"},"breakpoints":[{"line":3}]}}"#,
+            r#"{"command":"launch","arguments":{"program":"examples/arraysum.s","registers":{"a0":512,"a1":5},"memory":{"512":[1,2,3,4,5]}}}"#,
+            r#"{"command":"configurationDone"}"#,
+            r#"{"command":"stackTrace","arguments":{"threadId":1}}"#,
+            r#"{"command":"next","arguments":{"threadId":1}}"#,
+            r#"{"command":"variables","arguments":{"variablesReference":1}}"#,
+            r#"{"command":"readMemory","arguments":{"memoryReference":"0x800","offset":4,"count":6}}"#,
+            r#"{"command":"setBreakpoints","arguments":{"source":{"path":"examples/arraysum.s"},"breakpoints":[]}}"#,
+            r#"{"command":"continue","arguments":{"threadId":1}}"#,
+            r#"{"command":"evaluate","arguments":{"expression":"a0"}}"#,
+            r#"{"command":"disconnect"}"#
+        ]);
+        let messages: Vec<String> = messages.iter().map(describe).collect();
+
+        assert_eq!(messages[..4], [
+            r#"initialize {"supportsConfigurationDoneRequest":true,"supportsHitConditionalBreakpoints":true,"supportsReadMemoryRequest":true,"supportsStepBack":true,"supportsTerminateRequest":true}"#,
+            "initialized",
+            r#"setBreakpoints {"breakpoints":[{"id":1,"verified":false,"line":3,"message":"the program is not loaded yet"}]}"#,
+            "launch"
+        ]);
+        assert_eq!(messages[4], r#"breakpoint {"reason":"changed","breakpoint":{"id":1,"verified":true,"line":4}}"#);
+        assert_eq!(messages[5..7], [
+            "configurationDone",
+            r#"stopped {"reason":"breakpoint","threadId":1,"allThreadsStopped":true,"hitBreakpointIds":[1]}"#
+        ]);
+        assert!(messages[7].contains(r#""name":"loop","source":{"name":"arraysum.s","path":"examples/arraysum.s"},"line":4"#));
+        assert_eq!(messages[8..10], ["next", r#"stopped {"reason":"step","threadId":1,"allThreadsStopped":true}"#]);
+        assert!(messages[10].starts_with(r#"variables {"variables":[{"name":"a0","value":"0x00000200 (512)","evaluateName":"a0","memoryReference":"0x800","#));
+        assert_eq!(messages[11], r#"readMemory {"address":"0x804","data":"AgAAAAMA","unreadableBytes":0}"#);
+        assert_eq!(messages[12], r#"setBreakpoints {"breakpoints":[]}"#);
+        assert_eq!(messages[13..16], [
+            r#"continue {"allThreadsContinued":true}"#,
+            r#"exited {"exitCode":15}"#,
+            "terminated"
+        ]);
+        assert_eq!(messages[16..], ["evaluate", "disconnect"]);
+    }
+
+    #[test]
+    fn test_read_memory_count() {
+        let messages = session(&[
+            r#"{"command":"launch","arguments":{"program":"examples/arraysum.s"}}"#,
+            r#"{"command":"readMemory","arguments":{"memoryReference":"0x800","count":-1}}"#,
+            r#"{"command":"readMemory","arguments":{"memoryReference":"0x800","count":9223372036854775807}}"#,
+            r#"{"command":"disconnect"}"#
+        ]);
+
+        assert_eq!(messages[1].get("success"), Some(&Json::from(false)));
+        assert_eq!(messages[1].get("message"), Some(&Json::from("'-1' is not a byte count")));
+        let body = messages[2].get("body").unwrap();
+        // The request is cut down to the most a single read returns, of which what lies past memory is unreadable
+        assert_eq!(body.get("data").and_then(Json::as_str).map(str::len), Some(2048usize.div_ceil(3) * 4));
+        assert_eq!(body.get("unreadableBytes"), Some(&Json::from(MAX_READ - 2048)));
+    }
+
+    #[test]
+    fn test_content_length_too_large() {
+        let input = "Content-Length: 99999999999\r\n\r\n{}";
+
+        let result = DebugAdapter::new().serve(input.as_bytes(), Vec::new());
+
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,106 @@
+use std::ops::Range;
+use crate::processor::Processor;
+use crate::instruction::{JAL, JALR};
+use crate::xlen::Xlen;
+
+/// A predicate over the state of a processor
+pub type Condition<X> = Box<dyn Fn(&Processor<X>) -> bool>;
+
+/// Stops a processor before it executes the instruction at an address.
+pub struct Breakpoint<X: Xlen> {
+    pub address: usize,
+    condition: Option<Condition<X>>,
+    ignore: usize,
+    hits: usize
+}
+
+impl<X: Xlen> Breakpoint<X> {
+    pub fn new(address: usize) -> Breakpoint<X> {
+        Breakpoint { address, condition: None, ignore: 0, hits: 0 }
+    }
+
+    /// Only counts a hit when `condition` holds for the processor about to execute the instruction
+    pub fn when<F: Fn(&Processor<X>) -> bool + 'static>(mut self, condition: F) -> Breakpoint<X> {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    /// Lets the first `count` hits pass without stopping, like gdb's ignore count
+    pub fn ignore(mut self, count: usize) -> Breakpoint<X> {
+        self.ignore = count;
+        self
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        self.condition.is_some()
+    }
+
+    /// The number of times the processor reached the address with the condition holding
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Whether the processor about to execute the instruction at `pc` reached the breakpoint
+    pub(crate) fn reached(&self, processor: &Processor<X>, pc: usize) -> bool {
+        pc == self.address && self.condition.as_ref().is_none_or(|condition| condition(processor))
+    }
+
+    /// Counts a hit, returning whether the processor should stop
+    pub(crate) fn hit(&mut self) -> bool {
+        self.hits += 1;
+        self.hits > self.ignore
+    }
+}
+
+/// The accesses a memory watchpoint stops at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    /// Both reads and writes
+    Access
+}
+
+impl WatchAccess {
+    pub fn reads(&self) -> bool {
+        *self != WatchAccess::Write
+    }
+
+    pub fn writes(&self) -> bool {
+        *self != WatchAccess::Read
+    }
+}
+
+/// Stops a processor after an instruction that accessed memory or wrote a register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// Accesses to any of the cells in `range`
+    Memory { range: Range<usize>, access: WatchAccess },
+    /// Writes to the integer register, even of the value it already held
+    Register(usize)
+}
+
+/// What a watchpoint watches, in a `WatchHit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Memory(usize),
+    Register(usize)
+}
+
+/// A watchpoint that stopped the processor, with the value before and after the access.
+/// Both are the value read for reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// The id `Processor::add_watchpoint` returned
+    pub id: usize,
+    pub location: Location,
+    pub old: u64,
+    pub new: u64
+}
+
+/// Whether an instruction is a call, linking the return address in `ra` or `t0`,
+/// which stepping over it runs until it is reached
+pub fn is_call(word: u32) -> bool {
+    let rd = (word >> 7) & 0b11111;
+    matches!(word & 0b1111111, JAL | JALR) && (rd == 1 || rd == 5)
+}
@@ -0,0 +1,456 @@
+use crate::csr;
+use crate::instruction::*;
+use crate::instruction::Instruction::{
+    BFormatInstruction,
+    IFormatInstruction,
+    RFormatInstruction,
+    UFormatInstruction,
+    JFormatInstruction,
+    SFormatInstruction,
+    VFormatInstruction,
+    VConfigInstruction
+};
+use crate::vector;
+
+const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6"
+];
+
+/// Returns the ABI name of an integer register, e.g. `sp` for x2.
+pub fn register_name(index: usize) -> &'static str {
+    ABI_NAMES[index]
+}
+
+/// The index of an integer register named by its ABI name, `fp` or `x0` to `x31`
+pub fn register_index(name: &str) -> Option<usize> {
+    match name {
+        "fp" => Some(8),
+        name => ABI_NAMES.iter().position(|&abi| abi == name)
+            .or_else(|| name.strip_prefix('x')?.parse().ok().filter(|&index| index < 32))
+    }
+}
+
+/// Returns the assembler name of a CSR, falling back to its hexadecimal address.
+fn csr_name(address: u32) -> String {
+    csr::name_of(address)
+        .unwrap_or_else(|| format!("{:#x}", address))
+}
+
+/// Returns the assembler name of a vector register, e.g. `v8`.
+fn vector_name(index: usize) -> String {
+    format!("v{}", index)
+}
+
+/// The device input, device output, memory read and memory write bits of a fence predecessor or successor set
+fn fence_set(bits: i16) -> String {
+    "iorw".chars()
+        .enumerate()
+        .filter(|(index, _)| bits >> (3 - index) & 1 == 1)
+        .map(|(_, letter)| letter)
+        .collect()
+}
+
+/// Turns an encoded instruction back into assembly that `assembly_compiler` accepts.
+pub fn disassemble(bits: u32) -> String {
+    match Instruction::from(bits) {
+        Some(instruction) => format_instruction(instruction),
+        None => format!("unknown {:#010x}", bits)
+    }
+}
+
+fn format_instruction(instruction: Instruction) -> String {
+    match instruction {
+        IFormatInstruction { imm, rs1, funct3, rd, opcode } => {
+            let (rd, rs1_name) = (register_name(rd), register_name(rs1));
+            match opcode {
+                OP_IMM => {
+                    // The lowest bit of funct7 doubles as the sixth shift amount bit on RV64
+                    let funct = (imm as u32 & 0xFC0) >> 5 << 3 | funct3;
+                    let unary = match (funct3, imm as u32 & 0xFFF) {
+                        (SLLI, CLZ) => Some("clz"),
+                        (SLLI, CTZ) => Some("ctz"),
+                        (SLLI, CPOP) => Some("cpop"),
+                        (SLLI, SEXT_B) => Some("sext.b"),
+                        (SLLI, SEXT_H) => Some("sext.h"),
+                        (SRLI, ORC_B) => Some("orc.b"),
+                        (SRLI, REV8) => Some("rev8"),
+                        (SRLI, REV8_RV64) => Some("rev8"),
+                        (SRLI, BREV8) => Some("brev8"),
+                        (SLLI, ZIP) => Some("zip"),
+                        (SRLI, UNZIP) => Some("unzip"),
+                        (SLLI, SHA256SUM0) => Some("sha256sum0"),
+                        (SLLI, SHA256SUM1) => Some("sha256sum1"),
+                        (SLLI, SHA256SIG0) => Some("sha256sig0"),
+                        (SLLI, SHA256SIG1) => Some("sha256sig1"),
+                        _ => None
+                    };
+                    if let Some(op) = unary {
+                        return format!("{} {}, {}", op, rd, rs1_name);
+                    }
+
+                    let op = match funct3 {
+                        ADDI => "addi",
+                        SLTI => "slti",
+                        SLTIU => "sltiu",
+                        XORI => "xori",
+                        ORI => "ori",
+                        ANDI => "andi",
+                        _ => match funct {
+                            SLLI => "slli",
+                            BCLRI => "bclri",
+                            BINVI => "binvi",
+                            BSETI => "bseti",
+                            SRLI => "srli",
+                            SRAI => "srai",
+                            RORI => "rori",
+                            BEXTI => "bexti",
+                            _ => "unknown"
+                        }
+                    };
+                    let imm = if funct3 == SLLI || funct3 == SRLI { imm & 0b111111 } else { imm };
+                    format!("{} {}, {}, {}", op, rd, rs1_name, imm)
+                },
+                OP_IMM_32 => {
+                    let op = match (imm as u32 & 0xFFF) >> 5 << 3 | funct3 {
+                        SLLI => "slliw",
+                        SRLI => "srliw",
+                        SRAI => "sraiw",
+                        _ if funct3 == ADDI => "addiw",
+                        _ => "unknown"
+                    };
+                    let imm = if funct3 == ADDI { imm } else { imm & 0b11111 };
+                    format!("{} {}, {}, {}", op, rd, rs1_name, imm)
+                },
+                JALR => format!("jalr {}, {}, {}", rd, rs1_name, imm),
+                LOAD => {
+                    let op = match funct3 {
+                        LB => "lb",
+                        LH => "lh",
+                        LW => "lw",
+                        LBU => "lbu",
+                        LHU => "lhu",
+                        LWU => "lwu",
+                        LD => "ld",
+                        _ => "unknown"
+                    };
+                    format!("{} {}, {}({})", op, rd, imm, rs1_name)
+                },
+                SYSTEM => {
+                    let csr = csr_name(imm as u32 & 0xFFF);
+                    match funct3 {
+                        CSRRW => format!("csrrw {}, {}, {}", rd, csr, rs1_name),
+                        CSRRS => format!("csrrs {}, {}, {}", rd, csr, rs1_name),
+                        CSRRC => format!("csrrc {}, {}, {}", rd, csr, rs1_name),
+                        CSRRWI => format!("csrrwi {}, {}, {}", rd, csr, rs1),
+                        CSRRSI => format!("csrrsi {}, {}, {}", rd, csr, rs1),
+                        CSRRCI => format!("csrrci {}, {}, {}", rd, csr, rs1),
+                        _ if imm == EBREAK => String::from("ebreak"),
+                        _ if imm == SRET => String::from("sret"),
+                        _ if imm == MRET => String::from("mret"),
+                        _ if imm == WFI => String::from("wfi"),
+                        _ if imm & !0b11111 == SFENCE_VMA && (rs1 | imm as usize & 0b11111) == 0 => String::from("sfence.vma"),
+                        _ if imm & !0b11111 == SFENCE_VMA => {
+                            format!("sfence.vma {}, {}", rs1_name, register_name(imm as usize & 0b11111))
+                        },
+                        _ => String::from("ecall")
+                    }
+                },
+                FENCE => format!("fence {}, {}", fence_set(imm >> 4), fence_set(imm)),
+                _ => String::from("unknown")
+            }
+        },
+        RFormatInstruction { rd, funct3, rs1, rs2, funct7, opcode: OP_32 } => {
+            let op = match (funct7 << 3) + funct3 {
+                PACKW if rs2 == 0 => return format!("zext.h {}, {}", register_name(rd), register_name(rs1)),
+                PACKW => "packw",
+                ADD => "addw",
+                SUB => "subw",
+                SLL => "sllw",
+                SRL => "srlw",
+                SRA => "sraw",
+                _ => "unknown"
+            };
+            format!("{} {}, {}, {}", op, register_name(rd), register_name(rs1), register_name(rs2))
+        },
+        RFormatInstruction { rd, funct3, rs1, rs2, funct7, .. } => {
+            if (funct7 << 3) + funct3 == ZEXT_H && rs2 == 0 {
+                return format!("zext.h {}, {}", register_name(rd), register_name(rs1));
+            }
+
+            let aes = match ((funct7 << 3) + funct3) & AES32_MASK {
+                AES32ESI => Some("aes32esi"),
+                AES32ESMI => Some("aes32esmi"),
+                AES32DSI => Some("aes32dsi"),
+                AES32DSMI => Some("aes32dsmi"),
+                _ => None
+            };
+            if let Some(op) = aes {
+                let (rd, rs1, rs2) = (register_name(rd), register_name(rs1), register_name(rs2));
+                return format!("{} {}, {}, {}, {}", op, rd, rs1, rs2, funct7 >> 5);
+            }
+
+            let op = match (funct7 << 3) + funct3 {
+                ADD => "add",
+                SUB => "sub",
+                SLL => "sll",
+                SLT => "slt",
+                SLTU => "sltu",
+                XOR => "xor",
+                SRL => "srl",
+                SRA => "sra",
+                OR => "or",
+                AND => "and",
+                SH1ADD => "sh1add",
+                SH2ADD => "sh2add",
+                SH3ADD => "sh3add",
+                ANDN => "andn",
+                ORN => "orn",
+                XNOR => "xnor",
+                MIN => "min",
+                MINU => "minu",
+                MAX => "max",
+                MAXU => "maxu",
+                ROL => "rol",
+                ROR => "ror",
+                CLMUL => "clmul",
+                CLMULH => "clmulh",
+                CLMULR => "clmulr",
+                BCLR => "bclr",
+                BEXT => "bext",
+                BINV => "binv",
+                BSET => "bset",
+                PACK => "pack",
+                PACKH => "packh",
+                XPERM4 => "xperm4",
+                XPERM8 => "xperm8",
+                SHA512SUM0R => "sha512sum0r",
+                SHA512SUM1R => "sha512sum1r",
+                SHA512SIG0L => "sha512sig0l",
+                SHA512SIG0H => "sha512sig0h",
+                SHA512SIG1L => "sha512sig1l",
+                SHA512SIG1H => "sha512sig1h",
+                _ => "unknown"
+            };
+            format!("{} {}, {}, {}", op, register_name(rd), register_name(rs1), register_name(rs2))
+        },
+        UFormatInstruction { imm, rd, opcode } => {
+            let op = if opcode == LUI { "lui" } else { "auipc" };
+            format!("{} {}, {}", op, register_name(rd), imm)
+        },
+        JFormatInstruction { imm, rd, .. } => format!("jal {}, {}", register_name(rd), imm),
+        BFormatInstruction { imm, rs1, rs2, funct3 } => {
+            let op = match funct3 {
+                BEQ => "beq",
+                BNE => "bne",
+                BLT => "blt",
+                BGE => "bge",
+                BLTU => "bltu",
+                BGEU => "bgeu",
+                _ => "unknown"
+            };
+            format!("{} {}, {}, {}", op, register_name(rs1), register_name(rs2), imm)
+        },
+        SFormatInstruction { imm, rs1, rs2, funct3 } => {
+            let op = match funct3 {
+                SB => "sb",
+                SH => "sh",
+                SW => "sw",
+                SD => "sd",
+                _ => "unknown"
+            };
+            format!("{} {}, {}({})", op, register_name(rs2), imm, register_name(rs1))
+        },
+        VFormatInstruction { funct6, vm, vs2, vs1, funct3, vd, opcode } => {
+            let mask = if vm { "" } else { ", v0.t" };
+            let vd_name = vector_name(vd);
+            let (vs2_name, vs1_name) = (vector_name(vs2), vector_name(vs1));
+            // Shifts take an unsigned immediate, the other .vi forms a signed one
+            let immediate = if [VSLL, VSRL, VSRA].contains(&funct6) { vs1 as i32 } else { (vs1 as i32) << 27 >> 27 };
+            match (opcode, funct3, funct6) {
+                (LOAD_FP | STORE_FP, width, mode) => {
+                    let op = if opcode == LOAD_FP { "l" } else { "s" };
+                    let eew = vector::element_width(width).unwrap();
+                    match mode {
+                        VSTRIDED => format!("v{}se{}.v {}, ({}), {}{}", op, eew, vd_name, register_name(vs1), register_name(vs2), mask),
+                        _ => format!("v{}e{}.v {}, ({}){}", op, eew, vd_name, register_name(vs1), mask)
+                    }
+                },
+                (_, OPMVV, VWXUNARY0) => format!("vmv.x.s {}, {}", register_name(vd), vs2_name),
+                (_, OPMVX, VRXUNARY0) => format!("vmv.s.x {}, {}", vd_name, register_name(vs1)),
+                (_, OPIVI, VMVNRR) => format!("vmv{}r.v {}, {}", vs1 + 1, vd_name, vs2_name),
+                (_, OPIVV, VMERGE) if vm => format!("vmv.v.v {}, {}", vd_name, vs1_name),
+                (_, OPIVX, VMERGE) if vm => format!("vmv.v.x {}, {}", vd_name, register_name(vs1)),
+                (_, OPIVI, VMERGE) if vm => format!("vmv.v.i {}, {}", vd_name, immediate),
+                (_, OPIVV, VMERGE) => format!("vmerge.vvm {}, {}, {}, v0", vd_name, vs2_name, vs1_name),
+                (_, OPIVX, VMERGE) => format!("vmerge.vxm {}, {}, {}, v0", vd_name, vs2_name, register_name(vs1)),
+                (_, OPIVI, VMERGE) => format!("vmerge.vim {}, {}, {}, v0", vd_name, vs2_name, immediate),
+                (_, _, _) => {
+                    let op = vector::mnemonic_of(funct3, funct6).unwrap_or("unknown");
+                    let operand = match funct3 {
+                        OPIVX | OPMVX => register_name(vs1).to_owned(),
+                        OPIVI => immediate.to_string(),
+                        _ => vs1_name
+                    };
+                    format!("{} {}, {}, {}{}", op, vd_name, vs2_name, operand, mask)
+                }
+            }
+        },
+        VConfigInstruction { rd, rs1, rs2, vtypei, funct } => {
+            match funct {
+                VSETVL => format!("vsetvl {}, {}, {}", register_name(rd), register_name(rs1), register_name(rs2)),
+                VSETIVLI => format!("vsetivli {}, {}, {}", register_name(rd), rs1, vector::vtype_name(vtypei)),
+                _ => format!("vsetvli {}, {}, {}", register_name(rd), register_name(rs1), vector::vtype_name(vtypei))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::disassemble;
+
+    #[test]
+    fn test_disassemble_add() {
+        assert_eq!(disassemble(0b0000000_00001_00000_000_00101_0110011), "add t0, zero, ra");
+    }
+
+    #[test]
+    fn test_disassemble_srai() {
+        assert_eq!(disassemble(0b0100000_10100_00000_101_00101_0010011), "srai t0, zero, 20");
+    }
+
+    #[test]
+    fn test_disassemble_rori() {
+        assert_eq!(disassemble(0b0110000_00111_00100_101_00101_0010011), "rori t0, tp, 7");
+    }
+
+    #[test]
+    fn test_disassemble_cpop() {
+        assert_eq!(disassemble(0b0110000_00010_01011_001_01010_0010011), "cpop a0, a1");
+    }
+
+    #[test]
+    fn test_disassemble_zext_h() {
+        assert_eq!(disassemble(0b0000100_00000_01011_100_01010_0110011), "zext.h a0, a1");
+        assert_eq!(disassemble(0b0000100_00000_01011_100_01010_0111011), "zext.h a0, a1");
+        assert_eq!(disassemble(0b0000100_01100_01011_100_01010_0111011), "packw a0, a1, a2");
+    }
+
+    #[test]
+    fn test_disassemble_clmulh() {
+        assert_eq!(disassemble(0b0000101_01100_01011_011_01010_0110011), "clmulh a0, a1, a2");
+    }
+
+    #[test]
+    fn test_disassemble_aes32esmi() {
+        assert_eq!(disassemble(0b10_10011_01011_01010_000_01010_0110011), "aes32esmi a0, a0, a1, 2");
+    }
+
+    #[test]
+    fn test_disassemble_sha256sig0() {
+        assert_eq!(disassemble(0b0001000_00010_01011_001_01010_0010011), "sha256sig0 a0, a1");
+    }
+
+    #[test]
+    fn test_disassemble_lw() {
+        assert_eq!(disassemble(0b111111111111_11100_010_00111_0000011), "lw t2, -1(t3)");
+    }
+
+    #[test]
+    fn test_disassemble_sw() {
+        assert_eq!(disassemble(0b0000000_00111_11100_010_00000_0100011), "sw t2, 0(t3)");
+    }
+
+    #[test]
+    fn test_disassemble_beq() {
+        assert_eq!(disassemble(0b0_100110_00011_00101_000_1001_0_1100011), "beq t0, gp, 1234");
+    }
+
+    #[test]
+    fn test_disassemble_lui() {
+        assert_eq!(disassemble(0b00000000010011010010_00101_0110111), "lui t0, 1234");
+    }
+
+    #[test]
+    fn test_disassemble_slli_rv64() {
+        assert_eq!(disassemble(0b000000_100001_00101_001_00101_0010011), "slli t0, t0, 33");
+    }
+
+    #[test]
+    fn test_disassemble_addiw() {
+        assert_eq!(disassemble(0b111111111111_01011_000_01010_0011011), "addiw a0, a1, -1");
+    }
+
+    #[test]
+    fn test_disassemble_sraw() {
+        assert_eq!(disassemble(0b0100000_01100_01011_101_01010_0111011), "sraw a0, a1, a2");
+    }
+
+    #[test]
+    fn test_disassemble_ld_sd() {
+        assert_eq!(disassemble(0b000000001000_00010_011_01010_0000011), "ld a0, 8(sp)");
+        assert_eq!(disassemble(0b0000000_01010_00010_011_01000_0100011), "sd a0, 8(sp)");
+    }
+
+    #[test]
+    fn test_disassemble_vsetvli() {
+        assert_eq!(disassemble(0b0_00011010000_01011_111_00101_1010111), "vsetvli t0, a1, e32, m1, ta, ma");
+        assert_eq!(disassemble(0b11_0000001000_00100_111_00000_1010111), "vsetivli zero, 4, e16, m1, tu, mu");
+    }
+
+    #[test]
+    fn test_disassemble_vle32() {
+        assert_eq!(disassemble(0b000_0_00_1_00000_01010_110_00001_0000111), "vle32.v v1, (a0)");
+        assert_eq!(disassemble(0b000_0_10_0_01011_01010_110_00001_0100111), "vsse32.v v1, (a0), a1, v0.t");
+    }
+
+    #[test]
+    fn test_disassemble_vadd() {
+        assert_eq!(disassemble(0b000000_1_00010_00011_000_00001_1010111), "vadd.vv v1, v2, v3");
+        assert_eq!(disassemble(0b000000_0_00010_11111_011_00001_1010111), "vadd.vi v1, v2, -1, v0.t");
+    }
+
+    #[test]
+    fn test_disassemble_vredsum() {
+        assert_eq!(disassemble(0b000000_1_00001_01000_010_01000_1010111), "vredsum.vs v8, v1, v8");
+    }
+
+    #[test]
+    fn test_disassemble_vmv() {
+        assert_eq!(disassemble(0b010000_1_01000_00000_010_01010_1010111), "vmv.x.s a0, v8");
+        assert_eq!(disassemble(0b010111_1_00000_00101_011_00100_1010111), "vmv.v.i v4, 5");
+        assert_eq!(disassemble(0b100111_1_00100_00001_011_00010_1010111), "vmv2r.v v2, v4");
+    }
+
+    #[test]
+    fn test_disassemble_system() {
+        assert_eq!(disassemble(0x00000073), "ecall");
+        assert_eq!(disassemble(0x00100073), "ebreak");
+        assert_eq!(disassemble(0x10200073), "sret");
+        assert_eq!(disassemble(0x30200073), "mret");
+        assert_eq!(disassemble(0x10500073), "wfi");
+        assert_eq!(disassemble(0x12000073), "sfence.vma");
+        assert_eq!(disassemble(0x12b50073), "sfence.vma a0, a1");
+        assert_eq!(disassemble(0x0ff0000f), "fence iorw, iorw");
+    }
+
+    #[test]
+    fn test_disassemble_reserved() {
+        assert_eq!(disassemble(0x00000000), "unknown 0x00000000");
+        assert_eq!(disassemble(0xffffffff), "unknown 0xffffffff");
+        assert_eq!(disassemble(0b0100010_00001_00000_101_00101_0010011), "unknown 0x44105293");
+    }
+
+    #[test]
+    fn test_disassemble_csrrw() {
+        assert_eq!(disassemble(0x30529073), "csrrw zero, mtvec, t0");
+    }
+
+    #[test]
+    fn test_disassemble_csrrsi_unknown_csr() {
+        assert_eq!(disassemble(0x7ff2e573), "csrrsi a0, 0x7ff, 5");
+    }
+}
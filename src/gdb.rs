@@ -0,0 +1,436 @@
+//! A stub speaking gdb's remote serial protocol, so that gdb can debug a program running on a `Processor`.
+//!
+//! gdb sees memory as bytes, through `Processor::read_bytes`: the data word a program accesses
+//! at cell `n` appears at address `4 * n`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, BufReader, Read, Write};
+use std::ops::Range;
+use crate::debug::{Breakpoint, Location, WatchAccess, Watchpoint};
+use crate::disassembler::register_name;
+use crate::processor::{ExecError, HaltReason, Processor};
+use crate::trap::Exception;
+use crate::xlen::Xlen;
+
+/// Register number of the pc in `g` and `p` packets, after x0 to x31
+const PC: usize = 32;
+/// The largest packet gdb may send us
+const PACKET_SIZE: usize = 4096;
+
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGBUS: u8 = 10;
+const SIGSEGV: u8 = 11;
+
+pub struct GdbStub<X: Xlen> {
+    processor: Processor<X>,
+    /// The ids of the processor's breakpoints by address
+    breakpoints: HashMap<usize, usize>,
+    /// The ids of the processor's watchpoints by packet type, address and length
+    watchpoints: HashMap<(u8, usize, usize), usize>,
+    ack: bool
+}
+
+impl<X: Xlen> GdbStub<X> {
+    pub fn new(processor: Processor<X>) -> GdbStub<X> {
+        GdbStub { processor, breakpoints: HashMap::new(), watchpoints: HashMap::new(), ack: true }
+    }
+
+    pub fn into_processor(self) -> Processor<X> {
+        self.processor
+    }
+
+    /// Answers the packets read from `input` until gdb detaches, kills the program or closes the connection
+    pub fn serve<R: Read, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        let mut bytes = BufReader::new(input).bytes();
+        loop {
+            // Acknowledgements and interrupts between packets are ignored, there is nothing running to interrupt
+            match bytes.next().transpose()? {
+                None => return Ok(()),
+                Some(b'$') => {},
+                Some(_) => continue
+            }
+            let mut packet = Vec::new();
+            loop {
+                match bytes.next().transpose()? {
+                    None => return Ok(()),
+                    Some(b'#') => break,
+                    Some(byte) => packet.push(byte)
+                }
+            }
+            let checksum = [bytes.next().transpose()?, bytes.next().transpose()?];
+            let valid = match checksum {
+                [Some(high), Some(low)] => u8::from_str_radix(&String::from_utf8_lossy(&[high, low]), 16)
+                    .is_ok_and(|checksum| checksum == sum(&packet)),
+                _ => return Ok(())
+            };
+            if self.ack {
+                output.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if !valid {
+                output.flush()?;
+                continue;
+            }
+
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+            // Killing the program is not answered
+            if packet == "k" {
+                return Ok(());
+            }
+            send(&mut output, &self.handle(&packet))?;
+            if packet.starts_with('D') {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The reply to a packet, empty for the packets the stub does not support
+    fn handle(&mut self, packet: &str) -> String {
+        let (kind, body) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        match kind {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => (0..=PC).map(|index| self.register(index)).collect(),
+            "G" => {
+                for (index, value) in body.as_bytes().chunks(X::BITS as usize / 4).enumerate().take(PC + 1) {
+                    self.set_register(index, std::str::from_utf8(value).unwrap_or_default());
+                }
+                String::from("OK")
+            },
+            "p" => match usize::from_str_radix(body, 16) {
+                Ok(index) if index <= PC => self.register(index),
+                _ => String::from("E01")
+            },
+            "P" => match body.split_once('=').map(|(index, value)| (usize::from_str_radix(index, 16), value)) {
+                Some((Ok(index), value)) if index <= PC => {
+                    self.set_register(index, value);
+                    String::from("OK")
+                },
+                _ => String::from("E01")
+            },
+            "m" => match parse_pair(body) {
+                Some((address, length)) => self.read_memory(address, length),
+                None => String::from("E01")
+            },
+            "M" => match body.split_once(':').and_then(|(range, data)| Some((parse_pair(range)?, decode_hex(data)?))) {
+                Some(((address, length), data)) if data.len() == length => self.write_memory(address, &data),
+                _ => String::from("E01")
+            },
+            "s" | "c" => {
+                if let Ok(address) = usize::from_str_radix(body, 16) {
+                    self.processor.set_pc(address);
+                }
+                if kind == "s" { self.step() } else { self.resume() }
+            },
+            // Going backwards needs the processor to keep a history
+            "b" => match body {
+                "s" => match self.processor.step_back() {
+                    Some(_) => format!("S{:02x}", SIGTRAP),
+                    None => self.stop_reply(HaltReason::StartOfHistory)
+                },
+                "c" => {
+                    let reason = self.processor.reverse_continue();
+                    self.stop_reply(reason)
+                },
+                _ => String::new()
+            },
+            "Z" | "z" => self.point(kind == "Z", body),
+            "H" | "D" => String::from("OK"),
+            "q" | "Q" => self.query(packet),
+            _ => String::new()
+        }
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+;ReverseStep+;ReverseContinue+", PACKET_SIZE);
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let xml = target_xml(X::BITS);
+            return match parse_pair(range) {
+                Some((offset, _)) if offset >= xml.len() => String::from("l"),
+                Some((offset, length)) if offset.saturating_add(length) >= xml.len() => format!("l{}", &xml[offset..]),
+                Some((offset, length)) => format!("m{}", &xml[offset..offset + length]),
+                None => String::from("E01")
+            };
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.ack = false;
+                String::from("OK")
+            },
+            "qAttached" => String::from("1"),
+            "qC" => String::from("QC1"),
+            "qfThreadInfo" => String::from("m1"),
+            "qsThreadInfo" => String::from("l"),
+            _ => String::new()
+        }
+    }
+
+    /// Executes one instruction, even if there is a breakpoint on it
+    fn step(&mut self) -> String {
+        let outcome = match self.processor.step() {
+            Ok(outcome) if !outcome.retired && matches!(outcome.halt, Some(HaltReason::Breakpoint(_))) => self.processor.step(),
+            outcome => outcome
+        };
+        match outcome {
+            Ok(outcome) => match outcome.halt {
+                Some(reason) => self.stop_reply(reason),
+                None => format!("S{:02x}", SIGTRAP)
+            },
+            Err(error) => exception_reply(&error)
+        }
+    }
+
+    fn resume(&mut self) -> String {
+        match self.processor.run_until(|_| false) {
+            Ok(outcome) => self.stop_reply(outcome.halt.unwrap_or(HaltReason::EndOfProgram)),
+            Err(error) => exception_reply(&error)
+        }
+    }
+
+    fn stop_reply(&self, reason: HaltReason) -> String {
+        match reason {
+            HaltReason::Breakpoint(_) => format!("T{:02x}swbreak:;", SIGTRAP),
+            HaltReason::Watchpoint(hit) => match hit.location {
+                Location::Memory(cell) => {
+                    let kind = match self.processor.watchpoints().find(|&(id, _)| id == hit.id) {
+                        Some((_, Watchpoint::Memory { access: WatchAccess::Read, .. })) => "rwatch",
+                        Some((_, Watchpoint::Memory { access: WatchAccess::Access, .. })) => "awatch",
+                        _ => "watch"
+                    };
+                    format!("T{:02x}{}:{:x};", SIGTRAP, kind, cell * 4)
+                },
+                Location::Register(_) => format!("S{:02x}", SIGTRAP)
+            },
+            HaltReason::Returned(code) | HaltReason::Exit(code) | HaltReason::TestFinisher(code) => format!("W{:02x}", code as u8),
+            HaltReason::EndOfProgram => String::from("W00"),
+            HaltReason::StartOfHistory => format!("T{:02x}replaylog:begin;", SIGTRAP),
+            HaltReason::Ebreak | HaltReason::Reached(_) | HaltReason::InstructionLimit => format!("S{:02x}", SIGTRAP)
+        }
+    }
+
+    /// Inserts or removes a breakpoint, `Z0` and `Z1`, or a watchpoint, `Z2` to `Z4` for writes, reads and both
+    fn point(&mut self, insert: bool, body: &str) -> String {
+        let mut fields = body.split(',');
+        let (kind, address, length) = match (fields.next(), fields.next().map(|field| usize::from_str_radix(field, 16)), fields.next()) {
+            (Some(kind), Some(Ok(address)), Some(length)) => (kind, address, usize::from_str_radix(length, 16).unwrap_or(4)),
+            _ => return String::from("E01")
+        };
+        match (kind, insert) {
+            ("0" | "1", true) => {
+                if !self.breakpoints.contains_key(&address) {
+                    let id = self.processor.add_breakpoint(Breakpoint::new(address));
+                    self.breakpoints.insert(address, id);
+                }
+            },
+            ("0" | "1", false) => {
+                if let Some(id) = self.breakpoints.remove(&address) {
+                    self.processor.remove_breakpoint(id);
+                }
+            },
+            ("2" | "3" | "4", true) => {
+                let access = match kind {
+                    "2" => WatchAccess::Write,
+                    "3" => WatchAccess::Read,
+                    _ => WatchAccess::Access
+                };
+                let Some(range) = cells(address, length) else {
+                    return String::from("E01");
+                };
+                let key = (kind.as_bytes()[0], address, length);
+                if !self.watchpoints.contains_key(&key) {
+                    let id = self.processor.add_watchpoint(Watchpoint::Memory { range, access });
+                    self.watchpoints.insert(key, id);
+                }
+            },
+            ("2" | "3" | "4", false) => {
+                if let Some(id) = self.watchpoints.remove(&(kind.as_bytes()[0], address, length)) {
+                    self.processor.remove_watchpoint(id);
+                }
+            },
+            _ => return String::new()
+        }
+        String::from("OK")
+    }
+
+    /// A register in target byte order, or `x`s for the registers RV32E does not have
+    fn register(&self, index: usize) -> String {
+        let bytes = X::BITS as usize / 8;
+        let value = match index {
+            PC => self.processor.pc() as u64,
+            index => match self.processor.get_registry_value(index) {
+                Ok(value) => value.as_u64(),
+                Err(_) => return "xx".repeat(bytes)
+            }
+        };
+        encode_hex(&value.to_le_bytes()[..bytes])
+    }
+
+    fn set_register(&mut self, index: usize, hex: &str) {
+        let Some(bytes) = decode_hex(hex) else { return };
+        let value = bytes.iter().rev().fold(0u64, |value, &byte| value << 8 | byte as u64);
+        // Registers RV32E does not have are ignored, as gdb shows them as unavailable
+        match index {
+            PC => self.processor.set_pc(value as usize),
+            index => { let _ = self.processor.set_register_value(index, X::from_u64(value)); }
+        }
+    }
+
+    fn read_memory(&self, address: usize, length: usize) -> String {
+        match self.processor.read_bytes(address, length) {
+            Ok(bytes) => encode_hex(&bytes),
+            Err(_) => String::from("E14")
+        }
+    }
+
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> String {
+        match self.processor.write_bytes(address, data) {
+            Ok(()) => String::from("OK"),
+            Err(_) => String::from("E14")
+        }
+    }
+}
+
+/// The memory cells holding the bytes `address..address + length`, unless that runs past the address space
+fn cells(address: usize, length: usize) -> Option<Range<usize>> {
+    Some(address / 4..address.checked_add(length.max(1))?.div_ceil(4))
+}
+
+fn exception_reply(error: &ExecError) -> String {
+    match error.exception {
+        Exception::IllegalInstruction => format!("S{:02x}", SIGILL),
+        Exception::InstructionAddressMisaligned(_) => format!("S{:02x}", SIGBUS),
+        Exception::InstructionAccessFault(_) | Exception::LoadAccessFault(_) | Exception::StoreAccessFault(_)
+            | Exception::InstructionPageFault(_) | Exception::LoadPageFault(_) | Exception::StorePageFault(_) => format!("S{:02x}", SIGSEGV),
+        Exception::Breakpoint | Exception::EnvironmentCall(_) => format!("S{:02x}", SIGTRAP)
+    }
+}
+
+/// Describes the registers of the `g` packet to gdb
+fn target_xml(xlen: u32) -> String {
+    let mut xml = format!(concat!(
+        r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target version="1.0">"#,
+        r#"<architecture>riscv:rv{}</architecture><feature name="org.gnu.gdb.riscv.cpu">"#
+    ), xlen);
+    for index in 0..32 {
+        let kind = match index {
+            1 => "code_ptr",
+            2 => "data_ptr",
+            _ => "int"
+        };
+        let _ = write!(xml, r#"<reg name="{}" bitsize="{}" type="{}" regnum="{}"/>"#, register_name(index), xlen, kind, index);
+    }
+    let _ = write!(xml, r#"<reg name="pc" bitsize="{}" type="code_ptr" regnum="{}"/></feature></target>"#, xlen, PC);
+    xml
+}
+
+fn send<W: Write>(output: &mut W, reply: &str) -> io::Result<()> {
+    write!(output, "${}#{:02x}", reply, sum(reply.as_bytes()))?;
+    output.flush()
+}
+
+fn sum(packet: &[u8]) -> u8 {
+    packet.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// The `<address>,<length>` of memory and `qXfer` packets, in hexadecimal
+fn parse_pair(text: &str) -> Option<(usize, usize)> {
+    let (first, second) = text.split_once(',')?;
+    Some((usize::from_str_radix(first, 16).ok()?, usize::from_str_radix(second, 16).ok()?))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gdb::{sum, GdbStub};
+    use crate::processor::Processor;
+
+    fn packet(data: &str) -> String {
+        format!("${}#{:02x}", data, sum(data.as_bytes()))
+    }
+
+    /// The replies of a stub for arraysum to the packets, without acknowledgements
+    fn session(packets: &[&str]) -> Vec<String> {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/arraysum.s").unwrap();
+        processor.store_in_memory(512, &[1, 2, 3, 4, 5]).unwrap();
+        processor.set_register_value(10, 512).unwrap();
+        processor.set_register_value(11, 5).unwrap();
+        processor.set_history_limit(100);
+        let input: String = packets.iter().map(|data| format!("+{}", packet(data))).collect();
+
+        let mut output = Vec::new();
+        GdbStub::new(processor).serve(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+            .split('$')
+            .skip(1)
+            .map(|reply| reply.split('#').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_registers() {
+        let replies = session(&["?", "p20", "pa", "Pa=00100000", "pa", "P20=08000000", "g"]);
+
+        assert_eq!(replies[..6], ["S05", "00000000", "00020000", "OK", "00100000", "OK"]);
+        assert_eq!(replies[6].len(), 33 * 8);
+        assert_eq!(&replies[6][80..88], "00100000");
+        assert_eq!(&replies[6][256..], "08000000");
+    }
+
+    #[test]
+    fn test_memory() {
+        let replies = session(&["m0,8", "m800,6", "M801,2:beef", "m800,4", "m10000,4", "mfffffffffffffff0,20", "Mfffffffffffffffe,4:01020304"]);
+
+        assert_eq!(replies, ["9302000013030000", "010000000200", "OK", "01beef00", "E14", "E14", "E14"]);
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let replies = session(&["Z0,8,4", "c", "s", "p20", "z0,8,4", "Z3,808,4", "c", "z3,808,4", "c", "pa"]);
+
+        assert_eq!(replies, ["OK", "T05swbreak:;", "S05", "0c000000", "OK", "OK", "T05rwatch:808;", "OK", "W0f", "0f000000"]);
+    }
+
+    #[test]
+    fn test_watchpoint_past_address_space() {
+        let replies = session(&["Z2,fffffffffffffff0,20", "Z2,fffffffffffffff0,f"]);
+
+        assert_eq!(replies, ["E01", "OK"]);
+    }
+
+    #[test]
+    fn test_reverse_execution() {
+        let replies = session(&["Z0,8,4", "c", "c", "p6", "bc", "p6", "bs", "bs", "bs", "p20", "c"]);
+
+        assert_eq!(replies, ["OK", "T05swbreak:;", "T05swbreak:;", "01000000", "T05swbreak:;", "00000000",
+            "S05", "S05", "T05replaylog:begin;", "00000000", "T05swbreak:;"]);
+    }
+
+    #[test]
+    fn test_target_description() {
+        let replies = session(&["qSupported:multiprocess+;swbreak+", "QStartNoAckMode", "qXfer:features:read:target.xml:0,ffff", "vMustReplyEmpty",
+            "qXfer:features:read:target.xml:10,ffffffffffffffff", "D"]);
+
+        assert!(replies[0].contains("qXfer:features:read+"));
+        assert!(replies[0].contains("ReverseStep+;ReverseContinue+"));
+        assert_eq!(replies[1], "OK");
+        assert!(replies[2].starts_with("l<?xml"));
+        assert!(replies[2].contains("<architecture>riscv:rv32</architecture>"));
+        assert!(replies[2].ends_with(r#"<reg name="pc" bitsize="32" type="code_ptr" regnum="32"/></feature></target>"#));
+        assert_eq!(replies[3], "");
+        assert!(replies[4].starts_with("l") && replies[4].ends_with("</target>"));
+        assert_eq!(replies[5], "OK");
+    }
+}
@@ -7,7 +7,7 @@ pub trait Immediate {
 
     fn sign_extend(instruction: u32, lsb: usize) -> u32 {
         let mut i = 0u32;
-        let sign = if instruction.bit(31) == true { u32::MAX } else { 0 };
+        let sign = if instruction.bit(31) { u32::MAX } else { 0 };
         u32::set_bit_range(&mut i, 31, lsb, sign);
         i
     }
@@ -20,7 +20,7 @@ struct BitMover<'a> {
 }
 
 impl<'a> BitMover<'a> {
-    fn move_bits(instruction: &u32, msb: usize, lsb: usize) -> BitMover {
+    fn move_bits(instruction: &u32, msb: usize, lsb: usize) -> BitMover<'_> {
         BitMover {
             instruction,
             msb,
@@ -29,7 +29,7 @@ impl<'a> BitMover<'a> {
     }
     
     fn to(&self, i: &mut u32, msb: usize, lsb: usize) -> u32 {
-        let range: u32 = u32::bit_range(&self.instruction, self.msb, self.lsb);
+        let range: u32 = u32::bit_range(self.instruction, self.msb, self.lsb);
         u32::set_bit_range(i, msb, lsb, range);
         i.to_owned()
     }
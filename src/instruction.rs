@@ -4,11 +4,21 @@ use Instruction::{
     RFormatInstruction,
     UFormatInstruction,
     JFormatInstruction,
-    SFormatInstruction
+    SFormatInstruction,
+    VFormatInstruction,
+    VConfigInstruction
 };
+use crate::crypto;
 use crate::immediates::{BImmediate, IImmediate, Immediate, JImmediate, SImmediate, UImmediate};
+use crate::csr::Privilege;
+use crate::isa::{Extension, Isa};
+use crate::memory::Memory;
 use crate::register::Register;
+use crate::trap::Exception;
+use crate::math_utils;
+use crate::vector;
 use crate::math_utils::MixedIntegerOps;
+use crate::xlen::Xlen;
 
 /// opcodes
 pub const OP_IMM: u32 = 0b0010011;
@@ -21,6 +31,16 @@ pub const JAL: u32    = 0b1101111;
 pub const BRANCH: u32 = 0b1100011;
 pub const LOAD: u32   = 0b0000011;
 pub const STORE: u32  = 0b0100011;
+pub const SYSTEM: u32 = 0b1110011;
+
+/// RV64 opcodes for the instructions operating on the lower 32 bits
+pub const OP_IMM_32: u32 = 0b0011011;
+pub const OP_32: u32     = 0b0111011;
+
+/// Vector opcodes, the loads and stores share theirs with the floating point ones
+pub const OP_V: u32     = 0b1010111;
+pub const LOAD_FP: u32  = 0b0000111;
+pub const STORE_FP: u32 = 0b0100111;
 
 /// functions
 pub const ADDI: u32  = 0b0000000000;
@@ -44,6 +64,79 @@ pub const SRA: u32  = 0b0100000101;
 pub const OR: u32   = 0b0000000110;
 pub const AND: u32  = 0b0000000111;
 
+/// Zba
+pub const SH1ADD: u32 = 0b0010000010;
+pub const SH2ADD: u32 = 0b0010000100;
+pub const SH3ADD: u32 = 0b0010000110;
+
+/// Zbb
+pub const ANDN: u32   = 0b0100000111;
+pub const ORN: u32    = 0b0100000110;
+pub const XNOR: u32   = 0b0100000100;
+pub const MIN: u32    = 0b0000101100;
+pub const MINU: u32   = 0b0000101101;
+pub const MAX: u32    = 0b0000101110;
+pub const MAXU: u32   = 0b0000101111;
+pub const ROL: u32    = 0b0110000001;
+pub const ROR: u32    = 0b0110000101;
+pub const RORI: u32   = 0b0110000101;
+pub const ZEXT_H: u32 = 0b0000100100;
+
+/// Zbb instructions with a single source, encoded in the immediate of OP_IMM
+pub const CLZ: u32    = 0b0110000_00000;
+pub const CTZ: u32    = 0b0110000_00001;
+pub const CPOP: u32   = 0b0110000_00010;
+pub const SEXT_B: u32 = 0b0110000_00100;
+pub const SEXT_H: u32 = 0b0110000_00101;
+pub const ORC_B: u32  = 0b0010100_00111;
+pub const REV8: u32   = 0b0110100_11000;
+pub const REV8_RV64: u32 = 0b011010_111000;
+
+/// Zbc
+pub const CLMUL: u32  = 0b0000101001;
+pub const CLMULR: u32 = 0b0000101010;
+pub const CLMULH: u32 = 0b0000101011;
+
+/// Zbs
+pub const BCLR: u32  = 0b0100100001;
+pub const BCLRI: u32 = 0b0100100001;
+pub const BEXT: u32  = 0b0100100101;
+pub const BEXTI: u32 = 0b0100100101;
+pub const BINV: u32  = 0b0110100001;
+pub const BINVI: u32 = 0b0110100001;
+pub const BSET: u32  = 0b0010100001;
+pub const BSETI: u32 = 0b0010100001;
+
+/// Zbkb and Zbkx
+pub const PACK: u32   = 0b0000100100;
+pub const PACKH: u32  = 0b0000100111;
+/// Encoded with OP_32, it is only on RV64
+pub const PACKW: u32  = 0b0000100100;
+pub const XPERM4: u32 = 0b0010100010;
+pub const XPERM8: u32 = 0b0010100100;
+pub const BREV8: u32  = 0b0110100_00111;
+pub const ZIP: u32    = 0b0000100_01111;
+pub const UNZIP: u32  = 0b0000100_01111;
+
+/// Zknd and Zkne, the upper two bits of funct7 select the byte to operate on
+pub const AES32_MASK: u32 = 0b0011111111;
+pub const AES32ESI: u32   = 0b0010001000;
+pub const AES32ESMI: u32  = 0b0010011000;
+pub const AES32DSI: u32   = 0b0010101000;
+pub const AES32DSMI: u32  = 0b0010111000;
+
+/// Zknh
+pub const SHA256SUM0: u32  = 0b0001000_00000;
+pub const SHA256SUM1: u32  = 0b0001000_00001;
+pub const SHA256SIG0: u32  = 0b0001000_00010;
+pub const SHA256SIG1: u32  = 0b0001000_00011;
+pub const SHA512SUM0R: u32 = 0b0101000000;
+pub const SHA512SUM1R: u32 = 0b0101001000;
+pub const SHA512SIG0L: u32 = 0b0101010000;
+pub const SHA512SIG0H: u32 = 0b0101110000;
+pub const SHA512SIG1L: u32 = 0b0101011000;
+pub const SHA512SIG1H: u32 = 0b0101111000;
+
 pub const BEQ: u32  = 0b000;
 pub const BNE: u32  = 0b001;
 pub const BLT: u32  = 0b100;
@@ -56,14 +149,100 @@ pub const LH: u32  = 0b001;
 pub const LW: u32  = 0b010;
 pub const LBU: u32 = 0b100;
 pub const LHU: u32 = 0b101;
+pub const LWU: u32 = 0b110;
+pub const LD: u32  = 0b011;
 
 pub const SB: u32 = 0b000;
 pub const SH: u32 = 0b001;
 pub const SW: u32 = 0b010;
+pub const SD: u32 = 0b011;
+
+pub const CSRRW: u32  = 0b001;
+pub const CSRRS: u32  = 0b010;
+pub const CSRRC: u32  = 0b011;
+pub const CSRRWI: u32 = 0b101;
+pub const CSRRSI: u32 = 0b110;
+pub const CSRRCI: u32 = 0b111;
+
+/// SYSTEM instructions with funct3 = 0, told apart by their immediate
+pub const ECALL: i16  = 0b000000000000;
+pub const EBREAK: i16 = 0b000000000001;
+pub const SRET: i16   = 0b000100000010;
+pub const MRET: i16   = 0b001100000010;
+pub const WFI: i16    = 0b000100000101;
+/// `sfence.vma` is an R-type instruction, so rs2 makes up the low five bits of its immediate
+pub const SFENCE_VMA: i16 = 0b000100100000;
+
+/// Vector operand categories, encoded in funct3
+pub const OPIVV: u32 = 0b000;
+pub const OPMVV: u32 = 0b010;
+pub const OPIVI: u32 = 0b011;
+pub const OPIVX: u32 = 0b100;
+pub const OPMVX: u32 = 0b110;
+pub const OPCFG: u32 = 0b111;
+
+/// Vector configuration, encoded in the upper two bits
+pub const VSETVLI: u32  = 0b00;
+pub const VSETVL: u32   = 0b10;
+pub const VSETIVLI: u32 = 0b11;
+
+/// Vector load and store element widths and addressing modes
+pub const VE8: u32  = 0b000;
+pub const VE16: u32 = 0b101;
+pub const VE32: u32 = 0b110;
+pub const VE64: u32 = 0b111;
+pub const VUNIT_STRIDE: u32 = 0b00;
+pub const VSTRIDED: u32     = 0b10;
+
+/// Vector integer functions (funct6)
+pub const VADD: u32   = 0b000000;
+pub const VSUB: u32   = 0b000010;
+pub const VRSUB: u32  = 0b000011;
+pub const VMINU: u32  = 0b000100;
+pub const VMIN: u32   = 0b000101;
+pub const VMAXU: u32  = 0b000110;
+pub const VMAX: u32   = 0b000111;
+pub const VAND: u32   = 0b001001;
+pub const VOR: u32    = 0b001010;
+pub const VXOR: u32   = 0b001011;
+pub const VMERGE: u32 = 0b010111;
+pub const VMSEQ: u32  = 0b011000;
+pub const VMSNE: u32  = 0b011001;
+pub const VMSLTU: u32 = 0b011010;
+pub const VMSLT: u32  = 0b011011;
+pub const VMSLEU: u32 = 0b011100;
+pub const VMSLE: u32  = 0b011101;
+pub const VMSGTU: u32 = 0b011110;
+pub const VMSGT: u32  = 0b011111;
+pub const VSLL: u32   = 0b100101;
+pub const VMVNRR: u32 = 0b100111;
+pub const VSRL: u32   = 0b101000;
+pub const VSRA: u32   = 0b101001;
+
+/// Vector reductions, mask and move functions (funct6)
+pub const VREDSUM: u32   = 0b000000;
+pub const VREDAND: u32   = 0b000001;
+pub const VREDOR: u32    = 0b000010;
+pub const VREDXOR: u32   = 0b000011;
+pub const VREDMINU: u32  = 0b000100;
+pub const VREDMIN: u32   = 0b000101;
+pub const VREDMAXU: u32  = 0b000110;
+pub const VREDMAX: u32   = 0b000111;
+pub const VWXUNARY0: u32 = 0b010000;
+pub const VRXUNARY0: u32 = 0b010000;
+pub const VMANDN: u32    = 0b011000;
+pub const VMAND: u32     = 0b011001;
+pub const VMOR: u32      = 0b011010;
+pub const VMXOR: u32     = 0b011011;
+pub const VMORN: u32     = 0b011100;
+pub const VMNAND: u32    = 0b011101;
+pub const VMNOR: u32     = 0b011110;
+pub const VMXNOR: u32    = 0b011111;
+pub const VMUL: u32      = 0b100101;
 
-type Memory = [u32; 1024];
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Instruction {
     IFormatInstruction {
         imm: i16,
@@ -82,7 +261,8 @@ pub enum Instruction {
         funct3: u32,
         rs1: usize,
         rs2: usize,
-        funct7: u32
+        funct7: u32,
+        opcode: u32
     },
     UFormatInstruction {
         imm: i32,
@@ -100,168 +280,460 @@ pub enum Instruction {
         rs1: usize,
         rs2: usize,
         funct3: u32
+    },
+    /// OP-V instructions as well as the vector loads and stores, whose funct6 holds
+    /// nf, mew and mop, funct3 the element width and vd the register to store
+    VFormatInstruction {
+        funct6: u32,
+        /// Set when the instruction is not masked by v0
+        vm: bool,
+        vs2: usize,
+        vs1: usize,
+        funct3: u32,
+        vd: usize,
+        opcode: u32
+    },
+    /// vsetvli, vsetivli and vsetvl, with the immediate AVL of vsetivli in rs1
+    VConfigInstruction {
+        rd: usize,
+        rs1: usize,
+        rs2: usize,
+        vtypei: u32,
+        funct: u32
     }
 }
 
-// Implement SCALL/SBREAK/CSRR* with a single SYSTEM instruction that always traps
+// Implement SCALL/SBREAK with a single SYSTEM instruction that always traps
 // Implement FENCE and FENCE.I as NOPs
 
 impl Instruction {
     pub fn from(bits: u32) -> Option<Instruction> {
         let opcode_mask = 0b1111111;
         let opcode = bits & opcode_mask;
-        match opcode {
-            OP_IMM | JALR | LOAD => Some(Instruction::parse_iformat(bits)),
-            OP => Some(Instruction::parse_rformat(bits)),
+        let instruction = match opcode {
+            OP_IMM | OP_IMM_32 | JALR | LOAD | SYSTEM | FENCE => Some(Instruction::parse_iformat(bits)),
+            OP | OP_32 => Some(Instruction::parse_rformat(bits)),
             LUI | AUIPC => Some(Instruction::parse_uformat(bits)),
             JAL => Some(Instruction::parse_jformat(bits)),
             BRANCH => Some(Instruction::parse_bformat(bits)),
             STORE => Some(Instruction::parse_sformat(bits)),
-            FENCE => todo!(),
+            OP_V if bits >> 12 & 0b111 == OPCFG => Instruction::parse_vconfig(bits),
+            OP_V => Some(Instruction::parse_vformat(bits)),
+            // The other widths belong to the floating point loads and stores
+            LOAD_FP | STORE_FP => vector::element_width(bits >> 12 & 0b111)
+                .map(|_| Instruction::parse_vformat(bits)),
+            // Everything else is reserved, including the all-zero word and the all-ones word
+            _ => None
+        };
+        instruction.filter(|instruction| instruction.is_defined())
+    }
+
+    /// Whether the function fields of the instruction select one that exists. Reserved
+    /// encodings and those of unimplemented extensions are illegal instructions.
+    fn is_defined(&self) -> bool {
+        match *self {
+            IFormatInstruction { imm, funct3, opcode: OP_IMM, .. } if funct3 == SLLI || funct3 == SRLI => {
+                let imm = imm as u32 & 0xFFF;
+                // The lowest bit of funct7 doubles as the sixth shift amount bit on RV64
+                match ((imm & 0xFC0) >> 5 << 3 | funct3, imm) {
+                    (SLLI | BCLRI | BINVI | BSETI, _) => true,
+                    (SRLI | SRAI | RORI | BEXTI, _) => true,
+                    (_, CLZ | CTZ | CPOP | SEXT_B | SEXT_H) => funct3 == SLLI,
+                    (_, SHA256SUM0 | SHA256SUM1 | SHA256SIG0 | SHA256SIG1) => funct3 == SLLI,
+                    (_, ORC_B | REV8 | REV8_RV64 | BREV8) => funct3 == SRLI,
+                    // zip and unzip share their immediate
+                    (_, ZIP) => true,
+                    _ => false
+                }
+            },
+            IFormatInstruction { opcode: OP_IMM, .. } => true,
+            IFormatInstruction { imm, funct3, opcode: OP_IMM_32, .. } => {
+                // The word shifts have a 5-bit shift amount, so bit 5 has to be clear
+                matches!((imm as u32 & 0xFFF) >> 5 << 3 | funct3, SLLI | SRLI | SRAI) || funct3 == ADDI
+            },
+            IFormatInstruction { funct3, opcode: JALR | FENCE, .. } => funct3 == 0,
+            IFormatInstruction { funct3, opcode: LOAD, .. } => funct3 != 0b111,
+            IFormatInstruction { imm, rs1, funct3: 0, rd, opcode: SYSTEM } => {
+                rd == 0 && ((rs1 == 0 && matches!(imm, ECALL | EBREAK | SRET | MRET | WFI)) || imm & !0b11111 == SFENCE_VMA)
+            },
+            IFormatInstruction { funct3, opcode: SYSTEM, .. } => funct3 != 0b100,
+            IFormatInstruction { .. } => false,
+            RFormatInstruction { funct3, funct7, opcode: OP, .. } => {
+                // Every OP instruction outside of the base ISA belongs to an extension
+                matches!((funct7 << 3) + funct3, ADD | SUB | SLL | SLT | SLTU | XOR | SRL | SRA | OR | AND)
+                    || !self.extensions(32).is_empty()
+            },
+            RFormatInstruction { funct3, funct7, .. } => {
+                matches!((funct7 << 3) + funct3, ADD | SUB | SLL | SRL | SRA) || !self.extensions(64).is_empty()
+            },
+            BFormatInstruction { funct3, .. } => funct3 != 0b010 && funct3 != 0b011,
+            SFormatInstruction { funct3, .. } => funct3 <= SD,
+            UFormatInstruction { .. } | JFormatInstruction { .. } => true,
+            VFormatInstruction { funct6, vm, vs2, vs1, funct3, opcode, .. } => {
+                vector::is_defined(funct6, vm, vs2, vs1, funct3, opcode)
+            },
+            VConfigInstruction { .. } => true
+        }
+    }
+
+    /// The integer registers the instruction reads or writes
+    pub fn registers(&self) -> Vec<usize> {
+        match *self {
+            // The immediate CSR forms encode an immediate in place of rs1
+            IFormatInstruction { rd, funct3, opcode: SYSTEM, .. } if funct3 & 0b100 != 0 => vec![rd],
+            IFormatInstruction { rd, rs1, .. } => vec![rd, rs1],
+            RFormatInstruction { rd, rs1, rs2, .. } => vec![rd, rs1, rs2],
+            UFormatInstruction { rd, .. } | JFormatInstruction { rd, .. } => vec![rd],
+            BFormatInstruction { rs1, rs2, .. } | SFormatInstruction { rs1, rs2, .. } => vec![rs1, rs2],
+            VFormatInstruction { funct6, vs2, vs1, opcode: LOAD_FP | STORE_FP, .. } if funct6 & 0b11 == VSTRIDED => vec![vs1, vs2],
+            VFormatInstruction { vs1, opcode: LOAD_FP | STORE_FP, .. } => vec![vs1],
+            VFormatInstruction { vs1, funct3: OPIVX | OPMVX, .. } => vec![vs1],
+            VFormatInstruction { funct6: VWXUNARY0, vd, funct3: OPMVV, .. } => vec![vd],
+            VFormatInstruction { .. } => vec![],
+            VConfigInstruction { rd, rs2, funct: VSETVL, rs1, .. } => vec![rd, rs1, rs2],
+            VConfigInstruction { rd, funct: VSETIVLI, .. } => vec![rd],
+            VConfigInstruction { rd, rs1, .. } => vec![rd, rs1]
+        }
+    }
+
+    /// The extensions that provide the instruction on a hart with `xlen` bit registers, any one of them being enough.
+    /// Empty for instructions of the base integer ISA.
+    pub fn extensions(&self, xlen: u32) -> &'static [Extension] {
+        const ZBB: &[Extension] = &[Extension::Zbb];
+        const ZBB_ZBKB: &[Extension] = &[Extension::Zbb, Extension::Zbkb];
+        const ZBKB: &[Extension] = &[Extension::Zbkb];
+        const ZBS: &[Extension] = &[Extension::Zbs];
+        const ZKNH: &[Extension] = &[Extension::Zknh];
+
+        match *self {
+            IFormatInstruction { imm, funct3, opcode: OP_IMM, .. } if funct3 == SLLI || funct3 == SRLI => {
+                // The lowest bit of funct7 doubles as the sixth shift amount bit on RV64
+                match ((imm as u32 & 0xFC0) >> 5 << 3 | funct3, imm as u32 & 0xFFF) {
+                    (SLLI | SRLI | SRAI, _) => &[],
+                    (BCLRI | BINVI | BSETI | BEXTI, _) => ZBS,
+                    (RORI, _) => ZBB_ZBKB,
+                    (_, CLZ | CTZ | CPOP | SEXT_B | SEXT_H | ORC_B) => ZBB,
+                    (_, REV8 | REV8_RV64) => ZBB_ZBKB,
+                    (_, BREV8 | ZIP) => ZBKB,
+                    (_, SHA256SUM0 | SHA256SUM1 | SHA256SIG0 | SHA256SIG1) => ZKNH,
+                    _ => &[]
+                }
+            },
+            IFormatInstruction { funct3, opcode: SYSTEM, .. } if funct3 != 0 => &[Extension::Zicsr],
+            VFormatInstruction { .. } | VConfigInstruction { .. } => &[Extension::V],
+            RFormatInstruction { funct3, funct7, rs2, opcode: OP, .. } => {
+                match (funct7 << 3) + funct3 {
+                    SH1ADD | SH2ADD | SH3ADD => &[Extension::Zba],
+                    ANDN | ORN | XNOR | ROL | ROR => ZBB_ZBKB,
+                    MIN | MINU | MAX | MAXU => ZBB,
+                    // zext.h is `pack rd, rs1, x0` on RV32 and `packw rd, rs1, x0` on RV64
+                    PACK if rs2 == 0 && xlen == 32 => ZBB_ZBKB,
+                    PACK | PACKH => ZBKB,
+                    CLMUL | CLMULH => &[Extension::Zbc, Extension::Zbkc],
+                    CLMULR => &[Extension::Zbc],
+                    BCLR | BEXT | BINV | BSET => ZBS,
+                    XPERM4 | XPERM8 => &[Extension::Zbkx],
+                    SHA512SUM0R | SHA512SUM1R | SHA512SIG0L | SHA512SIG0H | SHA512SIG1L | SHA512SIG1H => ZKNH,
+                    aes if aes & AES32_MASK == AES32ESI || aes & AES32_MASK == AES32ESMI => &[Extension::Zkne],
+                    aes if aes & AES32_MASK == AES32DSI || aes & AES32_MASK == AES32DSMI => &[Extension::Zknd],
+                    _ => &[]
+                }
+            },
+            RFormatInstruction { funct3, funct7, rs2, opcode: OP_32, .. } => {
+                match (funct7 << 3) + funct3 {
+                    PACKW if rs2 == 0 => ZBB_ZBKB,
+                    PACKW => ZBKB,
+                    _ => &[]
+                }
+            },
+            _ => &[]
+        }
+    }
+
+    /// The only XLEN the instruction exists for, if it is specific to RV32 or RV64
+    fn xlen(&self) -> Option<u32> {
+        match *self {
+            IFormatInstruction { opcode: OP_IMM_32, .. } | RFormatInstruction { opcode: OP_32, .. } => Some(64),
+            IFormatInstruction { funct3: LWU | LD, opcode: LOAD, .. } | SFormatInstruction { funct3: SD, .. } => Some(64),
+            IFormatInstruction { imm, funct3, opcode: OP_IMM, .. } if funct3 == SLLI || funct3 == SRLI => {
+                match imm as u32 & 0xFFF {
+                    REV8 | ZIP => Some(32),
+                    // Shift amounts above 31 only exist on RV64, and so does the encoding of rev8 that uses one
+                    _ if imm & 0b100000 != 0 => Some(64),
+                    _ => None
+                }
+            },
+            RFormatInstruction { funct3, funct7, opcode: OP, .. } => {
+                match (funct7 << 3) + funct3 {
+                    SHA512SUM0R | SHA512SUM1R | SHA512SIG0L | SHA512SIG0H | SHA512SIG1L | SHA512SIG1H => Some(32),
+                    aes if [AES32ESI, AES32ESMI, AES32DSI, AES32DSMI].contains(&(aes & AES32_MASK)) => Some(32),
+                    _ => None
+                }
+            },
+            _ => None
+        }
+    }
+
+    /// Whether a hart implementing `isa` can execute the instruction
+    pub fn is_supported_by(&self, isa: &Isa) -> bool {
+        let extensions = self.extensions(isa.xlen());
+        self.registers().iter().all(|&index| index < isa.registers())
+            && self.xlen().is_none_or(|xlen| xlen == isa.xlen())
+            && (extensions.is_empty() || extensions.iter().any(|extension| isa.has(*extension)))
+    }
+
+    /// Whether a branch goes to its target with the current register values, `None` for anything but a branch
+    pub fn branch_taken<X: Xlen>(&self, register: &Register<X>) -> Option<bool> {
+        let BFormatInstruction { rs1, rs2, funct3, .. } = *self else {
+            return None;
+        };
+        let (a, b) = (register.get(rs1), register.get(rs2));
+        match funct3 {
+            BEQ => Some(a == b),
+            BNE => Some(a != b),
+            BLT => Some(a.lt_signed(b)),
+            BGE => Some(!a.lt_signed(b)),
+            BLTU => Some(a < b),
+            BGEU => Some(a >= b),
             _ => None
         }
     }
 
-    pub fn execute(self, register: &mut Register, memory: &mut Memory) {
+    pub fn execute<X: Xlen, M: Memory<X>>(self, register: &mut Register<X>, memory: &mut M) -> Result<(), Exception> {
+        if !self.is_defined() || !self.is_supported_by(register.isa()) {
+            return Err(Exception::IllegalInstruction);
+        }
+
+        // Shift amounts are 5 bits wide on RV32 and 6 bits wide on RV64
+        let shamt_mask = X::BITS - 1;
+        // The pc holds the address of this instruction until it retires, control transfers
+        // redirect the next pc and everything else falls through to the following instruction
+        let pc = register.pc();
+        let mut next_pc = pc + 4;
         match self {
             IFormatInstruction { funct3, rd, rs1, imm, opcode } =>
                 match opcode {
                     OP_IMM => {
+                        let immediate = X::from_i32(imm as i32);
                         match funct3 {
                             ADDI => { // Addi
                                 let i = register.get(rs1);
-                                register.put(rd, MixedIntegerOps::wrapping_add_signed(i, imm as i32));
+                                register.put(rd, i.wrapping_add(immediate));
                             },
-                            SLLI => { // Slli
-                                register.put(rd, register.get(rs1) << imm)
+                            SLLI => { // Slli, the single-bit and the unary bit manipulation instructions
+                                let shift = imm as u32 & shamt_mask;
+                                let i = register.get(rs1);
+                                let bit = X::from_u32(1) << shift;
+                                match (imm as u32 & 0xFFF & !shamt_mask) >> 5 << 3 | funct3 {
+                                    SLLI => register.put(rd, i << shift),
+                                    BCLRI => register.put(rd, i & !bit),
+                                    BINVI => register.put(rd, i ^ bit),
+                                    BSETI => register.put(rd, i | bit),
+                                    _ => match imm as u32 & 0xFFF {
+                                        CLZ => register.put(rd, X::from_u32(i.leading_zeros())),
+                                        CTZ => register.put(rd, X::from_u32(i.trailing_zeros())),
+                                        CPOP => register.put(rd, X::from_u32(i.count_ones())),
+                                        SEXT_B => register.put(rd, X::from_i32(i.as_u32() as i8 as i32)),
+                                        SEXT_H => register.put(rd, X::from_i32(i.as_u32() as i16 as i32)),
+                                        ZIP if X::BITS == 32 => register.put(rd, X::from_u32(crypto::zip(i.as_u32()))),
+                                        SHA256SUM0 => register.put(rd, X::from_i32(crypto::sha256sum0(i.as_u32()) as i32)),
+                                        SHA256SUM1 => register.put(rd, X::from_i32(crypto::sha256sum1(i.as_u32()) as i32)),
+                                        SHA256SIG0 => register.put(rd, X::from_i32(crypto::sha256sig0(i.as_u32()) as i32)),
+                                        SHA256SIG1 => register.put(rd, X::from_i32(crypto::sha256sig1(i.as_u32()) as i32)),
+                                        _ => return Err(Exception::IllegalInstruction)
+                                    }
+                                }
                             },
                             SLTI => { // Slti
-                                let i = register.get(rs1) as i32;
-                                if i < (imm as i32) {
-                                    register.put(rd, 1);
+                                let i = register.get(rs1);
+                                if i.lt_signed(immediate) {
+                                    register.put(rd, X::from_u32(1));
                                 } else {
-                                    register.put(rd, 0);
+                                    register.put(rd, X::from_u32(0));
                                 }
                             },
                             SLTIU => { // Sltiu
                                 let i = register.get(rs1);
-                                if i < (imm as u32) {
-                                    register.put(rd, 1);
+                                if i < immediate {
+                                    register.put(rd, X::from_u32(1));
                                 } else {
-                                    register.put(rd, 0);
+                                    register.put(rd, X::from_u32(0));
                                 }
                             },
                             XORI => { // Xori
                                 let i = register.get(rs1);
-                                register.put(rd, i ^ (imm as u32));
+                                register.put(rd, i ^ immediate);
                             },
-                            SRLI => { // Srli and Srai
-                                // need to discriminate between srli and srai
-                                let discriminator = imm >> 10;
-                                match discriminator {
-                                    0b00 => {
-                                        let shift = imm & 0b11111;
-                                        let i = register.get(rs1);
-                                        register.put(rd, i >> shift);
-                                    },
-                                    0b01 => {
-                                        let shift = imm & 0b11111;
-                                        let i = register.get(rs1) as i32;
-                                        register.put(rd, (i >> shift) as u32);
-                                    },
-                                    _ => panic!()
+                            SRLI => { // Srli, Srai and the other right-hand bit manipulation instructions
+                                // need to discriminate on the upper bits of the immediate
+                                let shift = imm as u32 & shamt_mask;
+                                let i = register.get(rs1);
+                                match (imm as u32 & 0xFFF & !shamt_mask) >> 5 << 3 | funct3 {
+                                    SRLI => register.put(rd, i >> shift),
+                                    SRAI => register.put(rd, i.sra(shift)),
+                                    RORI => register.put(rd, i.rotate_right(shift)),
+                                    BEXTI => register.put(rd, (i >> shift) & X::from_u32(1)),
+                                    _ => match imm as u32 & 0xFFF {
+                                        ORC_B => register.put(rd, X::from_u64(math_utils::orc_b(i.as_u64()))),
+                                        REV8 if X::BITS == 32 => register.put(rd, i.swap_bytes()),
+                                        REV8_RV64 if X::BITS == 64 => register.put(rd, i.swap_bytes()),
+                                        BREV8 => register.put(rd, X::from_u64(crypto::brev8(i.as_u64()))),
+                                        UNZIP if X::BITS == 32 => register.put(rd, X::from_u32(crypto::unzip(i.as_u32()))),
+                                        _ => return Err(Exception::IllegalInstruction)
+                                    }
                                 }
                             }
                             ORI => { // Ori
                                 let i = register.get(rs1);
-                                register.put(rd, i | (imm as u32));
+                                register.put(rd, i | immediate);
                             },
                             ANDI => { // Andi
                                 let i = register.get(rs1);
-                                register.put(rd, i & (imm as u32));
+                                register.put(rd, i & immediate);
                             },
-                            _ => return
+                            _ => return Err(Exception::IllegalInstruction)
+                        }
+                    },
+                    OP_IMM_32 => {
+                        if X::BITS == 32 {
+                            return Err(Exception::IllegalInstruction);
+                        }
+                        // The word instructions operate on the lower 32 bits and sign-extend the result
+                        let i = register.get(rs1).as_u32();
+                        let shift = imm as u32 & 0b11111;
+                        match (imm as u32 & 0xFFF) >> 5 << 3 | funct3 {
+                            SLLI => register.put(rd, X::from_i32((i << shift) as i32)),
+                            SRLI => register.put(rd, X::from_i32((i >> shift) as i32)),
+                            SRAI => register.put(rd, X::from_i32((i as i32) >> shift)),
+                            _ if funct3 == ADDI => register.put(rd, X::from_i32((i as i32).wrapping_add(imm as i32))),
+                            _ => return Err(Exception::IllegalInstruction)
                         }
                     },
                     JALR => {
-                        let t = register.pc();
-                        register.update_pc((register.get(rs1) as i32 + imm as i32) as usize);
-                        if rd != 0 {
-                            register.put(rd, t as u32);
+                        let target = register.get(rs1).wrapping_add(X::from_i32(imm as i32));
+                        next_pc = target.as_usize() & !1;
+                        if !next_pc.is_multiple_of(4) {
+                            return Err(Exception::InstructionAddressMisaligned(next_pc));
                         }
+                        register.put(rd, X::from_usize(pc + 4));
                     },
                     LOAD => {
+                        let i = register.get(rs1).wrapping_add(X::from_i32(imm as i32)).as_usize();
+                        let m = memory.load(i)?;
                         match funct3 {
-                            LB => {
-                                let m = register.get(rs1) as i32;
-                                let offset = imm as i32;
-                                let i = m + offset;
-                                register.put(rd, memory[i as usize] as i8 as u32)
-                            },
-                            LH => {
-                                let m = register.get(rs1) as i32;
-                                let offset = imm as i32;
-                                let i = m + offset;
-                                register.put(rd, memory[i as usize] as i16 as u32)
-                            },
-                            LW => {
-                                let m = register.get(rs1) as i32;
-                                let offset = imm as i32;
-                                let i = m + offset;
-                                register.put(rd, memory[i as usize] as i32 as u32)
+                            LB => register.put(rd, X::from_i32(m.as_u32() as i8 as i32)),
+                            LH => register.put(rd, X::from_i32(m.as_u32() as i16 as i32)),
+                            LW => register.put(rd, X::from_i32(m.as_u32() as i32)),
+                            LBU => register.put(rd, X::from_u32(m.as_u32() as u8 as u32)),
+                            LHU => register.put(rd, X::from_u32(m.as_u32() as u16 as u32)),
+                            LWU | LD if X::BITS == 32 => return Err(Exception::IllegalInstruction),
+                            LWU => register.put(rd, X::from_u32(m.as_u32())),
+                            LD => register.put(rd, m),
+                            _ => return Err(Exception::IllegalInstruction)
+                        }
+                    },
+                    SYSTEM => {
+                        let csr = imm as u32 & 0xFFF;
+                        // The immediate forms encode a zero-extended 5-bit value in place of rs1
+                        let source = if funct3 & 0b100 == 0 { register.get(rs1).as_u64() } else { rs1 as u64 };
+                        match funct3 {
+                            CSRRW | CSRRWI => {
+                                // With rd = x0 the CSR is not read at all
+                                let old = if rd != 0 { register.csr().read(csr)? } else { 0 };
+                                register.csr_mut().write(csr, source)?;
+                                if rd != 0 {
+                                    register.put(rd, X::from_u64(old));
+                                }
                             },
-                            LBU => {
-                                let m = register.get(rs1) as i32;
-                                let offset = imm as i32;
-                                let i = m + offset;
-                                register.put(rd, memory[i as usize] as u8 as u32)
+                            CSRRS | CSRRSI => {
+                                let old = register.csr().read(csr)?;
+                                // With rs1 = x0 (or a zero immediate) the CSR is not written at all
+                                if rs1 != 0 {
+                                    register.csr_mut().write(csr, old | source)?;
+                                }
+                                if rd != 0 {
+                                    register.put(rd, X::from_u64(old));
+                                }
                             },
-                            LHU => {
-                                let m = register.get(rs1) as i32;
-                                let offset = imm as i32;
-                                let i = m + offset;
-                                register.put(rd, memory[i as usize] as u16 as u32)
+                            CSRRC | CSRRCI => {
+                                let old = register.csr().read(csr)?;
+                                if rs1 != 0 {
+                                    register.csr_mut().write(csr, old & !source)?;
+                                }
+                                if rd != 0 {
+                                    register.put(rd, X::from_u64(old));
+                                }
                             },
-                            _ => return
+                            _ if imm == ECALL => return Err(Exception::EnvironmentCall(register.csr().privilege())),
+                            _ if imm == EBREAK => return Err(Exception::Breakpoint),
+                            _ if imm == SRET => next_pc = register.csr_mut().sret()? as usize,
+                            _ if imm == MRET => next_pc = register.csr_mut().mret()? as usize,
+                            // wfi may resume at any time, so not waiting at all is a valid implementation.
+                            // A program waiting for an interrupt takes it before its next instruction
+                            _ if imm == WFI => {},
+                            // sfence.vma, the processor fences its TLB once the instruction retires
+                            _ if register.csr().privilege() == Privilege::User => return Err(Exception::IllegalInstruction),
+                            _ => {}
                         }
                     },
-                    _ => return
+                    // A single hart without caches observes its memory accesses in order anyway
+                    FENCE => {},
+                    _ => return Err(Exception::IllegalInstruction)
                 },
-            RFormatInstruction { funct3, funct7, rs1, rs2, rd } => {
+            RFormatInstruction { funct3, funct7, rs1, rs2, rd, opcode: OP_32 } => {
+                if X::BITS == 32 {
+                    return Err(Exception::IllegalInstruction);
+                }
+                // The word instructions operate on the lower 32 bits and sign-extend the result
+                let i = register.get(rs1).as_u32();
+                let j = register.get(rs2).as_u32();
+                match (funct7 << 3) + funct3 {
+                    ADD => register.put(rd, X::from_i32(i.wrapping_add(j) as i32)),
+                    SUB => register.put(rd, X::from_i32(i.wrapping_sub(j) as i32)),
+                    SLL => register.put(rd, X::from_i32((i << (j & 0b11111)) as i32)),
+                    SRL => register.put(rd, X::from_i32((i >> (j & 0b11111)) as i32)),
+                    SRA => register.put(rd, X::from_i32((i as i32) >> (j & 0b11111))),
+                    // zext.h is packw with rs2 = x0
+                    PACKW => register.put(rd, X::from_i32(((j & 0xffff) << 16 | (i & 0xffff)) as i32)),
+                    _ => return Err(Exception::IllegalInstruction)
+                }
+            },
+            RFormatInstruction { funct3, funct7, rs1, rs2, rd, .. } => {
                 let funct = (funct7 << 3) + funct3;
+                let one = X::from_u32(1);
                 match funct {
                     ADD => { // Add
                         let i = register.get(rs1);
                         let j = register.get(rs2);
-                        register.put(rd, i + j);
+                        register.put(rd, i.wrapping_add(j));
                     },
                     SUB => {
                         let i = register.get(rs1);
                         let j = register.get(rs2);
-                        register.put(rd, i - j);
+                        register.put(rd, i.wrapping_sub(j));
                     },
                     SLL => {
                         let i = register.get(rs1);
-                        let j = register.get(rs2) & 0b11111;
+                        let j = register.get(rs2).as_u32() & shamt_mask;
                         register.put(rd, i << j)
                     },
                     SLT => {
-                        let i = register.get(rs1) as i32;
-                        let j = register.get(rs2) as i32;
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
 
-                        if i < j {
-                            register.put(rd, 1);
+                        if i.lt_signed(j) {
+                            register.put(rd, one);
                         } else {
-                            register.put(rd, 0);
+                            register.put(rd, X::from_u32(0));
                         }
                     },
                     SLTU => {
                         let i = register.get(rs1);
                         let j = register.get(rs2);
                         if i < j {
-                            register.put(rd, 1);
+                            register.put(rd, one);
                         } else {
-                            register.put(rd, 0);
+                            register.put(rd, X::from_u32(0));
                         }
                     },
                     XOR => {
@@ -271,13 +743,13 @@ impl Instruction {
                     },
                     SRL => {
                         let i = register.get(rs1);
-                        let j = register.get(rs2) & 0b11111;
+                        let j = register.get(rs2).as_u32() & shamt_mask;
                         register.put(rd, i >> j);
                     },
                     SRA => {
-                        let i = register.get(rs1) as i32;
-                        let j = register.get(rs2) & 0b11111;
-                        register.put(rd, (i >> j) as u32);
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, i.sra(j));
                     },
                     OR => {
                         let i = register.get(rs1);
@@ -287,85 +759,234 @@ impl Instruction {
                     AND => {
                         let i = register.get(rs1);
                         let j = register.get(rs2);
-                        println!("i: {}, j: {}, i & j: {}", i, j, i&j);
                         register.put(rd, i & j);
                     }
-                    _ => return
-                }
-            },
-            UFormatInstruction { imm, rd, opcode } =>
-                match opcode {
-                    LUI => {
-                        register.put(rd, (imm as u32) << 12);
+                    SH1ADD => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, j.wrapping_add(i << 1));
                     },
-                    AUIPC => {
-                        let u_immediate = (imm as u32) << 12;
-                        register.put(rd, register.pc() as u32 + u_immediate);
+                    SH2ADD => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, j.wrapping_add(i << 2));
                     },
-                    _ => return
-                },
-            JFormatInstruction { imm, rd, opcode } =>
-                match opcode {
-                    JAL => {
-                        if rd > 0 {
-                            register.put(rd, register.pc() as u32 + 4);
-                        }
-                        register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
+                    SH3ADD => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, j.wrapping_add(i << 3));
                     },
-                    _ => return
-                },
-            BFormatInstruction { imm, rs1, rs2, funct3 } =>
-                match funct3 {
-                    BEQ => {
-                        if register.get(rs1) == register.get(rs2) {
-                            register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
-                        }
+                    ANDN => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, i & !j);
                     },
-                    BNE => {
-                        if register.get(rs1) != register.get(rs2) {
-                            register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
-                        }
+                    ORN => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, i | !j);
                     },
-                    BLT => {
-                        if (register.get(rs1) as i32) < (register.get(rs2) as i32) {
-                            register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
-                        }
+                    XNOR => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, !(i ^ j));
                     },
-                    BGE => {
-                        if (register.get(rs1) as i32) >= (register.get(rs2) as i32) {
-                            register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
-                        }
+                    MIN => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, if i.lt_signed(j) { i } else { j });
                     },
-                    BLTU => {
-                        if register.get(rs1) < register.get(rs2) {
-                            register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
-                        }
+                    MINU => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, i.min(j));
                     },
-                    BGEU => {
-                        if register.get(rs1) >= register.get(rs2) {
-                            register.update_pc(MixedIntegerOps::wrapping_add_signed(register.pc(), imm));
-                        }
+                    MAX => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, if i.lt_signed(j) { j } else { i });
                     },
-                    _ => return
-                },
-            SFormatInstruction { imm, rs1, rs2, funct3 } => {
-                match funct3 {
-                    SB => {
-                        let m = (register.get(rs1) as i32 + imm) as usize;
-                        memory[m] = register.get(rs2) as u8 as u32;
+                    MAXU => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, i.max(j));
+                    },
+                    ROL => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, i.rotate_left(j));
+                    },
+                    ROR => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, i.rotate_right(j));
+                    },
+                    PACK => { // zext.h is pack with rs2 = x0
+                        let half = X::BITS / 2;
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        register.put(rd, (j << half) | (i & (!X::default() >> half)));
+                    },
+                    PACKH => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2);
+                        let byte = X::from_u32(0xFF);
+                        register.put(rd, ((j & byte) << 8) | (i & byte));
+                    },
+                    XPERM4 => {
+                        let i = register.get(rs1).as_u64();
+                        let j = register.get(rs2).as_u64();
+                        register.put(rd, X::from_u64(crypto::xperm4(i, j, X::BITS)));
+                    },
+                    XPERM8 => {
+                        let i = register.get(rs1).as_u64();
+                        let j = register.get(rs2).as_u64();
+                        register.put(rd, X::from_u64(crypto::xperm8(i, j, X::BITS)));
+                    },
+                    SHA512SUM0R if X::BITS == 32 => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::sha512sum0r(i, j)));
+                    },
+                    SHA512SUM1R if X::BITS == 32 => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::sha512sum1r(i, j)));
                     },
-                    SH => {
-                        let m = (register.get(rs1) as i32 + imm) as usize;
-                        memory[m] = register.get(rs2) as u16 as u32;
+                    SHA512SIG0L if X::BITS == 32 => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::sha512sig0l(i, j)));
                     },
-                    SW => {
-                        let m = (register.get(rs1) as i32 + imm) as usize;
-                        memory[m] = register.get(rs2) as u32;
+                    SHA512SIG0H if X::BITS == 32 => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::sha512sig0h(i, j)));
                     },
-                    _ => return
+                    SHA512SIG1L if X::BITS == 32 => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::sha512sig1l(i, j)));
+                    },
+                    SHA512SIG1H if X::BITS == 32 => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::sha512sig1h(i, j)));
+                    },
+                    CLMUL => {
+                        let i = register.get(rs1).as_u64();
+                        let j = register.get(rs2).as_u64();
+                        register.put(rd, X::from_u64(math_utils::clmul(i, j) as u64));
+                    },
+                    CLMULH => {
+                        let i = register.get(rs1).as_u64();
+                        let j = register.get(rs2).as_u64();
+                        register.put(rd, X::from_u64((math_utils::clmul(i, j) >> X::BITS) as u64));
+                    },
+                    CLMULR => {
+                        let i = register.get(rs1).as_u64();
+                        let j = register.get(rs2).as_u64();
+                        register.put(rd, X::from_u64((math_utils::clmul(i, j) >> (X::BITS - 1)) as u64));
+                    },
+                    BCLR => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, i & !(one << j));
+                    },
+                    BEXT => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, (i >> j) & one);
+                    },
+                    BINV => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, i ^ (one << j));
+                    },
+                    BSET => {
+                        let i = register.get(rs1);
+                        let j = register.get(rs2).as_u32() & shamt_mask;
+                        register.put(rd, i | (one << j));
+                    },
+                    aes if X::BITS == 32 && aes & AES32_MASK == AES32ESI => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::aes32esi(i, j, funct7 >> 5)));
+                    },
+                    aes if X::BITS == 32 && aes & AES32_MASK == AES32ESMI => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::aes32esmi(i, j, funct7 >> 5)));
+                    },
+                    aes if X::BITS == 32 && aes & AES32_MASK == AES32DSI => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::aes32dsi(i, j, funct7 >> 5)));
+                    },
+                    aes if X::BITS == 32 && aes & AES32_MASK == AES32DSMI => {
+                        let i = register.get(rs1).as_u32();
+                        let j = register.get(rs2).as_u32();
+                        register.put(rd, X::from_u32(crypto::aes32dsmi(i, j, funct7 >> 5)));
+                    },
+                    _ => return Err(Exception::IllegalInstruction)
+                }
+            },
+            UFormatInstruction { imm, rd, opcode } =>
+                match opcode {
+                    LUI => {
+                        register.put(rd, X::from_i32(((imm as u32) << 12) as i32));
+                    },
+                    AUIPC => {
+                        let u_immediate = X::from_i32(((imm as u32) << 12) as i32);
+                        register.put(rd, X::from_usize(pc).wrapping_add(u_immediate));
+                    },
+                    _ => return Err(Exception::IllegalInstruction)
+                },
+            JFormatInstruction { imm, rd, opcode } => {
+                if opcode == JAL {
+                    next_pc = MixedIntegerOps::wrapping_add_signed(pc, imm);
+                    if !next_pc.is_multiple_of(4) {
+                        return Err(Exception::InstructionAddressMisaligned(next_pc));
+                    }
+                    register.put(rd, X::from_usize(pc + 4));
+                }
+            },
+            BFormatInstruction { imm, .. } => {
+                if self.branch_taken(register).ok_or(Exception::IllegalInstruction)? {
+                    next_pc = MixedIntegerOps::wrapping_add_signed(pc, imm);
+                    if !next_pc.is_multiple_of(4) {
+                        return Err(Exception::InstructionAddressMisaligned(next_pc));
+                    }
+                }
+            },
+            SFormatInstruction { imm, rs1, rs2, funct3 } => {
+                let m = register.get(rs1).wrapping_add(X::from_i32(imm)).as_usize();
+                let value = register.get(rs2);
+                match funct3 {
+                    SB => memory.store(m, X::from_u32(value.as_u32() as u8 as u32))?,
+                    SH => memory.store(m, X::from_u32(value.as_u32() as u16 as u32))?,
+                    SW => memory.store(m, X::from_u32(value.as_u32()))?,
+                    SD if X::BITS == 32 => return Err(Exception::IllegalInstruction),
+                    SD => memory.store(m, value)?,
+                    _ => return Err(Exception::IllegalInstruction)
+                }
+            },
+            VFormatInstruction { funct6, vm, vs2, vs1, funct3, vd, opcode: opcode @ (LOAD_FP | STORE_FP) } => {
+                // Only unit-stride and strided accesses of single fields are implemented
+                if funct6 >> 2 != 0 {
+                    return Err(Exception::IllegalInstruction);
                 }
+                vector::load_store(register, memory, opcode == STORE_FP, vd, vs1, vs2, vm, funct6 & 0b11, funct3)?;
+            },
+            VFormatInstruction { funct6, vm, vs2, vs1, funct3, vd, .. } => {
+                vector::operate(register, funct6, vm, vs2, vs1, funct3, vd)?;
+            },
+            VConfigInstruction { rd, rs1, rs2, vtypei, funct } => {
+                vector::configure(register, rd, rs1, rs2, vtypei, funct);
             }
         }
+        register.update_pc(next_pc);
+        Ok(())
     }
 
     fn parse_iformat(bits: u32) -> Instruction {
@@ -386,6 +1007,7 @@ impl Instruction {
     }
 
     fn parse_rformat(bits: u32) -> Instruction {
+        let opcode = bits & 0b1111111;
         let rd = (bits >> 7 & 0b11111) as usize;
         let funct3 = bits >> 12 & 0b111;
         let rs1 = (bits >> 15 & 0b11111) as usize;
@@ -396,7 +1018,8 @@ impl Instruction {
             rs2,
             funct3,
             funct7,
-            rd
+            rd,
+            opcode
         }
     }
 
@@ -404,7 +1027,8 @@ impl Instruction {
         let opcode = bits & 0b1111111;
         let rd = (bits >> 7 & 0b11111) as usize;
         let imm: u32 = UImmediate::from_instruction(bits).into();
-        let imm = imm as i32;
+        // The immediate holds the upper 20 bits, execution shifts them back into place
+        let imm = (imm >> 12) as i32;
         UFormatInstruction {
             imm,
             rd,
@@ -428,7 +1052,7 @@ impl Instruction {
     fn parse_bformat(bits: u32) -> Instruction {
         let rs1 = (bits >> 15 & 0b11111) as usize;
         let rs2 = (bits >> 20 & 0b11111) as usize;
-        let funct3 = (bits >> 12 & 0b111) as u32;
+        let funct3 = bits >> 12 & 0b111;
         let imm: u32 = BImmediate::from_instruction(bits).into();
         let imm = imm as i32;
 
@@ -440,10 +1064,40 @@ impl Instruction {
         }
     }
 
+    fn parse_vformat(bits: u32) -> Instruction {
+        VFormatInstruction {
+            funct6: bits >> 26,
+            vm: bits >> 25 & 1 == 1,
+            vs2: (bits >> 20 & 0b11111) as usize,
+            vs1: (bits >> 15 & 0b11111) as usize,
+            funct3: bits >> 12 & 0b111,
+            vd: (bits >> 7 & 0b11111) as usize,
+            opcode: bits & 0b1111111
+        }
+    }
+
+    fn parse_vconfig(bits: u32) -> Option<Instruction> {
+        let funct = if bits >> 31 == 0 { VSETVLI } else { bits >> 30 };
+        let vtypei = match funct {
+            VSETVLI => bits >> 20 & 0x7FF,
+            VSETIVLI => bits >> 20 & 0x3FF,
+            // vsetvl has a funct7 of 0b1000000, the rest of it is reserved
+            _ if bits >> 25 & 0b111111 != 0 => return None,
+            _ => 0
+        };
+        Some(VConfigInstruction {
+            rd: (bits >> 7 & 0b11111) as usize,
+            rs1: (bits >> 15 & 0b11111) as usize,
+            rs2: (bits >> 20 & 0b11111) as usize,
+            vtypei,
+            funct
+        })
+    }
+
     fn parse_sformat(bits: u32) -> Instruction {
         let rs1 = (bits >> 15 & 0b11111) as usize;
         let rs2 = (bits >> 20 & 0b11111) as usize;
-        let funct3 = (bits >> 12 & 0b111) as u32;
+        let funct3 = bits >> 12 & 0b111;
 
         let imm: u32 = SImmediate::from_instruction(bits).into();
         let imm = imm as i32;
@@ -459,6 +1113,7 @@ impl Instruction {
 
 #[cfg(test)]
 mod tests {
+    use crate::csr;
     use crate::instruction::*;
     use crate::register::Register;
 
@@ -475,9 +1130,10 @@ mod tests {
             funct3: 0b000,
             rs1: 4,
             rs2: 24,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(25), 0x80000000);
     }
@@ -496,7 +1152,7 @@ mod tests {
             rd: 7,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(7), 0x1ffff800);
     }
@@ -514,9 +1170,10 @@ mod tests {
             funct3: 0b111,
             rs1: 10,
             rs2: 11,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(12), 0x2);
     }
@@ -535,7 +1192,7 @@ mod tests {
             rd: 11,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(11), 0x114);
     }
@@ -551,7 +1208,7 @@ mod tests {
             rd: 10,
             opcode: AUIPC
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0x100000);
     }
@@ -567,11 +1224,89 @@ mod tests {
             rd: 13,
             opcode: LUI
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(13), 0x3000);
     }
 
+    #[test]
+    fn test_jal() {
+        let mut register = Register::new();
+        register.update_pc(8);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = JFormatInstruction {
+            imm: -8,
+            rd: 1,
+            opcode: JAL
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(1), 12);
+        assert_eq!(register.pc(), 0);
+    }
+
+    #[test]
+    fn test_jalr_clears_bit_0() {
+        let mut register = Register::new();
+        register.put(5, 0x100);
+        register.update_pc(8);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 5,
+            rd: 1,
+            rs1: 5,
+            funct3: 0b000,
+            opcode: JALR
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(1), 12);
+        assert_eq!(register.pc(), 0x104);
+    }
+
+    #[test]
+    fn test_jump_misaligned() {
+        let mut register = Register::new();
+        register.put(5, 0x101);
+        register.update_pc(8);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction { imm: 2, rd: 1, rs1: 5, funct3: 0b000, opcode: JALR };
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::InstructionAddressMisaligned(0x102)));
+        let instruction = JFormatInstruction { imm: 6, rd: 1, opcode: JAL };
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::InstructionAddressMisaligned(14)));
+        let instruction = BFormatInstruction { imm: -2, rs1: 0, rs2: 0, funct3: BEQ };
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::InstructionAddressMisaligned(6)));
+
+        assert_eq!(register.get(1), 0);
+        assert_eq!(register.pc(), 8);
+    }
+
+    #[test]
+    fn test_addi_discards_x0() {
+        let mut register = Register::new();
+        register.put(1, 5);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 1,
+            rd: 0,
+            rs1: 1,
+            funct3: ADDI,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(0), 0);
+        assert_eq!(register.pc(), 4);
+    }
+
     #[test]
     fn test_or() {
         let mut register = Register::new();
@@ -585,9 +1320,10 @@ mod tests {
             funct3: 0b110,
             rs1: 8,
             rs2: 26,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(26), 0x100010);
     }
@@ -606,7 +1342,7 @@ mod tests {
             rd: 8,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(8), 0x333337ff);
     }
@@ -624,9 +1360,10 @@ mod tests {
             funct3: 0b001,
             rs1: 12,
             rs2: 26,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(16), 0xffe00000);
     }
@@ -645,7 +1382,7 @@ mod tests {
             rd: 26,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(26), 0x33330000);
     }
@@ -663,9 +1400,10 @@ mod tests {
             funct3: 0b010,
             rs1: 26,
             rs2: 18,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(26), 0x0);
     }
@@ -683,9 +1421,10 @@ mod tests {
             funct3: 0b010,
             rs1: 26,
             rs2: 18,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(26), 0x0);
     }
@@ -703,9 +1442,10 @@ mod tests {
             funct3: 0b010,
             rs1: 26,
             rs2: 18,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(26), 0x1);
     }
@@ -724,7 +1464,7 @@ mod tests {
             rd: 27,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(27), 0x0);
     }
@@ -743,7 +1483,7 @@ mod tests {
             rd: 12,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(12), 0x0);
     }
@@ -762,7 +1502,7 @@ mod tests {
             rd: 5,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(5), 0x1);
     }
@@ -781,7 +1521,7 @@ mod tests {
             rd: 28,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(28), 0x0);
     }
@@ -800,7 +1540,7 @@ mod tests {
             rd: 2,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(2), 0x1);
     }
@@ -818,9 +1558,10 @@ mod tests {
             funct3: 0b011,
             rs1: 14,
             rs2: 24,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(14), 0x1);
     }
@@ -838,9 +1579,10 @@ mod tests {
             funct3: 0b011,
             rs1: 5,
             rs2: 14,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(19), 0x0);
     }
@@ -858,9 +1600,10 @@ mod tests {
             funct3: 0b101,
             rs1: 16,
             rs2: 27,
-            funct7: 0b0100000
+            funct7: 0b0100000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(16), -0x800000i32 as u32)
     }
@@ -879,7 +1622,7 @@ mod tests {
             rd: 25,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(25), -0x1i32 as u32)
     }
@@ -897,9 +1640,10 @@ mod tests {
             funct3: 0b101,
             rs1: 26,
             rs2: 11,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(11), 0x1ff7f)
     }
@@ -918,7 +1662,7 @@ mod tests {
             rd: 8,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(8), 0x3fffd2bf)
     }
@@ -936,9 +1680,10 @@ mod tests {
             funct3: 0b000,
             rs1: 24,
             rs2: 26,
-            funct7: 0b0100000
+            funct7: 0b0100000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(26), 0x5555554e)
     }
@@ -956,9 +1701,10 @@ mod tests {
             funct3: 0b100,
             rs1: 27,
             rs2: 24,
-            funct7: 0b0000000
+            funct7: 0b0000000,
+            opcode: OP
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(24), 0x66666666)
     }
@@ -977,7 +1723,7 @@ mod tests {
             rd: 10,
             opcode: OP_IMM
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0xcccccb34)
     }
@@ -997,7 +1743,7 @@ mod tests {
             rd: 10,
             opcode: LOAD
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0b00000000_00000000_00000000_00110100)
     }
@@ -1017,7 +1763,7 @@ mod tests {
             rd: 10,
             opcode: LOAD
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0b11111111_11111111_11001011_00110100)
     }
@@ -1037,11 +1783,29 @@ mod tests {
             rd: 10,
             opcode: LOAD
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0b11001100_11001100_11001011_00110100)
     }
 
+    #[test]
+    fn test_lw_access_fault() {
+        let mut register = Register::new();
+        register.put(24, 0xFFFFFFFF);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0x0,
+            rs1: 24,
+            funct3: LW,
+            rd: 10,
+            opcode: LOAD
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::LoadAccessFault(0xFFFFFFFF)));
+    }
+
     #[test]
     fn test_lbu() {
         let mut register = Register::new();
@@ -1057,7 +1821,7 @@ mod tests {
             rd: 10,
             opcode: LOAD
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0b00000000_00000000_00000000_00110100)
     }
@@ -1077,7 +1841,7 @@ mod tests {
             rd: 10,
             opcode: LOAD
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.get(10), 0b00000000_00000000_11001011_00110100)
     }
@@ -1096,7 +1860,7 @@ mod tests {
             rs2: 20,
             funct3: BEQ
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.pc(), 100);
     }
@@ -1115,9 +1879,9 @@ mod tests {
             rs2: 20,
             funct3: BEQ
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
-        assert_eq!(register.pc(), 0);
+        assert_eq!(register.pc(), 4);
     }
 
     #[test]
@@ -1134,7 +1898,7 @@ mod tests {
             rs2: 20,
             funct3: BNE
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.pc(), 100);
     }
@@ -1153,9 +1917,9 @@ mod tests {
             rs2: 20,
             funct3: BNE
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
-        assert_eq!(register.pc(), 0);
+        assert_eq!(register.pc(), 4);
     }
 
     #[test]
@@ -1172,7 +1936,7 @@ mod tests {
             rs2: 20,
             funct3: BLT
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.pc(), 100);
     }
@@ -1191,9 +1955,9 @@ mod tests {
             rs2: 20,
             funct3: BLT
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
-        assert_eq!(register.pc(), 0);
+        assert_eq!(register.pc(), 4);
     }
 
     #[test]
@@ -1210,7 +1974,7 @@ mod tests {
             rs2: 20,
             funct3: BGE
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.pc(), 100);
     }
@@ -1229,9 +1993,9 @@ mod tests {
             rs2: 20,
             funct3: BGE
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
-        assert_eq!(register.pc(), 0);
+        assert_eq!(register.pc(), 4);
     }
 
     #[test]
@@ -1248,7 +2012,7 @@ mod tests {
             rs2: 20,
             funct3: BLTU
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.pc(), 100);
     }
@@ -1267,9 +2031,9 @@ mod tests {
             rs2: 20,
             funct3: BLTU
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
-        assert_eq!(register.pc(), 0);
+        assert_eq!(register.pc(), 4);
     }
 
     #[test]
@@ -1286,7 +2050,7 @@ mod tests {
             rs2: 20,
             funct3: BGEU
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(register.pc(), 100);
     }
@@ -1305,9 +2069,9 @@ mod tests {
             rs2: 20,
             funct3: BGEU
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
-        assert_eq!(register.pc(), 0);
+        assert_eq!(register.pc(), 4);
     }
 
     #[test]
@@ -1324,7 +2088,7 @@ mod tests {
             rs2: 20,
             funct3: SB
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(memory[384], 0xFF);
     }
@@ -1343,7 +2107,7 @@ mod tests {
             rs2: 20,
             funct3: SH
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(memory[384], 0xFFFF);
     }
@@ -1362,8 +2126,1856 @@ mod tests {
             rs2: 20,
             funct3: SW
         };
-        instruction.execute(&mut register, &mut memory);
+        instruction.execute(&mut register, &mut memory).unwrap();
 
         assert_eq!(memory[384], 0xFFFFFF);
     }
+
+    #[test]
+    fn test_sw_access_fault() {
+        let mut register = Register::new();
+        register.put(10, 0x400);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = SFormatInstruction {
+            imm: 1,
+            rs1: 10,
+            rs2: 20,
+            funct3: SW
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::StoreAccessFault(0x401)));
+        assert_eq!(register.pc(), 0);
+    }
+
+    #[test]
+    fn test_csrrw() {
+        let mut register = Register::new();
+        register.put(10, 0x1234);
+        register.csr_mut().put(csr::MSCRATCH, 0xabcd);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MSCRATCH as i16,
+            rs1: 10,
+            funct3: CSRRW,
+            rd: 11,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(11), 0xabcd);
+        assert_eq!(register.csr().get(csr::MSCRATCH), 0x1234);
+    }
+
+    #[test]
+    fn test_csrrs() {
+        let mut register = Register::new();
+        register.put(10, 0b1010);
+        register.csr_mut().put(csr::MSCRATCH, 0b0101);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MSCRATCH as i16,
+            rs1: 10,
+            funct3: CSRRS,
+            rd: 11,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(11), 0b0101);
+        assert_eq!(register.csr().get(csr::MSCRATCH), 0b1111);
+    }
+
+    #[test]
+    fn test_csrrc() {
+        let mut register = Register::new();
+        register.put(10, 0b0110);
+        register.csr_mut().put(csr::MSCRATCH, 0b1111);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MSCRATCH as i16,
+            rs1: 10,
+            funct3: CSRRC,
+            rd: 11,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(11), 0b1111);
+        assert_eq!(register.csr().get(csr::MSCRATCH), 0b1001);
+    }
+
+    #[test]
+    fn test_csrrwi() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MSCRATCH as i16,
+            rs1: 0b10101,
+            funct3: CSRRWI,
+            rd: 0,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.csr().get(csr::MSCRATCH), 0b10101);
+    }
+
+    #[test]
+    fn test_csrrs_read_only() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MHARTID as i16,
+            rs1: 0,
+            funct3: CSRRS,
+            rd: 10,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(10), 0);
+    }
+
+    #[test]
+    fn test_csrrw_read_only() {
+        let mut register = Register::new();
+        register.put(10, 1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MHARTID as i16,
+            rs1: 10,
+            funct3: CSRRW,
+            rd: 11,
+            opcode: SYSTEM
+        };
+        let result = instruction.execute(&mut register, &mut memory);
+
+        assert_eq!(result, Err(Exception::IllegalInstruction));
+        assert_eq!(register.get(11), 0);
+    }
+
+    #[test]
+    fn test_csrrw_nonexistent() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0x7ff,
+            rs1: 0,
+            funct3: CSRRW,
+            rd: 0,
+            opcode: SYSTEM
+        };
+        let result = instruction.execute(&mut register, &mut memory);
+
+        assert_eq!(result, Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_sh1add() {
+        let mut register = Register::new();
+        register.put(10, 0x10);
+        register.put(11, 0x5);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: SH1ADD & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: SH1ADD >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x25);
+    }
+
+    #[test]
+    fn test_sh2add() {
+        let mut register = Register::new();
+        register.put(10, 0x10);
+        register.put(11, 0x5);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: SH2ADD & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: SH2ADD >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x45);
+    }
+
+    #[test]
+    fn test_sh3add() {
+        let mut register = Register::new();
+        register.put(10, 0x10);
+        register.put(11, 0x5);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: SH3ADD & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: SH3ADD >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x85);
+    }
+
+    #[test]
+    fn test_andn() {
+        let mut register = Register::new();
+        register.put(10, 0xc);
+        register.put(11, 0xa);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: ANDN & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: ANDN >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0b0100);
+    }
+
+    #[test]
+    fn test_orn() {
+        let mut register = Register::new();
+        register.put(10, 0x0);
+        register.put(11, 0xffff0000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: ORN & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: ORN >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x0000FFFF);
+    }
+
+    #[test]
+    fn test_xnor() {
+        let mut register = Register::new();
+        register.put(10, 0xf0f0f0f0);
+        register.put(11, 0xff00ff00);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: XNOR & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: XNOR >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xF00FF00F);
+    }
+
+    #[test]
+    fn test_min() {
+        let mut register = Register::new();
+        register.put(10, -1i32 as u32);
+        register.put(11, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: MIN & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: MIN >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xFFFFFFFF);
+    }
+
+    #[test]
+    fn test_minu() {
+        let mut register = Register::new();
+        register.put(10, -1i32 as u32);
+        register.put(11, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: MINU & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: MINU >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 1);
+    }
+
+    #[test]
+    fn test_max() {
+        let mut register = Register::new();
+        register.put(10, -1i32 as u32);
+        register.put(11, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: MAX & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: MAX >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 1);
+    }
+
+    #[test]
+    fn test_maxu() {
+        let mut register = Register::new();
+        register.put(10, -1i32 as u32);
+        register.put(11, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: MAXU & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: MAXU >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xFFFFFFFF);
+    }
+
+    #[test]
+    fn test_rol() {
+        let mut register = Register::new();
+        register.put(10, 0x80000001);
+        register.put(11, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: ROL & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: ROL >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x00000003);
+    }
+
+    #[test]
+    fn test_ror() {
+        let mut register = Register::new();
+        register.put(10, 0x80000001);
+        register.put(11, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: ROR & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: ROR >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xC0000000);
+    }
+
+    #[test]
+    fn test_clmul() {
+        let mut register = Register::new();
+        register.put(10, 0x5);
+        register.put(11, 0x3);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: CLMUL & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: CLMUL >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0b1111);
+    }
+
+    #[test]
+    fn test_clmulh() {
+        let mut register = Register::new();
+        register.put(10, 0x80000000);
+        register.put(11, 0x80000000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: CLMULH & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: CLMULH >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x40000000);
+    }
+
+    #[test]
+    fn test_clmulr() {
+        let mut register = Register::new();
+        register.put(10, 0x80000000);
+        register.put(11, 0x80000000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: CLMULR & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: CLMULR >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x80000000);
+    }
+
+    #[test]
+    fn test_bclr() {
+        let mut register = Register::new();
+        register.put(10, 0xff);
+        register.put(11, 0x3);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: BCLR & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: BCLR >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xF7);
+    }
+
+    #[test]
+    fn test_bext() {
+        let mut register = Register::new();
+        register.put(10, 0x8);
+        register.put(11, 0x3);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: BEXT & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: BEXT >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 1);
+    }
+
+    #[test]
+    fn test_binv() {
+        let mut register = Register::new();
+        register.put(10, 0x0);
+        register.put(11, 0x1f);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: BINV & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: BINV >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x80000000);
+    }
+
+    #[test]
+    fn test_bset() {
+        let mut register = Register::new();
+        register.put(10, 0x0);
+        register.put(11, 0x4);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: BSET & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: BSET >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x10);
+    }
+
+    #[test]
+    fn test_zext_h() {
+        let mut register = Register::new();
+        register.put(10, 0x12345678);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: ZEXT_H & 0b111,
+            rs1: 10,
+            rs2: 0,
+            funct7: ZEXT_H >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x5678);
+    }
+
+    #[test]
+    fn test_zext_h_rv64() {
+        let mut register = Register::new();
+        register.put(10, u64::MAX);
+
+        let mut memory = [0u64; 1024];
+
+        let zext_h = RFormatInstruction { rd: 12, funct3: PACKW & 0b111, rs1: 10, rs2: 0, funct7: PACKW >> 3, opcode: OP_32 };
+        zext_h.execute(&mut register, &mut memory).unwrap();
+        assert_eq!(register.get(12), 0xffff);
+
+        // pack with x0 zero-extends the lower word instead, and needs Zbkb on RV64
+        let pack = RFormatInstruction { rd: 12, funct3: PACK & 0b111, rs1: 10, rs2: 0, funct7: PACK >> 3, opcode: OP };
+        assert_eq!(pack.extensions(64), &[Extension::Zbkb]);
+        pack.execute(&mut register, &mut memory).unwrap();
+        assert_eq!(register.get(12), 0xffffffff);
+    }
+
+    #[test]
+    fn test_rori() {
+        let mut register = Register::new();
+        register.put(10, 0x12345678);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0b0110000_00100,
+            rs1: 10,
+            funct3: SRLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x81234567);
+    }
+
+    #[test]
+    fn test_bclri() {
+        let mut register = Register::new();
+        register.put(10, 0xff);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0b0100100_00011,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xF7);
+    }
+
+    #[test]
+    fn test_bexti() {
+        let mut register = Register::new();
+        register.put(10, 0x8);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0b0100100_00011,
+            rs1: 10,
+            funct3: SRLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 1);
+    }
+
+    #[test]
+    fn test_binvi() {
+        let mut register = Register::new();
+        register.put(10, 0x0);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0b0110100_11111,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x80000000);
+    }
+
+    #[test]
+    fn test_bseti() {
+        let mut register = Register::new();
+        register.put(10, 0x0);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0b0010100_00100,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x10);
+    }
+
+    #[test]
+    fn test_clz() {
+        let mut register = Register::new();
+        register.put(10, 0x10000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: CLZ as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 15);
+    }
+
+    #[test]
+    fn test_ctz() {
+        let mut register = Register::new();
+        register.put(10, 0x10000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: CTZ as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 16);
+    }
+
+    #[test]
+    fn test_cpop() {
+        let mut register = Register::new();
+        register.put(10, 0xf0f0);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: CPOP as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 8);
+    }
+
+    #[test]
+    fn test_sext_b() {
+        let mut register = Register::new();
+        register.put(10, 0x80);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: SEXT_B as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xFFFFFF80);
+    }
+
+    #[test]
+    fn test_sext_h() {
+        let mut register = Register::new();
+        register.put(10, 0x8000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: SEXT_H as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xFFFF8000);
+    }
+
+    #[test]
+    fn test_orc_b() {
+        let mut register = Register::new();
+        register.put(10, 0x120300);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: ORC_B as i16,
+            rs1: 10,
+            funct3: SRLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x00FFFF00);
+    }
+
+    #[test]
+    fn test_rev8() {
+        let mut register = Register::new();
+        register.put(10, 0x12345678);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: REV8 as i16,
+            rs1: 10,
+            funct3: SRLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x78563412);
+    }
+
+    #[test]
+    fn test_pack() {
+        let mut register = Register::new();
+        register.put(10, 0x12345678);
+        register.put(11, 0x9abcdef0);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: PACK & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: PACK >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xdef05678);
+    }
+
+    #[test]
+    fn test_packh() {
+        let mut register = Register::new();
+        register.put(10, 0x12345678);
+        register.put(11, 0x9abcdef0);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: PACKH & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: PACKH >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xf078);
+    }
+
+    #[test]
+    fn test_xperm4() {
+        let mut register = Register::new();
+        register.put(10, 0x76543210);
+        register.put(11, 0x1234567);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: XPERM4 & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: XPERM4 >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x01234567);
+    }
+
+    #[test]
+    fn test_xperm8() {
+        let mut register = Register::new();
+        register.put(10, 0x44332211);
+        register.put(11, 0x10203);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: XPERM8 & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: XPERM8 >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x11223344);
+    }
+
+    #[test]
+    fn test_sha512sum0r() {
+        let mut register = Register::new();
+        register.put(10, 0xf3bcc908);
+        register.put(11, 0x6a09e667);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: SHA512SUM0R & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: SHA512SUM0R >> 3,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xaac80c2a);
+    }
+
+    #[test]
+    fn test_aes32esi() {
+        let mut register = Register::new();
+        register.put(10, 0x0);
+        register.put(11, 0x5300);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: AES32ESI & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: (0b01 << 5) | (AES32ESI >> 3),
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xed00);
+    }
+
+    #[test]
+    fn test_aes32dsi() {
+        let mut register = Register::new();
+        register.put(10, 0x1);
+        register.put(11, 0xed000000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 12,
+            funct3: AES32DSI & 0b111,
+            rs1: 10,
+            rs2: 11,
+            funct7: (0b11 << 5) | (AES32DSI >> 3),
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x53000001);
+    }
+
+    #[test]
+    fn test_brev8() {
+        let mut register = Register::new();
+        register.put(10, 0x1020380);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: BREV8 as i16,
+            rs1: 10,
+            funct3: SRLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x8040c001);
+    }
+
+    #[test]
+    fn test_zip() {
+        let mut register = Register::new();
+        register.put(10, 0xffff0000);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: ZIP as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xaaaaaaaa);
+    }
+
+    #[test]
+    fn test_unzip() {
+        let mut register = Register::new();
+        register.put(10, 0xaaaaaaaa);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: UNZIP as i16,
+            rs1: 10,
+            funct3: SRLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0xffff0000);
+    }
+
+    #[test]
+    fn test_sha256sig1() {
+        let mut register = Register::new();
+        register.put(10, 0x18);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: SHA256SIG1 as i16,
+            rs1: 10,
+            funct3: SLLI,
+            rd: 12,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(12), 0x000f0000);
+    }
+
+    #[test]
+    fn test_addi_rv64() {
+        let mut register = Register::new();
+        register.put(1, 0xFFFFFFFF);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 1,
+            rs1: 1,
+            funct3: ADDI,
+            rd: 2,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(2), 0x1_00000000);
+    }
+
+    #[test]
+    fn test_addiw() {
+        let mut register = Register::new();
+        register.put(1, 0x7FFFFFFF);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 1,
+            rs1: 1,
+            funct3: ADDI,
+            rd: 2,
+            opcode: OP_IMM_32
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(2), 0xFFFFFFFF_80000000);
+    }
+
+    #[test]
+    fn test_addiw_rv32() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 1,
+            rs1: 1,
+            funct3: ADDI,
+            rd: 2,
+            opcode: OP_IMM_32
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_slli_rv64() {
+        let mut register = Register::new();
+        register.put(1, 0x1);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 33,
+            rs1: 1,
+            funct3: SLLI,
+            rd: 2,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(2), 0x2_00000000);
+    }
+
+    #[test]
+    fn test_srai_rv64() {
+        let mut register = Register::new();
+        register.put(1, 0x80000000_00000000);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: 0b0100000_00000 | 63,
+            rs1: 1,
+            funct3: SRLI,
+            rd: 2,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(2), 0xFFFFFFFF_FFFFFFFF);
+    }
+
+    #[test]
+    fn test_sraw() {
+        let mut register = Register::new();
+        register.put(1, 0x00000001_80000000);
+        register.put(2, 0x24);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 3,
+            funct3: 0b101,
+            rs1: 1,
+            rs2: 2,
+            funct7: 0b0100000,
+            opcode: OP_32
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(3), 0xFFFFFFFF_F8000000);
+    }
+
+    #[test]
+    fn test_subw() {
+        let mut register = Register::new();
+        register.put(1, 0x1);
+        register.put(2, 0x2);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 3,
+            funct3: 0b000,
+            rs1: 1,
+            rs2: 2,
+            funct7: 0b0100000,
+            opcode: OP_32
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(3), 0xFFFFFFFF_FFFFFFFF);
+    }
+
+    #[test]
+    fn test_lw_rv64() {
+        let mut register = Register::new();
+        register.put(24, 0xFF);
+
+        let mut memory = [0u64; 1024];
+        memory[0xFF] = 0xcccccb34;
+
+        let instruction = IFormatInstruction {
+            imm: 0x0,
+            rs1: 24,
+            funct3: LW,
+            rd: 10,
+            opcode: LOAD
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(10), 0xffffffff_cccccb34)
+    }
+
+    #[test]
+    fn test_lwu() {
+        let mut register = Register::new();
+        register.put(24, 0xFF);
+
+        let mut memory = [0u64; 1024];
+        memory[0xFF] = 0xcccccb34;
+
+        let instruction = IFormatInstruction {
+            imm: 0x0,
+            rs1: 24,
+            funct3: LWU,
+            rd: 10,
+            opcode: LOAD
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(10), 0xcccccb34)
+    }
+
+    #[test]
+    fn test_ld() {
+        let mut register = Register::new();
+        register.put(24, 0xFF);
+
+        let mut memory = [0u64; 1024];
+        memory[0x100] = 0x12345678_9abcdef0;
+
+        let instruction = IFormatInstruction {
+            imm: 0x1,
+            rs1: 24,
+            funct3: LD,
+            rd: 10,
+            opcode: LOAD
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(10), 0x12345678_9abcdef0)
+    }
+
+    #[test]
+    fn test_sd() {
+        let mut register = Register::new();
+        register.put(10, 0x100);
+        register.put(20, 0x12345678_9abcdef0);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = SFormatInstruction {
+            imm: -1,
+            rs1: 10,
+            rs2: 20,
+            funct3: SD
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(memory[0xFF], 0x12345678_9abcdef0);
+    }
+
+    #[test]
+    fn test_sw_rv64() {
+        let mut register = Register::new();
+        register.put(10, 0x100);
+        register.put(20, 0x12345678_9abcdef0);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = SFormatInstruction {
+            imm: 0,
+            rs1: 10,
+            rs2: 20,
+            funct3: SW
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(memory[0x100], 0x9abcdef0);
+    }
+
+    #[test]
+    fn test_lui_rv64() {
+        let mut register = Register::new();
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = UFormatInstruction {
+            imm: 0x80000,
+            rd: 13,
+            opcode: LUI
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(13), 0xFFFFFFFF_80000000);
+    }
+
+    #[test]
+    fn test_rev8_rv64() {
+        let mut register = Register::new();
+        register.put(1, 0x01234567_89abcdef);
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: REV8_RV64 as i16,
+            rs1: 1,
+            funct3: SRLI,
+            rd: 2,
+            opcode: OP_IMM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(2), 0xefcdab89_67452301);
+    }
+
+    #[test]
+    fn test_csrr_misa_rv64() {
+        let mut register = Register::new();
+
+        let mut memory = [0u64; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MISA as i16,
+            rs1: 0,
+            funct3: CSRRS,
+            rd: 10,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(10) >> 62, 2);
+    }
+
+    #[test]
+    fn test_add_rv32e() {
+        let mut register = Register::with_isa(Isa::all_embedded());
+        register.put(10, 0x1);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 11,
+            funct3: 0b000,
+            rs1: 10,
+            rs2: 10,
+            funct7: 0b0000000,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(11), 0x2);
+    }
+
+    #[test]
+    fn test_add_rv32e_upper_register() {
+        let mut register = Register::with_isa(Isa::all_embedded());
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 10,
+            funct3: 0b000,
+            rs1: 10,
+            rs2: 16,
+            funct7: 0b0000000,
+            opcode: OP
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_csrrwi_rv32e_immediate() {
+        let mut register = Register::with_isa(Isa::all_embedded());
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: csr::MSCRATCH as i16,
+            rs1: 31,
+            funct3: CSRRWI,
+            rd: 0,
+            opcode: SYSTEM
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.csr().get(csr::MSCRATCH), 31);
+    }
+
+    #[test]
+    fn test_cpop_disabled_extension() {
+        let mut register = Register::with_isa(Isa::parse("rv32i_zba").unwrap());
+        register.put(11, 0xFF);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = IFormatInstruction {
+            imm: CPOP as i16,
+            rs1: 11,
+            funct3: SLLI,
+            rd: 10,
+            opcode: OP_IMM
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_clmul_zbkc() {
+        let mut register = Register::with_isa(Isa::parse("rv32i_zbkc").unwrap());
+        register.put(1, 0b11);
+        register.put(2, 0b11);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 3,
+            funct3: 0b001,
+            rs1: 1,
+            rs2: 2,
+            funct7: 0b0000101,
+            opcode: OP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(3), 0b101);
+    }
+
+    #[test]
+    fn test_vsetvli() {
+        let mut register = Register::new();
+        register.put(11, 10);
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = VConfigInstruction {
+            rd: 5,
+            rs1: 11,
+            rs2: 0,
+            vtypei: 0b11_010_000,
+            funct: VSETVLI
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(5), 4);
+        assert_eq!(register.csr().get(csr::VL), 4);
+        assert_eq!(register.csr().get(csr::VTYPE), 0b11_010_000);
+    }
+
+    #[test]
+    fn test_vsetvli_vlmax() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = VConfigInstruction {
+            rd: 5,
+            rs1: 0,
+            rs2: 0,
+            vtypei: 0b000_011,
+            funct: VSETVLI
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(5), 128);
+    }
+
+    #[test]
+    fn test_vsetivli_illegal_sew() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = VConfigInstruction {
+            rd: 5,
+            rs1: 4,
+            rs2: 0,
+            vtypei: 0b011_000,
+            funct: VSETIVLI
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(5), 0);
+        assert_eq!(register.csr().get(csr::VTYPE), 1 << 31);
+    }
+
+    #[test]
+    fn test_vadd_without_vsetvli() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = VFormatInstruction {
+            funct6: VADD,
+            vm: true,
+            vs2: 2,
+            vs1: 3,
+            funct3: OPIVV,
+            vd: 1,
+            opcode: OP_V
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_vle32_vadd_vx() {
+        let mut register = Register::new();
+        register.put(10, 100);
+        register.put(11, 5);
+
+        let mut memory = [0u32; 1024];
+        memory[100..104].copy_from_slice(&[1, 2, 3, 0xFFFFFFFF]);
+
+        vector::configure(&mut register, 0, 4, 0, 0b010_000, VSETIVLI);
+        let load = VFormatInstruction {
+            funct6: VUNIT_STRIDE,
+            vm: true,
+            vs2: 0,
+            vs1: 10,
+            funct3: VE32,
+            vd: 1,
+            opcode: LOAD_FP
+        };
+        load.execute(&mut register, &mut memory).unwrap();
+        let add = VFormatInstruction {
+            funct6: VADD,
+            vm: true,
+            vs2: 1,
+            vs1: 11,
+            funct3: OPIVX,
+            vd: 2,
+            opcode: OP_V
+        };
+        add.execute(&mut register, &mut memory).unwrap();
+
+        let elements: Vec<u64> = (0..4).map(|index| register.vector().get(2, index, 32)).collect();
+        assert_eq!(elements, vec![6, 7, 8, 4]);
+    }
+
+    #[test]
+    fn test_vlse8_strided() {
+        let mut register = Register::new();
+        register.put(10, 100);
+        register.put(11, 2);
+
+        let mut memory = [0u32; 1024];
+        memory[100..106].copy_from_slice(&[0x101, 0, 0x102, 0, 0x103, 0]);
+
+        vector::configure(&mut register, 0, 3, 0, 0b000_000, VSETIVLI);
+        let instruction = VFormatInstruction {
+            funct6: VSTRIDED,
+            vm: true,
+            vs2: 11,
+            vs1: 10,
+            funct3: VE8,
+            vd: 1,
+            opcode: LOAD_FP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        let elements: Vec<u64> = (0..3).map(|index| register.vector().get(1, index, 8)).collect();
+        assert_eq!(elements, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_vse32_masked() {
+        let mut register = Register::new();
+        register.put(10, 100);
+
+        let mut memory = [0u32; 1024];
+
+        vector::configure(&mut register, 0, 4, 0, 0b010_000, VSETIVLI);
+        for index in 0..4 {
+            register.vector_mut().set(1, index, 32, index as u64 + 1);
+        }
+        register.vector_mut().set_mask(0, 1, true);
+        register.vector_mut().set_mask(0, 3, true);
+        let instruction = VFormatInstruction {
+            funct6: VUNIT_STRIDE,
+            vm: false,
+            vs2: 0,
+            vs1: 10,
+            funct3: VE32,
+            vd: 1,
+            opcode: STORE_FP
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(memory[100..104], [0, 2, 0, 4]);
+    }
+
+    #[test]
+    fn test_vsub_masked_undisturbed() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        // vl = 2 out of 4 elements, tail and mask undisturbed
+        vector::configure(&mut register, 0, 2, 0, 0b010_000, VSETIVLI);
+        for index in 0..4 {
+            register.vector_mut().set(1, index, 32, 10);
+            register.vector_mut().set(2, index, 32, 3);
+        }
+        register.vector_mut().set_mask(0, 0, true);
+        let instruction = VFormatInstruction {
+            funct6: VSUB,
+            vm: false,
+            vs2: 1,
+            vs1: 2,
+            funct3: OPIVV,
+            vd: 3,
+            opcode: OP_V
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        let elements: Vec<u64> = (0..4).map(|index| register.vector().get(3, index, 32)).collect();
+        assert_eq!(elements, vec![7, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_vsub_masked_agnostic() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        // vl = 2 out of 4 elements, tail and mask agnostic
+        vector::configure(&mut register, 0, 2, 0, 0b11_010_000, VSETIVLI);
+        for index in 0..4 {
+            register.vector_mut().set(1, index, 32, 10);
+            register.vector_mut().set(2, index, 32, 3);
+        }
+        register.vector_mut().set_mask(0, 0, true);
+        let instruction = VFormatInstruction {
+            funct6: VSUB,
+            vm: false,
+            vs2: 1,
+            vs1: 2,
+            funct3: OPIVV,
+            vd: 3,
+            opcode: OP_V
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        let elements: Vec<u64> = (0..4).map(|index| register.vector().get(3, index, 32)).collect();
+        assert_eq!(elements, vec![7, 0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF]);
+    }
+
+    #[test]
+    fn test_vadd_masked_into_v0() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        vector::configure(&mut register, 0, 2, 0, 0b010_000, VSETIVLI);
+        let instruction = VFormatInstruction {
+            funct6: VADD,
+            vm: false,
+            vs2: 1,
+            vs1: 2,
+            funct3: OPIVV,
+            vd: 0,
+            opcode: OP_V
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_vmsle_vredmax() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        vector::configure(&mut register, 0, 4, 0, 0b010_000, VSETIVLI);
+        for (index, value) in [5, 0xFFFFFFFE, 9, 1].iter().enumerate() {
+            register.vector_mut().set(1, index, 32, *value);
+        }
+        register.vector_mut().set(2, 0, 32, 0x80000000);
+        // v0 = v1 <= 5, then the signed maximum of the elements up to 5
+        let compare = VFormatInstruction {
+            funct6: VMSLE,
+            vm: true,
+            vs2: 1,
+            vs1: 5,
+            funct3: OPIVI,
+            vd: 0,
+            opcode: OP_V
+        };
+        compare.execute(&mut register, &mut memory).unwrap();
+        let reduce = VFormatInstruction {
+            funct6: VREDMAX,
+            vm: false,
+            vs2: 1,
+            vs1: 2,
+            funct3: OPMVV,
+            vd: 3,
+            opcode: OP_V
+        };
+        reduce.execute(&mut register, &mut memory).unwrap();
+
+        let mask: Vec<bool> = (0..4).map(|index| register.vector().mask(0, index)).collect();
+        assert_eq!(mask, vec![true, true, false, true]);
+        assert_eq!(register.vector().get(3, 0, 32), 5);
+    }
+
+    #[test]
+    fn test_vmseq_vv_misaligned() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        vector::configure(&mut register, 0, 31, 0, 0b010_011, VSETIVLI);
+        let instruction = VFormatInstruction {
+            funct6: VMSEQ,
+            vm: true,
+            vs2: 8,
+            vs1: 31,
+            funct3: OPIVV,
+            vd: 0,
+            opcode: OP_V
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_vmv_x_s_rv64() {
+        let mut register = Register::new();
+
+        let mut memory = [0u64; 1024];
+
+        vector::configure(&mut register, 0, 1, 0, 0b010_000, VSETIVLI);
+        register.vector_mut().set(1, 0, 32, 0xFFFFFFFF);
+        let instruction = VFormatInstruction {
+            funct6: VWXUNARY0,
+            vm: true,
+            vs2: 1,
+            vs1: 0,
+            funct3: OPMVV,
+            vd: 10,
+            opcode: OP_V
+        };
+        instruction.execute(&mut register, &mut memory).unwrap();
+
+        assert_eq!(register.get(10), 0xFFFFFFFF_FFFFFFFF);
+    }
+
+    #[test]
+    fn test_vmv2r_misaligned() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = VFormatInstruction {
+            funct6: VMVNRR,
+            vm: true,
+            vs2: 3,
+            vs1: 1,
+            funct3: OPIVI,
+            vd: 2,
+            opcode: OP_V
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_vector_disabled_extension() {
+        let mut register = Register::with_isa(Isa::parse("rv32i").unwrap());
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = VConfigInstruction {
+            rd: 5,
+            rs1: 0,
+            rs2: 0,
+            vtypei: 0,
+            funct: VSETVLI
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
+
+    #[test]
+    fn test_from_reserved() {
+        // All zeros, all ones and a compressed encoding
+        assert!(Instruction::from(0x00000000).is_none());
+        assert!(Instruction::from(0xFFFFFFFF).is_none());
+        assert!(Instruction::from(0x00004501).is_none());
+        // srai with a funct7 that selects nothing
+        assert!(Instruction::from(0b0100010_00001_00000_101_00101_0010011).is_none());
+        // slliw with the sixth shift amount bit set
+        assert!(Instruction::from(0b0000001_00001_00101_001_00101_0011011).is_none());
+        // add with the funct7 of the M extension
+        assert!(Instruction::from(0b0000001_00001_00101_000_00101_0110011).is_none());
+        // The reserved branch, load and store funct3s
+        assert!(Instruction::from(0b0000000_00001_00101_010_00000_1100011).is_none());
+        assert!(Instruction::from(0b000000000000_00101_111_00101_0000011).is_none());
+        assert!(Instruction::from(0b0000000_00001_00101_100_00000_0100011).is_none());
+        // jalr with a non-zero funct3
+        assert!(Instruction::from(0b000000000000_00001_001_00000_1100111).is_none());
+        // ecall with a destination register
+        assert!(Instruction::from(0b000000000000_00000_000_00001_1110011).is_none());
+        // vsetvl with bits 30:25 set and vmv.x.s with vs1 set
+        assert!(Instruction::from(0b1000001_01100_01011_111_01010_1010111).is_none());
+        assert!(Instruction::from(0b010000_1_01000_00001_010_01010_1010111).is_none());
+    }
+
+    #[test]
+    fn test_from_system_and_fence() {
+        assert!(matches!(Instruction::from(0x00000073), Some(IFormatInstruction { imm: ECALL, opcode: SYSTEM, .. })));
+        assert!(matches!(Instruction::from(0x00100073), Some(IFormatInstruction { imm: EBREAK, opcode: SYSTEM, .. })));
+        assert!(matches!(Instruction::from(0x0FF0000F), Some(IFormatInstruction { opcode: FENCE, .. })));
+    }
+
+    #[test]
+    fn test_wfi() {
+        let mut register = Register::new();
+        register.update_pc(8);
+        let mut memory = [0u32; 1024];
+
+        let instruction = Instruction::from(0x10500073).unwrap();
+        assert!(matches!(instruction, IFormatInstruction { imm: WFI, opcode: SYSTEM, .. }));
+        assert_eq!(instruction.execute(&mut register, &mut memory), Ok(()));
+        assert_eq!(register.pc(), 12);
+    }
+
+    #[test]
+    fn test_from_lui() {
+        // lui t0, 0x12345
+        assert!(matches!(Instruction::from(0x123452B7), Some(UFormatInstruction { imm: 0x12345, rd: 5, opcode: LUI })));
+    }
+
+    #[test]
+    fn test_execute_undefined() {
+        let mut register = Register::new();
+
+        let mut memory = [0u32; 1024];
+
+        let instruction = RFormatInstruction {
+            rd: 5,
+            funct3: 0b000,
+            rs1: 1,
+            rs2: 2,
+            funct7: 0b0000001,
+            opcode: OP
+        };
+
+        assert_eq!(instruction.execute(&mut register, &mut memory), Err(Exception::IllegalInstruction));
+    }
 }
@@ -0,0 +1,374 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The extensions a hart can be configured with on top of its base integer ISA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    V,
+    // The supervisor and user privilege levels, which misa lists among the extensions
+    S,
+    U,
+    Zicsr,
+    Zicntr,
+    Zihpm,
+    Zba,
+    Zbb,
+    Zbc,
+    Zbs,
+    Zbkb,
+    Zbkc,
+    Zbkx,
+    Zknd,
+    Zkne,
+    Zknh
+}
+
+const EXTENSIONS: &[(Extension, &str)] = &[
+    (Extension::Zicsr, "zicsr"),
+    (Extension::Zicntr, "zicntr"),
+    (Extension::Zihpm, "zihpm"),
+    (Extension::Zba, "zba"),
+    (Extension::Zbb, "zbb"),
+    (Extension::Zbc, "zbc"),
+    (Extension::Zbs, "zbs"),
+    (Extension::Zbkb, "zbkb"),
+    (Extension::Zbkc, "zbkc"),
+    (Extension::Zbkx, "zbkx"),
+    (Extension::Zknd, "zknd"),
+    (Extension::Zkne, "zkne"),
+    (Extension::Zknh, "zknh"),
+];
+
+/// Implemented single-letter extensions
+const LETTERS: &[(Extension, &str)] = &[
+    (Extension::V, "v"),
+    (Extension::S, "s"),
+    (Extension::U, "u"),
+];
+
+/// Shorthands that stand for several extensions at once
+const SHORTHANDS: &[(&str, &[Extension])] = &[
+    ("zkn", &[Extension::Zbkb, Extension::Zbkc, Extension::Zbkx, Extension::Zkne, Extension::Zknd, Extension::Zknh]),
+];
+
+/// Standard single-letter extensions that this emulator does not implement
+const UNSUPPORTED: &[char] = &['g', 'm', 'a', 'f', 'd', 'q', 'c', 'h'];
+
+/// The smallest VLEN of the V extension, `zvl<N>b` raises it
+const MINIMUM_VLEN: usize = 128;
+
+/// misa bits for the single-letter extensions
+const MISA_B: u64 = 1 << 1;
+const MISA_E: u64 = 1 << 4;
+const MISA_I: u64 = 1 << 8;
+const MISA_S: u64 = 1 << 18;
+const MISA_U: u64 = 1 << 20;
+const MISA_V: u64 = 1 << 21;
+
+impl Extension {
+    pub fn name(&self) -> &'static str {
+        LETTERS.iter()
+            .chain(EXTENSIONS)
+            .find(|(extension, _)| extension == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+
+    fn is_single_letter(&self) -> bool {
+        LETTERS.iter().any(|(extension, _)| extension == self)
+    }
+
+    /// Extensions that have to be present for this one to work
+    fn implies(&self) -> &'static [Extension] {
+        match self {
+            Extension::Zicntr | Extension::Zihpm => &[Extension::Zicsr],
+            // A hart with supervisor mode has user mode too
+            Extension::S => &[Extension::U],
+            _ => &[]
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsaError {
+    /// The string does not start with `rv32` or `rv64` followed by a base ISA
+    InvalidBase(String),
+    /// An extension that is not part of the RISC-V naming conventions
+    UnknownExtension(String),
+    /// A standard extension that this emulator does not implement
+    UnsupportedExtension(String),
+    /// The ISA does not match the register width of the processor it configures
+    XlenMismatch { expected: u32, found: u32 }
+}
+
+impl fmt::Display for IsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsaError::InvalidBase(isa) =>
+                write!(f, "'{}' does not start with rv32i, rv32e or rv64i", isa),
+            IsaError::UnknownExtension(extension) =>
+                write!(f, "unknown extension '{}'", extension),
+            IsaError::UnsupportedExtension(extension) =>
+                write!(f, "extension '{}' is not implemented", extension),
+            IsaError::XlenMismatch { expected, found } =>
+                write!(f, "expected an rv{} ISA but found rv{}", expected, found)
+        }
+    }
+}
+
+impl std::error::Error for IsaError {}
+
+/// The base ISA and the extensions a hart implements, as named by an ISA string like `rv32i_zicsr_zba`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Isa {
+    xlen: u32,
+    embedded: bool,
+    extensions: Vec<Extension>,
+    vlen: usize
+}
+
+impl Isa {
+    /// Every implemented extension on top of RV32I or RV64I, with the smallest VLEN
+    pub fn all(xlen: u32) -> Isa {
+        Isa {
+            xlen,
+            embedded: false,
+            extensions: LETTERS.iter()
+                .map(|(extension, _)| *extension)
+                .chain(EXTENSIONS.iter().map(|(extension, _)| *extension))
+                .collect(),
+            vlen: MINIMUM_VLEN
+        }
+    }
+
+    /// Every implemented extension on top of RV32E
+    pub fn all_embedded() -> Isa {
+        Isa { embedded: true, ..Isa::all(32) }
+    }
+
+    pub fn parse(isa: &str) -> Result<Isa, IsaError> {
+        let lowercase = isa.to_lowercase();
+        let (xlen, rest) = match lowercase.split_at_checked(4) {
+            Some(("rv32", rest)) => (32, rest),
+            Some(("rv64", rest)) => (64, rest),
+            _ => return Err(IsaError::InvalidBase(isa.to_owned()))
+        };
+        let embedded = match rest.chars().next() {
+            Some('i') => false,
+            Some('e') if xlen == 32 => true,
+            _ => return Err(IsaError::InvalidBase(isa.to_owned()))
+        };
+
+        let mut extensions = Vec::new();
+        let mut vlen = 0;
+        // Single-letter extensions come first, the multi-letter ones are separated by underscores
+        let (single, multi) = match rest[1..].find(['_', 'z']) {
+            Some(index) => rest[1..].split_at(index),
+            None => (&rest[1..], "")
+        };
+        for letter in single.chars() {
+            match letter {
+                'b' => extensions.extend([Extension::Zba, Extension::Zbb, Extension::Zbs]),
+                'v' => {
+                    extensions.push(Extension::V);
+                    vlen = vlen.max(MINIMUM_VLEN);
+                },
+                's' => extensions.push(Extension::S),
+                'u' => extensions.push(Extension::U),
+                letter if UNSUPPORTED.contains(&letter) =>
+                    return Err(IsaError::UnsupportedExtension(letter.to_string())),
+                letter => return Err(IsaError::UnknownExtension(letter.to_string()))
+            }
+        }
+        for name in multi.split('_').filter(|name| !name.is_empty()) {
+            if let Some((_, implied)) = SHORTHANDS.iter().find(|(shorthand, _)| *shorthand == name) {
+                extensions.extend_from_slice(implied);
+            } else if let Some((extension, _)) = EXTENSIONS.iter().find(|(_, known)| *known == name) {
+                extensions.push(*extension);
+            } else if let Some(minimum) = Isa::parse_zvl(name) {
+                vlen = vlen.max(minimum);
+            } else {
+                return Err(IsaError::UnknownExtension(name.to_owned()));
+            }
+        }
+
+        let implied: Vec<Extension> = extensions.iter()
+            .flat_map(|extension| extension.implies())
+            .copied()
+            .collect();
+        extensions.extend(implied);
+        extensions.sort();
+        extensions.dedup();
+
+        Ok(Isa { xlen, embedded, extensions, vlen })
+    }
+
+    /// The VLEN of a `zvl<N>b` extension, N being a power of two from 32 to 65536
+    fn parse_zvl(name: &str) -> Option<usize> {
+        let vlen: usize = name.strip_prefix("zvl")?.strip_suffix('b')?.parse().ok()?;
+        (vlen.is_power_of_two() && (32..=65536).contains(&vlen)).then_some(vlen)
+    }
+
+    pub fn xlen(&self) -> u32 {
+        self.xlen
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        self.embedded
+    }
+
+    /// The number of integer registers, 16 on RV32E and 32 otherwise
+    pub fn registers(&self) -> usize {
+        if self.embedded { 16 } else { 32 }
+    }
+
+    pub fn has(&self, extension: Extension) -> bool {
+        self.extensions.contains(&extension)
+    }
+
+    /// The width of the vector registers in bits
+    pub fn vlen(&self) -> usize {
+        self.vlen
+    }
+
+    /// The value of `misa`: MXL in the upper two bits and one bit per single-letter extension,
+    /// including the supervisor and user modes if the hart implements them
+    pub fn misa(&self) -> u64 {
+        let mxl: u64 = if self.xlen == 64 { 2 } else { 1 };
+        let base = if self.embedded { MISA_E } else { MISA_I };
+        let bitmanip = [Extension::Zba, Extension::Zbb, Extension::Zbs]
+            .iter()
+            .all(|extension| self.has(*extension));
+
+        let letters = [(Extension::S, MISA_S), (Extension::U, MISA_U), (Extension::V, MISA_V)]
+            .iter()
+            .filter(|(extension, _)| self.has(*extension))
+            .fold(0, |misa, (_, bit)| misa | bit);
+
+        (mxl << (self.xlen - 2)) | base | letters | if bitmanip { MISA_B } else { 0 }
+    }
+}
+
+impl FromStr for Isa {
+    type Err = IsaError;
+
+    fn from_str(isa: &str) -> Result<Self, Self::Err> {
+        Isa::parse(isa)
+    }
+}
+
+/// Formats the ISA in its canonical form, e.g. `rv32iv_zicsr_zba_zvl256b`
+impl fmt::Display for Isa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rv{}{}", self.xlen, if self.embedded { "e" } else { "i" })?;
+        let (letters, names): (Vec<&Extension>, Vec<&Extension>) = self.extensions.iter()
+            .partition(|extension| extension.is_single_letter());
+        for extension in letters {
+            write!(f, "{}", extension.name())?;
+        }
+        for extension in names {
+            write!(f, "_{}", extension.name())?;
+        }
+        let implied = if self.has(Extension::V) { MINIMUM_VLEN } else { 0 };
+        if self.vlen > implied {
+            write!(f, "_zvl{}b", self.vlen)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::isa::{Extension, Isa, IsaError};
+
+    #[test]
+    fn test_parse() {
+        let isa = Isa::parse("rv32i_zicsr_zba").unwrap();
+
+        assert_eq!(isa.xlen(), 32);
+        assert!(isa.has(Extension::Zicsr));
+        assert!(isa.has(Extension::Zba));
+        assert!(!isa.has(Extension::Zbb));
+    }
+
+    #[test]
+    fn test_parse_rv64() {
+        let isa = Isa::parse("RV64I_Zbb").unwrap();
+
+        assert_eq!(isa.xlen(), 64);
+        assert!(isa.has(Extension::Zbb));
+    }
+
+    #[test]
+    fn test_parse_embedded() {
+        let isa = Isa::parse("rv32e").unwrap();
+
+        assert!(isa.is_embedded());
+        assert_eq!(isa.registers(), 16);
+        assert_eq!(Isa::parse("rv64e"), Err(IsaError::InvalidBase(String::from("rv64e"))));
+    }
+
+    #[test]
+    fn test_parse_implied() {
+        let isa = Isa::parse("rv32i_zicntr").unwrap();
+
+        assert!(isa.has(Extension::Zicsr));
+    }
+
+    #[test]
+    fn test_parse_shorthands() {
+        let isa = Isa::parse("rv32ib_zkn").unwrap();
+
+        assert!(isa.has(Extension::Zbs));
+        assert!(isa.has(Extension::Zknh));
+        assert!(!isa.has(Extension::Zbc));
+    }
+
+    #[test]
+    fn test_parse_unsupported() {
+        assert_eq!(Isa::parse("rv32imac_zicsr_zba"), Err(IsaError::UnsupportedExtension(String::from("m"))));
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert_eq!(Isa::parse("rv32i_zfoo"), Err(IsaError::UnknownExtension(String::from("zfoo"))));
+        assert_eq!(Isa::parse("x86"), Err(IsaError::InvalidBase(String::from("x86"))));
+    }
+
+    #[test]
+    fn test_misa() {
+        assert_eq!(Isa::parse("rv32i").unwrap().misa(), (1 << 30) | (1 << 8));
+        assert_eq!(Isa::parse("rv32eu").unwrap().misa(), (1 << 30) | (1 << 20) | (1 << 4));
+        assert_eq!(Isa::parse("rv32is").unwrap().misa(), (1 << 30) | (1 << 20) | (1 << 18) | (1 << 8));
+        assert_eq!(Isa::parse("rv64ibsu").unwrap().misa(), (2 << 62) | (1 << 20) | (1 << 18) | (1 << 8) | (1 << 1));
+    }
+
+    #[test]
+    fn test_parse_vector() {
+        let isa = Isa::parse("rv32iv").unwrap();
+
+        assert!(isa.has(Extension::V));
+        assert_eq!(isa.vlen(), 128);
+        assert_eq!(isa.misa(), (1 << 30) | (1 << 21) | (1 << 8));
+        assert_eq!(Isa::parse("rv64iv_zvl512b").unwrap().vlen(), 512);
+        assert_eq!(Isa::parse("rv32iv_zvl64b").unwrap().vlen(), 128);
+        assert_eq!(Isa::parse("rv32iv_zvl100b"), Err(IsaError::UnknownExtension(String::from("zvl100b"))));
+    }
+
+    #[test]
+    fn test_display_vector() {
+        let isa = Isa::parse("rv32i_zvl256b_zicsr_zvl1024b").unwrap();
+        assert_eq!(isa.to_string(), "rv32i_zicsr_zvl1024b");
+
+        let isa = Isa::parse("rv32iv_zicsr").unwrap();
+        assert_eq!(isa.to_string(), "rv32iv_zicsr");
+    }
+
+    #[test]
+    fn test_display() {
+        let isa = Isa::parse("rv32i_zba_zicsr").unwrap();
+
+        assert_eq!(isa.to_string(), "rv32i_zicsr_zba");
+        assert_eq!(Isa::parse("rv32is_zicsr").unwrap().to_string(), "rv32isu_zicsr");
+    }
+}
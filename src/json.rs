@@ -0,0 +1,320 @@
+use std::fmt;
+use std::fmt::Write as _;
+
+/// A JSON value, enough of it for the debug adapter's messages and the JSON tracer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members keep their order, which makes the output predictable
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { text: text.as_bytes(), position: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        match parser.position == text.len() {
+            true => Ok(value),
+            false => Err(parser.error("trailing characters"))
+        }
+    }
+
+    /// An object from its members
+    pub fn object<const N: usize>(members: [(&str, Json); N]) -> Json {
+        Json::Object(members.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(member, _)| member == name).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(text: &str) -> Json {
+        Json::String(text.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(text: String) -> Json {
+        Json::String(text)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(number: i64) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Json {
+        Json::Array(values)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(text) => write!(f, "{}", quote(text)),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    write!(f, "{}{}", if index == 0 { "" } else { "," }, value)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    write!(f, "{}{}:{}", if index == 0 { "" } else { "," }, quote(name), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// A JSON string literal holding `text`
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => { let _ = write!(quoted, "\\u{:04x}", c as u32); },
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => {
+                self.position += 1;
+                let mut values = Vec::new();
+                if !self.consume(b']') {
+                    loop {
+                        values.push(self.value()?);
+                        if self.consume(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Array(values))
+            },
+            Some(b'{') => {
+                self.position += 1;
+                let mut members = Vec::new();
+                if !self.consume(b'}') {
+                    loop {
+                        self.whitespace();
+                        let name = self.string()?;
+                        self.expect(b':')?;
+                        members.push((name, self.value()?));
+                        if self.consume(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Object(members))
+            },
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error("expected a value"))
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.position]).ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => break,
+                Some(b'\\') => match self.next() {
+                    Some(b'n') => bytes.push(b'\n'),
+                    Some(b't') => bytes.push(b'\t'),
+                    Some(b'r') => bytes.push(b'\r'),
+                    Some(b'b') => bytes.push(0x08),
+                    Some(b'f') => bytes.push(0x0c),
+                    Some(b'u') => {
+                        let mut code = self.code_unit()?;
+                        if (0xd800..0xdc00).contains(&code) && self.text[self.position..].starts_with(b"\\u") {
+                            self.position += 2;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (self.code_unit()? - 0xdc00);
+                        }
+                        let c = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                        bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    },
+                    Some(byte @ (b'"' | b'\\' | b'/')) => bytes.push(byte),
+                    _ => return Err(self.error("invalid escape"))
+                },
+                Some(byte) => bytes.push(byte)
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    fn code_unit(&mut self) -> Result<u32, String> {
+        let hex = self.text.get(self.position..self.position + 4).ok_or_else(|| self.error("invalid escape"))?;
+        self.position += 4;
+        std::str::from_utf8(hex).ok()
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| self.error("invalid escape"))
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        match self.text[self.position..].starts_with(keyword.as_bytes()) {
+            true => {
+                self.position += keyword.len();
+                Ok(value)
+            },
+            false => Err(self.error("expected a value"))
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips whitespace and the byte if it comes next, returning whether it did
+    fn consume(&mut self, byte: u8) -> bool {
+        self.whitespace();
+        let found = self.peek() == Some(byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        match self.consume(byte) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+
+    #[test]
+    fn test_parse() {
+        let json = Json::parse(r#" {"seq": 1, "arguments": {"lines": [3, -4.5e1], "path": "a\"bé\n", "ok": true, "none": null}} "#).unwrap();
+
+        assert_eq!(json.get("seq").and_then(Json::as_i64), Some(1));
+        let arguments = json.get("arguments").unwrap();
+        assert_eq!(arguments.get("lines"), Some(&Json::Array(vec![Json::Number(3.0), Json::Number(-45.0)])));
+        assert_eq!(arguments.get("path").and_then(Json::as_str), Some("a\"b\u{e9}\n"));
+        assert_eq!(arguments.get("ok").and_then(Json::as_bool), Some(true));
+        assert_eq!(arguments.get("none"), Some(&Json::Null));
+        assert!(Json::parse(r#"{"seq": }"#).is_err());
+        assert!(Json::parse("[1] 2").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let json = Json::object([
+            ("name", Json::from("a0\t")),
+            ("values", Json::from(vec![Json::from(7usize), Json::from(-2i64), Json::Null])),
+            ("stopped", Json::from(false))
+        ]);
+
+        assert_eq!(json.to_string(), r#"{"name":"a0\u0009","values":[7,-2,null],"stopped":false}"#);
+        assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+    }
+}
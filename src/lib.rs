@@ -1,6 +1,26 @@
+// Binary literals are grouped by instruction field rather than by nibble
+#![allow(clippy::unusual_byte_groupings)]
+
 mod register;
 mod instruction;
 mod math_utils;
 mod assembly_compiler;
+mod crypto;
 mod immediates;
+mod vector;
+mod memory;
+mod mmu;
+mod json;
+pub mod clint;
+pub mod csr;
+pub mod dap;
+pub mod debug;
+pub mod disassembler;
+pub mod gdb;
+pub mod isa;
 pub mod processor;
+pub mod snapshot;
+pub mod tlb;
+pub mod trace;
+pub mod trap;
+pub mod xlen;
@@ -1,5 +1,3 @@
-use std::u32;
-
 pub trait MixedIntegerOps {
     fn wrapping_add_signed(self, rhs: i32) -> Self;
 }
@@ -23,3 +21,17 @@ impl MixedIntegerOps for usize {
         }
     }
 }
+
+/// Carry-less multiplication of two registers, returning the full double-width product
+pub fn clmul(a: u64, b: u64) -> u128 {
+    (0..64)
+        .filter(|i| (b >> i) & 1 == 1)
+        .fold(0u128, |product, i| product ^ ((a as u128) << i))
+}
+
+/// Sets every byte that has any bit set to 0xFF, and every zero byte to 0x00
+pub fn orc_b(value: u64) -> u64 {
+    (0..8)
+        .filter(|byte| (value >> (byte * 8)) & 0xFF != 0)
+        .fold(0u64, |result, byte| result | (0xFF << (byte * 8)))
+}
@@ -0,0 +1,97 @@
+use crate::trap::Exception;
+use crate::xlen::Xlen;
+
+/// The cells instructions load from and store to, one register wide each.
+pub trait Memory<X: Xlen> {
+    fn load(&mut self, address: usize) -> Result<X, Exception>;
+
+    fn store(&mut self, address: usize, value: X) -> Result<(), Exception>;
+}
+
+impl<X: Xlen, const N: usize> Memory<X> for [X; N] {
+    fn load(&mut self, address: usize) -> Result<X, Exception> {
+        self.get(address).copied().ok_or(Exception::LoadAccessFault(address))
+    }
+
+    fn store(&mut self, address: usize, value: X) -> Result<(), Exception> {
+        let cell = self.get_mut(address).ok_or(Exception::StoreAccessFault(address))?;
+        *cell = value;
+        Ok(())
+    }
+}
+
+impl<X: Xlen> Memory<X> for Vec<X> {
+    fn load(&mut self, address: usize) -> Result<X, Exception> {
+        self.get(address).copied().ok_or(Exception::LoadAccessFault(address))
+    }
+
+    fn store(&mut self, address: usize, value: X) -> Result<(), Exception> {
+        let cell = self.get_mut(address).ok_or(Exception::StoreAccessFault(address))?;
+        *cell = value;
+        Ok(())
+    }
+}
+
+/// A load or a store performed by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access<X> {
+    Read { address: usize, value: X },
+    /// `old` is the value the write replaced
+    Write { address: usize, old: X, value: X }
+}
+
+/// Wraps a memory to record every access that succeeds, in order
+pub struct Recorder<'a, X: Xlen, M: Memory<X>> {
+    memory: &'a mut M,
+    pub accesses: Vec<Access<X>>
+}
+
+impl<'a, X: Xlen, M: Memory<X>> Recorder<'a, X, M> {
+    pub fn new(memory: &'a mut M) -> Recorder<'a, X, M> {
+        Recorder { memory, accesses: Vec::new() }
+    }
+}
+
+impl<X: Xlen, M: Memory<X>> Memory<X> for Recorder<'_, X, M> {
+    fn load(&mut self, address: usize) -> Result<X, Exception> {
+        let value = self.memory.load(address)?;
+        self.accesses.push(Access::Read { address, value });
+        Ok(value)
+    }
+
+    fn store(&mut self, address: usize, value: X) -> Result<(), Exception> {
+        let old = self.memory.load(address).map_err(|_| Exception::StoreAccessFault(address))?;
+        self.memory.store(address, value)?;
+        self.accesses.push(Access::Write { address, old, value });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{Access, Memory, Recorder};
+    use crate::trap::Exception;
+
+    #[test]
+    fn test_array_bounds() {
+        let mut memory = [0u32; 4];
+
+        assert_eq!(memory.store(3, 7), Ok(()));
+        assert_eq!(memory.load(3), Ok(7));
+        assert_eq!(memory.load(4), Err(Exception::LoadAccessFault(4)));
+        assert_eq!(memory.store(4, 7), Err(Exception::StoreAccessFault(4)));
+    }
+
+    #[test]
+    fn test_recorder() {
+        let mut memory = [0u32; 4];
+        let mut recorder = Recorder::new(&mut memory);
+
+        recorder.store(1, 5).unwrap();
+        recorder.load(1).unwrap();
+        recorder.load(9).unwrap_err();
+        recorder.store(9, 5).unwrap_err();
+
+        assert_eq!(recorder.accesses, vec![Access::Write { address: 1, old: 0, value: 5 }, Access::Read { address: 1, value: 5 }]);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::csr::{self, CsrFile, Privilege};
+use crate::memory::Memory;
+use crate::tlb::Tlb;
+use crate::trap::Exception;
+use crate::xlen::Xlen;
+
+/// Page table entry fields
+const PTE_V: u64 = 1 << 0;
+const PTE_R: u64 = 1 << 1;
+const PTE_W: u64 = 1 << 2;
+const PTE_X: u64 = 1 << 3;
+const PTE_U: u64 = 1 << 4;
+const PTE_A: u64 = 1 << 6;
+const PTE_D: u64 = 1 << 7;
+
+/// Memory cells are four bytes wide as far as byte addresses go, so a page of 4 KiB holds 1024 of them
+const CELL_BYTES: u64 = 4;
+const PAGE_CELLS: u64 = 1024;
+
+/// Why the hart accesses memory, which decides the permission a page needs and the exception a fault raises
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessType {
+    Fetch,
+    Load,
+    Store
+}
+
+impl AccessType {
+    fn page_fault(self, address: usize) -> Exception {
+        match self {
+            AccessType::Fetch => Exception::InstructionPageFault(address),
+            AccessType::Load => Exception::LoadPageFault(address),
+            AccessType::Store => Exception::StorePageFault(address)
+        }
+    }
+
+    fn access_fault(self, address: usize) -> Exception {
+        match self {
+            AccessType::Fetch => Exception::InstructionAccessFault(address),
+            AccessType::Load => Exception::LoadAccessFault(address),
+            AccessType::Store => Exception::StoreAccessFault(address)
+        }
+    }
+}
+
+/// Sv32 address translation as the CSRs configured it when an instruction started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Translation {
+    /// The physical page number of the root page table
+    root: u64,
+    /// `satp.ASID`, the address space the TLB caches translations for
+    asid: u16,
+    privilege: Privilege,
+    /// `mstatus.SUM`, supervisor mode may load and store on user pages
+    sum: bool,
+    /// `mstatus.MXR`, loads may read executable pages
+    mxr: bool
+}
+
+impl Translation {
+    /// The translation of supervisor and user mode addresses once `satp` selects Sv32 on RV32.
+    /// `None` if addresses are physical, which they always are in machine mode
+    pub fn of(csr: &CsrFile) -> Option<Translation> {
+        let satp = csr.get(csr::SATP);
+        if csr.xlen() != 32 || satp & (1 << 31) == 0 || csr.privilege() == Privilege::Machine {
+            return None;
+        }
+        let mstatus = csr.get(csr::MSTATUS);
+        Some(Translation {
+            root: satp & 0x3fffff,
+            asid: ((satp >> 22) & 0x1ff) as u16,
+            privilege: csr.privilege(),
+            sum: mstatus & csr::MSTATUS_SUM as u64 != 0,
+            mxr: mstatus & csr::MSTATUS_MXR as u64 != 0
+        })
+    }
+
+    /// Translates an address through the TLB if there is one, walking the page tables in `memory` when it
+    /// misses. Addresses are in the units of the access: bytes for fetches and cells for loads and stores,
+    /// and so is the physical address returned
+    pub fn translate<X: Xlen, M: Memory<X>>(&self, memory: &mut M, tlb: Option<&mut Tlb>, address: usize, access: AccessType) -> Result<usize, Exception> {
+        let scale = if access == AccessType::Fetch { 1 } else { CELL_BYTES };
+        let virtual_address = address as u64 * scale;
+        if virtual_address > u32::MAX as u64 {
+            return Err(access.page_fault(address));
+        }
+
+        let updated = PTE_A | if access == AccessType::Store { PTE_D } else { 0 };
+        let (pte, level) = match tlb {
+            Some(tlb) => match tlb.lookup(virtual_address, self.asid) {
+                // Entries that would fault or need their accessed or dirty bit set are walked again
+                Some((pte, level)) if self.permits(pte, access) && pte & updated == updated => (pte, level),
+                _ => {
+                    let (pte, level) = self.walk(memory, virtual_address, address, access)?;
+                    tlb.insert(virtual_address, self.asid, pte, level);
+                    (pte, level)
+                }
+            },
+            None => self.walk(memory, virtual_address, address, access)?
+        };
+        let offset_bits = 12 + 10 * level;
+        let physical_address = ((pte >> 10) >> (10 * level) << offset_bits) | (virtual_address & ((1 << offset_bits) - 1));
+        Ok((physical_address / scale) as usize)
+    }
+
+    /// Walks the page tables to the leaf entry translating a virtual byte address, setting its accessed
+    /// and dirty bits. Returns the entry as updated and its level, 1 for a superpage and 0 for a page
+    fn walk<X: Xlen, M: Memory<X>>(&self, memory: &mut M, virtual_address: u64, address: usize, access: AccessType) -> Result<(u64, usize), Exception> {
+        let vpn = [(virtual_address >> 12) & 0x3ff, virtual_address >> 22];
+        let mut table = self.root;
+        for level in [1, 0] {
+            let entry = (table * PAGE_CELLS + vpn[level]) as usize;
+            let pte = memory.load(entry).map_err(|_| access.access_fault(address))?.as_u64();
+            if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) {
+                return Err(access.page_fault(address));
+            }
+            let ppn = pte >> 10;
+            if pte & (PTE_R | PTE_X) == 0 {
+                table = ppn;
+                continue;
+            }
+
+            // A superpage has to be aligned to its 4 MiB
+            if !self.permits(pte, access) || (level == 1 && ppn & 0x3ff != 0) {
+                return Err(access.page_fault(address));
+            }
+            let updated = pte | PTE_A | if access == AccessType::Store { PTE_D } else { 0 };
+            if updated != pte {
+                memory.store(entry, X::from_u64(updated)).map_err(|_| access.access_fault(address))?;
+            }
+            return Ok((updated, level));
+        }
+        // The last level pointing to yet another table
+        Err(access.page_fault(address))
+    }
+
+    /// Whether a leaf entry lets the privilege level make the access
+    fn permits(&self, pte: u64, access: AccessType) -> bool {
+        let user_page = pte & PTE_U != 0;
+        let privileged = match self.privilege {
+            Privilege::User => user_page,
+            // Supervisor mode never executes user code
+            _ => !user_page || (self.sum && access != AccessType::Fetch)
+        };
+        privileged && match access {
+            AccessType::Fetch => pte & PTE_X != 0,
+            AccessType::Load => pte & PTE_R != 0 || (self.mxr && pte & PTE_X != 0),
+            AccessType::Store => pte & PTE_W != 0
+        }
+    }
+}
+
+/// Translates the addresses of an instruction's loads and stores before they reach memory,
+/// passing them through unchanged without a translation
+pub struct Mmu<'a, X: Xlen, M: Memory<X>> {
+    memory: &'a mut M,
+    translation: Option<Translation>,
+    tlb: Option<&'a mut Tlb>,
+    xlen: std::marker::PhantomData<X>
+}
+
+impl<'a, X: Xlen, M: Memory<X>> Mmu<'a, X, M> {
+    pub fn new(memory: &'a mut M, translation: Option<Translation>, tlb: Option<&'a mut Tlb>) -> Mmu<'a, X, M> {
+        Mmu { memory, translation, tlb, xlen: std::marker::PhantomData }
+    }
+}
+
+impl<X: Xlen, M: Memory<X>> Memory<X> for Mmu<'_, X, M> {
+    fn load(&mut self, address: usize) -> Result<X, Exception> {
+        let physical = match self.translation {
+            Some(translation) => translation.translate(self.memory, self.tlb.as_deref_mut(), address, AccessType::Load)?,
+            None => address
+        };
+        self.memory.load(physical).map_err(|_| Exception::LoadAccessFault(address))
+    }
+
+    fn store(&mut self, address: usize, value: X) -> Result<(), Exception> {
+        let physical = match self.translation {
+            Some(translation) => translation.translate(self.memory, self.tlb.as_deref_mut(), address, AccessType::Store)?,
+            None => address
+        };
+        self.memory.store(physical, value).map_err(|_| Exception::StoreAccessFault(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csr::Privilege;
+    use crate::memory::Memory;
+    use crate::mmu::{AccessType, Mmu, Translation, PTE_A, PTE_D, PTE_R, PTE_U, PTE_V, PTE_W, PTE_X};
+    use crate::tlb::{Tlb, TlbConfig, TlbStats};
+    use crate::trap::Exception;
+
+    /// A root table in page 1 mapping the 4 MiB at 0x00400000 to themselves, and 0x00000000 to a table in page 3
+    /// that maps its first page to page 4 and its second to page 5
+    fn memory() -> Vec<u32> {
+        let mut memory = vec![0u32; 6 * 1024];
+        memory[1024] = 3 << 10 | PTE_V as u32;
+        memory[1024 + 1] = 0x400 << 10 | (PTE_V | PTE_R | PTE_W) as u32;
+        memory[3 * 1024] = 4 << 10 | (PTE_V | PTE_R | PTE_X | PTE_U) as u32;
+        memory[3 * 1024 + 1] = 5 << 10 | (PTE_V | PTE_R | PTE_W) as u32;
+        memory
+    }
+
+    fn translation(privilege: Privilege) -> Translation {
+        Translation { root: 1, asid: 0, privilege, sum: false, mxr: false }
+    }
+
+    #[test]
+    fn test_translate_pages() {
+        let mut memory = memory();
+        let user = translation(Privilege::User);
+
+        assert_eq!(user.translate(&mut memory, None, 0x10, AccessType::Fetch), Ok(4 * 4096 + 0x10));
+        assert_eq!(user.translate(&mut memory, None, 5, AccessType::Load), Ok(4 * 1024 + 5));
+        assert_eq!(memory[3 * 1024] as u64 & (PTE_A | PTE_D), PTE_A);
+        assert_eq!(user.translate(&mut memory, None, 5, AccessType::Store), Err(Exception::StorePageFault(5)));
+        assert_eq!(user.translate(&mut memory, None, 1024, AccessType::Load), Err(Exception::LoadPageFault(1024)));
+        assert_eq!(user.translate(&mut memory, None, 0x800000, AccessType::Fetch), Err(Exception::InstructionPageFault(0x800000)));
+    }
+
+    #[test]
+    fn test_translate_superpage() {
+        let mut memory = memory();
+        let supervisor = translation(Privilege::Supervisor);
+
+        assert_eq!(supervisor.translate(&mut memory, None, 0x100000 + 7, AccessType::Store), Ok(0x100000 + 7));
+        assert_eq!(memory[1025] as u64 & (PTE_A | PTE_D), PTE_A | PTE_D);
+        memory[1025] = 0x401 << 10 | (PTE_V | PTE_R | PTE_W) as u32;
+        assert_eq!(supervisor.translate(&mut memory, None, 0x100000, AccessType::Load), Err(Exception::LoadPageFault(0x100000)));
+    }
+
+    #[test]
+    fn test_supervisor_user_memory() {
+        let mut memory = memory();
+        let supervisor = translation(Privilege::Supervisor);
+        let sum = Translation { sum: true, ..supervisor };
+        let mxr = Translation { mxr: true, ..supervisor };
+
+        assert_eq!(supervisor.translate(&mut memory, None, 5, AccessType::Load), Err(Exception::LoadPageFault(5)));
+        assert_eq!(sum.translate(&mut memory, None, 5, AccessType::Load), Ok(4 * 1024 + 5));
+        assert_eq!(sum.translate(&mut memory, None, 0, AccessType::Fetch), Err(Exception::InstructionPageFault(0)));
+        assert_eq!(supervisor.translate(&mut memory, None, 4096, AccessType::Fetch), Err(Exception::InstructionPageFault(4096)));
+        memory[3 * 1024 + 1] = 5 << 10 | (PTE_V | PTE_X) as u32;
+        assert_eq!(supervisor.translate(&mut memory, None, 1024, AccessType::Load), Err(Exception::LoadPageFault(1024)));
+        assert_eq!(mxr.translate(&mut memory, None, 1024, AccessType::Load), Ok(5 * 1024));
+    }
+
+    #[test]
+    fn test_tlb() {
+        let mut memory = memory();
+        let mut tlb = Tlb::new(TlbConfig::default()).unwrap();
+        let user = translation(Privilege::User);
+        let supervisor = translation(Privilege::Supervisor);
+
+        assert_eq!(user.translate(&mut memory, Some(&mut tlb), 5, AccessType::Load), Ok(4 * 1024 + 5));
+        // The TLB keeps the translation after the page table changes, until a fence
+        memory[3 * 1024] = 5 << 10 | (PTE_V | PTE_R | PTE_U) as u32;
+        assert_eq!(user.translate(&mut memory, Some(&mut tlb), 6, AccessType::Load), Ok(4 * 1024 + 6));
+        tlb.fence(None, None);
+        assert_eq!(user.translate(&mut memory, Some(&mut tlb), 6, AccessType::Load), Ok(5 * 1024 + 6));
+
+        // A store to a page that was only read walks again to set the dirty bit
+        assert_eq!(supervisor.translate(&mut memory, Some(&mut tlb), 1024, AccessType::Load), Ok(5 * 1024));
+        assert_eq!(memory[3 * 1024 + 1] as u64 & PTE_D, 0);
+        assert_eq!(supervisor.translate(&mut memory, Some(&mut tlb), 1024, AccessType::Store), Ok(5 * 1024));
+        assert_eq!(memory[3 * 1024 + 1] as u64 & PTE_D, PTE_D);
+        assert_eq!(supervisor.translate(&mut memory, Some(&mut tlb), 1025, AccessType::Store), Ok(5 * 1024 + 1));
+        assert_eq!(tlb.stats(), TlbStats { hits: 3, misses: 3, evictions: 0, fences: 1 });
+    }
+
+    #[test]
+    fn test_mmu() {
+        let mut memory = memory();
+        let mut mmu = Mmu::new(&mut memory, Some(translation(Privilege::Supervisor)), None);
+
+        assert_eq!(mmu.store(1024 + 2, 9), Ok(()));
+        assert_eq!(mmu.load(1024 + 2), Ok(9));
+        assert_eq!(mmu.load(3 * 1024), Err(Exception::LoadPageFault(3 * 1024)));
+        let mut bare = Mmu::new(&mut memory, None, None);
+        assert_eq!(bare.load(5 * 1024 + 2), Ok(9));
+        assert_eq!(bare.store(6 * 1024, 9), Err(Exception::StoreAccessFault(6 * 1024)));
+    }
+}
@@ -1,86 +1,937 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::ops::Range;
-use crate::assembly_compiler;
-use crate::instruction::{Instruction, JALR};
+use crate::{assembly_compiler, disassembler};
+use crate::assembly_compiler::SourceMap;
+use crate::clint::{Bus, Clint, TimeSource};
+use crate::csr::{HpmEvent, Privilege};
+use crate::debug::{Breakpoint, Location, WatchHit, Watchpoint};
+use crate::instruction::{Instruction, BRANCH, JAL, JALR, LOAD, SFENCE_VMA, STORE, SYSTEM};
+use crate::isa::{Isa, IsaError};
+use crate::memory::{Access, Memory, Recorder};
+use crate::mmu::{AccessType, Mmu, Translation};
 use crate::register::Register;
+use crate::snapshot::Snapshot;
+use crate::tlb::{Tlb, TlbConfig, TlbConfigError, TlbStats};
+use crate::trace::{NoopTracer, TraceEvent, Tracer};
+use crate::trap::{Exception, Interrupt, Trap};
+use crate::xlen::Xlen;
 
+/// The number of memory cells a processor starts with
+const CELLS: usize = 1024;
 const SP: usize = 2;
+/// Where `load_into_memory` places its data
+const DATA: usize = 512;
+const A0: usize = 10;
+const A7: usize = 17;
 
-pub struct Processor {
-    register: Register,
-    memory: [u32; 1024],
-    instruction_index: (usize, usize)
+/// The `exit` system call number, passed in `a7` with the exit code in `a0`
+const SYS_EXIT: u64 = 93;
+const ECALL: u32 = 0x00000073;
+const EBREAK: u32 = 0x00100073;
+
+/// A single hart with its memory, `X` being the register width: `u32` for RV32 and `u64` for RV64.
+pub struct Processor<X: Xlen = u32> {
+    register: Register<X>,
+    memory: Vec<X>,
+    instruction_index: (usize, usize),
+    conditions: HaltConditions,
+    halt: Option<HaltReason>,
+    tracer: Box<dyn Tracer>,
+    source: SourceMap,
+    breakpoints: BTreeMap<usize, Breakpoint<X>>,
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    /// The id the next breakpoint or watchpoint gets, they are numbered together like in gdb
+    next_id: usize,
+    /// The pc of the breakpoint the processor stopped at, which the next step executes instead of stopping again
+    resume: Option<usize>,
+    /// What the last instructions retired changed, the most recent last
+    history: VecDeque<Undo<X>>,
+    history_limit: usize,
+    /// The privilege level, CSRs and CLINT before the instruction being stepped, while history is kept
+    context_before: Option<Context>,
+    /// Caches Sv32 translations if the processor models a TLB
+    tlb: Option<Tlb>,
+    clint: Clint
 }
 
 impl Processor {
+    /// Creates an RV32 processor
     pub fn new() -> Processor {
+        Processor::with_xlen()
+    }
+
+    /// Creates an RV32E processor, which only has the registers x0-x15
+    pub fn embedded() -> Processor {
+        Processor::with_register(Register::with_isa(Isa::all_embedded()))
+    }
+}
+
+impl<X: Xlen> Processor<X> {
+    /// Creates a processor whose XLEN is given by `X`, e.g. `Processor::<u64>::with_xlen()` for RV64
+    pub fn with_xlen() -> Processor<X> {
+        Processor::with_register(Register::new())
+    }
+
+    /// Creates a processor implementing only the extensions named by an ISA string like `rv32i_zicsr_zba`
+    pub fn from_isa(isa: &str) -> Result<Processor<X>, IsaError> {
+        let isa = Isa::parse(isa)?;
+        if isa.xlen() != X::BITS {
+            return Err(IsaError::XlenMismatch { expected: X::BITS, found: isa.xlen() });
+        }
+        Ok(Processor::with_register(Register::with_isa(isa)))
+    }
+
+    fn with_register(register: Register<X>) -> Processor<X> {
         let mut proc = Processor {
-            register: Register::new(),
-            memory: [0u32; 1024],
-            instruction_index: (0, 0)
+            register,
+            memory: vec![X::default(); CELLS],
+            instruction_index: (0, 0),
+            conditions: HaltConditions::default(),
+            halt: None,
+            tracer: Box::new(NoopTracer),
+            source: SourceMap::default(),
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            next_id: 1,
+            resume: None,
+            history: VecDeque::new(),
+            context_before: None,
+            history_limit: 0,
+            tlb: None,
+            clint: Clint::default()
         };
 
         // Initialize stack pointer to memory address 256
-        proc.set_register_value(SP, 256);
+        proc.register.put(SP, X::from_u32(256));
         proc
     }
 
-    pub fn load_instructions(&mut self, file_path: &str) -> (usize, usize) {
-        let file = File::open(file_path).expect("no such file");
+    /// Assembles a program and places it at the start of memory, returning the range of cells it occupies
+    pub fn load_instructions(&mut self, file_path: &str) -> Result<(usize, usize), LoadError> {
+        let file = File::open(file_path)
+            .map_err(|error| LoadError::Io(format!("{}: {}", file_path, error)))?;
         let buf = BufReader::new(file);
 
         let instructions: Vec<String> = buf.lines()
-            .flatten()
-            .collect();
+            .collect::<Result<_, _>>()
+            .map_err(|error| LoadError::Io(format!("{}: {}", file_path, error)))?;
 
-        let instructions: Vec<u32> = assembly_compiler::compile(instructions);
+        let source = assembly_compiler::source_map(&instructions).map_err(LoadError::Assembly)?;
+        let instructions: Vec<X> = assembly_compiler::compile(instructions, self.register.isa(), self.tracer.as_mut())
+            .map_err(LoadError::Assembly)?
+            .into_iter()
+            .map(X::from_u32)
+            .collect();
 
-        self.memory[0..instructions.len()]
+        let size = self.memory.len();
+        self.memory.get_mut(0..instructions.len())
+            .ok_or(LoadError::Memory(MemoryError { range: 0..instructions.len(), size }))?
             .copy_from_slice(instructions.as_slice());
 
         self.instruction_index = (0, instructions.len());
-        self.instruction_index
+        self.source = source;
+        self.halt = None;
+        self.resume = None;
+        self.history.clear();
+        self.flush_tlb();
+        Ok(self.instruction_index)
+    }
+
+    /// Models a TLB of the given shape in front of Sv32 translation, replacing any earlier one along with
+    /// its statistics. Without one, the default, every access walks the page tables
+    pub fn set_tlb(&mut self, config: Option<TlbConfig>) -> Result<(), TlbConfigError> {
+        self.tlb = config.map(Tlb::new).transpose()?;
+        Ok(())
     }
 
-    /// Copies the slice into memory
-    pub fn load_into_memory(&mut self, src: &[u32]) -> usize {
-        let len = src.len();
-        self.memory[512..512 + len].copy_from_slice(src);
-        512
+    /// What the TLB did since it was set or its statistics were last reset, `None` without a TLB
+    pub fn tlb_stats(&self) -> Option<TlbStats> {
+        self.tlb.as_ref().map(Tlb::stats)
     }
 
-    pub fn set_register_value(&mut self, index: usize, value: u32) {
+    pub fn reset_tlb_stats(&mut self) {
+        if let Some(tlb) = &mut self.tlb {
+            tlb.reset_stats();
+        }
+    }
+
+    /// The timer and software interrupt device
+    pub fn clint(&self) -> &Clint {
+        &self.clint
+    }
+
+    /// Chooses what makes `mtime` advance, one tick per instruction retired by default
+    pub fn set_time_source(&mut self, source: TimeSource) {
+        self.clint.set_source(source);
+    }
+
+    /// Moves `mtime` forward, e.g. to let a timer interrupt fire with `TimeSource::Manual`
+    pub fn advance_time(&mut self, ticks: u64) {
+        self.clint.advance(ticks);
+    }
+
+    /// Shows `mtime` in the `time` CSR and raises or clears the interrupts whose CLINT line changed
+    fn update_clint(&mut self) {
+        let csr = self.register.csr_mut();
+        csr.set_time(self.clint.mtime());
+        for (interrupt, pending) in self.clint.edges() {
+            csr.set_pending(interrupt, pending);
+        }
+    }
+
+    fn flush_tlb(&mut self) {
+        if let Some(tlb) = &mut self.tlb {
+            tlb.flush();
+        }
+    }
+
+    /// The number of cells in memory
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Grows or shrinks memory to `cells` cells, zeroing any new ones. Memory starts out with 1024 cells,
+    /// a single page, so page tables need more
+    pub fn set_memory_size(&mut self, cells: usize) {
+        self.memory.resize(cells, X::default());
+    }
+
+    /// Copies the slice into memory, returning the cell it starts at
+    pub fn load_into_memory(&mut self, src: &[X]) -> Result<usize, MemoryError> {
+        let range = DATA..DATA + src.len();
+        let size = self.memory.len();
+        self.memory.get_mut(range.clone())
+            .ok_or(MemoryError { range, size })?
+            .copy_from_slice(src);
+        Ok(DATA)
+    }
+
+    /// Copies the slice into memory starting at the cell `address`
+    pub fn store_in_memory(&mut self, address: usize, src: &[X]) -> Result<(), MemoryError> {
+        let range = address..address + src.len();
+        let size = self.memory.len();
+        self.memory.get_mut(range.clone())
+            .ok_or(MemoryError { range, size })?
+            .copy_from_slice(src);
+        Ok(())
+    }
+
+    /// Reads memory as bytes, the way a debugger shows it: byte `address` is byte `address % 4`
+    /// of the low 32 bits of cell `address / 4`. Instructions appear at their pc that way.
+    pub fn read_bytes(&self, address: usize, length: usize) -> Result<Vec<u8>, MemoryError> {
+        let cells = self.get_copy_of_memory(address / 4..address.saturating_add(length).div_ceil(4))?;
+        Ok((address..address + length)
+            .map(|byte| (cells[byte / 4 - address / 4].as_u64() >> (byte % 4 * 8)) as u8)
+            .collect())
+    }
+
+    /// Writes memory as bytes, addressed like `read_bytes`
+    pub fn write_bytes(&mut self, address: usize, data: &[u8]) -> Result<(), MemoryError> {
+        let start = address / 4;
+        let mut cells = self.get_copy_of_memory(start..address.saturating_add(data.len()).div_ceil(4))?;
+        for (byte, &value) in (address..).zip(data) {
+            let cell = &mut cells[byte / 4 - start];
+            let shift = byte % 4 * 8;
+            *cell = X::from_u64(cell.as_u64() & !(0xff << shift) | (value as u64) << shift);
+        }
+        self.store_in_memory(start, &cells)
+    }
+
+    /// Raises or clears an interrupt, as the device behind it would. It is taken before the next instruction
+    /// once `mstatus.MIE` and its bit in `mie` enable it
+    pub fn set_interrupt_pending(&mut self, interrupt: Interrupt, pending: bool) {
+        self.register.csr_mut().set_pending(interrupt, pending);
+    }
+
+    /// Writes an integer register, which has to exist in the processor's ISA. Writes to x0 are discarded
+    pub fn set_register_value(&mut self, index: usize, value: X) -> Result<(), RegisterError> {
+        self.check_register(index)?;
         self.register.put(index, value);
+        Ok(())
+    }
+
+    /// Sends the events of loading and running programs to `tracer` instead of discarding them
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer>) {
+        self.tracer = tracer;
+    }
+
+    /// Chooses when the program stops, on top of returning to a zero `ra` or leaving the loaded instructions
+    pub fn set_halt_conditions(&mut self, conditions: HaltConditions) {
+        self.conditions = conditions;
+    }
+
+    /// The address of a label of the loaded program
+    pub fn label(&self, name: &str) -> Option<usize> {
+        self.source.labels.get(name).copied()
+    }
+
+    /// Every label of the loaded program with its address
+    pub fn labels(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.source.labels.iter().map(|(name, &address)| (name.as_str(), address))
+    }
+
+    /// The line of the loaded program's source the instruction at `address` comes from, counting from one
+    pub fn source_line(&self, address: usize) -> Option<usize> {
+        self.source.lines.get(address / 4).map(|index| index + 1)
+    }
+
+    /// The address of the first instruction on a line of the loaded program's source or, for lines
+    /// without one, on the next line that has one
+    pub fn line_address(&self, line: usize) -> Option<usize> {
+        self.source.lines.iter()
+            .position(|&index| index + 1 >= line)
+            .map(|cell| cell * 4)
+    }
+
+    /// A label of the loaded program at `address`, the first in alphabetical order if there are several
+    pub fn label_at(&self, address: usize) -> Option<&str> {
+        self.source.labels.iter()
+            .filter(|&(_, &labelled)| labelled == address)
+            .map(|(name, _)| name.as_str())
+            .min()
+    }
+
+    /// Stops every run and step before the instruction at the breakpoint's address, returning its id
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint<X>) -> usize {
+        let id = self.take_id();
+        self.breakpoints.insert(id, breakpoint);
+        id
+    }
+
+    /// Stops every run and step after an instruction that made an access the watchpoint watches, returning its id
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.take_id();
+        self.watchpoints.insert(id, watchpoint);
+        id
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint<X>)> {
+        self.breakpoints.iter().map(|(&id, breakpoint)| (id, breakpoint))
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.watchpoints.iter().map(|(&id, watchpoint)| (id, watchpoint))
+    }
+
+    /// Removes a breakpoint, returning whether there was one with that id
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.breakpoints.remove(&id).is_some()
+    }
+
+    /// Removes a watchpoint, returning whether there was one with that id
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watchpoints.remove(&id).is_some()
+    }
+
+    fn take_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// Runs the program until it halts and reports why, or the exception that stopped it without a trap handler
+    pub fn execute_instructions(&mut self) -> Result<HaltReason, ExecError> {
+        loop {
+            if let Some(reason) = self.step()?.halt {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Executes a single instruction, or reports why the processor is halted without executing anything.
+    /// Exceptions and pending interrupts move the pc to the trap handler once the program set `mtvec`.
+    /// Until then an exception the instruction raises is returned and leaves the pc at the instruction,
+    /// so stepping again raises it again.
+    ///
+    /// Once `satp` enables Sv32 translation, the pc and the addresses of loads and stores in supervisor
+    /// and user mode are virtual. A virtual pc outside of the loaded instructions does not end the program
+    pub fn step(&mut self) -> Result<StepOutcome, ExecError> {
+        let pc = self.register.pc();
+        if let Some(reason) = self.halt {
+            return Ok(StepOutcome::halted(pc, None, reason));
+        }
+        let translation = Translation::of(self.register.csr());
+        if translation.is_none() && !(self.instruction_index.0..self.instruction_index.1).contains(&(pc / 4)) {
+            return Ok(self.stop(pc, None, HaltReason::EndOfProgram));
+        }
+        if self.conditions.addresses.contains(&pc) {
+            return Ok(self.stop(pc, None, HaltReason::Reached(pc)));
+        }
+        if self.resume.take() != Some(pc) {
+            if let Some(id) = self.breakpoint_at(pc) {
+                self.resume = Some(pc);
+                return Ok(StepOutcome::halted(pc, None, HaltReason::Breakpoint(id)));
+            }
+        }
+
+        if self.history_limit > 0 {
+            let csr = self.register.csr();
+            self.context_before = Some(Context { privilege: csr.privilege(), csrs: csr.values(), clint: self.clint.state() });
+        }
+        self.update_clint();
+        if let Some(interrupt) = self.register.csr().pending_interrupt() {
+            if self.tracer.enabled() {
+                self.tracer.trace(&TraceEvent::Interrupt { pc, interrupt });
+            }
+            return Ok(self.take_trap(Trap::Interrupt(interrupt), Undo::at(pc), None, 0));
+        }
+
+        // The page table walk of the fetch counts as the instruction's first accesses
+        let mut walk = Recorder::new(&mut self.memory);
+        let physical = match translation.map(|translation| translation.translate(&mut walk, self.tlb.as_mut(), pc, AccessType::Fetch)) {
+            Some(Ok(physical)) => physical,
+            Some(Err(exception)) => {
+                let undo = Undo { pc, writes: Vec::new(), accesses: walk.accesses, context: None };
+                return self.exception(exception, undo, 0);
+            },
+            None => pc
+        };
+        let walk = walk.accesses;
+        let binary = match self.memory.get(physical / 4) {
+            Some(_) if !pc.is_multiple_of(4) => return self.exception(Exception::InstructionAddressMisaligned(pc), Undo::at(pc), 0),
+            Some(word) => word.as_u32(),
+            None => return self.exception(Exception::InstructionAccessFault(pc), Undo::at(pc), 0)
+        };
+        let tracing = self.tracer.enabled();
+        let privilege = self.register.csr().privilege();
+        if tracing {
+            self.tracer.trace(&TraceEvent::Fetch { pc, word: binary });
+        }
+        let instruction = match Instruction::from(binary) {
+            Some(instruction) => instruction,
+            None => return self.exception(Exception::IllegalInstruction, Undo::at(pc), binary)
+        };
+        if tracing {
+            self.tracer.trace(&TraceEvent::Decode { pc, disassembly: &disassembler::disassemble(binary) });
+        }
+
+        if let Instruction::IFormatInstruction { opcode, rd, rs1, ..} = instruction {
+            if opcode == JALR && rd == 0 && rs1 == 1 && self.register.get(rs1) == X::default() {
+                let code = self.register.get(A0).as_u32() as i32;
+                return Ok(self.stop(pc, Some(binary), HaltReason::Returned(code)));
+            }
+        }
+        if binary == ECALL && self.conditions.ecall_exit && self.register.get(A7).as_u64() == SYS_EXIT {
+            let code = self.register.get(A0).as_u32() as i32;
+            return Ok(self.stop(pc, Some(binary), HaltReason::Exit(code)));
+        }
+        if binary == EBREAK && self.conditions.ebreak {
+            return Ok(self.stop(pc, Some(binary), HaltReason::Ebreak));
+        }
+        let store = matches!(instruction, Instruction::SFormatInstruction { .. });
+        let fence = match instruction {
+            Instruction::IFormatInstruction { opcode: SYSTEM, funct3: 0, imm, rs1, .. } if imm & !0b11111 == SFENCE_VMA => {
+                // x0 stands for every address and every address space
+                let rs2 = imm as usize & 0b11111;
+                let address = (rs1 != 0).then(|| self.register.get(rs1).as_u64());
+                Some((address, (rs2 != 0).then(|| (self.register.get(rs2).as_u64() & 0x1ff) as u16)))
+            },
+            _ => None
+        };
+
+        // Branches write no registers, so this is what the branch is about to do
+        let taken = instruction.branch_taken(&self.register).unwrap_or(false);
+        let watching = !self.watchpoints.is_empty();
+        let finishing = store && self.conditions.test_finisher.is_some();
+        let (result, writes, accesses) = if tracing || watching || finishing || self.history_limit > 0 {
+            self.register.record_writes();
+            let mut bus = Bus::new(&mut self.memory, &mut self.clint);
+            let mut recorder = Recorder::new(&mut bus);
+            recorder.accesses = walk;
+            let result = instruction.execute(&mut self.register, &mut Mmu::new(&mut recorder, translation, self.tlb.as_mut()));
+            let accesses = recorder.accesses;
+            (result, self.register.take_writes(), accesses)
+        } else {
+            let mut bus = Bus::new(&mut self.memory, &mut self.clint);
+            (instruction.execute(&mut self.register, &mut Mmu::new(&mut bus, translation, self.tlb.as_mut())), Vec::new(), Vec::new())
+        };
+        if tracing {
+            for &(index, _, value) in &writes {
+                self.tracer.trace(&TraceEvent::RegisterWrite { index, value: value.as_u64() });
+            }
+            for &access in &accesses {
+                self.tracer.trace(&match access {
+                    Access::Read { address, value } => TraceEvent::MemoryRead { address, value: value.as_u64() },
+                    Access::Write { address, value, .. } => TraceEvent::MemoryWrite { address, value: value.as_u64() }
+                });
+            }
+        }
+        if let Err(exception) = result {
+            return self.exception(exception, Undo { pc, writes, accesses, context: None }, binary);
+        }
+        if let (Some(tlb), Some((address, asid))) = (&mut self.tlb, fence) {
+            tlb.fence(address, asid);
+        }
+        let next_pc = self.register.pc();
+        self.register.csr_mut().retire(hpm_event(binary, taken));
+        self.clint.retire();
+        if tracing {
+            self.tracer.trace(&TraceEvent::Retire { pc, word: binary, privilege });
+        }
+        let mut outcome = StepOutcome { pc, next_pc, instruction: Some(binary), retired: true, trap: None, halt: None };
+        // The store is the last write, after those of the page table walk. With Sv32 its address is physical
+        let stored = match accesses.last() {
+            Some(&Access::Write { address, .. }) if store => Some(address),
+            _ => None
+        };
+        if let Some(finisher) = self.conditions.test_finisher.filter(|&finisher| stored == Some(finisher)) {
+            // Like riscv-tests' tohost, an odd value ends the test with the rest of the value as its exit code
+            let value = self.memory.get(finisher).map_or(0, |value| value.as_u32());
+            if value & 1 == 1 {
+                self.halt = Some(HaltReason::TestFinisher((value >> 1) as i32));
+                outcome.halt = self.halt;
+            }
+        }
+        if outcome.halt.is_none() && watching {
+            outcome.halt = self.watch_hit(&writes, &accesses).map(HaltReason::Watchpoint);
+        }
+        self.remember(Undo { pc, writes, accesses, context: None });
+        Ok(outcome)
+    }
+
+    /// Enters the trap handler for an exception the instruction at `undo.pc` raised, if the program installed one.
+    /// Otherwise the exception is reported to the host
+    fn exception(&mut self, exception: Exception, undo: Undo<X>, binary: u32) -> Result<StepOutcome, ExecError> {
+        if !self.register.csr().has_trap_handler() {
+            return Err(self.raise(exception, undo.pc, binary));
+        }
+        let tval = ExecError { pc: undo.pc, instruction: binary, exception }.tval();
+        // An ecall never retires, it counts as it enters the handler
+        if let Exception::EnvironmentCall(_) = exception {
+            self.register.csr_mut().count(HpmEvent::Ecall);
+        }
+        if self.tracer.enabled() {
+            self.tracer.trace(&TraceEvent::Trap { pc: undo.pc, exception, tval });
+        }
+        Ok(self.take_trap(Trap::Exception(exception), undo, Some(binary), tval))
     }
 
-    pub fn execute_instructions(&mut self) {
-        println!("--------------------------");
-        while self.register.pc() / 4 < self.instruction_index.1 {
-            let binary = self.memory[self.register.pc() / 4];
-            println!("[executing] Input: {:0>32b}", binary);
-            let instruction = Instruction::from(binary).unwrap();
-            println!("[executing] Instruction: {:?}", instruction);
+    /// Moves the pc to the trap handler instead of retiring the instruction at `undo.pc`
+    fn take_trap(&mut self, trap: Trap, undo: Undo<X>, instruction: Option<u32>, tval: u64) -> StepOutcome {
+        let pc = undo.pc;
+        let handler = self.register.csr_mut().trap(trap, pc as u64, tval) as usize;
+        self.register.update_pc(handler);
+        self.remember(undo);
+        StepOutcome { pc, next_pc: handler, instruction, retired: false, trap: Some(trap), halt: None }
+    }
 
-            if let Instruction::IFormatInstruction { opcode, rd, rs1, ..} = instruction {
-                if opcode == JALR && rd == 0 && rs1 == 1 && self.register.get(rs1) == 0 {
-                    break;
-                }
+    fn remember(&mut self, mut undo: Undo<X>) {
+        undo.context = self.context_before.take();
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
             }
+            self.history.push_back(undo);
+        }
+    }
 
-            self.register.update_pc(self.register.pc() + 4);
+    /// Executes at most `limit` instructions and reports why execution stopped,
+    /// which is `HaltReason::InstructionLimit` if the program was still running
+    pub fn run(&mut self, limit: usize) -> Result<HaltReason, ExecError> {
+        for _ in 0..limit {
+            if let Some(reason) = self.step()?.halt {
+                return Ok(reason);
+            }
+        }
+        Ok(HaltReason::InstructionLimit)
+    }
 
-            instruction.execute(&mut self.register, &mut self.memory);
-            println!("[executing] Register: {:?}", self.register);
-            println!("--------------------------");
+    /// Steps until `predicate` holds for an outcome or the processor halts, returning that last outcome
+    pub fn run_until<F: FnMut(&StepOutcome) -> bool>(&mut self, mut predicate: F) -> Result<StepOutcome, ExecError> {
+        loop {
+            let outcome = self.step()?;
+            if outcome.halt.is_some() || predicate(&outcome) {
+                return Ok(outcome);
+            }
         }
     }
 
-    pub fn get_copy_of_memory(&mut self, range: Range<usize>) -> Vec<u32> {
-        self.memory[range].to_owned()
+    /// Counts a hit for every breakpoint the processor reached at `pc`, returning the first that stops it
+    fn breakpoint_at(&mut self, pc: usize) -> Option<usize> {
+        let reached: Vec<usize> = self.breakpoints.iter()
+            .filter(|(_, breakpoint)| breakpoint.reached(self, pc))
+            .map(|(&id, _)| id)
+            .collect();
+        let mut stop = None;
+        for id in reached {
+            if self.breakpoints.get_mut(&id).is_some_and(|breakpoint| breakpoint.hit()) {
+                stop = stop.or(Some(id));
+            }
+        }
+        stop
     }
 
-    pub fn get_registry_value(&self, index: usize) -> u32 {
-        self.register.get(index)
+    /// Captures the state of the machine, which `restore` can return this or another processor to
+    pub fn snapshot(&self) -> Snapshot<X> {
+        let csr = self.register.csr();
+        Snapshot {
+            isa: self.register.isa().clone(),
+            pc: self.register.pc(),
+            program: self.instruction_index,
+            privilege: csr.privilege(),
+            registers: (0..self.register.count()).map(|index| self.register.get(index)).collect(),
+            csrs: csr.values(),
+            vector: self.register.vector().bytes().to_vec(),
+            memory: self.memory.to_vec(),
+            clint: self.clint.state()
+        }
+    }
+
+    /// Puts the machine back into the state of a snapshot, including its ISA. Breakpoints, watchpoints,
+    /// the tracer and labels are kept, the reverse execution history is discarded and a halted processor runs again
+    pub fn restore(&mut self, snapshot: &Snapshot<X>) {
+        let mut register = Register::with_isa(snapshot.isa.clone());
+        for (index, &value) in snapshot.registers.iter().enumerate() {
+            register.put(index, value);
+        }
+        register.update_pc(snapshot.pc);
+        register.csr_mut().restore(snapshot.privilege, &snapshot.csrs);
+        register.vector_mut().bytes_mut().copy_from_slice(&snapshot.vector);
+        self.register = register;
+        self.memory = snapshot.memory.clone();
+        self.clint.restore(snapshot.clint);
+        self.instruction_index = snapshot.program;
+        self.halt = None;
+        self.resume = None;
+        self.history.clear();
+        self.flush_tlb();
+    }
+
+    /// Keeps what the last `limit` instructions changed, so that `step_back` can undo them.
+    /// Zero, the default, records nothing. Changes made through the processor's methods are not recorded,
+    /// and neither are those to vector registers. CSRs and the privilege level are, as are accessed and dirty
+    /// bits set in page tables.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    /// The number of instructions `step_back` can undo
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Undoes the last instruction retired, returning the pc it was at, or `None` if there is no history left
+    pub fn step_back(&mut self) -> Option<usize> {
+        let undo = self.history.pop_back()?;
+        let mut bus = Bus::new(&mut self.memory, &mut self.clint);
+        for access in undo.accesses.iter().rev() {
+            if let Access::Write { address, old, .. } = *access {
+                // Only memory shrinking since could take away the cell
+                let _ = bus.store(address, old);
+            }
+        }
+        for &(index, old, _) in undo.writes.iter().rev() {
+            self.register.put(index, old);
+        }
+        if let Some(context) = &undo.context {
+            self.register.csr_mut().restore(context.privilege, &context.csrs);
+            self.clint.restore(context.clint);
+        }
+        self.register.update_pc(undo.pc);
+        self.halt = None;
+        self.resume = None;
+        // Cached translations may be from the page tables as they are no longer
+        self.flush_tlb();
+        Some(undo.pc)
+    }
+
+    /// Steps back until the pc is at a breakpoint whose condition holds, or to before an instruction
+    /// that hit a watchpoint, without counting hits. Continuing from there does not stop at the same breakpoint
+    pub fn reverse_continue(&mut self) -> HaltReason {
+        while let Some(undo) = self.history.back() {
+            let hit = self.watch_hit(&undo.writes, &undo.accesses);
+            let pc = self.step_back().unwrap_or_default();
+            if let Some(hit) = hit {
+                return HaltReason::Watchpoint(hit);
+            }
+            if let Some((&id, _)) = self.breakpoints.iter().find(|(_, breakpoint)| breakpoint.reached(self, pc)) {
+                self.resume = Some(pc);
+                return HaltReason::Breakpoint(id);
+            }
+        }
+        HaltReason::StartOfHistory
+    }
+
+    /// The first watchpoint an instruction's register writes and memory accesses hit
+    fn watch_hit(&self, writes: &[(usize, X, X)], accesses: &[Access<X>]) -> Option<WatchHit> {
+        self.watchpoints.iter().find_map(|(&id, watchpoint)| match watchpoint {
+            Watchpoint::Register(index) => writes.iter()
+                .find(|(written, _, _)| written == index)
+                .map(|&(_, old, new)| WatchHit { id, location: Location::Register(*index), old: old.as_u64(), new: new.as_u64() }),
+            Watchpoint::Memory { range, access: watched } => accesses.iter().find_map(|&access| match access {
+                Access::Read { address, value } if watched.reads() && range.contains(&address) =>
+                    Some(WatchHit { id, location: Location::Memory(address), old: value.as_u64(), new: value.as_u64() }),
+                Access::Write { address, old, value } if watched.writes() && range.contains(&address) =>
+                    Some(WatchHit { id, location: Location::Memory(address), old: old.as_u64(), new: value.as_u64() }),
+                _ => None
+            })
+        })
+    }
+
+    /// Records an exception in the machine trap CSRs and reports it to the host,
+    /// as there is no trap handler to take it
+    fn raise(&mut self, exception: Exception, pc: usize, binary: u32) -> ExecError {
+        let error = ExecError { pc, instruction: binary, exception };
+        self.register.csr_mut().record_exception(exception, pc as u64, error.tval());
+        if self.tracer.enabled() {
+            self.tracer.trace(&TraceEvent::Trap { pc, exception, tval: error.tval() });
+        }
+        error
+    }
+
+    /// Halts the processor, every further step reports the same reason
+    fn stop(&mut self, pc: usize, instruction: Option<u32>, reason: HaltReason) -> StepOutcome {
+        self.halt = Some(reason);
+        StepOutcome::halted(pc, instruction, reason)
+    }
+
+    pub fn get_copy_of_memory(&self, range: Range<usize>) -> Result<Vec<X>, MemoryError> {
+        self.memory.get(range.clone())
+            .map(|cells| cells.to_owned())
+            .ok_or(MemoryError { range, size: self.memory.len() })
+    }
+
+    /// The address of the next instruction to execute
+    pub fn pc(&self) -> usize {
+        self.register.pc()
+    }
+
+    /// Moves execution to `pc`, as if the program had jumped there
+    pub fn set_pc(&mut self, pc: usize) {
+        self.register.update_pc(pc);
+    }
+
+    /// The privilege level the hart runs in
+    pub fn privilege(&self) -> Privilege {
+        self.register.csr().privilege()
+    }
+
+    /// The number of integer registers, 16 on RV32E and 32 otherwise
+    pub fn register_count(&self) -> usize {
+        self.register.count()
+    }
+
+    pub fn get_registry_value(&self, index: usize) -> Result<X, RegisterError> {
+        self.check_register(index)?;
+        Ok(self.register.get(index))
+    }
+
+    fn check_register(&self, index: usize) -> Result<(), RegisterError> {
+        match index < self.register.count() {
+            true => Ok(()),
+            false => Err(RegisterError { index, count: self.register.count() })
+        }
+    }
+
+    pub fn get_csr_value(&self, address: u32) -> X {
+        X::from_u64(self.register.csr().get(address))
+    }
+}
+
+/// Why a program could not be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The file could not be read
+    Io(String),
+    /// A line that does not assemble to an instruction of the processor's ISA
+    Assembly(String),
+    /// The program does not fit in memory
+    Memory(MemoryError)
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "cannot read {}", error),
+            LoadError::Assembly(error) => write!(f, "{}", error),
+            LoadError::Memory(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// An access by the host to cells outside of the processor's memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryError {
+    pub range: Range<usize>,
+    /// The number of cells in memory
+    pub size: usize
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cells {}..{} are outside of a memory of {} cells", self.range.start, self.range.end, self.size)
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+/// An access by the host to an integer register the processor does not have, like x16 on RV32E
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterError {
+    pub index: usize,
+    /// The number of integer registers
+    pub count: usize
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x{} does not exist, there are {} registers", self.index, self.count)
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+/// An exception raised by the program that no trap handler took
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecError {
+    pub pc: usize,
+    /// The instruction word at `pc`
+    pub instruction: u32,
+    pub exception: Exception
+}
+
+impl ExecError {
+    /// The value reported in `mtval`: the instruction word for illegal instructions,
+    /// the faulting address for misaligned, faulting and page faulting accesses, the pc for breakpoints and zero otherwise
+    pub fn tval(&self) -> u64 {
+        match self.exception {
+            Exception::IllegalInstruction => self.instruction as u64,
+            Exception::InstructionAddressMisaligned(address) | Exception::InstructionAccessFault(address)
+                | Exception::LoadAccessFault(address) | Exception::StoreAccessFault(address)
+                | Exception::InstructionPageFault(address) | Exception::LoadPageFault(address)
+                | Exception::StorePageFault(address) => address as u64,
+            Exception::Breakpoint => self.pc as u64,
+            Exception::EnvironmentCall(_) => 0
+        }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unhandled exception {:?} at pc {}, mtval {:#010x}", self.exception, self.pc, self.tval())
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+/// Optional ways for a program to stop, all of them disabled by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HaltConditions {
+    /// Stop at an `ecall` requesting the `exit` system call, 93 in `a7`, with `a0` as the exit code
+    pub ecall_exit: bool,
+    /// Stop at an `ebreak`
+    pub ebreak: bool,
+    /// Stop when a store writes an odd value to this physical memory cell, with the value shifted right by one as the exit code
+    pub test_finisher: Option<usize>,
+    /// Stop before executing the instruction at any of these addresses
+    pub addresses: Vec<usize>
+}
+
+/// Why a processor stopped executing instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The program returned to the zero address left in `ra`, with `jalr x0, x1, 0`, and `a0` as the exit code
+    Returned(i32),
+    /// The program called `exit`
+    Exit(i32),
+    /// The program executed an `ebreak`
+    Ebreak,
+    /// The program wrote its exit code to the test finisher
+    TestFinisher(i32),
+    /// The pc reached one of the addresses to stop at
+    Reached(usize),
+    /// The pc is outside of the instructions that were loaded
+    EndOfProgram,
+    /// `run` executed as many instructions as it was allowed to
+    InstructionLimit,
+    /// The pc reached the breakpoint with this id, the next step executes the instruction there
+    Breakpoint(usize),
+    /// The instruction that just retired made an access a watchpoint watches,
+    /// or going backwards, the instruction about to be executed
+    Watchpoint(WatchHit),
+    /// `reverse_continue` undid every instruction in the history
+    StartOfHistory
+}
+
+impl HaltReason {
+    /// The exit code a host process should report, 1 when the program did not finish by itself
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HaltReason::Returned(code) | HaltReason::Exit(code) | HaltReason::TestFinisher(code) => *code,
+            HaltReason::Ebreak | HaltReason::Reached(_) | HaltReason::EndOfProgram
+                | HaltReason::Breakpoint(_) | HaltReason::Watchpoint(_) | HaltReason::StartOfHistory => 0,
+            HaltReason::InstructionLimit => 1
+        }
+    }
+}
+
+/// What happened during a single step of a processor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepOutcome {
+    /// The pc before the step
+    pub pc: usize,
+    /// The pc after the step, the same as `pc` unless an instruction retired
+    pub next_pc: usize,
+    /// The instruction word at `pc`, if one was fetched
+    pub instruction: Option<u32>,
+    /// Whether the instruction completed and updated the architectural state
+    pub retired: bool,
+    /// The exception or interrupt that moved the pc to the trap handler instead of retiring the instruction
+    pub trap: Option<Trap>,
+    /// Set when the processor stopped. Every further step reports the same reason,
+    /// except for breakpoints and watchpoints which the next step continues from
+    pub halt: Option<HaltReason>
+}
+
+impl StepOutcome {
+    fn halted(pc: usize, instruction: Option<u32>, reason: HaltReason) -> StepOutcome {
+        StepOutcome { pc, next_pc: pc, instruction, retired: false, trap: None, halt: Some(reason) }
+    }
+}
+
+/// What an instruction changed, with the values it replaced
+struct Undo<X: Xlen> {
+    pc: usize,
+    writes: Vec<(usize, X, X)>,
+    accesses: Vec<Access<X>>,
+    /// What the instruction changed besides registers and memory, traps included
+    context: Option<Context>
+}
+
+/// The privilege level, every CSR value and the CLINT registers, as they were before an instruction
+struct Context {
+    privilege: Privilege,
+    csrs: BTreeMap<u32, u64>,
+    clint: (u64, u64, bool)
+}
+
+impl<X: Xlen> Undo<X> {
+    /// Only moves the pc back, for traps taken before the instruction executed
+    fn at(pc: usize) -> Undo<X> {
+        Undo { pc, writes: Vec::new(), accesses: Vec::new(), context: None }
+    }
+}
+
+/// Classifies a retired instruction for the hpm counters
+fn hpm_event(binary: u32, taken: bool) -> Option<HpmEvent> {
+    match binary & 0b1111111 {
+        LOAD => Some(HpmEvent::Load),
+        STORE => Some(HpmEvent::Store),
+        BRANCH if taken => Some(HpmEvent::TakenBranch),
+        JAL | JALR => Some(HpmEvent::Jump),
+        _ => None
+    }
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -1,17 +1,49 @@
+use crate::csr::CsrFile;
+use crate::isa::Isa;
+use crate::vector::VectorRegisters;
+use crate::xlen::Xlen;
+
 #[derive(Debug)]
-pub struct Register {
-    _x: [u32; 32],
-    _pc: usize
+pub struct Register<X: Xlen> {
+    _x: [X; 32],
+    _pc: usize,
+    _csr: CsrFile,
+    _v: VectorRegisters,
+    _isa: Isa,
+    /// The integer register writes since recording started, with the value each replaced
+    _writes: Option<Vec<(usize, X, X)>>
 }
 
-impl Register {
-    pub fn new() -> Register {
+impl<X: Xlen> Register<X> {
+    /// Creates a hart implementing every extension
+    pub fn new() -> Register<X> {
+        Register::with_isa(Isa::all(X::BITS))
+    }
+
+    pub fn with_isa(isa: Isa) -> Register<X> {
+        if isa.xlen() != X::BITS {
+            panic!("Cannot create an rv{} hart with {} bit registers", isa.xlen(), X::BITS);
+        }
+
         Register {
-            _x: [0; 32],
-            _pc: 0
+            _x: [X::default(); 32],
+            _pc: 0,
+            _csr: CsrFile::new(&isa),
+            _v: VectorRegisters::new(isa.vlen()),
+            _isa: isa,
+            _writes: None
         }
     }
 
+    pub fn isa(&self) -> &Isa {
+        &self._isa
+    }
+
+    /// The number of integer registers that exist, 16 on RV32E and 32 otherwise
+    pub fn count(&self) -> usize {
+        self._isa.registers()
+    }
+
     pub fn pc(&self) -> usize {
         self._pc
     }
@@ -20,21 +52,52 @@ impl Register {
         self._pc = pc;
     }
 
-    pub fn put(&mut self, index: usize, value: u32) {
+    pub fn csr(&self) -> &CsrFile {
+        &self._csr
+    }
+
+    pub fn csr_mut(&mut self) -> &mut CsrFile {
+        &mut self._csr
+    }
+
+    pub fn vector(&self) -> &VectorRegisters {
+        &self._v
+    }
+
+    pub fn vector_mut(&mut self) -> &mut VectorRegisters {
+        &mut self._v
+    }
+
+    pub fn put(&mut self, index: usize, value: X) {
+        // x0 is hardwired to zero, writes to it are discarded
         if index == 0 {
-            panic!("Cannot modify the register at index 0");
+            return;
         }
 
-        if index > 31 {
-            panic!("The register only has a length of 32, tried to modify index {}", index)
+        if index >= self.count() {
+            panic!("The register only has a length of {}, tried to modify index {}", self.count(), index)
         }
 
+        if let Some(writes) = &mut self._writes {
+            writes.push((index, self._x[index], value));
+        }
         self._x[index] = value;
     }
 
-    pub fn get(&self, index: usize) -> u32 {
-        if index > 31 {
-            panic!("The register only has a length of 32, tried to access index {}", index)
+    /// Starts recording the integer register writes, discarding any recorded before
+    pub fn record_writes(&mut self) {
+        self._writes = Some(Vec::new());
+    }
+
+    /// Stops recording and returns the writes in order as the register, its old and its new value.
+    /// x0 is never written
+    pub fn take_writes(&mut self) -> Vec<(usize, X, X)> {
+        self._writes.take().unwrap_or_default()
+    }
+
+    pub fn get(&self, index: usize) -> X {
+        if index >= self.count() {
+            panic!("The register only has a length of {}, tried to access index {}", self.count(), index)
         }
 
         self._x[index]
@@ -0,0 +1,263 @@
+//! Complete machine states that a processor can be put back into, and their on-disk format.
+//!
+//! A snapshot file is little-endian binary:
+//!
+//! | Field | Encoding |
+//! |-------|----------|
+//! | magic | the bytes `RVSNAP` |
+//! | version | u16, currently 2 |
+//! | ISA string | u16 length followed by UTF-8, e.g. `rv32i_zicsr` |
+//! | pc | u64 |
+//! | loaded program | u64 first cell, u64 end cell |
+//! | privilege level | u8, encoded as in `mstatus.MPP` |
+//! | integer registers | u64 each, x0 first, as many as the ISA has |
+//! | CSRs | u32 count, then u32 address and u64 value pairs |
+//! | vector registers | u32 length followed by the bytes of v0 to v31 |
+//! | memory | u32 count of cells, then u64 each |
+//! | CLINT | u64 `mtime`, u64 `mtimecmp`, u8 `msip` |
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use crate::csr::Privilege;
+use crate::isa::{Isa, IsaError};
+use crate::xlen::Xlen;
+
+const MAGIC: &[u8; 6] = b"RVSNAP";
+/// Bumped whenever the layout changes, files of other versions are rejected
+pub const VERSION: u16 = 2;
+
+/// The state of a processor that `Processor::restore` puts back: the registers, pc, CSRs and privilege level,
+/// the vector registers, memory, the CLINT's registers and the extent of the loaded program.
+///
+/// Breakpoints, watchpoints, the reverse execution history, the tracer and the labels of the program
+/// belong to the debugging session rather than the machine and are not part of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<X: Xlen = u32> {
+    pub(crate) isa: Isa,
+    pub(crate) pc: usize,
+    pub(crate) program: (usize, usize),
+    pub(crate) privilege: Privilege,
+    pub(crate) registers: Vec<X>,
+    pub(crate) csrs: BTreeMap<u32, u64>,
+    pub(crate) vector: Vec<u8>,
+    pub(crate) memory: Vec<X>,
+    /// `mtime`, `mtimecmp` and `msip`
+    pub(crate) clint: (u64, u64, bool)
+}
+
+impl<X: Xlen> Snapshot<X> {
+    pub fn isa(&self) -> &Isa {
+        &self.isa
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The cells the loaded program occupies
+    pub fn program(&self) -> (usize, usize) {
+        self.program
+    }
+
+    /// The integer registers, x0 first
+    pub fn registers(&self) -> &[X] {
+        &self.registers
+    }
+
+    pub fn memory(&self) -> &[X] {
+        &self.memory
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), SnapshotError> {
+        let file = File::create(path).map_err(|error| SnapshotError::Io(format!("{}: {}", path, error)))?;
+        let mut writer = BufWriter::new(file);
+        self.write_to(&mut writer)
+            .and_then(|_| writer.flush())
+            .map_err(|error| SnapshotError::Io(format!("{}: {}", path, error)))
+    }
+
+    pub fn load(path: &str) -> Result<Snapshot<X>, SnapshotError> {
+        let file = File::open(path).map_err(|error| SnapshotError::Io(format!("{}: {}", path, error)))?;
+        Snapshot::read_from(BufReader::new(file)).map_err(|error| match error {
+            SnapshotError::Io(error) => SnapshotError::Io(format!("{}: {}", path, error)),
+            error => error
+        })
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let isa = self.isa.to_string();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(isa.len() as u16).to_le_bytes())?;
+        writer.write_all(isa.as_bytes())?;
+        for value in [self.pc, self.program.0, self.program.1] {
+            writer.write_all(&(value as u64).to_le_bytes())?;
+        }
+        writer.write_all(&[self.privilege as u8])?;
+        for register in &self.registers {
+            writer.write_all(&register.as_u64().to_le_bytes())?;
+        }
+        writer.write_all(&(self.csrs.len() as u32).to_le_bytes())?;
+        for (address, value) in &self.csrs {
+            writer.write_all(&address.to_le_bytes())?;
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&(self.vector.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.vector)?;
+        writer.write_all(&(self.memory.len() as u32).to_le_bytes())?;
+        for cell in &self.memory {
+            writer.write_all(&cell.as_u64().to_le_bytes())?;
+        }
+        let (mtime, mtimecmp, msip) = self.clint;
+        writer.write_all(&mtime.to_le_bytes())?;
+        writer.write_all(&mtimecmp.to_le_bytes())?;
+        writer.write_all(&[msip as u8])?;
+        Ok(())
+    }
+
+    /// Reads a snapshot written by `write_to`, checking that it fits a processor whose registers are `X`
+    pub fn read_from<R: Read>(reader: R) -> Result<Snapshot<X>, SnapshotError> {
+        let mut reader = Reader { reader, xlen: PhantomData::<X> };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::Format(String::from("not a snapshot")));
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(SnapshotError::Version(version));
+        }
+
+        let length = reader.u16()? as usize;
+        let isa = String::from_utf8(reader.bytes(length)?)
+            .map_err(|_| SnapshotError::Format(String::from("the ISA string is not UTF-8")))?;
+        let isa = Isa::parse(&isa).map_err(SnapshotError::Isa)?;
+        if isa.xlen() != X::BITS {
+            return Err(SnapshotError::Isa(IsaError::XlenMismatch { expected: X::BITS, found: isa.xlen() }));
+        }
+        let pc = reader.u64()? as usize;
+        let program = (reader.u64()? as usize, reader.u64()? as usize);
+        let privilege = match reader.bytes(1)?[0] {
+            0b00 => Privilege::User,
+            0b01 => Privilege::Supervisor,
+            0b11 => Privilege::Machine,
+            level => return Err(SnapshotError::Format(format!("there is no privilege level {}", level)))
+        };
+        let registers = (0..isa.registers()).map(|_| reader.cell()).collect::<Result<_, _>>()?;
+        let count = reader.u32()?;
+        let csrs = (0..count).map(|_| Ok((reader.u32()?, reader.u64()?))).collect::<Result<_, SnapshotError>>()?;
+        let length = reader.u32()? as usize;
+        if length != 32 * isa.vlen() / 8 {
+            return Err(SnapshotError::Format(format!("{} bytes of vector registers do not match {}", length, isa)));
+        }
+        let vector = reader.bytes(length)?;
+        let count = reader.u32()? as usize;
+        let memory = (0..count).map(|_| reader.cell()).collect::<Result<_, _>>()?;
+        let clint = (reader.u64()?, reader.u64()?, reader.bytes(1)?[0] != 0);
+
+        Ok(Snapshot { isa, pc, program, privilege, registers, csrs, vector, memory, clint })
+    }
+}
+
+/// Why a snapshot could not be saved or loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    Io(String),
+    /// The file is not a snapshot or is cut short
+    Format(String),
+    /// The file was written in a format version this emulator does not read
+    Version(u16),
+    /// The snapshot's ISA cannot be implemented by the processor
+    Isa(IsaError)
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "cannot access {}", error),
+            SnapshotError::Format(error) => write!(f, "invalid snapshot: {}", error),
+            SnapshotError::Version(version) => write!(f, "snapshot version {} is not supported, expected {}", version, VERSION),
+            SnapshotError::Isa(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+struct Reader<R: Read, X: Xlen> {
+    reader: R,
+    xlen: PhantomData<X>
+}
+
+impl<R: Read, X: Xlen> Reader<R, X> {
+    fn bytes(&mut self, length: usize) -> Result<Vec<u8>, SnapshotError> {
+        let mut bytes = vec![0; length];
+        self.reader.read_exact(&mut bytes).map_err(|error| match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => SnapshotError::Format(String::from("the file ends early")),
+            _ => SnapshotError::Io(error.to_string())
+        })?;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// A register or memory cell, which has to fit in XLEN bits
+    fn cell(&mut self) -> Result<X, SnapshotError> {
+        let value = self.u64()?;
+        match X::from_u64(value).as_u64() == value {
+            true => Ok(X::from_u64(value)),
+            false => Err(SnapshotError::Format(format!("{:#x} does not fit in {} bits", value, X::BITS)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::isa::IsaError;
+    use crate::processor::Processor;
+    use crate::snapshot::{Snapshot, SnapshotError};
+
+    fn bytes(snapshot: &Snapshot) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        snapshot.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut processor = Processor::from_isa("rv32iv_zicsr_zvl256b").unwrap();
+        processor.set_register_value(5, 0xdeadbeef).unwrap();
+        processor.set_memory_size(2048);
+        processor.store_in_memory(2047, &[7]).unwrap();
+        let snapshot = processor.snapshot();
+
+        let read = Snapshot::read_from(bytes(&snapshot).as_slice()).unwrap();
+        assert_eq!(read, snapshot);
+        assert_eq!("rv32iv_zicsr_zvl256b", read.isa().to_string());
+        assert_eq!((0xdeadbeef, 7), (read.registers()[5], read.memory()[2047]));
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let snapshot = Processor::new().snapshot();
+        let mut version = bytes(&snapshot);
+        version[6] = 9;
+        let rv32 = bytes(&snapshot);
+
+        assert_eq!(Snapshot::<u32>::read_from(&b"RVSNAQ"[..]), Err(SnapshotError::Format(String::from("not a snapshot"))));
+        assert_eq!(Snapshot::<u32>::read_from(version.as_slice()), Err(SnapshotError::Version(9)));
+        assert_eq!(Snapshot::<u32>::read_from(&rv32[..rv32.len() - 1]), Err(SnapshotError::Format(String::from("the file ends early"))));
+        assert_eq!(Snapshot::<u64>::read_from(rv32.as_slice()), Err(SnapshotError::Isa(IsaError::XlenMismatch { expected: 64, found: 32 })));
+    }
+}
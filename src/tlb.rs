@@ -0,0 +1,314 @@
+//! A model of the TLB that caches Sv32 translations, for measuring how programs use it.
+//!
+//! Entries hold leaf page table entries of either size, a 4 KiB page or a 4 MiB superpage, tagged with the
+//! address space of `satp.ASID` unless the entry is global. Like hardware, the TLB is not kept coherent with
+//! the page tables: a program that changes them has to `sfence.vma` before its changes are guaranteed to apply.
+
+use std::fmt;
+
+/// The global bit of a page table entry, whose translation applies to every address space
+const PTE_G: u64 = 1 << 5;
+
+/// How a full set picks the entry a new translation replaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    /// The entry used least recently
+    Lru,
+    /// The entry filled first
+    Fifo,
+    /// Any entry, from a fixed seed so that runs are repeatable
+    Random
+}
+
+/// The shape of a TLB: `entries` split into sets of `ways` entries, so a TLB with as many ways as entries is
+/// fully associative and one with a single way is direct mapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlbConfig {
+    pub entries: usize,
+    pub ways: usize,
+    pub replacement: Replacement,
+    /// Whether entries are tagged with `satp.ASID`. Without tags a program that switches address spaces
+    /// has to flush the whole TLB
+    pub asids: bool
+}
+
+impl Default for TlbConfig {
+    /// A fully associative TLB of 32 entries with LRU replacement and ASIDs
+    fn default() -> Self {
+        TlbConfig { entries: 32, ways: 32, replacement: Replacement::Lru, asids: true }
+    }
+}
+
+/// Why a TLB cannot have a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlbConfigError {
+    /// A TLB needs at least one entry in at least one way
+    Empty,
+    /// The entries do not split into sets of `ways` entries
+    Associativity { entries: usize, ways: usize }
+}
+
+impl fmt::Display for TlbConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlbConfigError::Empty => write!(f, "a TLB needs at least one entry and one way"),
+            TlbConfigError::Associativity { entries, ways } =>
+                write!(f, "{} entries do not split into sets of {} ways", entries, ways)
+        }
+    }
+}
+
+impl std::error::Error for TlbConfigError {}
+
+/// What the TLB did since it was created or its statistics were last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TlbStats {
+    /// Translations found in the TLB. A hit on an entry whose accessed or dirty bit still has to be set
+    /// walks the page tables anyway
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries replaced to make room for another translation
+    pub evictions: u64,
+    /// `sfence.vma` instructions executed
+    pub fences: u64
+}
+
+impl TlbStats {
+    /// The fraction of translations that hit, zero before the first
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    /// The virtual page number of a page, or the upper ten bits of one for a superpage
+    tag: u64,
+    /// 0 for a page and 1 for a superpage, as in the page table walk
+    level: usize,
+    asid: u16,
+    pte: u64,
+    /// When the entry was filled and last used, in lookups
+    filled: u64,
+    used: u64
+}
+
+impl Entry {
+    fn translates(&self, virtual_address: u64) -> bool {
+        self.tag == virtual_address >> (12 + 10 * self.level)
+    }
+
+    fn global(&self) -> bool {
+        self.pte & PTE_G != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tlb {
+    config: TlbConfig,
+    /// The ways of set `n` are the entries `n * ways..(n + 1) * ways`
+    entries: Vec<Option<Entry>>,
+    clock: u64,
+    /// The xorshift state of random replacement
+    seed: u64,
+    stats: TlbStats
+}
+
+impl Tlb {
+    pub fn new(config: TlbConfig) -> Result<Tlb, TlbConfigError> {
+        if config.entries == 0 || config.ways == 0 {
+            return Err(TlbConfigError::Empty);
+        }
+        if !config.entries.is_multiple_of(config.ways) {
+            return Err(TlbConfigError::Associativity { entries: config.entries, ways: config.ways });
+        }
+        Ok(Tlb { config, entries: vec![None; config.entries], clock: 0, seed: 0x2545f4914f6cdd1d, stats: TlbStats::default() })
+    }
+
+    pub fn config(&self) -> TlbConfig {
+        self.config
+    }
+
+    pub fn stats(&self) -> TlbStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = TlbStats::default();
+    }
+
+    /// The leaf page table entry and its level that translate a virtual byte address in an address space
+    pub fn lookup(&mut self, virtual_address: u64, asid: u16) -> Option<(u64, usize)> {
+        self.clock += 1;
+        for level in [0, 1] {
+            let tag = virtual_address >> (12 + 10 * level);
+            let asids = self.config.asids;
+            let clock = self.clock;
+            let hit = self.set_mut(tag).iter_mut().flatten()
+                .find(|entry| entry.level == level && entry.tag == tag && (entry.global() || !asids || entry.asid == asid))
+                .map(|entry| {
+                    entry.used = clock;
+                    entry.pte
+                });
+            if let Some(pte) = hit {
+                self.stats.hits += 1;
+                return Some((pte, level));
+            }
+        }
+        self.stats.misses += 1;
+        None
+    }
+
+    /// Caches the translation a page table walk found, replacing any older one of the same page
+    pub fn insert(&mut self, virtual_address: u64, asid: u16, pte: u64, level: usize) {
+        let tag = virtual_address >> (12 + 10 * level);
+        let asid = if self.config.asids { asid } else { 0 };
+        let entry = Entry { tag, level, asid, pte, filled: self.clock, used: self.clock };
+        let replacement = self.config.replacement;
+        let random = self.random();
+        let set = self.set_mut(tag);
+        let same = set.iter().position(|old| old.is_some_and(|old| old.level == level && old.tag == tag && old.asid == asid));
+        let (way, evicted) = match same.or_else(|| set.iter().position(Option::is_none)) {
+            Some(way) => (way, false),
+            None => {
+                let ways = set.iter().flatten().enumerate();
+                let way = match replacement {
+                    Replacement::Lru => ways.min_by_key(|(_, old)| old.used).map(|(way, _)| way),
+                    Replacement::Fifo => ways.min_by_key(|(_, old)| old.filled).map(|(way, _)| way),
+                    Replacement::Random => Some(random as usize % set.len())
+                };
+                (way.unwrap_or_default(), true)
+            }
+        };
+        set[way] = Some(entry);
+        if evicted {
+            self.stats.evictions += 1;
+        }
+    }
+
+    /// Invalidates entries as `sfence.vma` does: those translating `virtual_address` if there is one, and those
+    /// of the address space `asid` if there is one. Global entries stay unless every address space is fenced
+    pub fn fence(&mut self, virtual_address: Option<u64>, asid: Option<u16>) {
+        self.stats.fences += 1;
+        let asids = self.config.asids;
+        for slot in &mut self.entries {
+            let fenced = slot.is_some_and(|entry| {
+                virtual_address.is_none_or(|address| entry.translates(address))
+                    && asid.is_none_or(|asid| !entry.global() && (!asids || entry.asid == asid))
+            });
+            if fenced {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Invalidates every entry, as when the machine is put into another state
+    pub fn flush(&mut self) {
+        self.entries.fill(None);
+    }
+
+    fn set_mut(&mut self, tag: u64) -> &mut [Option<Entry>] {
+        let ways = self.config.ways;
+        let set = tag as usize % (self.config.entries / ways);
+        &mut self.entries[set * ways..(set + 1) * ways]
+    }
+
+    fn random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tlb::{Replacement, Tlb, TlbConfig, TlbConfigError, TlbStats, PTE_G};
+
+    fn tlb(entries: usize, ways: usize, replacement: Replacement) -> Tlb {
+        Tlb::new(TlbConfig { entries, ways, replacement, asids: true }).unwrap()
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = TlbConfig { entries: 6, ways: 4, ..TlbConfig::default() };
+
+        assert_eq!(Tlb::new(config).unwrap_err(), TlbConfigError::Associativity { entries: 6, ways: 4 });
+        assert_eq!(Tlb::new(TlbConfig { entries: 0, ..config }).unwrap_err(), TlbConfigError::Empty);
+    }
+
+    #[test]
+    fn test_pages_and_superpages() {
+        let mut tlb = tlb(4, 4, Replacement::Lru);
+
+        assert_eq!(tlb.lookup(0x1234, 1), None);
+        tlb.insert(0x1234, 1, 0xcf, 0);
+        tlb.insert(0x400000, 1, 0xdf, 1);
+        assert_eq!(tlb.lookup(0x1ffc, 1), Some((0xcf, 0)));
+        assert_eq!(tlb.lookup(0x7ff000, 1), Some((0xdf, 1)));
+        assert_eq!(tlb.lookup(0x2000, 1), None);
+        assert_eq!(tlb.lookup(0x1000, 2), None);
+        assert_eq!(tlb.stats(), TlbStats { hits: 2, misses: 3, evictions: 0, fences: 0 });
+    }
+
+    #[test]
+    fn test_replacement() {
+        let mut lru = tlb(2, 2, Replacement::Lru);
+        let mut fifo = tlb(2, 2, Replacement::Fifo);
+        for tlb in [&mut lru, &mut fifo] {
+            tlb.insert(0x1000, 0, 1, 0);
+            tlb.lookup(0x3000, 0);
+            tlb.insert(0x2000, 0, 2, 0);
+            tlb.lookup(0x1000, 0);
+            tlb.insert(0x3000, 0, 3, 0);
+            assert_eq!(tlb.stats().evictions, 1);
+        }
+
+        assert_eq!((lru.lookup(0x1000, 0), lru.lookup(0x2000, 0)), (Some((1, 0)), None));
+        assert_eq!((fifo.lookup(0x1000, 0), fifo.lookup(0x2000, 0)), (None, Some((2, 0))));
+    }
+
+    #[test]
+    fn test_direct_mapped() {
+        let mut tlb = tlb(2, 1, Replacement::Lru);
+
+        tlb.insert(0x1000, 0, 1, 0);
+        tlb.insert(0x2000, 0, 2, 0);
+        tlb.insert(0x3000, 0, 3, 0);
+
+        assert_eq!((tlb.lookup(0x1000, 0), tlb.lookup(0x2000, 0), tlb.lookup(0x3000, 0)), (None, Some((2, 0)), Some((3, 0))));
+    }
+
+    #[test]
+    fn test_fence() {
+        let mut tlb = tlb(8, 8, Replacement::Lru);
+        let fill = |tlb: &mut Tlb| {
+            tlb.insert(0x1000, 1, 1, 0);
+            tlb.insert(0x1000, 2, 2, 0);
+            tlb.insert(0x2000, 1, 3 | PTE_G, 0);
+        };
+
+        fill(&mut tlb);
+        tlb.fence(Some(0x1800), None);
+        assert_eq!((tlb.lookup(0x1000, 1), tlb.lookup(0x1000, 2), tlb.lookup(0x2000, 2)), (None, None, Some((3 | PTE_G, 0))));
+        fill(&mut tlb);
+        tlb.fence(None, Some(1));
+        assert_eq!((tlb.lookup(0x1000, 1), tlb.lookup(0x1000, 2), tlb.lookup(0x2000, 1)), (None, Some((2, 0)), Some((3 | PTE_G, 0))));
+        tlb.fence(None, None);
+        assert_eq!((tlb.lookup(0x1000, 2), tlb.lookup(0x2000, 1)), (None, None));
+        assert_eq!(tlb.stats().fences, 3);
+    }
+
+    #[test]
+    fn test_without_asids() {
+        let mut tlb = Tlb::new(TlbConfig { asids: false, ..TlbConfig::default() }).unwrap();
+
+        tlb.insert(0x1000, 1, 1, 0);
+        assert_eq!(tlb.lookup(0x1000, 2), Some((1, 0)));
+        tlb.fence(None, Some(2));
+        assert_eq!(tlb.lookup(0x1000, 1), None);
+    }
+}
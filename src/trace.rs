@@ -0,0 +1,331 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::rc::Rc;
+use crate::csr::Privilege;
+use crate::disassembler::register_name;
+use crate::json::quote;
+use crate::trap::{Exception, Interrupt};
+
+/// Something that happened while assembling or running a program, values are zero-extended to 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent<'a> {
+    /// The assembler turned a line into an instruction word
+    Compile { line: &'a str, word: u32 },
+    /// The instruction word at `pc` was read
+    Fetch { pc: usize, word: u32 },
+    /// The instruction word was decoded, `disassembly` is how the disassembler prints it
+    Decode { pc: usize, disassembly: &'a str },
+    RegisterWrite { index: usize, value: u64 },
+    MemoryRead { address: usize, value: u64 },
+    MemoryWrite { address: usize, value: u64 },
+    /// The instruction raised an exception instead of retiring
+    Trap { pc: usize, exception: Exception, tval: u64 },
+    /// The hart took an interrupt before executing the instruction at `pc`
+    Interrupt { pc: usize, interrupt: Interrupt },
+    /// The instruction completed, after all of its register and memory writes. `privilege` is the level it ran in,
+    /// before an `mret` or `sret` changed it
+    Retire { pc: usize, word: u32, privilege: Privilege }
+}
+
+/// Receives the events of a processor or the assembler.
+pub trait Tracer {
+    fn trace(&mut self, event: &TraceEvent);
+
+    /// Events are only built for tracers that want them
+    fn enabled(&self) -> bool {
+        true
+    }
+}
+
+/// Shares a tracer, so that it can still be read after handing it to a processor
+impl<T: Tracer> Tracer for Rc<RefCell<T>> {
+    fn trace(&mut self, event: &TraceEvent) {
+        self.borrow_mut().trace(event);
+    }
+
+    fn enabled(&self) -> bool {
+        self.borrow().enabled()
+    }
+}
+
+/// Ignores every event, the default
+pub struct NoopTracer;
+
+impl Tracer for NoopTracer {
+    fn trace(&mut self, _event: &TraceEvent) {}
+
+    fn enabled(&self) -> bool {
+        false
+    }
+}
+
+/// Writes one aligned line of text per event
+pub struct HumanTracer<W: Write> {
+    writer: W
+}
+
+impl<W: Write> HumanTracer<W> {
+    pub fn new(writer: W) -> HumanTracer<W> {
+        HumanTracer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Tracer for HumanTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        let line = match *event {
+            TraceEvent::Compile { line, word } => format!("compile  {:08x} {}", word, line),
+            TraceEvent::Fetch { pc, word } => format!("fetch    {:#010x} {:08x}", pc, word),
+            TraceEvent::Decode { pc, disassembly } => format!("decode   {:#010x} {}", pc, disassembly),
+            TraceEvent::RegisterWrite { index, value } => format!("write    {:<4} {:#x}", register_name(index), value),
+            TraceEvent::MemoryRead { address, value } => format!("load     [{}] {:#x}", address, value),
+            TraceEvent::MemoryWrite { address, value } => format!("store    [{}] {:#x}", address, value),
+            TraceEvent::Trap { pc, exception, tval } => format!("trap     {:#010x} {:?}, tval {:#x}", pc, exception, tval),
+            TraceEvent::Interrupt { pc, interrupt } => format!("irq      {:#010x} {:?}", pc, interrupt),
+            TraceEvent::Retire { pc, word, .. } => format!("retire   {:#010x} {:08x}", pc, word)
+        };
+        // Tracing is best effort, a closed pipe must not stop the program being traced
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
+
+/// Writes one JSON object per event and line, with the event name in `"event"`
+pub struct JsonTracer<W: Write> {
+    writer: W
+}
+
+impl<W: Write> JsonTracer<W> {
+    pub fn new(writer: W) -> JsonTracer<W> {
+        JsonTracer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Tracer for JsonTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        let line = match *event {
+            TraceEvent::Compile { line, word } =>
+                format!(r#"{{"event":"compile","line":{},"word":{}}}"#, quote(line), word),
+            TraceEvent::Fetch { pc, word } =>
+                format!(r#"{{"event":"fetch","pc":{},"word":{}}}"#, pc, word),
+            TraceEvent::Decode { pc, disassembly } =>
+                format!(r#"{{"event":"decode","pc":{},"disassembly":{}}}"#, pc, quote(disassembly)),
+            TraceEvent::RegisterWrite { index, value } =>
+                format!(r#"{{"event":"register_write","register":{},"value":{}}}"#, index, value),
+            TraceEvent::MemoryRead { address, value } =>
+                format!(r#"{{"event":"memory_read","address":{},"value":{}}}"#, address, value),
+            TraceEvent::MemoryWrite { address, value } =>
+                format!(r#"{{"event":"memory_write","address":{},"value":{}}}"#, address, value),
+            TraceEvent::Trap { pc, exception, tval } =>
+                format!(r#"{{"event":"trap","pc":{},"cause":{},"tval":{}}}"#, pc, exception.cause(), tval),
+            TraceEvent::Interrupt { pc, interrupt } =>
+                format!(r#"{{"event":"interrupt","pc":{},"cause":{}}}"#, pc, interrupt.cause()),
+            TraceEvent::Retire { pc, word, .. } =>
+                format!(r#"{{"event":"retire","pc":{},"word":{}}}"#, pc, word)
+        };
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
+
+/// Writes one line per retired instruction in the format of Spike's `--log-commits`,
+/// `core 0: 3 0x00000000 (0x00000297) x5 0x00000000`, followed by the registers and memory it wrote.
+/// Memory writes read `mem <address> <value>`, addresses being the cells the program used.
+pub struct CommitLogTracer<W: Write> {
+    writer: W,
+    /// Hexadecimal digits of an XLEN wide value
+    digits: usize,
+    writes: String
+}
+
+impl<W: Write> CommitLogTracer<W> {
+    /// Creates a tracer for a processor with `xlen` bit registers
+    pub fn new(writer: W, xlen: u32) -> CommitLogTracer<W> {
+        CommitLogTracer { writer, digits: xlen as usize / 4, writes: String::new() }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Tracer for CommitLogTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        let digits = self.digits;
+        match *event {
+            TraceEvent::RegisterWrite { index, value } => {
+                let _ = write!(self.writes, " x{} 0x{:0digits$x}", index, value);
+            },
+            TraceEvent::MemoryWrite { address, value } => {
+                let _ = write!(self.writes, " mem 0x{:0digits$x} 0x{:0digits$x}", address, value);
+            },
+            // Spike only logs instructions that commit, a trap discards what the instruction did
+            TraceEvent::Trap { .. } => self.writes.clear(),
+            TraceEvent::Retire { pc, word, privilege } => {
+                let _ = writeln!(self.writer, "core 0: {} 0x{:0digits$x} (0x{:08x}){}", privilege as u8, pc, word, self.writes);
+                self.writes.clear();
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Where two commit logs first disagree, with the line numbers of the files they were read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The number of the commit that differs, counting from one
+    pub commit: usize,
+    /// The commits both logs agree on right before the divergence, oldest first
+    pub context: Vec<(usize, String)>,
+    /// The line of the expected log, `None` if it ended first
+    pub expected: Option<(usize, String)>,
+    /// The line of the log being checked, `None` if it ended first
+    pub found: Option<(usize, String)>
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "commit logs diverge at commit {}", self.commit)?;
+        for (number, line) in &self.context {
+            writeln!(f, "  {:>6}: {}", number, line)?;
+        }
+        match &self.expected {
+            Some((number, line)) => writeln!(f, "- {:>6}: {}", number, line)?,
+            None => writeln!(f, "- end of log")?
+        }
+        match &self.found {
+            Some((number, line)) => write!(f, "+ {:>6}: {}", number, line),
+            None => write!(f, "+ end of log")
+        }
+    }
+}
+
+/// Compares two commit logs commit by commit and returns the first that differs, with up to `context`
+/// commits before it. Lines that are not commits, like those of Spike's `-l`, are skipped and
+/// whitespace is not significant, so Spike's padded columns compare equal to `CommitLogTracer`'s.
+pub fn compare_commit_logs(expected: &str, found: &str, context: usize) -> Option<Divergence> {
+    let mut expected = commits(expected);
+    let mut found = commits(found);
+    let mut agreed: VecDeque<(usize, String)> = VecDeque::new();
+    let mut commit = 1;
+    loop {
+        match (expected.next(), found.next()) {
+            (None, None) => return None,
+            (Some(left), Some(right)) if left.2 == right.2 => {
+                if agreed.len() == context {
+                    agreed.pop_front();
+                }
+                if context > 0 {
+                    agreed.push_back((right.0, right.1.to_string()));
+                }
+                commit += 1;
+            },
+            (left, right) => return Some(Divergence {
+                commit,
+                context: agreed.into_iter().collect(),
+                expected: left.map(|(number, line, _)| (number, line.to_string())),
+                found: right.map(|(number, line, _)| (number, line.to_string()))
+            })
+        }
+    }
+}
+
+/// The commit lines of a log with their line numbers, counting from one, and their fields
+fn commits(log: &str) -> impl Iterator<Item = (usize, &str, Vec<&str>)> {
+    log.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim(), line.split_whitespace().collect::<Vec<_>>()))
+        .filter(|(_, _, fields)| match fields.as_slice() {
+            ["core", _, privilege, ..] => privilege.len() == 1 && privilege.chars().all(|c| c.is_ascii_digit()),
+            _ => false
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csr::Privilege;
+    use crate::trace::{compare_commit_logs, CommitLogTracer, HumanTracer, JsonTracer, TraceEvent, Tracer};
+    use crate::trap::Exception;
+
+    #[test]
+    fn test_human_tracer() {
+        let mut tracer = HumanTracer::new(Vec::new());
+
+        tracer.trace(&TraceEvent::Decode { pc: 8, disassembly: "addi a0, a0, 1" });
+        tracer.trace(&TraceEvent::RegisterWrite { index: 10, value: 5 });
+        tracer.trace(&TraceEvent::MemoryWrite { address: 512, value: 0xff });
+
+        let output = String::from_utf8(tracer.into_inner()).unwrap();
+        assert_eq!(output, "decode   0x00000008 addi a0, a0, 1\nwrite    a0   0x5\nstore    [512] 0xff\n");
+    }
+
+    #[test]
+    fn test_json_tracer() {
+        let mut tracer = JsonTracer::new(Vec::new());
+
+        tracer.trace(&TraceEvent::Compile { line: "lw a0, 0(\"a1\")", word: 0x0005a503 });
+        tracer.trace(&TraceEvent::Trap { pc: 4, exception: Exception::IllegalInstruction, tval: 0xffffffff });
+
+        let output = String::from_utf8(tracer.into_inner()).unwrap();
+        assert_eq!(output, concat!(
+            r#"{"event":"compile","line":"lw a0, 0(\"a1\")","word":369923}"#, "\n",
+            r#"{"event":"trap","pc":4,"cause":2,"tval":4294967295}"#, "\n"
+        ));
+    }
+
+    #[test]
+    fn test_commit_log_tracer() {
+        let mut tracer = CommitLogTracer::new(Vec::new(), 32);
+
+        tracer.trace(&TraceEvent::Fetch { pc: 0, word: 0x00000297 });
+        tracer.trace(&TraceEvent::RegisterWrite { index: 5, value: 0 });
+        tracer.trace(&TraceEvent::Retire { pc: 0, word: 0x00000297, privilege: Privilege::Machine });
+        tracer.trace(&TraceEvent::MemoryRead { address: 512, value: 3 });
+        tracer.trace(&TraceEvent::MemoryWrite { address: 1000, value: 7 });
+        tracer.trace(&TraceEvent::Retire { pc: 4, word: 0x00a2a023, privilege: Privilege::User });
+        tracer.trace(&TraceEvent::RegisterWrite { index: 10, value: 1 });
+        tracer.trace(&TraceEvent::Trap { pc: 8, exception: Exception::LoadAccessFault(2000), tval: 2000 });
+
+        let output = String::from_utf8(tracer.into_inner()).unwrap();
+        assert_eq!(output, concat!(
+            "core 0: 3 0x00000000 (0x00000297) x5 0x00000000\n",
+            "core 0: 0 0x00000004 (0x00a2a023) mem 0x000003e8 0x00000007\n"
+        ));
+    }
+
+    #[test]
+    fn test_compare_commit_logs() {
+        let spike = "core   0: 0x00000000 (0x00000297) auipc t0, 0x0\n\
+                     core   0: 3 0x00000000 (0x00000297) x5  0x00000000\n\
+                     core   0: 3 0x00000004 (0x00100513) x10 0x00000001\n\
+                     core   0: 3 0x00000008 (0x00a2a023) mem 0x00000000 0x00000001\n";
+        let ours = "core 0: 3 0x00000000 (0x00000297) x5 0x00000000\n\
+                    core 0: 3 0x00000004 (0x00100513) x10 0x00000001\n";
+
+        assert_eq!(compare_commit_logs(spike, spike, 3), None);
+        assert_eq!(compare_commit_logs(&spike.lines().take(3).collect::<Vec<_>>().join("\n"), ours, 3), None);
+
+        let divergence = compare_commit_logs(spike, &ours.replace("x10 0x00000001", "x10 0x00000002"), 1).unwrap();
+        assert_eq!(divergence.commit, 2);
+        assert_eq!(divergence.context, vec![(1, "core 0: 3 0x00000000 (0x00000297) x5 0x00000000".to_string())]);
+        assert_eq!(divergence.expected, Some((3, "core   0: 3 0x00000004 (0x00100513) x10 0x00000001".to_string())));
+        assert_eq!(divergence.to_string(), concat!(
+            "commit logs diverge at commit 2\n",
+            "       1: core 0: 3 0x00000000 (0x00000297) x5 0x00000000\n",
+            "-      3: core   0: 3 0x00000004 (0x00100513) x10 0x00000001\n",
+            "+      2: core 0: 3 0x00000004 (0x00100513) x10 0x00000002"
+        ));
+
+        let divergence = compare_commit_logs(spike, ours, 0).unwrap();
+        assert_eq!((divergence.commit, divergence.found), (3, None));
+        assert!(divergence.context.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::csr::Privilege;
+
+/// Synchronous exceptions raised while executing an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// A `jal`, `jalr` or taken branch to a target that is not a multiple of four, or a pc set to one.
+    /// The instruction does not retire, so `jal` and `jalr` leave their destination register alone
+    InstructionAddressMisaligned(usize),
+    /// A fetch from an address outside of memory
+    InstructionAccessFault(usize),
+    IllegalInstruction,
+    /// An `ebreak`
+    Breakpoint,
+    /// A load from an address outside of memory
+    LoadAccessFault(usize),
+    /// A store to an address outside of memory
+    StoreAccessFault(usize),
+    /// An `ecall` from the privilege level
+    EnvironmentCall(Privilege),
+    /// A fetch from a virtual address that the page tables do not let the hart execute
+    InstructionPageFault(usize),
+    /// A load from a virtual address that the page tables do not let the hart read
+    LoadPageFault(usize),
+    /// A store to a virtual address that the page tables do not let the hart write
+    StorePageFault(usize)
+}
+
+impl Exception {
+    /// The exception code reported in `mcause`.
+    pub fn cause(&self) -> u32 {
+        match self {
+            Exception::InstructionAddressMisaligned(_) => 0,
+            Exception::InstructionAccessFault(_) => 1,
+            Exception::IllegalInstruction => 2,
+            Exception::Breakpoint => 3,
+            Exception::LoadAccessFault(_) => 5,
+            Exception::StoreAccessFault(_) => 7,
+            Exception::EnvironmentCall(privilege) => 8 + *privilege as u32,
+            Exception::InstructionPageFault(_) => 12,
+            Exception::LoadPageFault(_) => 13,
+            Exception::StorePageFault(_) => 15
+        }
+    }
+}
+
+/// The interrupts of a hart without a PLIC, in their bit positions of `mip` and `mie`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    SupervisorSoftware = 1,
+    MachineSoftware = 3,
+    SupervisorTimer = 5,
+    MachineTimer = 7,
+    SupervisorExternal = 9,
+    MachineExternal = 11
+}
+
+impl Interrupt {
+    /// Pending and enabled interrupts are taken in this order
+    pub const PRIORITY: [Interrupt; 6] = [
+        Interrupt::MachineExternal, Interrupt::MachineSoftware, Interrupt::MachineTimer,
+        Interrupt::SupervisorExternal, Interrupt::SupervisorSoftware, Interrupt::SupervisorTimer
+    ];
+
+    /// The exception code reported in `mcause`, next to the interrupt bit
+    pub fn cause(&self) -> u32 {
+        *self as u32
+    }
+
+    /// The bit of the interrupt in `mip` and `mie`
+    pub fn bit(&self) -> u64 {
+        1 << self.cause()
+    }
+}
+
+/// Why a hart entered its trap handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    Exception(Exception),
+    Interrupt(Interrupt)
+}
+
+impl Trap {
+    /// The value of `mcause` or `scause` on a hart with `xlen` bit registers, whose top bit is set for interrupts
+    pub fn cause(&self, xlen: u32) -> u64 {
+        match self {
+            Trap::Exception(exception) => exception.cause() as u64,
+            Trap::Interrupt(interrupt) => 1 << (xlen - 1) | interrupt.cause() as u64
+        }
+    }
+}
@@ -0,0 +1,628 @@
+//! A subset of the vector extension (RVV 1.0): configuration, unit-stride and strided loads
+//! and stores, integer arithmetic and compares, mask logic, reductions and moves.
+//!
+//! Memory is word addressed like everywhere else in the emulator, so every element occupies
+//! one memory cell whatever its width and strides count cells. ELEN equals XLEN and only
+//! integer LMULs are implemented, other settings set `vill`.
+
+use crate::csr;
+use crate::instruction::*;
+use crate::memory::Memory;
+use crate::register::Register;
+use crate::trap::Exception;
+use crate::xlen::Xlen;
+
+/// vtype fields
+pub const VTYPE_VTA: u64 = 1 << 6;
+pub const VTYPE_VMA: u64 = 1 << 7;
+
+/// Mnemonics of the OP-V instructions that combine a vector with a vector, scalar or immediate operand
+const OPERATIONS: &[(&str, u32, u32)] = &[
+    ("vadd.vv", OPIVV, VADD),
+    ("vadd.vx", OPIVX, VADD),
+    ("vadd.vi", OPIVI, VADD),
+    ("vsub.vv", OPIVV, VSUB),
+    ("vsub.vx", OPIVX, VSUB),
+    ("vrsub.vx", OPIVX, VRSUB),
+    ("vrsub.vi", OPIVI, VRSUB),
+    ("vminu.vv", OPIVV, VMINU),
+    ("vminu.vx", OPIVX, VMINU),
+    ("vmin.vv", OPIVV, VMIN),
+    ("vmin.vx", OPIVX, VMIN),
+    ("vmaxu.vv", OPIVV, VMAXU),
+    ("vmaxu.vx", OPIVX, VMAXU),
+    ("vmax.vv", OPIVV, VMAX),
+    ("vmax.vx", OPIVX, VMAX),
+    ("vand.vv", OPIVV, VAND),
+    ("vand.vx", OPIVX, VAND),
+    ("vand.vi", OPIVI, VAND),
+    ("vor.vv", OPIVV, VOR),
+    ("vor.vx", OPIVX, VOR),
+    ("vor.vi", OPIVI, VOR),
+    ("vxor.vv", OPIVV, VXOR),
+    ("vxor.vx", OPIVX, VXOR),
+    ("vxor.vi", OPIVI, VXOR),
+    ("vmseq.vv", OPIVV, VMSEQ),
+    ("vmseq.vx", OPIVX, VMSEQ),
+    ("vmseq.vi", OPIVI, VMSEQ),
+    ("vmsne.vv", OPIVV, VMSNE),
+    ("vmsne.vx", OPIVX, VMSNE),
+    ("vmsne.vi", OPIVI, VMSNE),
+    ("vmsltu.vv", OPIVV, VMSLTU),
+    ("vmsltu.vx", OPIVX, VMSLTU),
+    ("vmslt.vv", OPIVV, VMSLT),
+    ("vmslt.vx", OPIVX, VMSLT),
+    ("vmsleu.vv", OPIVV, VMSLEU),
+    ("vmsleu.vx", OPIVX, VMSLEU),
+    ("vmsleu.vi", OPIVI, VMSLEU),
+    ("vmsle.vv", OPIVV, VMSLE),
+    ("vmsle.vx", OPIVX, VMSLE),
+    ("vmsle.vi", OPIVI, VMSLE),
+    ("vmsgtu.vx", OPIVX, VMSGTU),
+    ("vmsgtu.vi", OPIVI, VMSGTU),
+    ("vmsgt.vx", OPIVX, VMSGT),
+    ("vmsgt.vi", OPIVI, VMSGT),
+    ("vsll.vv", OPIVV, VSLL),
+    ("vsll.vx", OPIVX, VSLL),
+    ("vsll.vi", OPIVI, VSLL),
+    ("vsrl.vv", OPIVV, VSRL),
+    ("vsrl.vx", OPIVX, VSRL),
+    ("vsrl.vi", OPIVI, VSRL),
+    ("vsra.vv", OPIVV, VSRA),
+    ("vsra.vx", OPIVX, VSRA),
+    ("vsra.vi", OPIVI, VSRA),
+    ("vmul.vv", OPMVV, VMUL),
+    ("vmul.vx", OPMVX, VMUL),
+    ("vredsum.vs", OPMVV, VREDSUM),
+    ("vredand.vs", OPMVV, VREDAND),
+    ("vredor.vs", OPMVV, VREDOR),
+    ("vredxor.vs", OPMVV, VREDXOR),
+    ("vredminu.vs", OPMVV, VREDMINU),
+    ("vredmin.vs", OPMVV, VREDMIN),
+    ("vredmaxu.vs", OPMVV, VREDMAXU),
+    ("vredmax.vs", OPMVV, VREDMAX),
+    ("vmandn.mm", OPMVV, VMANDN),
+    ("vmand.mm", OPMVV, VMAND),
+    ("vmor.mm", OPMVV, VMOR),
+    ("vmxor.mm", OPMVV, VMXOR),
+    ("vmorn.mm", OPMVV, VMORN),
+    ("vmnand.mm", OPMVV, VMNAND),
+    ("vmnor.mm", OPMVV, VMNOR),
+    ("vmxnor.mm", OPMVV, VMXNOR),];
+
+/// Returns the funct3 and funct6 encoding a mnemonic like `vadd.vx`
+pub fn encoding_of(mnemonic: &str) -> Option<(u32, u32)> {
+    OPERATIONS.iter()
+        .find(|(name, _, _)| *name == mnemonic)
+        .map(|(_, funct3, funct6)| (*funct3, *funct6))
+}
+
+/// The inverse of `encoding_of`
+pub fn mnemonic_of(funct3: u32, funct6: u32) -> Option<&'static str> {
+    OPERATIONS.iter()
+        .find(|(_, category, funct)| *category == funct3 && *funct == funct6)
+        .map(|(name, _, _)| *name)
+}
+
+/// Encodes the settings of vsetvli like `e32, m1, ta, ma`, the policies defaulting to undisturbed
+pub fn vtype_of(settings: &[&str]) -> Option<u32> {
+    let mut vtype = 0;
+    for setting in settings {
+        vtype |= match *setting {
+            "e8" => 0b000 << 3,
+            "e16" => 0b001 << 3,
+            "e32" => 0b010 << 3,
+            "e64" => 0b011 << 3,
+            "m1" => 0b000,
+            "m2" => 0b001,
+            "m4" => 0b010,
+            "m8" => 0b011,
+            "ta" => VTYPE_VTA as u32,
+            "ma" => VTYPE_VMA as u32,
+            "tu" | "mu" => 0,
+            _ => return None
+        };
+    }
+    Some(vtype)
+}
+
+/// The inverse of `vtype_of`, settings it cannot express are printed as a number
+pub fn vtype_name(vtype: u32) -> String {
+    match VType::decode(vtype as u64, 64) {
+        Some(config) => format!("e{}, m{}, {}, {}", config.sew, config.lmul,
+                                if config.ta { "ta" } else { "tu" },
+                                if config.ma { "ma" } else { "mu" }),
+        None => vtype.to_string()
+    }
+}
+
+/// Whether the fields of an OP-V instruction or a vector load or store select an implemented
+/// instruction. Moves and mask instructions fix some of the fields that others use as operands.
+pub fn is_defined(funct6: u32, vm: bool, vs2: usize, vs1: usize, funct3: u32, opcode: u32) -> bool {
+    match (opcode, funct3, funct6) {
+        // Segments (nf) and mew are not implemented, unit-stride accesses reuse vs2 as lumop/sumop
+        (LOAD_FP | STORE_FP, _, VSTRIDED) => true,
+        (LOAD_FP | STORE_FP, _, VUNIT_STRIDE) => vs2 == 0,
+        (LOAD_FP | STORE_FP, _, _) => false,
+        (_, OPMVV, VWXUNARY0) => vs1 == 0,
+        (_, OPMVX, VRXUNARY0) => vs2 == 0,
+        (_, OPIVI, VMVNRR) => vm && matches!(vs1, 0 | 1 | 3 | 7),
+        (_, OPIVV | OPIVX | OPIVI, VMERGE) => !vm || vs2 == 0,
+        (_, OPMVV, logic) if (VMANDN..=VMXNOR).contains(&logic) => vm,
+        (_, funct3, funct6) => mnemonic_of(funct3, funct6).is_some()
+    }
+}
+
+/// The element width in bits of a vector load or store width encoding
+pub fn element_width(width: u32) -> Option<u32> {
+    match width {
+        VE8 => Some(8),
+        VE16 => Some(16),
+        VE32 => Some(32),
+        VE64 => Some(64),
+        _ => None
+    }
+}
+
+/// The settings of `vtype` that this emulator implements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VType {
+    pub sew: u32,
+    pub lmul: usize,
+    /// Tail agnostic, tail elements are filled with ones rather than left undisturbed
+    pub ta: bool,
+    /// Mask agnostic, inactive elements are filled with ones rather than left undisturbed
+    pub ma: bool
+}
+
+impl VType {
+    /// Decodes `vtype`, returning `None` for the settings that make it illegal
+    pub fn decode(vtype: u64, elen: u32) -> Option<VType> {
+        let vsew = (vtype >> 3) & 0b111;
+        let vlmul = vtype & 0b111;
+        // Fractional LMULs (0b101 to 0b111) are not implemented and 0b100 is reserved
+        if vtype >> 8 != 0 || vsew > 0b011 || vlmul > 0b011 || 8 << vsew > elen {
+            return None;
+        }
+
+        Some(VType {
+            sew: 8 << vsew,
+            lmul: 1 << vlmul,
+            ta: vtype & VTYPE_VTA != 0,
+            ma: vtype & VTYPE_VMA != 0
+        })
+    }
+
+    /// The number of elements of a register group
+    pub fn vlmax(&self, vlen: usize) -> usize {
+        self.lmul * vlen / self.sew as usize
+    }
+}
+
+/// The 32 vector registers, VLEN bits each.
+///
+/// The registers are stored as one little-endian byte array, so a register group is
+/// simply the bytes of consecutive registers.
+#[derive(Debug)]
+pub struct VectorRegisters {
+    vlenb: usize,
+    bytes: Vec<u8>
+}
+
+impl VectorRegisters {
+    pub fn new(vlen: usize) -> VectorRegisters {
+        VectorRegisters {
+            vlenb: vlen / 8,
+            bytes: vec![0; 32 * vlen / 8]
+        }
+    }
+
+    /// VLEN in bytes
+    pub fn vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    /// Every register's bytes, v0 first
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub(crate) fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    /// Reads element `index` of the register group starting at `base`
+    pub fn get(&self, base: usize, index: usize, sew: u32) -> u64 {
+        let width = sew as usize / 8;
+        let start = base * self.vlenb + index * width;
+        self.bytes[start..start + width]
+            .iter()
+            .rev()
+            .fold(0, |value, byte| (value << 8) | *byte as u64)
+    }
+
+    /// Writes element `index` of the register group starting at `base`, truncating `value` to `sew` bits
+    pub fn set(&mut self, base: usize, index: usize, sew: u32, value: u64) {
+        let width = sew as usize / 8;
+        let start = base * self.vlenb + index * width;
+        for byte in 0..width {
+            self.bytes[start + byte] = (value >> (8 * byte)) as u8;
+        }
+    }
+
+    /// Reads bit `index` of a mask register
+    pub fn mask(&self, register: usize, index: usize) -> bool {
+        (self.bytes[register * self.vlenb + index / 8] >> (index % 8)) & 1 == 1
+    }
+
+    pub fn set_mask(&mut self, register: usize, index: usize, value: bool) {
+        let byte = &mut self.bytes[register * self.vlenb + index / 8];
+        if value {
+            *byte |= 1 << (index % 8);
+        } else {
+            *byte &= !(1 << (index % 8));
+        }
+    }
+
+    /// Copies `count` whole registers
+    fn copy(&mut self, destination: usize, source: usize, count: usize) {
+        let length = count * self.vlenb;
+        self.bytes.copy_within(source * self.vlenb..source * self.vlenb + length, destination * self.vlenb);
+    }
+}
+
+fn sign_extend(value: u64, sew: u32) -> i64 {
+    ((value << (64 - sew)) as i64) >> (64 - sew)
+}
+
+fn truncate(value: u64, sew: u32) -> u64 {
+    if sew == 64 { value } else { value & ((1 << sew) - 1) }
+}
+
+/// The current vtype, or an illegal instruction exception if `vill` is set
+fn current<X: Xlen>(register: &Register<X>) -> Result<VType, Exception> {
+    VType::decode(register.csr().get(csr::VTYPE), X::BITS)
+        .ok_or(Exception::IllegalInstruction)
+}
+
+/// Register groups have to start at a register number that is a multiple of their size
+fn check_aligned(registers: &[usize], group: usize) -> Result<(), Exception> {
+    if registers.iter().any(|register| register % group != 0) {
+        return Err(Exception::IllegalInstruction);
+    }
+    Ok(())
+}
+
+/// Writes the body `results` to the group at `vd`, `None` being an inactive element,
+/// and fills the inactive and tail elements according to the mask and tail policies.
+fn write_elements(vector: &mut VectorRegisters, vd: usize, config: VType, vlmax: usize, results: &[Option<u64>]) {
+    for index in 0..vlmax {
+        match results.get(index) {
+            Some(Some(value)) => vector.set(vd, index, config.sew, *value),
+            Some(None) if config.ma => vector.set(vd, index, config.sew, u64::MAX),
+            None if config.ta => vector.set(vd, index, config.sew, u64::MAX),
+            _ => {}
+        }
+    }
+}
+
+/// Like `write_elements` for a mask register, whose tail extends to VLEN bits
+fn write_mask(vector: &mut VectorRegisters, vd: usize, config: VType, results: &[Option<bool>]) {
+    for index in 0..vector.vlenb() * 8 {
+        match results.get(index) {
+            Some(Some(value)) => vector.set_mask(vd, index, *value),
+            Some(None) if config.ma => vector.set_mask(vd, index, true),
+            None if config.ta => vector.set_mask(vd, index, true),
+            _ => {}
+        }
+    }
+}
+
+/// Element-wise integer operations, `a` being the element of vs2 and `b` the other operand
+fn arithmetic(funct3: u32, funct6: u32, a: u64, b: u64, sew: u32) -> Option<u64> {
+    let shift = (b & (sew as u64 - 1)) as u32;
+    let (signed_a, signed_b) = (sign_extend(a, sew), sign_extend(b, sew));
+    let result = match (funct3, funct6) {
+        (OPMVV | OPMVX, VMUL) => a.wrapping_mul(b),
+        (OPMVV | OPMVX, _) => return None,
+        (_, VADD) => a.wrapping_add(b),
+        (_, VSUB) => a.wrapping_sub(b),
+        (_, VRSUB) => b.wrapping_sub(a),
+        (_, VMINU) => a.min(b),
+        (_, VMIN) => signed_a.min(signed_b) as u64,
+        (_, VMAXU) => a.max(b),
+        (_, VMAX) => signed_a.max(signed_b) as u64,
+        (_, VAND) => a & b,
+        (_, VOR) => a | b,
+        (_, VXOR) => a ^ b,
+        (_, VSLL) => a << shift,
+        (_, VSRL) => a >> shift,
+        (_, VSRA) => (signed_a >> shift) as u64,
+        _ => return None
+    };
+    Some(truncate(result, sew))
+}
+
+/// Integer compares, producing a mask bit
+fn compare(funct6: u32, a: u64, b: u64, sew: u32) -> Option<bool> {
+    let (signed_a, signed_b) = (sign_extend(a, sew), sign_extend(b, sew));
+    match funct6 {
+        VMSEQ => Some(a == b),
+        VMSNE => Some(a != b),
+        VMSLTU => Some(a < b),
+        VMSLT => Some(signed_a < signed_b),
+        VMSLEU => Some(a <= b),
+        VMSLE => Some(signed_a <= signed_b),
+        VMSGTU => Some(a > b),
+        VMSGT => Some(signed_a > signed_b),
+        _ => None
+    }
+}
+
+/// Reductions fold the active elements of vs2 into element 0 of vs1
+fn reduce(funct6: u32, accumulator: u64, element: u64, sew: u32) -> u64 {
+    let (signed_accumulator, signed_element) = (sign_extend(accumulator, sew), sign_extend(element, sew));
+    let result = match funct6 {
+        VREDSUM => accumulator.wrapping_add(element),
+        VREDAND => accumulator & element,
+        VREDOR => accumulator | element,
+        VREDXOR => accumulator ^ element,
+        VREDMINU => accumulator.min(element),
+        VREDMIN => signed_accumulator.min(signed_element) as u64,
+        VREDMAXU => accumulator.max(element),
+        _ => signed_accumulator.max(signed_element) as u64
+    };
+    truncate(result, sew)
+}
+
+fn mask_logic(funct6: u32, a: bool, b: bool) -> bool {
+    match funct6 {
+        VMANDN => a & !b,
+        VMAND => a & b,
+        VMOR => a | b,
+        VMXOR => a ^ b,
+        VMORN => a | !b,
+        VMNAND => !(a & b),
+        VMNOR => !(a | b),
+        _ => !(a ^ b)
+    }
+}
+
+/// Executes vsetvli, vsetivli and vsetvl: `rs1` is the immediate AVL of vsetivli
+/// and `vtypei` is ignored by vsetvl, which reads vtype from `rs2`.
+pub fn configure<X: Xlen>(register: &mut Register<X>, rd: usize, rs1: usize, rs2: usize, vtypei: u32, funct: u32) {
+    let vtype = if funct == VSETVL { register.get(rs2).as_u64() } else { vtypei as u64 };
+    let avl = match funct {
+        VSETIVLI => Some(rs1 as u64),
+        _ if rs1 != 0 => Some(register.get(rs1).as_u64()),
+        // With rs1 = x0 the vector length is set to VLMAX, unless rd is x0 as well which keeps vl
+        _ if rd != 0 => None,
+        _ => Some(register.csr().get(csr::VL))
+    };
+
+    let vlen = register.vector().vlenb() * 8;
+    let vl = match VType::decode(vtype, X::BITS) {
+        Some(config) => {
+            let vlmax = config.vlmax(vlen);
+            register.csr_mut().put(csr::VTYPE, vtype);
+            avl.map_or(vlmax, |avl| avl.min(vlmax as u64) as usize)
+        },
+        None => {
+            register.csr_mut().put(csr::VTYPE, 1 << (X::BITS - 1));
+            0
+        }
+    };
+    register.csr_mut().put(csr::VL, vl as u64);
+    if rd != 0 {
+        register.put(rd, X::from_usize(vl));
+    }
+}
+
+/// Executes the unit-stride and strided loads and stores. `vd` is the register
+/// stored from for stores and `rs2` holds the stride, counted in memory cells.
+#[allow(clippy::too_many_arguments)]
+pub fn load_store<X: Xlen, M: Memory<X>>(register: &mut Register<X>, memory: &mut M, store: bool,
+                           vd: usize, rs1: usize, rs2: usize, vm: bool, mop: u32, width: u32) -> Result<(), Exception> {
+    let config = current(register)?;
+    let eew = element_width(width).ok_or(Exception::IllegalInstruction)?;
+    // The effective group size keeps the number of elements of the SEW/LMUL configuration
+    let emul = (config.lmul * eew as usize / config.sew as usize).max(1);
+    if eew > X::BITS || emul > 8 || (!vm && vd == 0 && !store) {
+        return Err(Exception::IllegalInstruction);
+    }
+    check_aligned(&[vd], emul)?;
+
+    let vl = register.csr().get(csr::VL) as usize;
+    if vl == 0 {
+        return Ok(());
+    }
+    let base = register.get(rs1).as_usize();
+    let stride = match mop {
+        VUNIT_STRIDE => 1,
+        VSTRIDED => register.get(rs2).as_usize(),
+        _ => return Err(Exception::IllegalInstruction)
+    };
+
+    let vector = register.vector_mut();
+    let active: Vec<bool> = (0..vl).map(|index| vm || vector.mask(0, index)).collect();
+    if store {
+        for index in (0..vl).filter(|index| active[*index]) {
+            let address = base.wrapping_add(index.wrapping_mul(stride));
+            memory.store(address, X::from_u64(vector.get(vd, index, eew)))?;
+        }
+    } else {
+        let results = (0..vl)
+            .map(|index| {
+                let address = base.wrapping_add(index.wrapping_mul(stride));
+                match active[index] {
+                    true => memory.load(address).map(|cell| Some(truncate(cell.as_u64(), eew))),
+                    false => Ok(None)
+                }
+            })
+            .collect::<Result<Vec<Option<u64>>, Exception>>()?;
+        let config = VType { sew: eew, lmul: emul, ..config };
+        write_elements(vector, vd, config, config.vlmax(vector.vlenb() * 8), &results);
+    }
+    Ok(())
+}
+
+/// Executes the OP-V instructions other than the configuration ones
+pub fn operate<X: Xlen>(register: &mut Register<X>, funct6: u32, vm: bool, vs2: usize, vs1: usize, funct3: u32, vd: usize) -> Result<(), Exception> {
+    if (funct3, funct6) == (OPIVI, VMVNRR) {
+        // vmv<nr>r.v copies whole registers whatever the configuration
+        let count = vs1 + 1;
+        if !matches!(count, 1 | 2 | 4 | 8) {
+            return Err(Exception::IllegalInstruction);
+        }
+        check_aligned(&[vd, vs2], count)?;
+        register.vector_mut().copy(vd, vs2, count);
+        return Ok(());
+    }
+
+    let config = current(register)?;
+    let sew = config.sew;
+    let vl = register.csr().get(csr::VL) as usize;
+    // The scalar operand of the .vx and .vi forms, which takes the place of vs1
+    let scalar = match funct3 {
+        OPIVX | OPMVX => Some(truncate(register.get(vs1).as_u64(), sew)),
+        OPIVI => Some(truncate(sign_extend(vs1 as u64, 5) as u64, sew)),
+        _ => None
+    };
+
+    if (funct3, funct6) == (OPMVV, VWXUNARY0) {
+        // vmv.x.s ignores vl and sign-extends element 0 into rd
+        let value = sign_extend(register.vector().get(vs2, 0, sew), sew) as u64;
+        if vd != 0 {
+            register.put(vd, X::from_u64(value));
+        }
+        return Ok(());
+    }
+
+    if vl == 0 {
+        return Ok(());
+    }
+    let vlen = register.vector().vlenb() * 8;
+    let vlmax = config.vlmax(vlen);
+    let vector = register.vector_mut();
+    let active: Vec<bool> = (0..vl).map(|index| vm || vector.mask(0, index)).collect();
+    let operand = |vector: &VectorRegisters, index: usize| scalar.unwrap_or_else(|| vector.get(vs1, index, sew));
+
+    match (funct3, funct6) {
+        (OPMVX, VRXUNARY0) => {
+            // vmv.s.x writes element 0 of a single register and treats the rest of it as tail
+            write_elements(vector, vd, config, vlen / sew as usize, &[scalar]);
+        },
+        (OPMVV, reduction) if reduction <= VREDMAX => {
+            check_aligned(&[vs2], config.lmul)?;
+            let result = (0..vl)
+                .filter(|index| active[*index])
+                .fold(vector.get(vs1, 0, sew), |accumulator, index| {
+                    reduce(reduction, accumulator, vector.get(vs2, index, sew), sew)
+                });
+            // The result is a single element, the rest of the register is tail
+            write_elements(vector, vd, config, vlen / sew as usize, &[Some(result)]);
+        },
+        (OPMVV, logic) if (VMANDN..=VMXNOR).contains(&logic) => {
+            let results: Vec<Option<bool>> = (0..vl)
+                .map(|index| Some(mask_logic(logic, vector.mask(vs2, index), vector.mask(vs1, index))))
+                .collect();
+            write_mask(vector, vd, config, &results);
+        },
+        (OPIVV | OPIVX | OPIVI, VMERGE) => {
+            if !vm && vd == 0 {
+                return Err(Exception::IllegalInstruction);
+            }
+            check_aligned(&[vd, vs2], config.lmul)?;
+            if funct3 == OPIVV {
+                check_aligned(&[vs1], config.lmul)?;
+            }
+            // vmerge selects with the mask rather than being masked, vmv.v.* is vmerge without a mask
+            let results: Vec<Option<u64>> = (0..vl)
+                .map(|index| {
+                    let selected = vm || vector.mask(0, index);
+                    Some(if selected { operand(vector, index) } else { vector.get(vs2, index, sew) })
+                })
+                .collect();
+            write_elements(vector, vd, config, vlmax, &results);
+        },
+        (OPIVV | OPIVX | OPIVI, compared) if (VMSEQ..=VMSGT).contains(&compared) => {
+            check_aligned(&[vs2], config.lmul)?;
+            if funct3 == OPIVV {
+                check_aligned(&[vs1], config.lmul)?;
+            }
+            let results: Vec<Option<bool>> = (0..vl)
+                .map(|index| {
+                    let result = compare(compared, vector.get(vs2, index, sew), operand(vector, index), sew);
+                    active[index].then_some(result).flatten()
+                })
+                .collect();
+            write_mask(vector, vd, config, &results);
+        },
+        _ => {
+            if !vm && vd == 0 {
+                // The mask register cannot be the destination of a masked instruction
+                return Err(Exception::IllegalInstruction);
+            }
+            check_aligned(&[vd, vs2], config.lmul)?;
+            if funct3 == OPIVV || funct3 == OPMVV {
+                check_aligned(&[vs1], config.lmul)?;
+            }
+            if arithmetic(funct3, funct6, 0, 0, sew).is_none() {
+                return Ok(());
+            }
+            let results: Vec<Option<u64>> = (0..vl)
+                .map(|index| {
+                    let a = vector.get(vs2, index, sew);
+                    active[index].then(|| arithmetic(funct3, funct6, a, operand(vector, index), sew)).flatten()
+                })
+                .collect();
+            write_elements(vector, vd, config, vlmax, &results);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::*;
+
+    #[test]
+    fn test_vtype_decode() {
+        let config = VType::decode(0b11_010_001, 32).unwrap();
+
+        assert_eq!(config, VType { sew: 32, lmul: 2, ta: true, ma: true });
+        assert_eq!(config.vlmax(128), 8);
+        assert_eq!(VType::decode(0b011_000, 32), None);
+        assert_eq!(VType::decode(0b011_000, 64).map(|config| config.sew), Some(64));
+        assert_eq!(VType::decode(0b000_111, 32), None);
+    }
+
+    #[test]
+    fn test_vtype_names() {
+        assert_eq!(vtype_of(&["e32", "m1", "ta", "ma"]), Some(0b11_010_000));
+        assert_eq!(vtype_of(&["e8", "m3"]), None);
+        assert_eq!(vtype_name(0b01_001_011), "e16, m8, ta, mu");
+        assert_eq!(vtype_name(0b000_101), "5");
+    }
+
+    #[test]
+    fn test_register_group_elements() {
+        let mut vector = VectorRegisters::new(128);
+
+        vector.set(2, 5, 32, 0x1_12345678);
+
+        assert_eq!(vector.get(2, 5, 32), 0x12345678);
+        assert_eq!(vector.get(3, 1, 32), 0x12345678);
+        assert_eq!(vector.get(3, 4, 8), 0x78);
+    }
+
+    #[test]
+    fn test_mask_bits() {
+        let mut vector = VectorRegisters::new(128);
+
+        vector.set_mask(0, 9, true);
+
+        assert!(vector.mask(0, 9));
+        assert_eq!(vector.get(0, 1, 8), 0b10);
+    }
+}
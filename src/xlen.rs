@@ -0,0 +1,159 @@
+use std::fmt::Debug;
+use std::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr};
+
+/// The width of the integer registers, implemented by `u32` for RV32 and `u64` for RV64.
+///
+/// Registers and memory cells hold the raw unsigned bits, signed operations go through
+/// the methods below so that the executor never has to know which width it is running.
+pub trait Xlen: Copy + Debug + Default + Eq + Ord
+    + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self>
+    + Shl<u32, Output = Self> + Shr<u32, Output = Self> {
+    const BITS: u32;
+
+    /// Zero-extends a 32-bit value
+    fn from_u32(value: u32) -> Self;
+
+    /// Sign-extends a 32-bit value
+    fn from_i32(value: i32) -> Self;
+
+    /// Truncates a 64-bit value
+    fn from_u64(value: u64) -> Self;
+
+    fn from_usize(value: usize) -> Self;
+
+    /// Truncates to the lower 32 bits
+    fn as_u32(self) -> u32;
+
+    fn as_u64(self) -> u64;
+
+    fn as_usize(self) -> usize;
+
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    /// Compares both values as two's complement numbers
+    fn lt_signed(self, rhs: Self) -> bool;
+
+    /// Arithmetic right shift
+    fn sra(self, shift: u32) -> Self;
+
+    fn leading_zeros(self) -> u32;
+
+    fn trailing_zeros(self) -> u32;
+
+    fn count_ones(self) -> u32;
+
+    fn rotate_left(self, shift: u32) -> Self;
+
+    fn rotate_right(self, shift: u32) -> Self;
+
+    fn swap_bytes(self) -> Self;
+}
+
+macro_rules! impl_xlen {
+    ($unsigned:ty, $signed:ty) => {
+        impl Xlen for $unsigned {
+            const BITS: u32 = <$unsigned>::BITS;
+
+            fn from_u32(value: u32) -> Self {
+                value as $unsigned
+            }
+
+            fn from_i32(value: i32) -> Self {
+                value as $signed as $unsigned
+            }
+
+            fn from_u64(value: u64) -> Self {
+                value as $unsigned
+            }
+
+            fn from_usize(value: usize) -> Self {
+                value as $unsigned
+            }
+
+            fn as_u32(self) -> u32 {
+                self as u32
+            }
+
+            fn as_u64(self) -> u64 {
+                self as u64
+            }
+
+            fn as_usize(self) -> usize {
+                self as usize
+            }
+
+            fn wrapping_add(self, rhs: Self) -> Self {
+                <$unsigned>::wrapping_add(self, rhs)
+            }
+
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                <$unsigned>::wrapping_sub(self, rhs)
+            }
+
+            fn lt_signed(self, rhs: Self) -> bool {
+                (self as $signed) < (rhs as $signed)
+            }
+
+            fn sra(self, shift: u32) -> Self {
+                ((self as $signed) >> shift) as $unsigned
+            }
+
+            fn leading_zeros(self) -> u32 {
+                <$unsigned>::leading_zeros(self)
+            }
+
+            fn trailing_zeros(self) -> u32 {
+                <$unsigned>::trailing_zeros(self)
+            }
+
+            fn count_ones(self) -> u32 {
+                <$unsigned>::count_ones(self)
+            }
+
+            fn rotate_left(self, shift: u32) -> Self {
+                <$unsigned>::rotate_left(self, shift)
+            }
+
+            fn rotate_right(self, shift: u32) -> Self {
+                <$unsigned>::rotate_right(self, shift)
+            }
+
+            fn swap_bytes(self) -> Self {
+                <$unsigned>::swap_bytes(self)
+            }
+        }
+    };
+}
+
+impl_xlen!(u32, i32);
+impl_xlen!(u64, i64);
+
+#[cfg(test)]
+mod tests {
+    use crate::xlen::Xlen;
+
+    #[test]
+    fn test_from_i32_sign_extends() {
+        assert_eq!(<u32 as Xlen>::from_i32(-1), 0xFFFFFFFF);
+        assert_eq!(<u64 as Xlen>::from_i32(-1), 0xFFFFFFFF_FFFFFFFF);
+    }
+
+    #[test]
+    fn test_from_u32_zero_extends() {
+        assert_eq!(<u64 as Xlen>::from_u32(0x80000000), 0x00000000_80000000);
+    }
+
+    #[test]
+    fn test_lt_signed() {
+        assert!(Xlen::lt_signed(0xFFFFFFFFu32, 0));
+        assert!(!Xlen::lt_signed(0xFFFFFFFFu64, 0));
+    }
+
+    #[test]
+    fn test_sra() {
+        assert_eq!(Xlen::sra(0x80000000u32, 4), 0xF8000000);
+        assert_eq!(Xlen::sra(0x80000000u64, 4), 0x08000000);
+    }
+}
@@ -1,33 +1,45 @@
 #[cfg(test)]
 mod tests {
-    use risc_v_emulator::processor::Processor;
+    use risc_v_emulator::clint::TimeSource;
+    use risc_v_emulator::csr::{self, Privilege};
+    use risc_v_emulator::debug::{Breakpoint, Location, WatchAccess, WatchHit, Watchpoint};
+    use risc_v_emulator::isa::IsaError;
+    use risc_v_emulator::processor::{ExecError, HaltConditions, HaltReason, LoadError, MemoryError, Processor, RegisterError};
+    use risc_v_emulator::snapshot::{Snapshot, SnapshotError};
+    use risc_v_emulator::tlb::{TlbConfig, TlbStats};
+    use risc_v_emulator::trace::{CommitLogTracer, JsonTracer, TraceEvent, Tracer};
+    use risc_v_emulator::trap::{Exception, Interrupt, Trap};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::rc::Rc;
 
     #[test]
     fn test_strlen() {
         let mut processor = Processor::new();
 
-        processor.load_instructions("examples/strlen.s");
+        processor.load_instructions("examples/strlen.s").unwrap();
         let bits: Vec<u32> = "hello".chars().map(|c| c as u32).collect();
-        let a0 = processor.load_into_memory(bits.as_slice());
-        processor.set_register_value(10, a0 as u32);
-        processor.execute_instructions();
+        let a0 = processor.load_into_memory(bits.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.execute_instructions().unwrap();
 
-        assert_eq!(5, processor.get_registry_value(10));
+        assert_eq!(5, processor.get_registry_value(10).unwrap());
     }
 
     #[test]
     fn test_strcopy() {
         let mut processor = Processor::new();
 
-        processor.load_instructions("examples/strcopy.s");
+        processor.load_instructions("examples/strcopy.s").unwrap();
         let bits: Vec<u32> = "hello".chars().map(|c| c as u32).collect();
-        let a1 = processor.load_into_memory(bits.as_slice());
+        let a1 = processor.load_into_memory(bits.as_slice()).unwrap();
         let a0 = a1 + 6;
-        processor.set_register_value(10, a0 as u32);
-        processor.set_register_value(11, a1 as u32);
-        processor.execute_instructions();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, a1 as u32).unwrap();
+        processor.execute_instructions().unwrap();
 
-        let result = processor.get_copy_of_memory(a0..a0 + 5);
+        let result = processor.get_copy_of_memory(a0..a0 + 5).unwrap();
         assert_eq!(bits, result);
     }
 
@@ -35,13 +47,13 @@ mod tests {
     fn test_bubsort() {
         let mut processor = Processor::new();
 
-        processor.load_instructions("examples/bubsort.s");
-        let a0 = processor.load_into_memory(&[1, 4, 3, 2, 5]);
-        processor.set_register_value(10, a0 as u32);
-        processor.set_register_value(11, 5);
-        processor.execute_instructions();
+        processor.load_instructions("examples/bubsort.s").unwrap();
+        let a0 = processor.load_into_memory(&[1, 4, 3, 2, 5]).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, 5).unwrap();
+        processor.execute_instructions().unwrap();
 
-        let result = processor.get_copy_of_memory(a0..a0 + 5);
+        let result = processor.get_copy_of_memory(a0..a0 + 5).unwrap();
         assert_eq!(vec![1, 2, 3, 4, 5], result);
     }
 
@@ -49,13 +61,13 @@ mod tests {
     fn test_strrev() {
         let mut processor = Processor::new();
 
-        processor.load_instructions("examples/strrev.s");
+        processor.load_instructions("examples/strrev.s").unwrap();
         let bits: Vec<u32> = "hello\0".chars().map(|c| c as u32).collect();
-        let a0 = processor.load_into_memory(bits.as_slice());
-        processor.set_register_value(10, a0 as u32);
-        processor.execute_instructions();
+        let a0 = processor.load_into_memory(bits.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.execute_instructions().unwrap();
 
-        let result = processor.get_copy_of_memory(a0..a0 + 5);
+        let result = processor.get_copy_of_memory(a0..a0 + 5).unwrap();
         let expected: Vec<u32> = "olleh".chars().map(|c| c as u32).collect();
         assert_eq!(expected, result);
     }
@@ -64,14 +76,14 @@ mod tests {
     fn test_arraysum() {
         let mut processor = Processor::new();
 
-        processor.load_instructions("examples/arraysum.s");
+        processor.load_instructions("examples/arraysum.s").unwrap();
         let ints: Vec<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
-        let a0 = processor.load_into_memory(ints.as_slice());
-        processor.set_register_value(10, a0 as u32);
-        processor.set_register_value(11, ints.len() as u32);
-        processor.execute_instructions();
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+        processor.execute_instructions().unwrap();
 
-        let result = processor.get_registry_value(10);
+        let result = processor.get_registry_value(10).unwrap();
         let expected: u32 = ints.iter().sum();
         assert_eq!(expected, result);
 
@@ -81,16 +93,899 @@ mod tests {
     fn test_binsearch() {
         let mut processor = Processor::new();
 
-        processor.load_instructions("examples/binsearch.s");
+        processor.load_instructions("examples/binsearch.s").unwrap();
         let ints: Vec<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
-        let a0 = processor.load_into_memory(ints.as_slice());
-        processor.set_register_value(10, a0 as u32);
-        processor.set_register_value(11, 8);
-        processor.set_register_value(12, ints.len() as u32);
-        processor.execute_instructions();
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, 8).unwrap();
+        processor.set_register_value(12, ints.len() as u32).unwrap();
+        processor.execute_instructions().unwrap();
 
-        let result = processor.get_registry_value(10);
+        let result = processor.get_registry_value(10).unwrap();
         assert_eq!(7, result);
     }
-}
 
+    #[test]
+    fn test_instret() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/instret.s").unwrap();
+        processor.execute_instructions().unwrap();
+
+        assert_eq!(4, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_bubsort_counts_loads() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/bubsort_loads.s").unwrap();
+        let a0 = processor.load_into_memory(&[1, 4, 3, 2, 5]).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, 5).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_copy_of_memory(a0..a0 + 5).unwrap();
+        assert_eq!(vec![1, 2, 3, 4, 5], result);
+        assert_eq!(24, processor.get_registry_value(12).unwrap());
+    }
+
+    #[test]
+    fn test_ecall_counter() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/ecall_counter.s").unwrap();
+
+        assert_eq!(HaltReason::Returned(1), processor.run(100).unwrap());
+        // The ecall trapped instead of retiring, and the final ret halts before it retires
+        assert_eq!(5, processor.get_csr_value(csr::INSTRET));
+    }
+
+    #[test]
+    fn test_taken_branch_counter() {
+        let path = std::env::temp_dir().join(format!("taken-branches-{}.s", std::process::id()));
+        let path = path.to_str().unwrap();
+        // The first branch is taken to the instruction that follows it, the second is not taken
+        std::fs::write(path, "li t0, 3\ncsrw mhpmevent3, t0\nbeq x0, x0, 4\nbne x0, x0, 8\nbeq x0, x0, 8\nnop\ncsrr a0, hpmcounter3\nret\n").unwrap();
+        let mut processor = Processor::new();
+        processor.load_instructions(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(HaltReason::Returned(2), processor.run(100).unwrap());
+    }
+
+    #[test]
+    fn test_aes_round() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/aes_round.s").unwrap();
+        // Round 1 of the FIPS-197 Appendix B example: the state followed by the round key
+        let a0 = processor.load_into_memory(&[
+            0xbee33d19, 0x2be2f4a0, 0x2a8dc69a, 0x0848f8e9,
+            0x17fefaa0, 0xb12c5488, 0x3939a323, 0x05766c2a
+        ]).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, a0 as u32 + 4).unwrap();
+        processor.set_register_value(12, a0 as u32 + 8).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_copy_of_memory(a0 + 8..a0 + 12).unwrap();
+        assert_eq!(vec![0xf27f9ca4, 0x2b359f68, 0x43ea5b6b, 0x49506a02], result);
+    }
+
+    #[test]
+    fn test_arraysum_rv64() {
+        let mut processor = Processor::<u64>::with_xlen();
+
+        processor.load_instructions("examples/arraysum64.s").unwrap();
+        let ints: Vec<u64> = vec![0xFFFFFFFF, 0x1_00000000, 0x7FFFFFFF_00000000, 1];
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u64).unwrap();
+        processor.set_register_value(11, ints.len() as u64).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_registry_value(10).unwrap();
+        let expected: u64 = ints.iter().sum();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_arraysum_rv32e() {
+        let mut processor = Processor::embedded();
+
+        processor.load_instructions("examples/arraysum.s").unwrap();
+        let ints: Vec<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_registry_value(10).unwrap();
+        let expected: u32 = ints.iter().sum();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_registers_out_of_range() {
+        let mut processor = Processor::embedded();
+        let error = RegisterError { index: 16, count: 16 };
+
+        assert_eq!(Err(error), processor.set_register_value(16, 1));
+        assert_eq!(Err(error), processor.get_registry_value(16));
+        assert_eq!(Err(RegisterError { index: 32, count: 32 }), Processor::new().get_registry_value(32));
+        assert_eq!(Ok(()), processor.set_register_value(15, 1));
+        assert_eq!(Ok(1), processor.get_registry_value(15));
+    }
+
+    #[test]
+    fn test_bubsort_rv32e() {
+        let mut processor = Processor::embedded();
+
+        let result = processor.load_instructions("examples/bubsort.s");
+
+        assert!(matches!(result, Err(LoadError::Assembly(message)) if message.contains("register x28 does not exist")));
+    }
+
+    #[test]
+    fn test_instret_from_isa() {
+        let mut processor = Processor::<u32>::from_isa("rv32i_zicntr").unwrap();
+
+        processor.load_instructions("examples/instret.s").unwrap();
+        processor.execute_instructions().unwrap();
+
+        assert_eq!(4, processor.get_registry_value(10).unwrap());
+        assert_eq!((1 << 30) | (1 << 8), processor.get_csr_value(csr::MISA));
+    }
+
+    #[test]
+    fn test_aes_round_from_isa() {
+        let mut processor = Processor::<u32>::from_isa("rv32i_zicsr").unwrap();
+
+        let result = processor.load_instructions("examples/aes_round.s");
+
+        assert!(matches!(result, Err(LoadError::Assembly(message)) if message.contains("is not part of rv32i_zicsr")));
+    }
+
+    #[test]
+    fn test_from_isa_xlen_mismatch() {
+        let result = Processor::<u32>::from_isa("rv64i");
+
+        assert_eq!(Some(IsaError::XlenMismatch { expected: 32, found: 64 }), result.err());
+    }
+    #[test]
+    fn test_arraysum_vector() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/arraysum_vector.s").unwrap();
+        let ints: Vec<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_registry_value(10).unwrap();
+        let expected: u32 = ints.iter().sum();
+        assert_eq!(expected, result);
+        assert_eq!(2, processor.get_csr_value(csr::VL));
+    }
+
+    #[test]
+    fn test_arraysum_vector_zvl256b() {
+        let mut processor = Processor::<u32>::from_isa("rv32iv_zvl256b").unwrap();
+
+        processor.load_instructions("examples/arraysum_vector.s").unwrap();
+        let ints: Vec<u32> = (1..=20).collect();
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_registry_value(10).unwrap();
+        let expected: u32 = ints.iter().sum();
+        assert_eq!(expected, result);
+        // 20 elements take 8, 8 and then 4
+        assert_eq!(4, processor.get_csr_value(csr::VL));
+    }
+
+    #[test]
+    fn test_arraysum_vector_rv64() {
+        let mut processor = Processor::<u64>::with_xlen();
+
+        processor.load_instructions("examples/arraysum_vector.s").unwrap();
+        let ints: Vec<u64> = vec![1, 2, 0xFFFFFFFF, 4, 5];
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u64).unwrap();
+        processor.set_register_value(11, ints.len() as u64).unwrap();
+        processor.execute_instructions().unwrap();
+
+        // Elements are 32 bits wide, so 0xFFFFFFFF is -1 and the sum is sign-extended
+        assert_eq!(11, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_arraysum_vector_masked() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/arraysum_vector_masked.s").unwrap();
+        let ints: Vec<i32> = vec![1, -2, 3, -4, 5, -6, 7];
+        let bits: Vec<u32> = ints.iter().map(|i| *i as u32).collect();
+        let a0 = processor.load_into_memory(bits.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+        processor.execute_instructions().unwrap();
+
+        let result = processor.get_registry_value(10).unwrap();
+        assert_eq!(16, result);
+    }
+
+    #[test]
+    fn test_arraysum_vector_without_v() {
+        let mut processor = Processor::<u32>::from_isa("rv32i_zicsr").unwrap();
+
+        let result = processor.load_instructions("examples/arraysum_vector.s");
+
+        assert!(matches!(result, Err(LoadError::Assembly(message)) if message.contains("is not part of rv32i_zicsr")));
+    }
+
+    #[test]
+    fn test_illegal_instruction() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/illegal_instruction.s").unwrap();
+        let error = processor.execute_instructions().unwrap_err();
+
+        assert_eq!("Unhandled exception IllegalInstruction at pc 4, mtval 0xffffffff", error.to_string());
+    }
+
+    #[test]
+    fn test_step() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/strlen.s").unwrap();
+        let outcome = processor.step().unwrap();
+
+        assert_eq!(0, outcome.pc);
+        assert_eq!(4, outcome.next_pc);
+        assert!(outcome.retired);
+        assert_eq!(None, outcome.halt);
+    }
+
+    #[test]
+    fn test_run_limit() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/instret.s").unwrap();
+
+        assert_eq!(HaltReason::InstructionLimit, processor.run(2).unwrap());
+        assert_eq!(8, processor.step().unwrap().pc);
+        assert_eq!(HaltReason::EndOfProgram, processor.run(100).unwrap());
+        assert_eq!(Some(HaltReason::EndOfProgram), processor.step().unwrap().halt);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/arraysum.s").unwrap();
+        let ints: Vec<u32> = vec![1, 2, 3];
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+        // Stop at the first backwards jump, after the first element is added
+        let outcome = processor.run_until(|outcome| outcome.next_pc < outcome.pc).unwrap();
+
+        assert_eq!(None, outcome.halt);
+        assert_eq!(1, processor.get_registry_value(5).unwrap());
+        assert_eq!(Some(HaltReason::Returned(6)), processor.run_until(|_| false).unwrap().halt);
+        assert_eq!(6, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_step_illegal_instruction() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/illegal_instruction.s").unwrap();
+        processor.step().unwrap();
+        let error = ExecError { pc: 4, instruction: 0xffffffff, exception: Exception::IllegalInstruction };
+
+        assert_eq!(Err(error), processor.step());
+        assert_eq!(4, processor.get_csr_value(csr::MEPC));
+        assert_eq!(Err(error), processor.run(100));
+    }
+
+    #[test]
+    fn test_access_fault() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/access_fault.s").unwrap();
+        let error = processor.run(100).unwrap_err();
+
+        assert_eq!(Exception::LoadAccessFault(2000), error.exception);
+        assert_eq!(4, error.pc);
+        assert_eq!(2000, error.tval());
+        assert_eq!(2000, processor.get_csr_value(csr::MTVAL));
+    }
+
+    #[test]
+    fn test_trap_handler() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/trap_handler.s").unwrap();
+
+        let outcomes: Vec<_> = (0..4).map(|_| processor.step().unwrap()).collect();
+        assert_eq!(Some(Trap::Exception(Exception::EnvironmentCall(Privilege::Machine))), outcomes[2].trap);
+        assert_eq!((8, 32, false), (outcomes[2].pc, outcomes[2].next_pc, outcomes[2].retired));
+        assert_eq!(11, processor.get_csr_value(csr::MCAUSE));
+        assert_eq!(HaltReason::Returned(21), processor.run(100).unwrap());
+        assert_eq!((2, 0xffffffff), (processor.get_csr_value(csr::MCAUSE), processor.get_csr_value(csr::MTVAL)));
+    }
+
+    #[test]
+    fn test_misaligned_jump_target() {
+        let path = std::env::temp_dir().join(format!("misaligned-{}.s", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        for (jump, target) in [("jal x1, 6", 14), ("jalr x1, x0, 3", 2), ("beq x0, x0, 10", 18)] {
+            std::fs::write(path, format!("li t0, 16\ncsrw mtvec, t0\n{}\nnop\ncsrr a0, mtval\nret\n", jump)).unwrap();
+            let mut processor = Processor::new();
+            processor.load_instructions(path).unwrap();
+
+            assert_eq!(HaltReason::Returned(target), processor.run(100).unwrap());
+            assert_eq!((0, 8), (processor.get_csr_value(csr::MCAUSE), processor.get_csr_value(csr::MEPC)));
+            assert_eq!(0, processor.get_registry_value(1).unwrap());
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_trap_handler_at_zero() {
+        let path = std::env::temp_dir().join(format!("handler-at-zero-{}.s", std::process::id()));
+        let path = path.to_str().unwrap();
+        // The program starts in what becomes its trap handler, the branch leaves it once a0 is set
+        std::fs::write(path, "bnez a0, 16\ncsrw mtvec, x0\nli a0, 1\n.word 0xffffffff\ncsrr a1, mcause\n").unwrap();
+        let mut processor = Processor::new();
+        processor.load_instructions(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(Ok(HaltReason::EndOfProgram), processor.execute_instructions());
+        assert_eq!((2, 12), (processor.get_registry_value(11).unwrap(), processor.get_csr_value(csr::MEPC)));
+    }
+
+    #[test]
+    fn test_interrupts() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/interrupts.s").unwrap();
+        let wait = processor.label("wait").unwrap();
+        let software = processor.label("software").unwrap();
+
+        for count in 1..=3 {
+            assert_eq!(HaltReason::InstructionLimit, processor.run(10).unwrap());
+            assert_eq!(wait, processor.pc());
+            processor.set_interrupt_pending(Interrupt::MachineSoftware, true);
+            let outcome = processor.step().unwrap();
+            processor.set_interrupt_pending(Interrupt::MachineSoftware, false);
+
+            assert_eq!(Some(Trap::Interrupt(Interrupt::MachineSoftware)), outcome.trap);
+            assert_eq!((wait, software), (outcome.pc, outcome.next_pc));
+            assert_eq!((0x80000003, wait as u32), (processor.get_csr_value(csr::MCAUSE), processor.get_csr_value(csr::MEPC)));
+            assert_eq!(csr::MSTATUS_MPIE | csr::MSTATUS_MPP, processor.get_csr_value(csr::MSTATUS));
+            processor.step().unwrap();
+            processor.step().unwrap();
+            assert_eq!((wait, count), (processor.pc(), processor.get_registry_value(8).unwrap()));
+        }
+        assert_eq!(HaltReason::Returned(3), processor.run(10).unwrap());
+    }
+
+    #[test]
+    fn test_supervisor_and_user_modes() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/supervisor.s").unwrap();
+        assert!(matches!(processor.run(3), Err(ExecError { exception: Exception::StoreAccessFault(1024), .. })));
+        processor.set_memory_size(4096);
+        let user = 0x1000 + processor.label("user").unwrap();
+
+        let outcome = processor.run_until(|outcome| outcome.next_pc == user).unwrap();
+        assert_eq!(processor.label("user"), Some(outcome.pc + 4));
+        assert_eq!(Privilege::User, processor.privilege());
+        let outcome = processor.run_until(|outcome| outcome.trap.is_some()).unwrap();
+        assert_eq!(Some(Trap::Exception(Exception::EnvironmentCall(Privilege::User))), outcome.trap);
+        assert_eq!((user + 20, processor.label("supervisor_trap").unwrap()), (outcome.pc, outcome.next_pc));
+        assert_eq!(42, processor.get_copy_of_memory(3072..3073).unwrap()[0]);
+
+        let outcome = processor.run_until(|outcome| outcome.trap.is_some()).unwrap();
+        assert_eq!(Some(Trap::Exception(Exception::LoadPageFault(3072))), outcome.trap);
+        assert_eq!((13, 3072), (processor.get_csr_value(csr::SCAUSE), processor.get_csr_value(csr::STVAL)));
+        assert_eq!(HaltReason::Returned(42 + 8 + 13 + 9), processor.run(100).unwrap());
+        // The data page was accessed and written, the user code page only accessed
+        assert_eq!(vec![0x5b, 0xcd7], processor.get_copy_of_memory(2049..2051).unwrap());
+    }
+
+    #[test]
+    fn test_tlb_statistics() {
+        let single = TlbConfig { entries: 1, ways: 1, ..TlbConfig::default() };
+        // The supervisor code, user code and user data pages, the unmapped page and the supervisor code again after the fence
+        let fully_associative = TlbStats { hits: 23, misses: 5, evictions: 0, fences: 1 };
+        let thrashing = TlbStats { hits: 17, misses: 11, evictions: 8, fences: 1 };
+
+        for (config, stats) in [(TlbConfig::default(), fully_associative), (single, thrashing)] {
+            let mut processor = Processor::new();
+            processor.load_instructions("examples/supervisor.s").unwrap();
+            processor.set_memory_size(4096);
+            assert_eq!(None, processor.tlb_stats());
+            processor.set_tlb(Some(config)).unwrap();
+
+            assert_eq!(HaltReason::Returned(72), processor.run(100).unwrap());
+            assert_eq!(Some(stats), processor.tlb_stats());
+        }
+    }
+
+    #[test]
+    fn test_timer_interrupts() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/timer.s").unwrap();
+
+        assert_eq!(HaltReason::Returned(3), processor.run(200).unwrap());
+        assert_eq!(85, processor.clint().mtimecmp());
+        assert_eq!(processor.get_csr_value(csr::MINSTRET) as u64, processor.clint().mtime());
+    }
+
+    #[test]
+    fn test_manual_time_source() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/timer.s").unwrap();
+        processor.set_time_source(TimeSource::Manual);
+        let wait = processor.label("wait").unwrap();
+
+        for count in 1..=3 {
+            assert_eq!(HaltReason::InstructionLimit, processor.run(50).unwrap());
+            assert_eq!(wait, processor.pc());
+            processor.advance_time(20);
+            let outcome = processor.step().unwrap();
+            assert_eq!(Some(Trap::Interrupt(Interrupt::MachineTimer)), outcome.trap);
+            assert_eq!((wait, processor.label("timer").unwrap()), (outcome.pc, outcome.next_pc));
+            processor.run(5).unwrap();
+            assert_eq!((wait, count), (processor.pc(), processor.get_registry_value(8).unwrap()));
+            assert_eq!(0, processor.get_csr_value(csr::MIP));
+        }
+        assert_eq!(HaltReason::Returned(3), processor.run(10).unwrap());
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let mut processor = Processor::new();
+
+        let result = processor.load_instructions("examples/missing.s");
+
+        assert!(matches!(result, Err(LoadError::Io(_))));
+    }
+
+    #[test]
+    fn test_load_malformed_assembly() {
+        let path = std::env::temp_dir().join(format!("malformed-{}.s", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        for (line, expected) in [
+            ("frob a0, a1", "line 2: unknown instruction 'frob'"),
+            ("add a0, a1, y2", "line 2: 'y2' is not a register"),
+            ("beq a0, a1, start", "line 2: 'start' is not a number")
+        ] {
+            std::fs::write(path, format!("start:\n{}\n", line)).unwrap();
+            let result = Processor::new().load_instructions(path);
+            assert!(matches!(&result, Err(LoadError::Assembly(message)) if message.starts_with(expected)), "{:?}", result);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut processor = Processor::new();
+
+        let error = MemoryError { range: 1000..1030, size: 1024 };
+        assert_eq!(Err(error.clone()), processor.get_copy_of_memory(1000..1030));
+        assert_eq!(Err(MemoryError { range: 512..1536, ..error }), processor.load_into_memory(&[0; 1024]));
+    }
+
+    #[test]
+    fn test_halt_on_ecall_exit() {
+        let mut processor = Processor::new();
+        processor.set_halt_conditions(HaltConditions { ecall_exit: true, ..Default::default() });
+
+        processor.load_instructions("examples/exit.s").unwrap();
+        assert!(matches!(processor.run(100), Err(ExecError { pc: 4, exception: Exception::Breakpoint, .. })));
+        processor.set_pc(8);
+        let reason = processor.run(100).unwrap();
+
+        assert_eq!(HaltReason::Exit(3), reason);
+        assert_eq!(3, reason.exit_code());
+        assert_eq!(Some(HaltReason::Exit(3)), processor.step().unwrap().halt);
+    }
+
+    #[test]
+    fn test_halt_on_ebreak() {
+        let mut processor = Processor::new();
+        processor.set_halt_conditions(HaltConditions { ebreak: true, ecall_exit: true, ..Default::default() });
+
+        processor.load_instructions("examples/exit.s").unwrap();
+
+        assert_eq!(HaltReason::Ebreak, processor.run(100).unwrap());
+        assert_eq!(1, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_halt_conditions_disabled() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/exit.s").unwrap();
+
+        // Without a trap handler ebreak and ecall are reported like any other exception
+        assert!(matches!(processor.run(100), Err(ExecError { pc: 4, exception: Exception::Breakpoint, .. })));
+        processor.set_pc(8);
+        let error = ExecError { pc: 16, instruction: 0x00000073, exception: Exception::EnvironmentCall(Privilege::Machine) };
+        assert_eq!(Err(error), processor.run(100));
+        processor.set_pc(20);
+        let reason = processor.run(100).unwrap();
+
+        assert_eq!(HaltReason::EndOfProgram, reason);
+        assert_eq!(0, reason.exit_code());
+        assert_eq!(4, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_halt_at_address() {
+        let mut processor = Processor::new();
+        processor.set_halt_conditions(HaltConditions { addresses: vec![4], ..Default::default() });
+
+        processor.load_instructions("examples/exit.s").unwrap();
+
+        assert_eq!(HaltReason::Reached(4), processor.run(100).unwrap());
+        assert_eq!(1, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_halt_on_test_finisher() {
+        let mut processor = Processor::new();
+        processor.set_halt_conditions(HaltConditions { test_finisher: Some(1000), ..Default::default() });
+
+        processor.load_instructions("examples/test_finisher.s").unwrap();
+        let reason = processor.run(100).unwrap();
+
+        assert_eq!(HaltReason::TestFinisher(3), reason);
+        assert_eq!(3, reason.exit_code());
+        assert_eq!(7, processor.get_registry_value(10).unwrap());
+    }
+
+    #[test]
+    fn test_test_finisher_is_physical() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/supervisor.s").unwrap();
+        processor.set_memory_size(4096);
+        // The user program stores to the virtual cell 2048, which its page table maps to 3072
+        processor.set_halt_conditions(HaltConditions { test_finisher: Some(3072), ..Default::default() });
+        let store = 0x1000 + processor.label("user").unwrap() + 12;
+        processor.run_until(|outcome| outcome.next_pc == store).unwrap();
+        processor.set_register_value(6, 43).unwrap();
+        assert_eq!(HaltReason::TestFinisher(21), processor.run(100).unwrap());
+    }
+
+    #[test]
+    fn test_halt_on_return() {
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/arraysum.s").unwrap();
+        let ints: Vec<u32> = vec![1, 2, 3];
+        let a0 = processor.load_into_memory(ints.as_slice()).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, ints.len() as u32).unwrap();
+
+        assert_eq!(6, processor.run(1000).unwrap().exit_code());
+        assert_eq!(HaltReason::Returned(6), processor.run(1000).unwrap());
+    }
+
+    /// Keeps the debug representation of every event
+    struct Events(Vec<String>);
+
+    impl Tracer for Events {
+        fn trace(&mut self, event: &TraceEvent) {
+            self.0.push(format!("{:?}", event));
+        }
+    }
+
+    #[test]
+    fn test_tracer() {
+        let events = Rc::new(RefCell::new(Events(Vec::new())));
+        let mut processor = Processor::new();
+        processor.set_tracer(Box::new(events.clone()));
+
+        processor.load_instructions("examples/test_finisher.s").unwrap();
+        processor.execute_instructions().unwrap();
+
+        let events = &events.borrow().0;
+        assert_eq!(r#"Compile { line: "addi a0, x0, 7", word: 7341331 }"#, events[0]);
+        assert_eq!(vec![
+            "Fetch { pc: 8, word: 10657827 }",
+            r#"Decode { pc: 8, disassembly: "sw a0, 0(t0)" }"#,
+            "MemoryWrite { address: 1000, value: 7 }",
+            "Retire { pc: 8, word: 10657827, privilege: Machine }"
+        ], events[12..16]);
+        assert_eq!(4, events.iter().filter(|event| event.starts_with("Retire")).count());
+    }
+
+    #[test]
+    fn test_json_tracer() {
+        let tracer = Rc::new(RefCell::new(JsonTracer::new(Vec::new())));
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/illegal_instruction.s").unwrap();
+        processor.set_tracer(Box::new(tracer.clone()));
+        processor.run(10).unwrap_err();
+
+        drop(processor);
+        let output = String::from_utf8(Rc::try_unwrap(tracer).ok().unwrap().into_inner().into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(vec![
+            r#"{"event":"fetch","pc":0,"word":1049875}"#,
+            r#"{"event":"decode","pc":0,"disassembly":"addi a0, zero, 1"}"#,
+            r#"{"event":"register_write","register":10,"value":1}"#,
+            r#"{"event":"retire","pc":0,"word":1049875}"#,
+            r#"{"event":"fetch","pc":4,"word":4294967295}"#,
+            r#"{"event":"trap","pc":4,"cause":2,"tval":4294967295}"#
+        ], lines);
+    }
+
+    #[test]
+    fn test_commit_log() {
+        let tracer = Rc::new(RefCell::new(CommitLogTracer::new(Vec::new(), 32)));
+        let mut processor = Processor::new();
+
+        processor.load_instructions("examples/test_finisher.s").unwrap();
+        processor.set_tracer(Box::new(tracer.clone()));
+        processor.execute_instructions().unwrap();
+
+        drop(processor);
+        let output = String::from_utf8(Rc::try_unwrap(tracer).ok().unwrap().into_inner().into_inner()).unwrap();
+        assert_eq!(output, concat!(
+            "core 0: 3 0x00000000 (0x00700513) x10 0x00000007\n",
+            "core 0: 3 0x00000004 (0x3e800293) x5 0x000003e8\n",
+            "core 0: 3 0x00000008 (0x00a2a023) mem 0x000003e8 0x00000007\n",
+            "core 0: 3 0x0000000c (0x00000513) x10 0x00000000\n"
+        ));
+    }
+
+    #[test]
+    fn test_commit_log_privilege() {
+        let tracer = Rc::new(RefCell::new(CommitLogTracer::new(Vec::new(), 32)));
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/supervisor.s").unwrap();
+        processor.set_memory_size(4096);
+        let user = 0x1000 + processor.label("user").unwrap();
+
+        processor.set_tracer(Box::new(tracer.clone()));
+        assert_eq!(HaltReason::Returned(72), processor.run(100).unwrap());
+
+        drop(processor);
+        let output = String::from_utf8(Rc::try_unwrap(tracer).ok().unwrap().into_inner().into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        let first = lines.iter().position(|line| line.contains(&format!(" 0x{:08x} (", user))).unwrap();
+        // mret and sret retire in the level they return from
+        assert!(lines.contains(&"core 0: 3 0x00000064 (0x30200073)"));
+        assert_eq!("core 0: 1 0x00000074 (0x10200073)", lines[first - 1]);
+        assert_eq!(vec![
+            "core 0: 0 0x00001078 (0x40000293) x5 0x00000400 mem 0x00000801 0x0000005b",
+            "core 0: 0 0x0000107c (0x005282b3) x5 0x00000800"
+        ], lines[first..first + 2]);
+    }
+
+    fn arraysum() -> (Processor, usize) {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/arraysum.s").unwrap();
+        let a0 = processor.load_into_memory(&[1, 2, 3, 4, 5]).unwrap();
+        processor.set_register_value(10, a0 as u32).unwrap();
+        processor.set_register_value(11, 5).unwrap();
+        (processor, a0)
+    }
+
+    #[test]
+    fn test_breakpoint_at_label() {
+        let (mut processor, _) = arraysum();
+        let address = processor.label("loop").unwrap();
+        let id = processor.add_breakpoint(Breakpoint::new(address));
+
+        assert_eq!(8, address);
+        assert_eq!(HaltReason::Breakpoint(id), processor.run(100).unwrap());
+        assert_eq!((8, 0), (processor.pc(), processor.get_registry_value(6).unwrap()));
+        assert_eq!(HaltReason::Breakpoint(id), processor.run(100).unwrap());
+        assert_eq!((8, 1), (processor.pc(), processor.get_registry_value(6).unwrap()));
+        assert_eq!(2, processor.breakpoints().next().unwrap().1.hits());
+
+        assert!(processor.remove_breakpoint(id));
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let (mut processor, _) = arraysum();
+        let done = processor.label("done").unwrap();
+        processor.add_breakpoint(Breakpoint::new(processor.label("loop").unwrap())
+            .when(|processor| processor.get_registry_value(6).unwrap() == 3));
+        let ignored = processor.add_breakpoint(Breakpoint::new(done).ignore(1));
+
+        let reason = processor.run(100).unwrap();
+        assert_eq!(HaltReason::Breakpoint(1), reason);
+        assert_eq!(6, processor.get_registry_value(5).unwrap());
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+        assert_eq!(1, processor.breakpoints().find(|&(id, _)| id == ignored).unwrap().1.hits());
+    }
+
+    #[test]
+    fn test_memory_watchpoints() {
+        let (mut processor, a0) = arraysum();
+        let read = processor.add_watchpoint(Watchpoint::Memory { range: a0 + 2..a0 + 3, access: WatchAccess::Read });
+        processor.add_watchpoint(Watchpoint::Memory { range: a0..a0 + 5, access: WatchAccess::Write });
+
+        let reason = processor.run(100).unwrap();
+        assert_eq!(HaltReason::Watchpoint(WatchHit { id: read, location: Location::Memory(a0 + 2), old: 3, new: 3 }), reason);
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/test_finisher.s").unwrap();
+        let write = processor.add_watchpoint(Watchpoint::Memory { range: 1000..1001, access: WatchAccess::Access });
+        let reason = processor.run(100).unwrap();
+        assert_eq!(HaltReason::Watchpoint(WatchHit { id: write, location: Location::Memory(1000), old: 0, new: 7 }), reason);
+        assert_eq!(12, processor.pc());
+    }
+
+    #[test]
+    fn test_register_watchpoint() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/test_finisher.s").unwrap();
+        let id = processor.add_watchpoint(Watchpoint::Register(10));
+
+        let reason = processor.run(100).unwrap();
+        assert_eq!(HaltReason::Watchpoint(WatchHit { id, location: Location::Register(10), old: 0, new: 7 }), reason);
+        let reason = processor.run(100).unwrap();
+        assert_eq!(HaltReason::Watchpoint(WatchHit { id, location: Location::Register(10), old: 7, new: 0 }), reason);
+
+        assert!(processor.remove_watchpoint(id));
+        assert_eq!(HaltReason::EndOfProgram, processor.run(100).unwrap());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let (mut processor, a0) = arraysum();
+        processor.set_history_limit(100);
+
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+        assert_eq!(39, processor.history_len());
+        assert_eq!(Some(0x24), processor.step_back());
+        assert_eq!((a0 as u32, 15), (processor.get_registry_value(10).unwrap(), processor.get_registry_value(5).unwrap()));
+        assert_eq!(Some(8), processor.step_back());
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/test_finisher.s").unwrap();
+        processor.set_history_limit(2);
+        for _ in 0..3 {
+            processor.step().unwrap();
+        }
+        assert_eq!(vec![7], processor.get_copy_of_memory(1000..1001).unwrap());
+        assert_eq!(Some(8), processor.step_back());
+        assert_eq!(vec![0], processor.get_copy_of_memory(1000..1001).unwrap());
+        assert_eq!(Some(4), processor.step_back());
+        assert_eq!(None, processor.step_back());
+        assert_eq!((4, 7, 0), (processor.pc(), processor.get_registry_value(10).unwrap(), processor.get_registry_value(5).unwrap()));
+    }
+
+    #[test]
+    fn test_step_back_restores_csrs() {
+        let mut processor = Processor::new();
+        processor.load_instructions("examples/supervisor.s").unwrap();
+        processor.set_memory_size(4096);
+        processor.set_history_limit(100);
+        let supervisor = processor.label("supervisor").unwrap();
+
+        let mret = processor.run_until(|outcome| outcome.next_pc == supervisor).unwrap().pc;
+        assert_eq!(Privilege::Supervisor, processor.privilege());
+        let instret = processor.get_csr_value(csr::INSTRET);
+        assert_eq!(Some(mret), processor.step_back());
+        assert_eq!(Privilege::Machine, processor.privilege());
+        assert_eq!((0x800, instret - 1), (processor.get_csr_value(csr::MSTATUS), processor.get_csr_value(csr::INSTRET)));
+
+        let ecall = processor.run_until(|outcome| outcome.trap.is_some()).unwrap().pc;
+        assert_eq!((Privilege::Supervisor, 8), (processor.privilege(), processor.get_csr_value(csr::SCAUSE)));
+        assert_eq!(Some(ecall), processor.step_back());
+        assert_eq!(Privilege::User, processor.privilege());
+        assert_eq!((0, 0x1078), (processor.get_csr_value(csr::SCAUSE), processor.get_csr_value(csr::SEPC)));
+        assert_eq!(HaltReason::Returned(72), processor.run(100).unwrap());
+    }
+
+    #[test]
+    fn test_step_back_restores_clint() {
+        let (mut processor, _) = arraysum();
+        processor.set_history_limit(100);
+
+        processor.run(5).unwrap();
+        let mtime = processor.clint().mtime();
+        processor.step().unwrap();
+        assert_eq!(mtime + 1, processor.clint().mtime());
+        processor.step_back();
+        assert_eq!(mtime, processor.clint().mtime());
+    }
+
+    #[test]
+    fn test_reverse_continue() {
+        let (mut processor, a0) = arraysum();
+        processor.set_history_limit(100);
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+
+        let id = processor.add_breakpoint(Breakpoint::new(processor.label("loop").unwrap())
+            .when(|processor| processor.get_registry_value(6).unwrap() == 2));
+        assert_eq!(HaltReason::Breakpoint(id), processor.reverse_continue());
+        assert_eq!((8, 3), (processor.pc(), processor.get_registry_value(5).unwrap()));
+        assert_eq!(0, processor.breakpoints().next().unwrap().1.hits());
+
+        let write = processor.add_watchpoint(Watchpoint::Register(5));
+        let reason = processor.reverse_continue();
+        assert_eq!(HaltReason::Watchpoint(WatchHit { id: write, location: Location::Register(5), old: 1, new: 3 }), reason);
+        assert_eq!((24, 1), (processor.pc(), processor.get_registry_value(5).unwrap()));
+        processor.remove_watchpoint(write);
+        let read = processor.add_watchpoint(Watchpoint::Memory { range: a0..a0 + 1, access: WatchAccess::Read });
+        assert_eq!(HaltReason::Watchpoint(WatchHit { id: read, location: Location::Memory(a0), old: 1, new: 1 }), processor.reverse_continue());
+        assert_eq!(20, processor.pc());
+
+        processor.remove_watchpoint(read);
+        processor.remove_breakpoint(id);
+        assert_eq!(HaltReason::StartOfHistory, processor.reverse_continue());
+        assert_eq!((0, 0), (processor.pc(), processor.history_len()));
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let (mut processor, a0) = arraysum();
+        let id = processor.add_breakpoint(Breakpoint::new(processor.label("loop").unwrap())
+            .when(|processor| processor.get_registry_value(6).unwrap() == 2));
+        assert_eq!(HaltReason::Breakpoint(id), processor.run(100).unwrap());
+        processor.remove_breakpoint(id);
+        let snapshot = processor.snapshot();
+        let instret = processor.get_csr_value(csr::INSTRET);
+
+        assert_eq!(HaltReason::Returned(15), processor.run(100).unwrap());
+        processor.restore(&snapshot);
+        assert_eq!((8, instret), (processor.pc(), processor.get_csr_value(csr::INSTRET)));
+        processor.store_in_memory(a0 + 4, &[10]).unwrap();
+        assert_eq!(HaltReason::Returned(20), processor.run(100).unwrap());
+
+        let path = std::env::temp_dir().join(format!("arraysum-{}.snapshot", std::process::id()));
+        let path = path.to_str().unwrap();
+        snapshot.save(path).unwrap();
+        let mut fork = Processor::new();
+        fork.restore(&Snapshot::load(path).unwrap());
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(HaltReason::Returned(15), fork.run(100).unwrap());
+        assert!(matches!(Snapshot::<u32>::load(path), Err(SnapshotError::Io(_))));
+    }
+
+    #[test]
+    fn test_rvdb() {
+        let mut rvdb = Command::new(env!("CARGO_BIN_EXE_rvdb"))
+            .args(["--reg", "a0=512", "--reg", "a1=5", "--mem", "512=1,2,3,4,5", "examples/arraysum.s"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        rvdb.stdin.take().unwrap()
+            .write_all(b"break done\nwatch t1\nrun\nset reg t1 5\ndelete\nx/2w 0x200\nstep\nc\nc\n")
+            .unwrap();
+
+        let output = String::from_utf8(rvdb.wait_with_output().unwrap().stdout).unwrap();
+        let lines: Vec<&str> = output.split("(rvdb) ").map(str::trim_end).filter(|line| !line.is_empty()).collect();
+        assert_eq!(vec![
+            "Breakpoint 1 at 0x00000024 <done>",
+            "Watchpoint 2: t1",
+            "Watchpoint 2: t1\nValue = 0x0\n=> 0x00000008 <loop>: bge t1, a1, 28",
+            "unknown command 'delete', try help",
+            "0x00000200: 0x00000001 0x00000002",
+            "=> 0x00000024 <done>: addi a0, t0, 0",
+            "Breakpoint 1, 0x00000024 <done>\n=> 0x00000024 <done>: addi a0, t0, 0",
+            "Program stopped: Returned(0), exit code 0"
+        ], lines);
+    }
+}
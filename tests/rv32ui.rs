@@ -0,0 +1,445 @@
+//! The rv32ui tests of riscv-tests, one test per instruction.
+//!
+//! `tests/riscv-tests/isa/rv64ui` holds the upstream sources, which riscv-tests also builds as rv32ui.
+//! The harness takes the place of `riscv_test.h` and `test_macros.h`: it expands the test macros the way
+//! they are defined for a 32-bit target, assembles the program and runs it until the exit call of
+//! `RVTEST_PASS` or `RVTEST_FAIL`, whose code names the failing test. The expected values are the ones
+//! written in the upstream sources.
+//!
+//! The emulator's assembler and memory call for a few adaptations:
+//! - labels, `la`, `lla` and `li` are resolved here into numeric offsets and `auipc` or `lui` with `addi`,
+//!   hexadecimal immediates become decimal ones and `fence` is emitted as its encoding
+//! - loads and stores address one word per cell, so the data section gives every byte address a cell of
+//!   its own and puts each datum in the cell of its first byte. The byte offsets of the tests then reach
+//!   the same data, but a load only sees what was stored at its own address, see `SKIPPED`
+//!
+//! fence_i, which rewrites its own code a halfword at a time, and ma_data, which makes misaligned
+//! accesses, are left out as neither means anything with one word per cell.
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use risc_v_emulator::processor::{HaltConditions, HaltReason, Processor};
+
+    /// Cases that load the bytes next to those a narrower store wrote, which only hold with byte-addressed memory
+    const SKIPPED: &[(&str, i64)] = &[("sb", 4), ("sh", 4)];
+
+    /// `fence` ordering every kind of access, which the assembler has no mnemonic for
+    const FENCE: u32 = 0x0ff0000f;
+
+    enum Item {
+        Label(String),
+        Instruction(String),
+        /// Pads the code with `nop`s to a multiple of this power of two bytes
+        Align(u32),
+        /// A value of this many bytes
+        Datum(usize, i64)
+    }
+
+    #[derive(Default)]
+    struct Program {
+        code: Vec<Item>,
+        data: Vec<Item>,
+        in_data: bool
+    }
+
+    impl Program {
+        fn expand(&mut self, statement: &str) {
+            let (label, statement) = split_label(statement);
+            let section = if self.in_data { &mut self.data } else { &mut self.code };
+            if let Some(label) = label {
+                section.push(Item::Label(label.to_string()));
+            }
+            if statement.is_empty() {
+                return;
+            }
+            let name_length = statement.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(statement.len());
+            let name = &statement[..name_length];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_') {
+                let arguments = statement[name_length..].trim()
+                    .strip_prefix('(')
+                    .and_then(|arguments| arguments.strip_suffix(')'))
+                    .map(|arguments| split(arguments, ','))
+                    .unwrap_or_default();
+                for statement in split(&expand_macro(name, &arguments), ';') {
+                    self.expand(&statement);
+                }
+            } else if let Some(directive) = statement.strip_prefix('.') {
+                let (directive, value) = directive.split_once(' ').unwrap_or((directive, ""));
+                match directive {
+                    "data" => self.in_data = true,
+                    "text" => self.in_data = false,
+                    "align" => section.push(Item::Align(number(value) as u32)),
+                    "byte" => section.push(Item::Datum(1, number(value))),
+                    "half" => section.push(Item::Datum(2, number(value))),
+                    "word" => section.push(Item::Datum(4, number(value))),
+                    _ => panic!("unsupported directive '.{}'", directive)
+                }
+            } else {
+                section.push(Item::Instruction(statement.to_string()));
+            }
+        }
+
+        /// The program in the emulator's assembly, its code followed by its data
+        fn assemble(&self) -> Vec<String> {
+            // Lay the code out first, as the size of each instruction is known before any label is
+            let mut code = Vec::new();
+            let mut address = 0;
+            for item in &self.code {
+                match item {
+                    Item::Align(power) => while address % (1 << power) != 0 {
+                        code.push(Item::Instruction(String::from("nop")));
+                        address += 4;
+                    },
+                    Item::Instruction(instruction) => {
+                        address += 4 * size(instruction);
+                        code.push(Item::Instruction(instruction.clone()));
+                    },
+                    Item::Label(label) => code.push(Item::Label(label.clone())),
+                    Item::Datum(..) => panic!("data in the code section")
+                }
+            }
+
+            // Data addresses are cells, with a cell for every byte
+            let mut labels: Vec<(usize, &str, i64)> = Vec::new();
+            let mut address = 0;
+            for (index, item) in code.iter().enumerate() {
+                match item {
+                    Item::Label(label) => labels.push((index, label, address)),
+                    Item::Instruction(instruction) => address += 4 * size(instruction) as i64,
+                    _ => {}
+                }
+            }
+            let mut data = Vec::new();
+            let mut cell = address / 4;
+            for item in &self.data {
+                match item {
+                    Item::Label(label) => labels.push((usize::MAX, label, cell)),
+                    Item::Datum(size, value) => {
+                        data.push(format!(".word {}", *value as u32));
+                        data.extend(std::iter::repeat_n(String::from(".word 0"), size - 1));
+                        cell += *size as i64;
+                    },
+                    _ => panic!("code in the data section")
+                }
+            }
+
+            let mut lines = Vec::new();
+            let mut pc = 0;
+            for (index, item) in code.iter().enumerate() {
+                if let Item::Instruction(instruction) = item {
+                    let target = |expression: &str| {
+                        let (label, offset) = match expression.find(['+', '-']) {
+                            Some(at) => (expression[..at].trim(), number(&expression[at..].replace(' ', ""))),
+                            None => (expression.trim(), 0)
+                        };
+                        let address = match label.strip_suffix('f').or(label.strip_suffix('b')) {
+                            Some(local) if local.chars().all(|c| c.is_ascii_digit()) => {
+                                let mut matching = labels.iter().filter(|(_, name, _)| *name == local);
+                                if label.ends_with('f') {
+                                    matching.find(|(at, ..)| *at > index)
+                                } else {
+                                    matching.rfind(|(at, ..)| *at < index)
+                                }
+                            },
+                            _ => labels.iter().find(|(_, name, _)| *name == label)
+                        };
+                        address.unwrap_or_else(|| panic!("no label '{}'", label)).2 + offset
+                    };
+                    lines.extend(resolve(instruction, pc, target));
+                    pc += 4 * size(instruction) as i64;
+                }
+            }
+            lines.extend(data);
+            lines
+        }
+    }
+
+    /// Splits at the separators outside of parentheses
+    fn split(text: &str, separator: char) -> Vec<String> {
+        let mut parts = vec![String::new()];
+        let mut depth = 0;
+        for c in text.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if c == separator && depth == 0 => {
+                    parts.push(String::new());
+                    continue;
+                },
+                _ => {}
+            }
+            parts.last_mut().unwrap().push(c);
+        }
+        parts.iter().map(|part| part.trim().to_string()).filter(|part| !part.is_empty()).collect()
+    }
+
+    fn split_label(statement: &str) -> (Option<&str>, &str) {
+        let length = statement.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(statement.len());
+        match statement[length..].strip_prefix(':') {
+            Some(rest) if length > 0 => (Some(&statement[..length]), rest.trim()),
+            _ => (None, statement)
+        }
+    }
+
+    fn number(token: &str) -> i64 {
+        let token = token.trim();
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token.strip_prefix('+').unwrap_or(token))
+        };
+        let value = match digits.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).map(|value| value as i64),
+            None => digits.parse()
+        };
+        let value = value.unwrap_or_else(|_| panic!("'{}' is not a number", token));
+        if negative { -value } else { value }
+    }
+
+    /// `MASK_XLEN`
+    fn mask(value: &str) -> i64 {
+        number(value) & 0xffff_ffff
+    }
+
+    /// `SEXT_IMM`, which sign-extends a 12-bit immediate
+    fn sext_imm(value: &str) -> i64 {
+        let value = number(value);
+        value | (-((value >> 11) & 1) << 11)
+    }
+
+    /// The instructions the emulator's assembler emits for one of the source's, which is what `la`, `lla` and
+    /// `li` need to be to keep the layout fixed
+    fn size(instruction: &str) -> usize {
+        match instruction.split_whitespace().next() {
+            Some("li" | "la" | "lla") => 2,
+            _ => 1
+        }
+    }
+
+    /// An instruction in the emulator's assembly, given how to find the address of a label expression
+    fn resolve(instruction: &str, pc: i64, target: impl Fn(&str) -> i64) -> Vec<String> {
+        let instruction = instruction.replace("TESTNUM", "gp");
+        let (mnemonic, operands) = instruction.split_once(char::is_whitespace).unwrap_or((&instruction, ""));
+        let operands = split(operands, ',');
+        let offset = |expression: &str| target(expression) - pc;
+        // The low 12 bits go to an addi, which sign-extends them, and the rest to the upper immediate
+        let pair = |upper: &str, rd: &str, value: i64| {
+            let value = value as i32;
+            let low = value << 20 >> 20;
+            vec![
+                format!("{} {}, {}", upper, rd, (value.wrapping_sub(low) >> 12) & 0xfffff),
+                format!("addi {}, {}, {}", rd, rd, low)
+            ]
+        };
+        match (mnemonic, operands.as_slice()) {
+            ("li", [rd, value]) => pair("lui", rd, number(value)),
+            ("la" | "lla", [rd, expression]) => pair("auipc", rd, offset(expression)),
+            ("fence", []) => vec![format!(".word {}", FENCE)],
+            ("j", [label]) => vec![format!("jal x0, {}", offset(label))],
+            ("jal", [label]) => vec![format!("jal x1, {}", offset(label))],
+            ("jal", [rd, label]) => vec![format!("jal {}, {}", rd, offset(label))],
+            ("jr", [rs]) => vec![format!("jalr x0, {}, 0", rs)],
+            ("jr", [rs, imm]) => vec![format!("jalr x0, {}, {}", rs, number(imm))],
+            ("beqz", [rs, label]) => vec![format!("beq {}, x0, {}", rs, offset(label))],
+            ("bnez", [rs, label]) => vec![format!("bne {}, x0, {}", rs, offset(label))],
+            // The GNU assembler takes the register form of these with an immediate for the immediate form
+            ("add" | "and" | "or" | "xor" | "sll" | "srl" | "sra" | "slt" | "sltu", [rd, rs, imm])
+                if imm.starts_with(|c: char| c.is_ascii_digit() || c == '-') =>
+                vec![format!("{}i {}, {}, {}", mnemonic, rd, rs, number(imm))],
+            ("beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu", [rs1, rs2, label]) =>
+                vec![format!("{} {}, {}, {}", mnemonic, rs1, rs2, offset(label))],
+            _ => {
+                let operands: Vec<String> = operands.iter()
+                    .map(|operand| if operand.contains("0x") { number(operand).to_string() } else { operand.clone() })
+                    .collect();
+                vec![format!("{} {}", mnemonic, operands.join(", "))]
+            }
+        }
+    }
+
+    /// The body of a macro from `riscv_test.h` or `test_macros.h`, for a 32-bit target
+    fn expand_macro(name: &str, a: &[String]) -> String {
+        let nops = |count: &str| "nop; ".repeat(number(count) as usize);
+        // TEST_CASE, which checks the value of a register once its code has run
+        let case = |number: &str, register: &str, result: i64, code: &str| format!(
+            "test_{n}: li TESTNUM, {n}; {code}; li x7, {result}; bne {register}, x7, fail",
+            n=number, code=code, result=result, register=register
+        );
+        // Runs the code twice, so that the second time around the instructions are in the caches
+        let twice = |number: &str, code: &str| format!(
+            "test_{n}: li TESTNUM, {n}; li x4, 0; 1: {code}; addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+            n=number, code=code
+        );
+        match name {
+            "RVTEST_RV64U" | "RVTEST_CODE_BEGIN" | "RVTEST_CODE_END" | "RVTEST_DATA_BEGIN" | "RVTEST_DATA_END" | "TEST_DATA" =>
+                String::new(),
+            "RVTEST_PASS" => String::from("fence; li TESTNUM, 1; li a7, 93; li a0, 0; ecall"),
+            "RVTEST_FAIL" => String::from(
+                "fence; 1: beqz TESTNUM, 1b; sll TESTNUM, TESTNUM, 1; or TESTNUM, TESTNUM, 1; li a7, 93; addi a0, TESTNUM, 0; ecall"
+            ),
+            "TEST_PASSFAIL" => String::from("bne x0, TESTNUM, pass; fail: RVTEST_FAIL; pass: RVTEST_PASS"),
+            "TEST_CASE" => case(&a[0], &a[1], mask(&a[2]), &a[3..].join(",")),
+
+            "TEST_IMM_OP" => case(&a[0], "x14", mask(&a[2]), &format!(
+                "li x1, {}; {} x14, x1, {}", mask(&a[3]), a[1], sext_imm(&a[4])
+            )),
+            "TEST_IMM_SRC1_EQ_DEST" => case(&a[0], "x1", mask(&a[2]), &format!(
+                "li x1, {}; {} x1, x1, {}", mask(&a[3]), a[1], sext_imm(&a[4])
+            )),
+            "TEST_IMM_DEST_BYPASS" => case(&a[0], "x6", mask(&a[3]), &format!(
+                "li x4, 0; 1: li x1, {}; {} x14, x1, {}; {}addi x6, x14, 0; addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+                mask(&a[4]), a[2], sext_imm(&a[5]), nops(&a[1])
+            )),
+            "TEST_IMM_SRC1_BYPASS" => case(&a[0], "x14", mask(&a[3]), &format!(
+                "li x4, 0; 1: {}li x1, {}; {} x14, x1, {}; addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+                nops(&a[1]), mask(&a[4]), a[2], sext_imm(&a[5])
+            )),
+            "TEST_IMM_ZEROSRC1" => case(&a[0], "x1", mask(&a[2]), &format!("{} x1, x0, {}", a[1], sext_imm(&a[3]))),
+            "TEST_IMM_ZERODEST" => case(&a[0], "x0", 0, &format!(
+                "li x1, {}; {} x0, x1, {}", mask(&a[2]), a[1], sext_imm(&a[3])
+            )),
+
+            "TEST_RR_OP" => case(&a[0], "x14", mask(&a[2]), &format!(
+                "li x1, {}; li x2, {}; {} x14, x1, x2", mask(&a[3]), mask(&a[4]), a[1]
+            )),
+            "TEST_RR_SRC1_EQ_DEST" => case(&a[0], "x1", mask(&a[2]), &format!(
+                "li x1, {}; li x2, {}; {} x1, x1, x2", mask(&a[3]), mask(&a[4]), a[1]
+            )),
+            "TEST_RR_SRC2_EQ_DEST" => case(&a[0], "x2", mask(&a[2]), &format!(
+                "li x1, {}; li x2, {}; {} x2, x1, x2", mask(&a[3]), mask(&a[4]), a[1]
+            )),
+            "TEST_RR_SRC12_EQ_DEST" => case(&a[0], "x1", mask(&a[2]), &format!("li x1, {}; {} x1, x1, x1", mask(&a[3]), a[1])),
+            "TEST_RR_DEST_BYPASS" => case(&a[0], "x6", mask(&a[3]), &format!(
+                "li x4, 0; 1: li x1, {}; li x2, {}; {} x14, x1, x2; {}addi x6, x14, 0; addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+                mask(&a[4]), mask(&a[5]), a[2], nops(&a[1])
+            )),
+            "TEST_RR_SRC12_BYPASS" => case(&a[0], "x14", mask(&a[4]), &format!(
+                "li x4, 0; 1: li x1, {}; {}li x2, {}; {}{} x14, x1, x2; addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+                mask(&a[5]), nops(&a[1]), mask(&a[6]), nops(&a[2]), a[3]
+            )),
+            "TEST_RR_SRC21_BYPASS" => case(&a[0], "x14", mask(&a[4]), &format!(
+                "li x4, 0; 1: li x2, {}; {}li x1, {}; {}{} x14, x1, x2; addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+                mask(&a[6]), nops(&a[1]), mask(&a[5]), nops(&a[2]), a[3]
+            )),
+            "TEST_RR_ZEROSRC1" => case(&a[0], "x2", mask(&a[2]), &format!("li x1, {}; {} x2, x0, x1", mask(&a[3]), a[1])),
+            "TEST_RR_ZEROSRC2" => case(&a[0], "x2", mask(&a[2]), &format!("li x1, {}; {} x2, x1, x0", mask(&a[3]), a[1])),
+            "TEST_RR_ZEROSRC12" => case(&a[0], "x1", mask(&a[2]), &format!("{} x1, x0, x0", a[1])),
+            "TEST_RR_ZERODEST" => case(&a[0], "x0", 0, &format!(
+                "li x1, {}; li x2, {}; {} x0, x1, x2", mask(&a[2]), mask(&a[3]), a[1]
+            )),
+
+            // Defined in srl.S and srli.S, with the expected value computed from the operands
+            "TEST_SRL" => format!("TEST_RR_OP({}, srl, {}, {}, {})", a[0], mask(&a[1]) >> number(&a[2]), a[1], a[2]),
+            "TEST_SRLI" => format!("TEST_IMM_OP({}, srli, {}, {}, {})", a[0], mask(&a[1]) >> number(&a[2]), a[1], a[2]),
+
+            "TEST_LD_OP" => case(&a[0], "x14", mask(&a[2]), &format!(
+                "li x15, {}; la x2, {}; {} x14, {}(x2)", mask(&a[2]), a[4], a[1], a[3]
+            )),
+            "TEST_ST_OP" => case(&a[0], "x14", mask(&a[3]), &format!(
+                "la x2, {base}; li x1, {result}; la x15, 7f; {store} x1, {offset}(x2); {load} x14, {offset}(x2); j 8f; 7: mv x14, x1; 8:",
+                base=a[5], result=mask(&a[3]), store=a[2], offset=a[4], load=a[1]
+            )),
+            "TEST_LD_DEST_BYPASS" => twice(&a[0], &format!(
+                "la x13, {}; {} x14, {}(x13); {}addi x6, x14, 0; li x7, {}; bne x6, x7, fail",
+                a[5], a[2], a[4], nops(&a[1]), mask(&a[3])
+            )),
+            "TEST_LD_SRC1_BYPASS" => twice(&a[0], &format!(
+                "la x13, {}; {}{} x14, {}(x13); li x7, {}; bne x14, x7, fail",
+                a[5], nops(&a[1]), a[2], a[4], mask(&a[3])
+            )),
+            "TEST_ST_SRC12_BYPASS" => twice(&a[0], &format!(
+                "li x13, {result}; {}la x12, {base}; {}{store} x13, {offset}(x12); {load} x14, {offset}(x12); li x7, {result}; bne x14, x7, fail",
+                nops(&a[1]), nops(&a[2]), result=mask(&a[5]), base=a[7], store=a[4], offset=a[6], load=a[3]
+            )),
+            "TEST_ST_SRC21_BYPASS" => twice(&a[0], &format!(
+                "la x2, {base}; {}li x1, {result}; {}{store} x1, {offset}(x2); {load} x14, {offset}(x2); li x7, {result}; bne x14, x7, fail",
+                nops(&a[1]), nops(&a[2]), result=mask(&a[5]), base=a[7], store=a[4], offset=a[6], load=a[3]
+            )),
+
+            "TEST_BR2_OP_TAKEN" => format!(
+                "test_{n}: li TESTNUM, {n}; li x1, {}; li x2, {}; {inst} x1, x2, 2f; bne x0, TESTNUM, fail; \
+                 1: bne x0, TESTNUM, 3f; 2: {inst} x1, x2, 1b; bne x0, TESTNUM, fail; 3:",
+                mask(&a[2]), mask(&a[3]), n=a[0], inst=a[1]
+            ),
+            "TEST_BR2_OP_NOTTAKEN" => format!(
+                "test_{n}: li TESTNUM, {n}; li x1, {}; li x2, {}; {inst} x1, x2, 1f; bne x0, TESTNUM, 2f; \
+                 1: bne x0, TESTNUM, fail; 2: {inst} x1, x2, 1b; 3:",
+                mask(&a[2]), mask(&a[3]), n=a[0], inst=a[1]
+            ),
+            "TEST_BR2_SRC12_BYPASS" => twice(&a[0], &format!(
+                "li x1, {}; {}li x2, {}; {}{} x1, x2, fail",
+                mask(&a[4]), nops(&a[1]), mask(&a[5]), nops(&a[2]), a[3]
+            )),
+            "TEST_JALR_SRC1_BYPASS" => format!(
+                "test_{n}: li TESTNUM, {n}; li x4, 0; 1: la x6, 2f; {}{} x13, x6, 0; bne x4, TESTNUM, fail; \
+                 2: addi x4, x4, 1; li x5, 2; bne x4, x5, 1b",
+                nops(&a[1]), a[2], n=a[0]
+            ),
+            _ => panic!("unknown macro '{}'", name)
+        }
+    }
+
+    /// The statements of a source file, with the lines of a macro joined and the parts for rv64 only left out
+    fn statements(source: &str) -> Vec<String> {
+        let mut statements = Vec::new();
+        let mut skipping = false;
+        for line in source.replace("\\\n", " ").lines() {
+            let line = line.trim();
+            if let Some(condition) = line.strip_prefix("#if") {
+                skipping = condition.trim() != "__riscv_xlen == 32";
+            } else if line.starts_with("#endif") {
+                skipping = false;
+            } else if !skipping && !line.starts_with('#') {
+                statements.extend(split(line, ';'));
+            }
+        }
+        statements
+    }
+
+    /// The number of the test a statement invokes a test macro for
+    fn case_number(statement: &str) -> Option<i64> {
+        let arguments = statement.strip_prefix("TEST_")?.split_once('(')?.1;
+        Some(number(arguments.split(',').next()?))
+    }
+
+    fn run(name: &str) {
+        let source = fs::read_to_string(format!("tests/riscv-tests/isa/rv64ui/{}.S", name)).expect("no test source");
+        let mut program = Program::default();
+        for statement in statements(&source) {
+            if !SKIPPED.iter().any(|&(test, case)| test == name && case_number(&statement) == Some(case)) {
+                program.expand(&statement);
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("rv32ui-{}-{}.s", name, std::process::id()));
+        fs::write(&path, program.assemble().join("\n")).unwrap();
+        let mut processor = Processor::new();
+        processor.set_memory_size(1 << 16);
+        let loaded = processor.load_instructions(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        processor.set_halt_conditions(HaltConditions { ecall_exit: true, ..HaltConditions::default() });
+
+        match processor.run(100_000).unwrap() {
+            HaltReason::Exit(0) => {},
+            HaltReason::Exit(code) => panic!("{}: test {} failed", name, code >> 1),
+            reason => panic!("{}: stopped with {:?} instead of passing", name, reason)
+        }
+    }
+
+    macro_rules! rv32ui_tests {
+        ($($name:ident),*) => {
+            $(
+                #[test]
+                fn $name() {
+                    run(stringify!($name));
+                }
+            )*
+        };
+    }
+
+    rv32ui_tests!(
+        add, addi, and, andi, auipc, beq, bge, bgeu, blt, bltu, bne, jal, jalr, lb, lbu, lh, lhu, lui, lw, or,
+        ori, sb, sh, simple, sll, slli, slt, slti, sltiu, sltu, sra, srai, srl, srli, sub, sw, xor, xori
+    );
+}